@@ -0,0 +1,438 @@
+use regex::Regex;
+use serde_yaml::{Mapping, Value};
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::Path;
+
+/// Variables of a host or a group, by name.
+type Vars = BTreeMap<String, String>;
+
+/// A host of an Ansible inventory, with the variables it inherits from its groups.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AnsibleHost {
+    /// Inventory hostname of the host.
+    pub name: String,
+    /// Variables of the host, its own overriding those of its groups.
+    pub vars: Vars,
+}
+
+impl AnsibleHost {
+    /// Returns the settings of the server monitoring the host: its `name`, and its `host`,
+    /// `user`, `port` and `private_key` from the `ansible_host`, `ansible_user`, `ansible_port` and
+    /// `ansible_ssh_private_key_file` variables. Hosts with `ansible_connection=local` are checked
+    /// locally.
+    pub fn settings(&self) -> Mapping {
+        let mut settings = Mapping::new();
+        settings.insert("name".into(), self.name.clone().into());
+        let host = self.vars.get("ansible_host").unwrap_or(&self.name);
+        settings.insert("host".into(), host.clone().into());
+        if let Some(user) = self.vars.get("ansible_user") {
+            settings.insert("user".into(), user.clone().into());
+        }
+        if let Some(port) = self.vars.get("ansible_port") {
+            let port = match port.parse::<u16>() {
+                Ok(port) => port.into(),
+                Err(_) => port.clone().into(),
+            };
+            settings.insert("port".into(), port);
+        }
+        if let Some(key) = self.vars.get("ansible_ssh_private_key_file") {
+            settings.insert("private_key".into(), key.clone().into());
+        }
+        if self.vars.get("ansible_connection").map(String::as_str) == Some("local") {
+            settings.insert("local".into(), true.into());
+        }
+        settings
+    }
+}
+
+/// A group of an Ansible inventory.
+#[derive(Debug, Default)]
+struct Group {
+    hosts: Vec<String>,
+    vars: Vars,
+    children: Vec<String>,
+}
+
+/// The groups of an Ansible inventory and the variables of its hosts, the hosts being listed in
+/// the order they first appear in.
+#[derive(Debug, Default)]
+struct Inventory {
+    groups: BTreeMap<String, Group>,
+    hosts: Vec<String>,
+    host_vars: BTreeMap<String, Vars>,
+}
+
+impl Inventory {
+    fn add_host(&mut self, group: &str, name: &str, vars: Vars) {
+        if !self.host_vars.contains_key(name) {
+            self.hosts.push(name.to_string());
+        }
+        self.host_vars
+            .entry(name.to_string())
+            .or_default()
+            .extend(vars);
+        let group = self.groups.entry(group.to_string()).or_default();
+        if !group.hosts.iter().any(|h| h == name) {
+            group.hosts.push(name.to_string());
+        }
+    }
+
+    /// Returns the hosts of a group and of its children.
+    fn members(&self, group: &str, seen: &mut BTreeSet<String>) -> BTreeSet<String> {
+        let mut members = BTreeSet::new();
+        if !seen.insert(group.to_string()) {
+            return members;
+        }
+        if group == "all" {
+            members.extend(self.hosts.iter().cloned());
+        }
+        if let Some(group) = self.groups.get(group) {
+            members.extend(group.hosts.iter().cloned());
+            for child in &group.children {
+                members.extend(self.members(child, seen));
+            }
+        }
+        members
+    }
+
+    /// Returns the depth of a group, `all` being at 0 and the groups without parent at 1.
+    fn depth(&self, group: &str, seen: &mut BTreeSet<String>) -> usize {
+        if group == "all" || !seen.insert(group.to_string()) {
+            return 0;
+        }
+        self.groups
+            .iter()
+            .filter(|(name, g)| name.as_str() != "all" && g.children.iter().any(|c| c == group))
+            .map(|(name, _)| self.depth(name, &mut seen.clone()))
+            .max()
+            .unwrap_or(0)
+            + 1
+    }
+
+    /// Resolves the hosts belonging to any of `groups` (every host if empty), with their
+    /// variables. As with Ansible, the variables of the parent groups are overridden by those of
+    /// their children, and those of the groups by those of the hosts.
+    fn resolve(&self, groups: &[String]) -> Result<Vec<AnsibleHost>, Box<dyn std::error::Error>> {
+        let mut selected = BTreeSet::new();
+        for group in groups {
+            if group != "all" && !self.groups.contains_key(group) {
+                return Err(format!("Unknown group `{}` in the inventory", group).into());
+            }
+            selected.extend(self.members(group, &mut BTreeSet::new()));
+        }
+
+        let mut ordered: Vec<(usize, &String)> = self
+            .groups
+            .keys()
+            .map(|name| (self.depth(name, &mut BTreeSet::new()), name))
+            .collect();
+        ordered.sort();
+        let members: Vec<(&String, BTreeSet<String>)> = ordered
+            .into_iter()
+            .map(|(_, name)| (name, self.members(name, &mut BTreeSet::new())))
+            .collect();
+
+        Ok(self
+            .hosts
+            .iter()
+            .filter(|name| groups.is_empty() || selected.contains(*name))
+            .map(|name| {
+                let mut vars = Vars::new();
+                for (group, hosts) in &members {
+                    if hosts.contains(name) {
+                        vars.extend(self.groups[*group].vars.clone());
+                    }
+                }
+                vars.extend(self.host_vars.get(name).cloned().unwrap_or_default());
+                AnsibleHost {
+                    name: name.clone(),
+                    vars,
+                }
+            })
+            .collect())
+    }
+}
+
+/// Loads the hosts of an Ansible inventory belonging to any of `groups` (every host if empty),
+/// with their variables.
+///
+/// Inventories whose extension is `.yml` or `.yaml` are read as YAML, the others as INI. Numeric
+/// host ranges, e.g. `www[01:50].example.com`, are expanded.
+///
+/// # Errors
+///
+/// Returns an error if the file cannot be read or parsed, or if a group does not exist.
+pub fn load(path: &str, groups: &[String]) -> Result<Vec<AnsibleHost>, Box<dyn std::error::Error>> {
+    let content = std::fs::read_to_string(path)
+        .map_err(|e| format!("Could not read the inventory {}: {}", path, e))?;
+    let yaml = matches!(
+        Path::new(path).extension().and_then(|e| e.to_str()),
+        Some("yml" | "yaml")
+    );
+    let inventory = match yaml {
+        true => parse_yaml(&content)?,
+        false => parse_ini(&content)?,
+    };
+    inventory.resolve(groups)
+}
+
+/// Parses an inventory in the INI format.
+fn parse_ini(content: &str) -> Result<Inventory, Box<dyn std::error::Error>> {
+    let mut inventory = Inventory::default();
+    let mut section = ("ungrouped".to_string(), "hosts");
+    for (number, line) in content.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') || line.starts_with(';') {
+            continue;
+        }
+        if let Some(header) = line.strip_prefix('[').and_then(|l| l.strip_suffix(']')) {
+            section = match header.split_once(':') {
+                Some((group, "vars")) => (group.to_string(), "vars"),
+                Some((group, "children")) => (group.to_string(), "children"),
+                Some(_) => return Err(format!("Invalid section [{}]", header).into()),
+                None => (header.to_string(), "hosts"),
+            };
+            inventory.groups.entry(section.0.clone()).or_default();
+            continue;
+        }
+        let (group, kind) = (&section.0, section.1);
+        match kind {
+            "vars" => {
+                let (key, value) = line
+                    .split_once('=')
+                    .ok_or_else(|| format!("Expected `key=value` at line {}", number + 1))?;
+                let vars = &mut inventory.groups.entry(group.clone()).or_default().vars;
+                vars.insert(key.trim().to_string(), unquote(value.trim()));
+            }
+            "children" => {
+                let children = &mut inventory.groups.entry(group.clone()).or_default().children;
+                children.push(line.to_string());
+            }
+            _ => {
+                let mut tokens = line.split_whitespace();
+                let pattern = tokens.next().unwrap_or_default();
+                let mut vars = Vars::new();
+                for token in tokens {
+                    let (key, value) = token.split_once('=').ok_or_else(|| {
+                        format!("Expected `key=value` at line {}: {}", number + 1, token)
+                    })?;
+                    vars.insert(key.to_string(), unquote(value));
+                }
+                // `host:port`, unless it is an IPv6 address
+                let pattern = match pattern.split_once(':') {
+                    Some((host, port)) if !port.contains(':') && !port.contains(']') => {
+                        vars.entry("ansible_port".to_string())
+                            .or_insert_with(|| port.to_string());
+                        host
+                    }
+                    _ => pattern,
+                };
+                for name in expand_range(pattern) {
+                    inventory.add_host(group, &name, vars.clone());
+                }
+            }
+        }
+    }
+    Ok(inventory)
+}
+
+/// Parses an inventory in the YAML format, whose top-level keys are groups (usually `all`).
+fn parse_yaml(content: &str) -> Result<Inventory, Box<dyn std::error::Error>> {
+    let value: Value = serde_yaml::from_str(content)?;
+    let mut inventory = Inventory::default();
+    for (name, group) in value.as_mapping().into_iter().flatten() {
+        let name = name.as_str().ok_or("Group names must be strings")?;
+        parse_yaml_group(&mut inventory, name, group)?;
+    }
+    Ok(inventory)
+}
+
+fn parse_yaml_group(
+    inventory: &mut Inventory,
+    name: &str,
+    group: &Value,
+) -> Result<(), Box<dyn std::error::Error>> {
+    inventory.groups.entry(name.to_string()).or_default();
+    for (host, vars) in group
+        .get("hosts")
+        .and_then(Value::as_mapping)
+        .into_iter()
+        .flatten()
+    {
+        let host = scalar(host).ok_or("Host names must be strings")?;
+        let vars = yaml_vars(vars);
+        for host in expand_range(&host) {
+            inventory.add_host(name, &host, vars.clone());
+        }
+    }
+    let vars = yaml_vars(group.get("vars").unwrap_or(&Value::Null));
+    inventory
+        .groups
+        .entry(name.to_string())
+        .or_default()
+        .vars
+        .extend(vars);
+    for (child, content) in group
+        .get("children")
+        .and_then(Value::as_mapping)
+        .into_iter()
+        .flatten()
+    {
+        let child = child.as_str().ok_or("Group names must be strings")?;
+        let children = &mut inventory
+            .groups
+            .entry(name.to_string())
+            .or_default()
+            .children;
+        children.push(child.to_string());
+        parse_yaml_group(inventory, child, content)?;
+    }
+    Ok(())
+}
+
+fn yaml_vars(vars: &Value) -> Vars {
+    vars.as_mapping()
+        .into_iter()
+        .flatten()
+        .filter_map(|(key, value)| Some((scalar(key)?, scalar(value)?)))
+        .collect()
+}
+
+fn scalar(value: &Value) -> Option<String> {
+    match value {
+        Value::String(s) => Some(s.clone()),
+        Value::Number(n) => Some(n.to_string()),
+        Value::Bool(b) => Some(b.to_string()),
+        _ => None,
+    }
+}
+
+fn unquote(value: &str) -> String {
+    let quoted = value.len() >= 2
+        && ((value.starts_with('"') && value.ends_with('"'))
+            || (value.starts_with('\'') && value.ends_with('\'')));
+    match quoted {
+        true => value[1..value.len() - 1].to_string(),
+        false => value.to_string(),
+    }
+}
+
+/// Expands the numeric ranges of a host pattern, e.g. `db[1:3]` into `db1`, `db2` and `db3`,
+/// keeping the leading zeros of the start, e.g. `www[01:10]` into `www01` to `www10`.
+fn expand_range(pattern: &str) -> Vec<String> {
+    let range = Regex::new(r"\[(\d+):(\d+)\]").expect("valid regex");
+    let Some(captures) = range.captures(pattern) else {
+        return vec![pattern.to_string()];
+    };
+    let whole = captures.get(0).expect("whole match");
+    let (start, end) = (&captures[1], &captures[2]);
+    let width = if start.starts_with('0') {
+        start.len()
+    } else {
+        0
+    };
+    let (Ok(first), Ok(last)) = (start.parse::<u64>(), end.parse::<u64>()) else {
+        return vec![pattern.to_string()];
+    };
+    (first..=last)
+        .flat_map(|i| {
+            expand_range(&format!(
+                "{}{:0width$}{}",
+                &pattern[..whole.start()],
+                i,
+                &pattern[whole.end()..],
+                width = width
+            ))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const INI: &str = "\
+mail.example.com ansible_connection=local
+
+[webservers]
+web[01:02].example.com
+bastion.example.com:2222 ansible_user=admin
+
+[dbservers]
+db1 ansible_host=10.0.0.5 ansible_ssh_private_key_file='~/.ssh/db'
+
+[webservers:vars]
+ansible_user=deploy
+
+[prod:children]
+webservers
+dbservers
+
+[prod:vars]
+ansible_user=root
+ansible_port=22
+";
+
+    #[test]
+    fn test_parse_ini() {
+        let inventory = parse_ini(INI).unwrap();
+        let hosts = inventory.resolve(&[]).unwrap();
+        let names: Vec<_> = hosts.iter().map(|h| h.name.as_str()).collect();
+        assert_eq!(
+            names,
+            [
+                "mail.example.com",
+                "web01.example.com",
+                "web02.example.com",
+                "bastion.example.com",
+                "db1"
+            ]
+        );
+        // The variables of the hosts override those of the children, overriding their parents
+        assert_eq!(hosts[1].vars["ansible_user"], "deploy");
+        assert_eq!(hosts[1].vars["ansible_port"], "22");
+        assert_eq!(hosts[3].vars["ansible_user"], "admin");
+        assert_eq!(hosts[3].vars["ansible_port"], "2222");
+        assert_eq!(hosts[4].vars["ansible_user"], "root");
+
+        let settings = serde_yaml::to_string(&hosts[4].settings()).unwrap();
+        assert_eq!(
+            settings,
+            "name: db1\nhost: 10.0.0.5\nuser: root\nport: 22\nprivate_key: ~/.ssh/db\n"
+        );
+        assert_eq!(hosts[0].settings().get("local"), Some(&Value::Bool(true)));
+
+        let web = inventory.resolve(&["webservers".to_string()]).unwrap();
+        assert_eq!(web.len(), 3);
+        let prod = inventory.resolve(&["prod".to_string()]).unwrap();
+        assert_eq!(prod.len(), 4);
+        assert!(inventory.resolve(&["unknown".to_string()]).is_err());
+    }
+
+    #[test]
+    fn test_parse_yaml() {
+        let inventory = parse_yaml(
+            "all:
+  hosts:
+    mail.example.com:
+  vars:
+    ansible_user: monitor
+  children:
+    webservers:
+      hosts:
+        web1.example.com:
+          ansible_port: 2222
+        web2.example.com:
+      vars:
+        ansible_user: deploy
+",
+        )
+        .unwrap();
+        let hosts = inventory.resolve(&["webservers".to_string()]).unwrap();
+        assert_eq!(hosts.len(), 2);
+        assert_eq!(hosts[0].vars["ansible_port"], "2222");
+        assert_eq!(hosts[0].vars["ansible_user"], "deploy");
+        let all = inventory.resolve(&[]).unwrap();
+        assert_eq!(all[0].vars["ansible_user"], "monitor");
+    }
+}
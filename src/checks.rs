@@ -1,36 +1,46 @@
-use crate::ssh;
+use crate::config::{Enforcement, LmSensor, QuotaKind, TemperatureSensor};
+use crate::platform::{self, Os, Platform, ShellProfile};
+use crate::report::{CheckResult, Observation, Status};
+use crate::runner::CommandRunner;
+use crate::utils;
+use log::warn;
 use regex::Regex;
-use ssh2::Session;
+use std::collections::{BTreeMap, HashMap};
+use std::time::Instant;
 
 /// Executes a check to count the number of folders in specified paths on a remote server.
 ///
 /// This function connects to a remote server via SSH and runs a command to count the number
-/// of directories in the given paths. It formats the results into a string, where each line
-/// corresponds to one of the input paths. Each line reports the number of folders found and
-/// is prefixed with an emoji to visually indicate the result: a green check mark (✅) indicates
-/// no folders were found, a red cross (❌) indicates folders were found, and "❌ Error:" is
-/// prefixed if an error occurred during command execution.
+/// of directories in the given paths. It returns one result per input path, reporting the number
+/// of folders found with a status reflecting the thresholds: a green check mark (✅) indicates
+/// the number of folders is below the thresholds, a warning sign (⚠️) or a red cross (❌) indicates
+/// the warning or critical threshold was reached, and "❌ Error:" is reported if an error occurred
+/// during command execution.
 ///
 /// The function leverages the `find` command on the remote server to count directories directly,
 /// minimizing the overhead and potential for misinterpretation compared to listing and manually
-/// counting entries.
+/// counting entries. On Windows, the directories are counted with `Get-ChildItem` instead, and
+/// with the `busybox` shell profile, with the portable `-prune` instead of `-maxdepth`.
 ///
 /// # Arguments
 ///
-/// * `sess` - A reference to an established SSH `Session` for executing commands on the remote server.
+/// * `runner` - Runs the commands on the server, e.g. an established SSH `Session`.
 /// * `server_name` - The name of the server where the check is performed. This is used for reporting
 ///   results and does not affect the execution of the SSH command.
+/// * `platform` - The operating system and shell profile of the server, selecting the command.
 /// * `paths` - A slice of `String` objects, each representing a path on the remote server to check
 ///   for the number of folders.
+/// * `max_folders` - The number of folders at which the result becomes critical.
+/// * `warning` - Optional number of folders at which the result becomes a warning.
 ///
 /// # Returns
 ///
-/// Returns a `String` containing the results of the check for each path. Each line in the returned
-/// string reports the number of folders found in the corresponding path, prefixed with an emoji to
-/// visually indicate the presence of folders or an error. The lines are formatted as follows:
+/// Returns a `CheckResult` for each path, reporting the number of folders found in the corresponding
+/// path. Once rendered, the results look as follows:
 /// - "✅ No folders @ `server_name:path`" if no folders are found,
-/// - "❌ 1 folder @ `server_name:path`" if exactly one folder is found,
-/// - "❌ N folders @ `server_name:path`" for N > 1 folders found,
+/// - "✅ N folders @ `server_name:path`" if fewer folders than the thresholds are found,
+/// - "⚠️ N folders @ `server_name:path`" if the warning threshold is reached,
+/// - "❌ N folders @ `server_name:path`" if the critical threshold (`max_folders`) is reached,
 /// - "❌ Error: error_description" if an error occurs during the execution of the SSH command.
 ///
 /// # Examples
@@ -39,8 +49,8 @@ use ssh2::Session;
 /// let session = // Assume `session` is an established SSH `Session`.
 /// let server_name = "example_server";
 /// let paths = vec![String::from("/path/to/directory1"), String::from("/path/to/directory2")];
-/// let result = number_of_folders(&session, server_name, &paths);
-/// println!("{}", result);
+/// let results = number_of_folders(&session, server_name, Platform::default(), &paths, &10, Some(5));
+/// results.iter().for_each(|r| println!("{}", r));
 /// ```
 ///
 /// This will execute the folder count check for each path specified in `paths` on `example_server`
@@ -48,440 +58,5602 @@ use ssh2::Session;
 ///
 /// # Note
 ///
-/// The function assumes that `ssh::run_ssh_command` can successfully connect and execute commands
-/// on the remote server. It handles command execution failures by including an error message in the
+/// The function assumes that the `runner` can successfully execute commands on the server. It handles command execution failures by including an error message in the
 /// output string. This function does not catch panics from parsing the command output, which should
 /// be considered when interpreting the results.
 pub fn number_of_folders(
-    sess: &Session,
+    runner: &dyn CommandRunner,
     server_name: &str,
+    platform: Platform,
     paths: &[String],
     max_folders: &i32,
-) -> String {
+    warning: Option<i32>,
+) -> Vec<CheckResult> {
+    count_entries(
+        runner,
+        server_name,
+        platform,
+        paths,
+        false,
+        f64::from(*max_folders),
+        warning.map(f64::from),
+    )
+}
+
+/// Counts the regular files directly in the given paths of a server, e.g. the jobs waiting in a
+/// spool directory, as `number_of_folders` counts the directories.
+///
+/// Only the files are counted, not the directories nor the files of the subdirectories, with
+/// `find -type f` (with `-prune` rather than `-maxdepth` on BusyBox) or `Get-ChildItem -File` on
+/// Windows.
+///
+/// # Returns
+///
+/// Returns a `CheckResult` per path, e.g. "120 files @ `web01:/var/spool/jobs`", whose status
+/// reflects the thresholds, with the number of files as metric, named after the path.
+pub fn number_of_files(
+    runner: &dyn CommandRunner,
+    server_name: &str,
+    platform: Platform,
+    paths: &[String],
+    max_files: u32,
+    warning: Option<u32>,
+) -> Vec<CheckResult> {
+    count_entries(
+        runner,
+        server_name,
+        platform,
+        paths,
+        true,
+        f64::from(max_files),
+        warning.map(f64::from),
+    )
+}
+
+/// Measures the disk space used by directories of a server, with their subdirectories, and
+/// classifies it against thresholds, for the log and scratch directories that need a size limit
+/// rather than a count or an age.
+///
+/// The size is measured with `du -sk`, in KiB on every Unix-like system, and on Windows by summing
+/// the lengths of the files listed by `Get-ChildItem -Recurse`. A directory with unreadable
+/// subdirectories is reported as an error by `du`.
+///
+/// # Returns
+///
+/// Returns a `CheckResult` per path, e.g. "4.20 GiB @ `web01:/var/log`", whose status reflects
+/// the thresholds, with the size in bytes as metric, named after the path.
+pub fn dir_size(
+    runner: &dyn CommandRunner,
+    server_name: &str,
+    platform: Platform,
+    paths: &[String],
+    max_size: u64,
+    warning: Option<u64>,
+) -> Vec<CheckResult> {
     paths
         .iter()
         .map(|path| {
-            let command = format!("find {} -maxdepth 1 -type d | tail -n +2 | wc -l", path);
-            ssh::run_ssh_command(sess, &command).map_or_else(
-                |err| format!("❌ Error: {}", err),
-                |output| {
-                    let count: usize = output.trim().parse().unwrap_or(0);
-                    match count {
-                        0 => format!("✅ No folders @ `{}:{}`", server_name, path),
-                        1 => format!("✅ {} folder @ `{}:{}`", count, server_name, path),
-                        _ if count >= *max_folders as usize => {
-                            format!("❌ {} folders @ `{}:{}`", count, server_name, path)
-                        }
-                        _ => format!("✅ {} folders @ `{}:{}`", count, server_name, path),
-                    }
-                },
+            let (command, unit) = match platform.os {
+                Os::Windows => (
+                    platform::powershell(&format!(
+                        "(Get-ChildItem -Recurse -File -Force -LiteralPath {} -ErrorAction Stop | \
+                         Measure-Object -Property Length -Sum).Sum",
+                        platform::powershell_quote(path)
+                    )),
+                    1,
+                ),
+                _ => (format!("du -sk {}", platform::shell_quote(path)), 1024),
+            };
+            let output = match runner.run(&command) {
+                Ok(output) => output,
+                Err(e) => {
+                    return CheckResult::new(Status::Critical, format!("Error: {}", e))
+                        .with_error(e.as_ref())
+                }
+            };
+            // `du` prints `4404020	/var/log`, and the sum is empty for an empty directory
+            let size = match output.split_whitespace().next() {
+                None if platform.os == Os::Windows => Some(0),
+                first => first.and_then(|s| s.parse::<u64>().ok()),
+            };
+            let Some(size) = size.map(|size| size * unit) else {
+                return CheckResult::new(
+                    Status::Critical,
+                    format!("Error: Could not parse the size of {}", path),
+                );
+            };
+            CheckResult::new(
+                Status::from_thresholds(size as f64, warning.map(|w| w as f64), max_size as f64),
+                format!("{} @ `{}:{}`", utils::format_size(size), server_name, path),
             )
+            .with_metric(path.as_str(), size as f64)
         })
-        .collect::<Vec<String>>()
-        .join("\n")
+        .collect()
 }
 
-/// Retrieves and parses the load average from a remote server over SSH and formats the result.
+/// Lists the largest directories directly under a path of a server, e.g. to find out which user
+/// is filling `/home` before it is full, flagging those above the thresholds.
 ///
-/// This function executes the `uptime` command on a remote server via SSH to retrieve the system's
-/// load averages. It then parses the output to extract the load average corresponding to a specified
-/// interval (1, 5, or 15 minutes). The function formats the load average with an emoji indicating
-/// whether the load is above a certain threshold (in this case, 50.0) and returns this as a string.
+/// The subdirectories are measured with `du -k -d 1`, sorted by size, the errors on unreadable
+/// directories being ignored so that the others are still measured. Windows is not supported.
 ///
 /// # Arguments
 ///
-/// * `sess` - A reference to an established SSH `Session` for executing commands on the remote server.
-/// * `server_name` - The name of the server where the command is executed. This is used for formatting
-///   the output string but does not influence the command execution.
-/// * `interval` - A `u16` specifying the interval for the load average to retrieve. Valid values are 1, 5,
-///   or 15, corresponding to the standard intervals provided by the `uptime` command for load averages.
+/// * `runner` - Runs the commands on the server, e.g. an established SSH `Session`.
+/// * `server_name` - The name of the server, used in the message of the result.
+/// * `platform` - The operating system of the server.
+/// * `parent` - The path whose subdirectories are measured.
+/// * `top` - The number of directories listed.
+/// * `max_size` - Size in bytes of a subdirectory at which the result becomes critical.
+/// * `warning` - Optional size in bytes of a subdirectory at which the result becomes a warning.
 ///
 /// # Returns
 ///
-/// Returns a `String` formatted with an emoji and the load average for the specified interval. If the load
-/// is greater than 50.0, a "❌" is prefixed, otherwise a "✅". If an error occurs during command execution
-/// or parsing, an error message is returned.
+/// Returns a single result, reflecting the largest subdirectory, such as "largest under
+/// `web01:/home`: /home/alice (120.00 GiB)", with its size as the `largest` metric. When a
+/// threshold is crossed, the `top` largest subdirectories are listed as details.
 ///
 /// # Errors
 ///
-/// If the `uptime` command fails to execute or if the output cannot be parsed to extract the load average,
-/// the function prints an error message to stderr and returns a string indicating the error.
-///
-/// # Examples
-///
-/// ```rust
-/// let session = // Assume `session` is an established SSH `Session`.
-/// let server_name = "example_server";
-/// let interval = 5; // Specify the interval for load average.
-/// let result = load(&session, server_name, interval);
-/// println!("{}", result);
-/// ```
-///
-/// This will print the load average for the past 5 minutes from `example_server`, formatted with
-/// an emoji indicating if the load is above 50.0.
-///
-/// # Notes
-///
-/// - The function assumes that the `ssh::run_ssh_command` function is available and correctly set up
-///   to execute SSH commands.
-/// - The choice of 50.0 as the threshold for determining high load is arbitrary and may not be suitable
-///   for all systems. Consider adjusting this threshold based on your system's capacity and typical loads.
-/// - The function currently only supports the fixed intervals of 1, 5, or 15 minutes, as these are the
-///   standard intervals reported by the `uptime` command.
-pub fn load(sess: &Session, server_name: &str, interval: u16) -> String {
-    let output = match ssh::run_ssh_command(sess, "uptime") {
+/// If the command fails or lists no subdirectory, or on Windows, a critical result indicating
+/// the error is returned.
+pub fn top_directories(
+    runner: &dyn CommandRunner,
+    server_name: &str,
+    platform: Platform,
+    parent: &str,
+    top: usize,
+    max_size: u64,
+    warning: Option<u64>,
+) -> Vec<CheckResult> {
+    if platform.os == Os::Windows {
+        return vec![CheckResult::new(
+            Status::Critical,
+            "Error: the largest directories are not listed on Windows",
+        )];
+    }
+    // The parent itself comes first, being the largest
+    let command = format!(
+        "du -k -d 1 {} 2>/dev/null | sort -rn | head -n {}",
+        platform::shell_quote(parent),
+        top + 1
+    );
+    let output = match runner.run(&command) {
         Ok(output) => output,
         Err(e) => {
-            eprintln!("Error: {}", e);
-            return "".to_string();
+            warn!(
+                "Could not measure the directories of {}: {}",
+                server_name, e
+            );
+            return vec![
+                CheckResult::new(Status::Critical, format!("Error: {}", e)).with_error(e.as_ref())
+            ];
         }
     };
 
-    let load_parts = output.split("load average:").nth(1);
-    let load = if let Some(parts) = load_parts {
-        parts
-            .split(',')
-            .nth(match interval {
-                1 => 0,
-                5 => 1,
-                15 => 2,
-                _ => 0,
-            })
-            .and_then(|s| s.trim().parse::<f64>().ok())
-    } else {
-        None
+    let parent_path = parent.trim_end_matches('/');
+    let directories: Vec<(u64, &str)> = output
+        .lines()
+        .filter_map(|line| {
+            let (size, path) = line.split_once(char::is_whitespace)?;
+            Some((size.parse::<u64>().ok()? * 1024, path.trim()))
+        })
+        .filter(|(_, path)| path.trim_end_matches('/') != parent_path)
+        .take(top)
+        .collect();
+    let Some(&(largest, largest_path)) = directories.first() else {
+        return vec![CheckResult::new(
+            Status::Critical,
+            format!("Error: no directory under {}", parent),
+        )];
     };
 
-    match load {
-        Some(load) => {
-            let emoji = if load > 50.0 { "❌" } else { "✅" };
-            format!(
-                "{} load {:.2} ({}min) @ {}",
-                emoji, load, interval, server_name
-            )
+    let status =
+        Status::from_thresholds(largest as f64, warning.map(|w| w as f64), max_size as f64);
+    let result = CheckResult::new(
+        status,
+        format!(
+            "largest under `{}:{}`: {} ({})",
+            server_name,
+            parent,
+            largest_path,
+            utils::format_size(largest)
+        ),
+    )
+    .with_metric("largest", largest as f64);
+    match status {
+        Status::Ok => vec![result],
+        _ => {
+            let listing: Vec<String> = directories
+                .iter()
+                .map(|(size, path)| format!("{:>12}  {}", utils::format_size(*size), path))
+                .collect();
+            vec![result.with_details(listing.join("\n"))]
         }
-        None => "❌ Error: Could not parse load average".to_string(),
     }
 }
 
-/// Performs HTTP GET requests to a list of URLs constructed from a specified host and path segments.
-///
-/// This function iterates over a slice of URL path segments, appends each segment to the given host
-/// to form complete URLs, and then performs an HTTP GET request to each URL. The function collects
-/// the results of these requests into a single `String`, where each line represents the outcome of a
-/// request to a specific URL. Successful requests are noted with a "✅", while failures due to
-/// either network errors or non-success HTTP status codes are marked with a "❌".
+/// Reads the expiry date of certificate files on a server with `openssl x509 -enddate`, e.g. the
+/// certificates of an internal CA or of a mail server that cannot be checked over the network.
 ///
 /// # Arguments
 ///
-/// * `host` - A string slice representing the base host to which the URL path segments will be appended.
-/// * `urls` - A slice of `String` objects, each representing a path segment to be appended to the host
-///   to form complete URLs for the GET requests.
+/// * `runner` - Runs the commands on the server, e.g. an established SSH `Session`.
+/// * `server_name` - The name of the server, used in the messages of the results.
+/// * `certificates` - The paths of the PEM certificates.
+/// * `warning_days` - The number of days before the expiry at which a result becomes a warning.
+/// * `min_days` - The number of days before the expiry at which a result becomes critical.
 ///
 /// # Returns
 ///
-/// Returns a `String` where each line corresponds to the result of a request to one of the constructed URLs.
-/// Successful requests are marked with "✅" followed by the URL. Unsuccessful requests are marked with "❌",
-/// followed by the URL and either the HTTP status code (for responses that were received but indicated failure)
-/// or the error message if the request failed to complete.
-///
-/// # Examples
-///
-/// ```rust
-/// let host = "http://example.com";
-/// let paths = vec![String::from("/api/health"), String::from("/api/status")];
-/// let results = ping(host, &paths);
-/// println!("{}", results);
-/// ```
-///
-/// This might print something like:
-///
-/// ```text
-/// ✅ http://example.com/api/health
-/// ❌ http://example.com/api/status == `404 Not Found`
-/// ```
-///
-/// # Note
+/// Returns one result per certificate, such as "/etc/postfix/mail.pem expires in 12 days
+/// (2025-06-01) @ mail01" or "/etc/postfix/mail.pem expired 3 days ago (2025-06-01) @ mail01",
+/// with the number of days left as metric named after the path, negative once expired.
 ///
-/// The function uses `reqwest::blocking::get` to perform synchronous HTTP GET requests. This means
-/// that each request will block the executing thread until a response is received or an error occurs.
-/// As such, the total execution time of this function will be at least the sum of the response times
-/// for all the requests, plus any additional overhead. For applications requiring non-blocking behavior
-/// or high levels of concurrency, consider using asynchronous requests or a different approach.
+/// # Errors
 ///
-/// Error handling in this function distinguishes between two types of failures: HTTP errors, where a
-/// response was received but indicated an error through its status code, and network or other errors,
-/// where the request could not be completed at all. In the former case, the specific status code is
-/// included in the output; in the latter, the error message provided by the failure is included.
-pub fn ping(host: &str, urls: &[String]) -> String {
-    let mut results = String::new();
-    urls.iter().for_each(|u| {
-        let request_url = format!("{}{}", host, u);
-        match reqwest::blocking::get(&request_url) {
-            Ok(response) => {
-                let status = response.status();
-                if status.is_success() {
-                    results.push_str(&format!("✅ {}\n", request_url));
-                } else {
-                    results.push_str(&format!("❌ {} == `{}`\n", request_url, status));
+/// A certificate that cannot be read or whose expiry date cannot be parsed gives a critical
+/// result indicating the error.
+pub fn certificate_files(
+    runner: &dyn CommandRunner,
+    server_name: &str,
+    certificates: &[String],
+    warning_days: u32,
+    min_days: u32,
+) -> Vec<CheckResult> {
+    let now = chrono::Utc::now();
+    certificates
+        .iter()
+        .map(|path| {
+            let output = match runner.run(&format!(
+                "openssl x509 -enddate -noout -in {}",
+                platform::shell_quote(path)
+            )) {
+                Ok(output) => output,
+                Err(e) => {
+                    warn!(
+                        "Could not read the certificate {} of {}: {}",
+                        path, server_name, e
+                    );
+                    return CheckResult::new(
+                        Status::Critical,
+                        format!("Error: {} @ `{}:{}`", e, server_name, path),
+                    )
+                    .with_error(e.as_ref());
                 }
-            }
-            Err(e) => results.push_str(&format!(
-                "❌ {} is not accessible\n```{}```\n",
-                request_url, e
-            )),
-        }
-    });
+            };
+            let Some(expiry) = certificate_expiry(&output) else {
+                return CheckResult::new(
+                    Status::Critical,
+                    format!(
+                        "Error: unexpected expiry date {} @ `{}:{}`",
+                        output.trim(),
+                        server_name,
+                        path
+                    ),
+                );
+            };
+
+            let days = (expiry - now).num_days();
+            let status = if days < i64::from(min_days) {
+                Status::Critical
+            } else if days < i64::from(warning_days) {
+                Status::Warning
+            } else {
+                Status::Ok
+            };
+            CheckResult::new(
+                status,
+                format!(
+                    "{} {} @ {}",
+                    path,
+                    describe_expiry(expiry, now),
+                    server_name
+                ),
+            )
+            .with_metric(path.as_str(), days as f64)
+        })
+        .collect()
+}
 
-    results.trim_end().to_string()
+/// Parses the expiry date printed by `openssl x509 -enddate`, e.g. `notAfter=Jun  1 12:00:00 2025
+/// GMT`.
+fn certificate_expiry(output: &str) -> Option<chrono::DateTime<chrono::Utc>> {
+    let date = output.trim().strip_prefix("notAfter=")?;
+    let date = date.split_whitespace().collect::<Vec<&str>>().join(" ");
+    chrono::NaiveDateTime::parse_from_str(&date, "%b %d %H:%M:%S %Y GMT")
+        .ok()
+        .map(|expiry| expiry.and_utc())
 }
 
-/// Reads and parses the temperature from a specified sensor file on a remote system via SSH.
+/// Describes when a certificate expires, e.g. "expires in 12 days (2025-06-01)" or "expired 3
+/// days ago (2025-06-01)".
+fn describe_expiry(
+    expiry: chrono::DateTime<chrono::Utc>,
+    now: chrono::DateTime<chrono::Utc>,
+) -> String {
+    let left = expiry - now;
+    let when = if left < chrono::Duration::zero() {
+        format!("expired {} ago", utils::format_duration(-left))
+    } else {
+        format!("expires in {}", utils::format_duration(left))
+    };
+    format!("{} ({})", when, expiry.format("%Y-%m-%d"))
+}
+
+/// Checks the renewal of the certificates managed by certbot (Let's Encrypt) on a server, whose
+/// renewal silently breaking is only noticed once they expire.
 ///
-/// This function executes a command to read the contents of a sensor file, where the temperature
-/// data is expected to be in a specific format, typically containing a string like "t=12345" where
-/// the digits represent the temperature in a unit such as millidegrees Celsius. The function then
-/// parses this format to extract the temperature value, converts it to degrees Celsius, and returns
-/// a formatted string indicating the temperature status. If the temperature is below 30°C, it is
-/// considered normal and marked with a "✅". Otherwise, it is marked as potentially problematic with
-/// a "❌". In case of errors at any step (e.g., command execution failure, regex compilation error,
-/// or parsing failure), an appropriate error message is returned.
+/// The certificates are those with a renewal configuration in `renewal/`, their expiry being read
+/// from `live/<name>/cert.pem` with `openssl x509 -enddate`. Certbot renews them 30 days before
+/// they expire, so one expiring in less than `overdue_days` should have been renewed already. The
+/// failures of the latest renewal are read from the most recent log of certbot that processed the
+/// renewal configurations, `certbot certificates` or `certbot renew --dry-run` not being run as
+/// they rotate the logs. Reading them usually requires root.
 ///
 /// # Arguments
 ///
-/// * `sess` - A reference to an established SSH `Session`, used to execute the command on the remote system.
-/// * `sensor` - The path to the sensor file on the remote system that contains the temperature data.
+/// * `runner` - Runs the commands on the server, e.g. an established SSH `Session`.
+/// * `server_name` - The name of the server, used in the messages of the results.
+/// * `config_dir` - The configuration directory of certbot, e.g. `/etc/letsencrypt`.
+/// * `logs_dir` - The log directory of certbot, e.g. `/var/log/letsencrypt`.
+/// * `overdue_days` - The number of days before the expiry under which the renewal is overdue.
 ///
 /// # Returns
 ///
-/// Returns a `String` that indicates the temperature reading and its status:
-/// - "✅ XX°C" if the temperature is successfully read and below 30°C.
-/// - "❌ XX°C" if the temperature is successfully read but 30°C or above.
-/// - "❌ Failed to parse temperature!" if the temperature value cannot be parsed from the file contents.
-/// - "❌ Cannot read temperature!" if the sensor data does not match the expected format.
-/// - Returns an empty string and prints an error message to stderr if there's an error executing the SSH command
-///   or compiling the regular expression.
-///
-/// # Examples
-///
-/// Assuming the sensor file "/sys/class/thermal/thermal_zone0/temp" contains "t=29500":
-///
-/// ```rust
-/// let session = // Assume `session` is an established SSH `Session`.
-/// let sensor_path = "/sys/class/thermal/thermal_zone0/temp";
-/// let temperature_status = temperature(&session, sensor_path);
-/// println!("{}", temperature_status);
-/// ```
-///
-/// This might print:
-///
-/// ```text
-/// ✅ 29°C
-/// ```
-///
-/// # Note
+/// Returns one result per certificate, such as "example.com expires in 75 days (2025-06-01) @
+/// web01", with the number of days left as metric named after the certificate. The result is
+/// critical if the certificate expired or its renewal is overdue, and a warning if its latest
+/// renewal failed, the error being given as details.
 ///
-/// This function assumes that the sensor data format and the command to read it ("cat /path/to/sensor") are consistent
-/// across the remote systems it is used with. Variations in sensor data format or the need to use a different
-/// command to access it may require modifications to the function.
+/// # Errors
 ///
-/// Error handling in this function provides basic feedback through returned error messages for specific failure
-/// points. For production use, it may be beneficial to implement more detailed error reporting or logging,
-/// especially for debugging issues with sensor data retrieval or parsing.
-pub fn temperature(sess: &Session, sensor: &str) -> String {
-    let command = format!("cat {}", sensor);
-    let output = match ssh::run_ssh_command(sess, &command) {
+/// If the command fails or no certificate is found, a critical result indicating the error is
+/// returned. A certificate that cannot be read gives a critical result.
+pub fn certbot(
+    runner: &dyn CommandRunner,
+    server_name: &str,
+    config_dir: &str,
+    logs_dir: &str,
+    overdue_days: u32,
+) -> Vec<CheckResult> {
+    let command = format!(
+        "for conf in {config}/renewal/*.conf; do [ -e \"$conf\" ] || continue; \
+         name=$(basename \"$conf\" .conf); \
+         echo \"$name $(openssl x509 -enddate -noout -in {config}/live/$name/cert.pem 2>&1 | head -n 1)\"; \
+         done; \
+         log=$(ls -t {logs}/letsencrypt.log* 2>/dev/null \
+         | xargs -r grep -l 'Processing '{config}/renewal 2>/dev/null | head -n 1); \
+         [ -z \"$log\" ] || grep -h 'Failed to renew certificate' \"$log\" || true",
+        config = platform::shell_quote(config_dir.trim_end_matches('/')),
+        logs = platform::shell_quote(logs_dir.trim_end_matches('/')),
+    );
+    let output = match runner.run(&command) {
         Ok(output) => output,
         Err(e) => {
-            eprintln!("Error: {}", e);
-            return "".to_string();
-        }
-    };
-
-    // Compile the regular expression to match the temperature value
-    let re = match Regex::new(r"t=(\d+)") {
-        Ok(re) => re,
-        Err(e) => {
-            eprintln!("Error: {}", e);
-            return "".to_string();
+            warn!("Could not read the certificates of {}: {}", server_name, e);
+            return vec![
+                CheckResult::new(Status::Critical, format!("Error: {}", e)).with_error(e.as_ref())
+            ];
         }
     };
 
-    if let Some(caps) = re.captures(&output) {
-        if let Some(matched) = caps.get(1) {
-            let temperature = match matched.as_str().parse::<u32>() {
-                Ok(temp) => temp / 1000, // Convert to degrees Celsius
-                Err(_) => return "❌ Failed to parse temperature!".to_string(),
+    // e.g. "2024-05-01 10:00:00,123:ERROR:certbot._internal.renewal:Failed to renew certificate
+    // example.com with error: ..."
+    let failures: HashMap<&str, &str> = output
+        .lines()
+        .filter_map(|line| {
+            let (_, failure) = line.split_once("Failed to renew certificate ")?;
+            Some(failure.split_once(" with error: ").unwrap_or((failure, "")))
+        })
+        .collect();
+    let now = chrono::Utc::now();
+    let results: Vec<CheckResult> = output
+        .lines()
+        .filter(|line| !line.contains("Failed to renew certificate "))
+        .filter_map(|line| line.split_once(' '))
+        .map(|(name, enddate)| {
+            let Some(expiry) = certificate_expiry(enddate) else {
+                return CheckResult::new(
+                    Status::Critical,
+                    format!("Error: {} @ `{}:{}`", enddate.trim(), server_name, name),
+                );
             };
-
-            if temperature < 30 {
-                return format!("✅ {}°C", temperature);
+            let days = (expiry - now).num_days();
+            let mut message = format!("{} {}", name, describe_expiry(expiry, now));
+            let mut status = Status::Ok;
+            if days >= 0 && days < i64::from(overdue_days) {
+                status = Status::Critical;
+                message.push_str(", renewal overdue");
+            } else if days < 0 {
+                status = Status::Critical;
             }
-            return format!("❌ {}°C", temperature);
-        }
+            let failure = failures.get(name);
+            if failure.is_some() {
+                status = status.max(Status::Warning);
+                message.push_str(", latest renewal failed");
+            }
+            let result = CheckResult::new(status, format!("{} @ {}", message, server_name))
+                .with_metric(name, days as f64);
+            match failure {
+                Some(error) if !error.is_empty() => result.with_details(error.to_string()),
+                _ => result,
+            }
+        })
+        .collect();
+    if results.is_empty() {
+        return vec![CheckResult::new(
+            Status::Critical,
+            format!(
+                "Error: no certificate in {}/renewal @ {}",
+                config_dir, server_name
+            ),
+        )];
     }
-    "❌ Cannot read temperature!".to_string()
+    results
 }
 
-/// Executes a custom command on a remote server via SSH and formats the output.
+/// Audits the permissions of the files and directories under paths of a server, reporting those
+/// that anyone can write to, the directories with the sticky bit (e.g. `/tmp`) excepted, those
+/// whose user or group no longer exists, and those not owned by `owner`.
 ///
-/// This function sends a specified command to be executed on a remote server through an established
-/// SSH session. It formats the command and its output for readability, marking the command with a
-/// warning emoji and encapsulating the command output in markdown code block syntax. If the command
-/// execution fails, it logs the error and returns an empty string.
+/// The offenders are found with `find -xdev`, which does not cross into other filesystems, and
+/// listed with `ls -ld`, the search stopping once `max_listed` of them are found (the timeout of
+/// the check bounding it otherwise). BusyBox `find` lacking `-nouser` and `-nogroup`, the unknown
+/// users and groups are only reported with the standard profile. Windows is not supported.
 ///
 /// # Arguments
 ///
-/// * `sess` - A reference to an established SSH `Session`. This session is used to execute the
-///   command on the remote server.
-/// * `command` - A string slice that holds the command to be executed on the remote server.
+/// * `runner` - Runs the commands on the server, e.g. an established SSH `Session`.
+/// * `server_name` - The name of the server, used in the message of the result.
+/// * `platform` - The operating system and shell profile of the server.
+/// * `paths` - The paths audited.
+/// * `owner` - Optional user expected to own every file.
+/// * `max_listed` - The number of offenders listed.
 ///
 /// # Returns
 ///
-/// Returns a `String` that starts with a warning emoji and the command itself in backticks, followed
-/// by the command output encapsulated in a markdown code block. If an error occurs during command
-/// execution, an empty string is returned and the error is logged to standard error.
+/// Returns a single result, such as "3 files with wrong permissions under /srv @ web01", critical
+/// if there is any offender, listed as details with the reason, e.g. "world-writable -rw-rw-rw-
+/// bob bob /srv/app/config.yml". The number of offenders is the `offenders` metric.
 ///
-/// # Examples
+/// # Errors
 ///
-/// ```rust
-/// let session = // Assume `session` is an established SSH `Session`.
-/// let command = "ls -la";
-/// let result = custom_command(&session, command);
-/// println!("{}", result);
-/// ```
+/// If the command fails, or on Windows, a critical result indicating the error is returned.
+pub fn permissions(
+    runner: &dyn CommandRunner,
+    server_name: &str,
+    platform: Platform,
+    paths: &[String],
+    owner: Option<&str>,
+    max_listed: usize,
+) -> Vec<CheckResult> {
+    if platform.os == Os::Windows {
+        return vec![CheckResult::new(
+            Status::Critical,
+            "Error: the permissions are not audited on Windows",
+        )];
+    }
+    let mut conditions = r"-perm -0002 ! -perm -1000".to_string();
+    if platform.shell != ShellProfile::Busybox {
+        conditions.push_str(" -o -nouser -o -nogroup");
+    }
+    if let Some(owner) = owner {
+        conditions.push_str(&format!(" -o ! -user {}", platform::shell_quote(owner)));
+    }
+    let command = format!(
+        r"find {} -xdev \( -type f -o -type d \) \( {} \) -exec ls -ld {{}} + 2>/dev/null | head -n {}",
+        paths
+            .iter()
+            .map(|path| platform::shell_quote(path))
+            .collect::<Vec<_>>()
+            .join(" "),
+        conditions,
+        max_listed + 1
+    );
+    let output = match runner.run(&command) {
+        Ok(output) => output,
+        Err(e) => {
+            warn!("Could not audit the permissions of {}: {}", server_name, e);
+            return vec![
+                CheckResult::new(Status::Critical, format!("Error: {}", e)).with_error(e.as_ref())
+            ];
+        }
+    };
+
+    // -rw-rw-rw- 1 bob bob 120 Jan  1  2024 /srv/app/config.yml
+    let re = Regex::new(r"^(\S+)\s+\d+\s+(\S+)\s+(\S+)\s+\S+\s+\S+\s+\S+\s+\S+\s+(.+)$")
+        .expect("valid regular expression");
+    let offenders: Vec<String> = output
+        .lines()
+        .filter_map(|line| {
+            let caps = re.captures(line)?;
+            let (mode, user, group, path) = (&caps[1], &caps[2], &caps[3], &caps[4]);
+            let modes: Vec<char> = mode.chars().collect();
+            let mut reasons = vec![];
+            if modes.get(8) == Some(&'w') && !matches!(modes.get(9), Some('t' | 'T')) {
+                reasons.push("world-writable");
+            }
+            if user.chars().all(|c| c.is_ascii_digit()) {
+                reasons.push("unknown user");
+            } else if owner.is_some_and(|owner| owner != user) {
+                reasons.push("wrong owner");
+            }
+            if group.chars().all(|c| c.is_ascii_digit()) {
+                reasons.push("unknown group");
+            }
+            Some(format!(
+                "{:<15} {} {} {} {}",
+                reasons.join(", "),
+                mode,
+                user,
+                group,
+                path
+            ))
+        })
+        .collect();
+
+    let under = paths.join(", ");
+    if offenders.is_empty() {
+        return vec![CheckResult::new(
+            Status::Ok,
+            format!(
+                "no file with wrong permissions under {} @ {}",
+                under, server_name
+            ),
+        )
+        .with_metric("offenders", 0.0)];
+    }
+    let count = if offenders.len() > max_listed {
+        format!("more than {}", max_listed)
+    } else {
+        offenders.len().to_string()
+    };
+    vec![CheckResult::new(
+        Status::Critical,
+        format!(
+            "{} files with wrong permissions under {} @ {}",
+            count, under, server_name
+        ),
+    )
+    .with_details(
+        offenders
+            .iter()
+            .take(max_listed)
+            .cloned()
+            .collect::<Vec<String>>()
+            .join("\n"),
+    )
+    .with_metric("offenders", offenders.len() as f64)]
+}
+
+/// Reports the users, groups or projects of a Linux server using a large share of their disk
+/// quota, before they run out of space and complain.
 ///
-/// This might output something like:
+/// The quotas are listed with `repquota` (`-u`, `-g` or `-P`), of the given filesystem or of all
+/// of them (`-a`), or with `xfs_quota -x -c 'report -N'` on XFS, both requiring root. The usage is
+/// compared to the soft limit, or to the hard one for the quotas without soft limit, the
+/// quotas without limits being ignored.
 ///
-/// ```text
-/// ⚠️ `ls -la`
-/// ```
-/// ```text
-/// total 12
-/// drwxr-xr-x  2 user user 4096 Jul 21 12:00 .
-/// drwxr-xr-x  4 user user 4096 Jul 20 14:43 ..
-/// -rw-r--r--  1 user user   66 Jul 21 12:00 file.txt
-/// ```
+/// # Arguments
 ///
-/// # Note
+/// * `runner` - Runs the commands on the server, e.g. an established SSH `Session`.
+/// * `server_name` - The name of the server, used in the messages of the results.
+/// * `quotas` - Kind of the quotas to report.
+/// * `filesystem` - Optional filesystem to report, required by `xfs_quota`.
+/// * `xfs` - Whether to read the quotas with `xfs_quota`.
+/// * `warning` - Optional share of a quota, in percent, at which a result becomes a warning.
+/// * `critical` - Share of a quota, in percent, at which a result becomes critical.
 ///
-/// This function is designed to execute arbitrary commands on a remote server, which can be potentially
-/// very dangerous if not used carefully. Ensure that the commands being executed are safe and that the
-/// `command` argument comes from a trusted source to prevent security risks such as command injection.
+/// # Returns
+///
+/// Returns a result per quota over a threshold, such as "alice uses 105% of their quota on
+/// /dev/sda1 (1.00 GiB of 976.56 MiB) @ hpc01", with the share as metric named after the user,
+/// group or project, or a single `Ok` result if every quota is below the thresholds.
 ///
-/// The function uses `eprintln!` to log errors to standard error, which is suitable for command-line
-/// applications but might need to be adapted for use in other contexts.
-pub fn custom_command(sess: &Session, command: &str) -> String {
-    let header = format!("⚠️ `{}`", command);
-    let output = match ssh::run_ssh_command(sess, command) {
+/// # Errors
+///
+/// If the command fails, a critical result indicating the error is returned.
+pub fn quota(
+    runner: &dyn CommandRunner,
+    server_name: &str,
+    quotas: QuotaKind,
+    filesystem: Option<&str>,
+    xfs: bool,
+    warning: Option<f64>,
+    critical: f64,
+) -> Vec<CheckResult> {
+    let command = match xfs {
+        true => format!(
+            "xfs_quota -x -c 'report -N -n -{}' {}",
+            match quotas {
+                QuotaKind::Users => 'u',
+                QuotaKind::Groups => 'g',
+                QuotaKind::Projects => 'p',
+            },
+            filesystem.map(platform::shell_quote).unwrap_or_default()
+        ),
+        false => format!(
+            "repquota -{} {}",
+            match quotas {
+                QuotaKind::Users => 'u',
+                QuotaKind::Groups => 'g',
+                QuotaKind::Projects => 'P',
+            },
+            filesystem.map_or("-a".to_string(), platform::shell_quote)
+        ),
+    };
+    let output = match runner.run(command.trim_end()) {
         Ok(output) => output,
         Err(e) => {
-            eprintln!("Error: {}", e);
-            return "".to_string();
+            warn!("Could not read the quotas of {}: {}", server_name, e);
+            return vec![
+                CheckResult::new(Status::Critical, format!("Error: {}", e)).with_error(e.as_ref())
+            ];
         }
     };
 
-    let formatted_output = format!("```\n{}```", output);
+    // `*** Report for user quotas on device /dev/sda1` precedes the quotas of every filesystem,
+    // which `repquota` lists as `alice +- 1050000 1000000 1100000 6days 120 0 0` and `xfs_quota`
+    // as `#1000 1050000 1000000 1100000 00 [6 days]`, in KiB
+    let mut device = filesystem.unwrap_or_default().to_string();
+    let mut results = vec![];
+    for line in output.lines() {
+        if let Some((_, on)) = line.split_once(" on device ") {
+            device = on.trim().to_string();
+            continue;
+        }
+        let columns: Vec<&str> = line.split_whitespace().collect();
+        let Some((name, rest)) = columns.split_first() else {
+            continue;
+        };
+        let numbers: Vec<u64> = rest
+            .iter()
+            .skip_while(|c| c.chars().all(|c| c == '+' || c == '-'))
+            .take(3)
+            .map_while(|c| c.parse().ok())
+            .collect();
+        let [used, soft, hard] = numbers[..] else {
+            continue;
+        };
+        let limit = if soft > 0 { soft } else { hard };
+        if limit == 0 {
+            continue;
+        }
+        let usage = 100.0 * used as f64 / limit as f64;
+        let status = Status::from_thresholds(usage, warning, critical);
+        if status == Status::Ok {
+            continue;
+        }
+        results.push(
+            CheckResult::new(
+                status,
+                format!(
+                    "{} uses {:.0}% of their quota on {} ({} of {}) @ {}",
+                    name,
+                    usage,
+                    device,
+                    utils::format_size(used * 1024),
+                    utils::format_size(limit * 1024),
+                    server_name
+                ),
+            )
+            .with_metric(*name, usage),
+        );
+    }
+    if results.is_empty() {
+        results.push(CheckResult::new(
+            Status::Ok,
+            format!(
+                "every quota below {:.0}% @ {}",
+                warning.unwrap_or(critical),
+                server_name
+            ),
+        ));
+    }
+    results
+}
 
-    format!("{}\n{}", header, formatted_output)
+/// Counts the directories, or the regular `files`, directly in the given paths, for
+/// `number_of_folders` and `number_of_files`.
+fn count_entries(
+    runner: &dyn CommandRunner,
+    server_name: &str,
+    platform: Platform,
+    paths: &[String],
+    files: bool,
+    critical: f64,
+    warning: Option<f64>,
+) -> Vec<CheckResult> {
+    let (noun, windows, find) = match files {
+        true => ("file", "-File", "f"),
+        false => ("folder", "-Directory", "d"),
+    };
+    paths
+        .iter()
+        .map(|path| {
+            let command = match platform.os {
+                Os::Windows => platform::powershell(&format!(
+                    "(Get-ChildItem {} -Force -LiteralPath {} -ErrorAction Stop).Count",
+                    windows,
+                    platform::powershell_quote(path)
+                )),
+                _ if platform.shell == ShellProfile::Busybox => {
+                    format!(
+                        "find {}/. ! -name . -prune -type {} | wc -l",
+                        platform::shell_quote(path),
+                        find
+                    )
+                }
+                // The path itself is listed among the directories
+                _ if files => format!(
+                    "find {} -maxdepth 1 -type f | wc -l",
+                    platform::shell_quote(path)
+                ),
+                _ => format!(
+                    "find {} -maxdepth 1 -type d | tail -n +2 | wc -l",
+                    platform::shell_quote(path)
+                ),
+            };
+            runner.run(&command).map_or_else(
+                |err| CheckResult::new(Status::Critical, format!("Error: {}", err)),
+                |output| {
+                    let count: usize = output.trim().parse().unwrap_or(0);
+                    let status = Status::from_thresholds(count as f64, warning, critical);
+                    let message = match count {
+                        0 => format!("No {}s @ `{}:{}`", noun, server_name, path),
+                        1 => format!("{} {} @ `{}:{}`", count, noun, server_name, path),
+                        _ => format!("{} {}s @ `{}:{}`", count, noun, server_name, path),
+                    };
+                    CheckResult::new(status, message).with_metric(path.as_str(), count as f64)
+                },
+            )
+        })
+        .collect()
 }
 
-/// Lists directories older than a specified number of days in a given location on a remote server.
+/// Retrieves and parses the load average from a remote server over SSH and formats the result.
 ///
-/// This function executes a `find` command on a remote server via SSH to identify directories within
-/// a specified location (`loc`) that are older than a given number of days (`cutoff`). It formats the
-/// list of these directories into a human-readable string. If an error occurs during command execution,
-/// an error message is logged, and an empty string is returned. If no directories meet the criteria,
-/// a message indicating this is returned instead.
+/// This function executes the `uptime` command on a remote server via SSH to retrieve the system's
+/// load averages. It then parses the output to extract the load average corresponding to a specified
+/// interval (1, 5, or 15 minutes) and classifies it against the warning and critical thresholds.
+///
+/// Windows has no load average, so the length of the processor queue (the threads ready to run
+/// but waiting for a processor), read with `Get-Counter`, is classified instead, regardless of the
+/// interval. With the `busybox` shell profile, the load is read from `/proc/loadavg`, whose
+/// format does not depend on the implementation of `uptime`.
 ///
 /// # Arguments
 ///
-/// * `sess` - A reference to an established SSH `Session` for executing commands on the remote server.
-/// * `loc` - A string slice that specifies the location on the remote server to search for old directories.
-/// * `cutoff` - The number of days used as the threshold for determining if a directory is considered "old".
+/// * `runner` - Runs the commands on the server, e.g. an established SSH `Session`.
+/// * `server_name` - The name of the server where the command is executed. This is used for formatting
+///   the output string but does not influence the command execution.
+/// * `platform` - The operating system and shell profile of the server, selecting the command.
+/// * `interval` - A `u16` specifying the interval for the load average to retrieve. Valid values are 1, 5,
+///   or 15, corresponding to the standard intervals provided by the `uptime` command for load averages.
+/// * `warning` - Optional load at which the result becomes a warning.
+/// * `critical` - Load at which the result becomes critical.
 ///
 /// # Returns
 ///
-/// Returns a `String` that either:
-/// - Lists the directories older than `cutoff` days in the specified location, formatted as a markdown
-///   code block for readability, or
-/// - Indicates that no directories older than `cutoff` days were found in the specified location, or
-/// - Returns an empty string if an error occurred during command execution.
+/// Returns a `CheckResult` with the load average for the specified interval, with a `Critical` status
+/// if the load reached `critical`, a `Warning` status if it reached `warning` and `Ok` otherwise.
+///
+/// # Errors
+///
+/// If the `uptime` command fails to execute or its output cannot be parsed to extract the load
+/// average, a critical result indicating the error is returned.
 ///
 /// # Examples
 ///
 /// ```rust
 /// let session = // Assume `session` is an established SSH `Session`.
-/// let location = "/var/log";
-/// let days_old = 30;
-/// let result = list_old_directories(&session, location, days_old);
-/// println!("{}", result);
+/// let server_name = "example_server";
+/// let interval = 5; // Specify the interval for load average.
+/// let results = load(&session, server_name, Platform::default(), interval, Some(20.0), 50.0);
+/// results.iter().for_each(|r| println!("{}", r));
 /// ```
 ///
-/// This might output something like:
+/// This will print the load average for the past 5 minutes from `example_server`, formatted with
+/// an emoji indicating if the load is above 20.0 or 50.0.
 ///
-/// ```text
+/// # Notes
+///
+/// - The function assumes that the `runner` is correctly set up to execute commands on the server.
+/// - The default critical threshold of 50.0 is arbitrary and may not be suitable for all systems.
+///   Consider adjusting the thresholds based on your system's capacity and typical loads.
+/// - The function currently only supports the fixed intervals of 1, 5, or 15 minutes, as these are the
+///   standard intervals reported by the `uptime` command.
+pub fn load(
+    runner: &dyn CommandRunner,
+    server_name: &str,
+    platform: Platform,
+    interval: u16,
+    warning: Option<f64>,
+    critical: f64,
+) -> Vec<CheckResult> {
+    let command = match platform.os {
+        Os::Linux if platform.shell == ShellProfile::Busybox => "cat /proc/loadavg".to_string(),
+        Os::Linux | Os::Macos | Os::Freebsd => "uptime".to_string(),
+        Os::Windows => platform::powershell(
+            r"(Get-Counter '\System\Processor Queue Length').CounterSamples[0].CookedValue",
+        ),
+    };
+    let output = match runner.run(&command) {
+        Ok(output) => output,
+        Err(e) => {
+            warn!("Could not get the load of {}: {}", server_name, e);
+            return vec![
+                CheckResult::new(Status::Critical, format!("Error: {}", e)).with_error(e.as_ref())
+            ];
+        }
+    };
+
+    if platform.os == Os::Windows {
+        return vec![match output.trim().parse::<f64>() {
+            Ok(queue) => CheckResult::new(
+                Status::from_thresholds(queue, warning, critical),
+                format!("processor queue {:.2} @ {}", queue, server_name),
+            )
+            .with_metric("processor_queue", queue),
+            Err(_) => CheckResult::new(Status::Critical, "Error: Could not parse processor queue"),
+        }];
+    }
+
+    // Linux prints `load average: 0.52, 0.58, 0.59`, the BSDs `load averages: 0.52, 0.58, 0.59`
+    // and macOS `load averages: 0.52 0.58 0.59`, while `/proc/loadavg` starts with `0.52 0.58 0.59`
+    let load_parts = match platform.shell {
+        ShellProfile::Busybox if platform.os == Os::Linux => Some(output.as_str()),
+        _ => output
+            .split("load average")
+            .nth(1)
+            .and_then(|parts| parts.split_once(':'))
+            .map(|(_, parts)| parts),
+    };
+    let load = if let Some(parts) = load_parts {
+        parts
+            .split(|c: char| c == ',' || c.is_whitespace())
+            .filter(|s| !s.is_empty())
+            .nth(match interval {
+                1 => 0,
+                5 => 1,
+                15 => 2,
+                _ => 0,
+            })
+            .and_then(|s| s.parse::<f64>().ok())
+    } else {
+        None
+    };
+
+    let result = match load {
+        Some(load) => CheckResult::new(
+            Status::from_thresholds(load, warning, critical),
+            format!("load {:.2} ({}min) @ {}", load, interval, server_name),
+        )
+        .with_metric(format!("load{}", interval), load),
+        None => CheckResult::new(Status::Critical, "Error: Could not parse load average"),
+    };
+    vec![result]
+}
+
+/// Samples the CPU utilization of a remote server and classifies it against thresholds, along
+/// with the share of time spent waiting for I/O.
+///
+/// The load average counts the processes waiting for a disk as well as those running, so that a
+/// storage server can be loaded while its processors are idle. This check measures the actual
+/// utilization instead, from two samples of the cumulated CPU times taken one second apart: the
+/// first line of `/proc/stat` on Linux (whatever the shell profile) and `kern.cp_time` on FreeBSD.
+/// macOS reports its utilization with `top`, and Windows with the `% Processor Time` counter,
+/// neither exposing the iowait.
+///
+/// # Arguments
+///
+/// * `runner` - Runs the commands on the server, e.g. an established SSH `Session`.
+/// * `server_name` - The name of the server, used in the messages of the results.
+/// * `platform` - The operating system of the server, selecting the command.
+/// * `warning` - Optional utilization, in percent, at which the result becomes a warning.
+/// * `critical` - Utilization, in percent, at which the result becomes critical.
+/// * `iowait_warning` - Optional iowait, in percent, at which the iowait becomes a warning.
+/// * `iowait_critical` - Optional iowait, in percent, at which the iowait becomes critical.
+///
+/// # Returns
+///
+/// Returns a `CheckResult` with the utilization, the time spent busy outside of iowait, and on
+/// Linux a second one with the iowait, with the `usage` and `iowait` metrics. The iowait is `Ok`
+/// when no threshold is set for it.
+///
+/// # Errors
+///
+/// If the command fails or its output cannot be parsed, a critical result indicating the error is
+/// returned.
+///
+/// # Examples
+///
+/// ```rust
+/// let session = // Assume `session` is an established SSH `Session`.
+/// let results = cpu(&session, "example_server", Platform::default(), Some(80.0), 95.0, None, Some(30.0));
+/// results.iter().for_each(|r| println!("{}", r));
+/// ```
+///
+/// This prints something like `✅ cpu 12.5% @ example_server` and `⚠️ iowait 31.0% @ example_server`.
+pub fn cpu(
+    runner: &dyn CommandRunner,
+    server_name: &str,
+    platform: Platform,
+    warning: Option<f64>,
+    critical: f64,
+    iowait_warning: Option<f64>,
+    iowait_critical: Option<f64>,
+) -> Vec<CheckResult> {
+    let command = match platform.os {
+        Os::Linux => "head -n 1 /proc/stat; sleep 1; head -n 1 /proc/stat".to_string(),
+        Os::Freebsd => "sysctl -n kern.cp_time; sleep 1; sysctl -n kern.cp_time".to_string(),
+        Os::Macos => "top -l 2 -n 0 -s 1 | grep 'CPU usage'".to_string(),
+        Os::Windows => platform::powershell(
+            r"(Get-Counter '\Processor(_Total)\% Processor Time').CounterSamples[0].CookedValue",
+        ),
+    };
+    let output = match runner.run(&command) {
+        Ok(output) => output,
+        Err(e) => {
+            warn!("Could not get the CPU usage of {}: {}", server_name, e);
+            return vec![
+                CheckResult::new(Status::Critical, format!("Error: {}", e)).with_error(e.as_ref())
+            ];
+        }
+    };
+
+    let usage = match platform.os {
+        Os::Linux | Os::Freebsd => cpu_times_usage(&output, platform.os),
+        // The first sample of `top` covers the time since boot, the last one the past second
+        Os::Macos => Regex::new(r"([\d.]+)% idle")
+            .expect("valid regular expression")
+            .captures_iter(&output)
+            .last()
+            .and_then(|captures| captures[1].parse::<f64>().ok())
+            .map(|idle| (100.0 - idle, None)),
+        Os::Windows => output.trim().parse::<f64>().ok().map(|usage| (usage, None)),
+    };
+    let Some((usage, iowait)) = usage else {
+        return vec![CheckResult::new(
+            Status::Critical,
+            "Error: Could not parse CPU usage",
+        )];
+    };
+
+    let mut results = vec![CheckResult::new(
+        Status::from_thresholds(usage, warning, critical),
+        format!("cpu {:.1}% @ {}", usage, server_name),
+    )
+    .with_metric("usage", usage)];
+    if let Some(iowait) = iowait {
+        results.push(
+            CheckResult::new(
+                Status::from_thresholds(
+                    iowait,
+                    iowait_warning,
+                    iowait_critical.unwrap_or(f64::INFINITY),
+                ),
+                format!("iowait {:.1}% @ {}", iowait, server_name),
+            )
+            .with_metric("iowait", iowait),
+        );
+    }
+    results
+}
+
+/// Computes the utilization and iowait, in percent, between two samples of the cumulated CPU
+/// times, one per line: `cpu user nice system idle iowait irq softirq steal guest guest_nice` on
+/// Linux, the guest time being included in the user time, and `user nice sys intr idle` on
+/// FreeBSD, which has no iowait.
+fn cpu_times_usage(output: &str, os: Os) -> Option<(f64, Option<f64>)> {
+    let (fields, idle, iowait) = match os {
+        Os::Freebsd => (5, 4, None),
+        _ => (8, 3, Some(4)),
+    };
+    let samples: Vec<Vec<u64>> = output
+        .lines()
+        .map(|line| {
+            line.split_whitespace()
+                .filter_map(|s| s.parse().ok())
+                .take(fields)
+                .collect::<Vec<u64>>()
+        })
+        .filter(|times| times.len() > idle.max(iowait.unwrap_or(0)))
+        .collect();
+    let [first, second] = samples.as_slice() else {
+        return None;
+    };
+
+    let delta = |i: usize| {
+        second
+            .get(i)
+            .unwrap_or(&0)
+            .saturating_sub(*first.get(i).unwrap_or(&0))
+    };
+    let total: u64 = (0..fields).map(delta).sum();
+    if total == 0 {
+        return None;
+    }
+    let percent = |time: u64| 100.0 * time as f64 / total as f64;
+    let waiting = iowait.map_or(0, delta);
+    Some((
+        percent(total - delta(idle) - waiting),
+        iowait.map(|_| percent(waiting)),
+    ))
+}
+
+/// Reads the boot time of a remote server and reports for how long it has been up.
+///
+/// The boot time is read as a Unix timestamp, from the `btime` line of `/proc/stat` on Linux
+/// (including BusyBox systems, whose `uptime` has no `-s`), from `kern.boottime` on FreeBSD and
+/// macOS, and from the `LastBootUpTime` of the operating system on Windows. Unlike the local time
+/// printed by `uptime -s`, it does not depend on the timezone of the server.
+///
+/// # Arguments
+///
+/// * `runner` - Runs the commands on the server, e.g. an established SSH `Session`.
+/// * `server_name` - The name of the server, used in the message of the result.
+/// * `platform` - The operating system of the server, selecting the command.
+///
+/// # Returns
+///
+/// Returns an `Ok` result such as "up 12 days @ web01", with the boot time as its observation,
+/// which the monitor compares with the previous run to report the reboots (see `State::rebooted`).
+///
+/// # Errors
+///
+/// If the command fails or its output cannot be parsed, a critical result indicating the error is
+/// returned.
+pub fn uptime(
+    runner: &dyn CommandRunner,
+    server_name: &str,
+    platform: Platform,
+) -> Vec<CheckResult> {
+    let command = match platform.os {
+        Os::Linux => "grep btime /proc/stat".to_string(),
+        Os::Macos | Os::Freebsd => "sysctl -n kern.boottime".to_string(),
+        Os::Windows => platform::powershell(
+            "([DateTimeOffset](Get-CimInstance Win32_OperatingSystem).LastBootUpTime)\
+             .ToUnixTimeSeconds()",
+        ),
+    };
+    let output = match runner.run(&command) {
+        Ok(output) => output,
+        Err(e) => {
+            warn!("Could not get the boot time of {}: {}", server_name, e);
+            return vec![
+                CheckResult::new(Status::Critical, format!("Error: {}", e)).with_error(e.as_ref())
+            ];
+        }
+    };
+
+    // `btime 1760000000`, `{ sec = 1760000000, usec = 0 } Thu Oct  9 08:53:20 2025` or a number
+    let boot = Regex::new(r"\d+")
+        .expect("valid regular expression")
+        .find(&output)
+        .and_then(|m| m.as_str().parse::<i64>().ok())
+        .and_then(|timestamp| chrono::DateTime::from_timestamp(timestamp, 0));
+    let Some(boot) = boot else {
+        return vec![CheckResult::new(
+            Status::Critical,
+            "Error: Could not parse boot time",
+        )];
+    };
+    let up = utils::format_duration(chrono::Utc::now() - boot);
+    vec![
+        CheckResult::new(Status::Ok, format!("up {} @ {}", up, server_name))
+            .with_observation(Observation::Boot(boot)),
+    ]
+}
+
+/// Reads the entropy available to the kernel random number generator of a Linux server, from
+/// `/proc/sys/kernel/random/entropy_avail`, and its pool size.
+///
+/// Since Linux 5.18, the generator no longer blocks once seeded and the available entropy always
+/// equals the pool size of 256 bits, so the check mostly matters for older kernels, whose reads of
+/// `/dev/random` block when the entropy is low unless `rngd` or `haveged` feed it.
+///
+/// # Arguments
+///
+/// * `runner` - Runs the commands on the server, e.g. an established SSH `Session`.
+/// * `server_name` - The name of the server, used in the message of the result.
+/// * `platform` - The operating system of the server, only Linux being supported.
+/// * `min_entropy` - Entropy in bits below which the result is critical.
+/// * `entropy_warning` - Entropy in bits below which the result is a warning.
+///
+/// # Returns
+///
+/// Returns a result such as "entropy 3012/4096 bits @ web01", with the `entropy` metric.
+///
+/// # Errors
+///
+/// If the command fails or its output cannot be parsed, or on other systems than Linux, a
+/// critical result indicating the error is returned.
+pub fn entropy(
+    runner: &dyn CommandRunner,
+    server_name: &str,
+    platform: Platform,
+    min_entropy: u32,
+    entropy_warning: Option<u32>,
+) -> Vec<CheckResult> {
+    if platform.os != Os::Linux {
+        return vec![CheckResult::new(
+            Status::Critical,
+            "Error: the entropy is only read on Linux",
+        )];
+    }
+    let command = "cat /proc/sys/kernel/random/entropy_avail /proc/sys/kernel/random/poolsize";
+    let output = match runner.run(command) {
+        Ok(output) => output,
+        Err(e) => {
+            warn!("Could not read the entropy of {}: {}", server_name, e);
+            return vec![
+                CheckResult::new(Status::Critical, format!("Error: {}", e)).with_error(e.as_ref())
+            ];
+        }
+    };
+
+    let values: Vec<u32> = output
+        .split_whitespace()
+        .filter_map(|value| value.parse().ok())
+        .collect();
+    let [entropy, pool_size] = values[..] else {
+        return vec![CheckResult::new(
+            Status::Critical,
+            "Error: Could not parse the entropy",
+        )];
+    };
+    let status = if entropy < min_entropy {
+        Status::Critical
+    } else if entropy_warning.is_some_and(|warning| entropy < warning) {
+        Status::Warning
+    } else {
+        Status::Ok
+    };
+    vec![CheckResult::new(
+        status,
+        format!("entropy {}/{} bits @ {}", entropy, pool_size, server_name),
+    )
+    .with_metric("entropy", f64::from(entropy))]
+}
+
+/// Command printing the running kernel, followed by the versions of the installed kernel packages,
+/// with `rpm` or `dpkg-query`.
+const KERNEL_COMMAND: &str = "uname -r && \
+     if command -v rpm >/dev/null && rpm -q kernel >/dev/null 2>&1; then \
+     rpm -q kernel --queryformat '%{VERSION}-%{RELEASE}.%{ARCH}\\n'; \
+     elif command -v dpkg-query >/dev/null; then \
+     dpkg-query -W -f '${Status} ${Package}\\n' 'linux-image-[0-9]*' \
+     | sed -n 's/^install ok installed linux-image-//p'; fi";
+
+/// Compares the running kernel of a Linux server with the newest installed kernel package, the
+/// kernel updates only applying after a reboot.
+///
+/// The kernel packages are listed with `rpm` (the `kernel` package, e.g. `5.14.0-362.8.1.el9_3.x86_64`)
+/// or `dpkg-query` (the `linux-image-*` packages, e.g. `linux-image-6.1.0-18-amd64`), their
+/// versions matching the output of `uname -r`. On Debian and Ubuntu, only the packages of the
+/// flavor of the running kernel are compared, e.g. `amd64` or `cloud-amd64`.
+///
+/// # Arguments
+///
+/// * `runner` - Runs the commands on the server, e.g. an established SSH `Session`.
+/// * `server_name` - The name of the server, used in the message of the result.
+/// * `platform` - The operating system of the server, only Linux being supported.
+/// * `on_outdated` - Status of the result when a newer kernel is installed.
+///
+/// # Returns
+///
+/// Returns a result such as "running kernel 6.1.0-18-amd64, 6.1.0-21-amd64 installed, reboot
+/// needed @ web01", with the status given by `on_outdated`, or "running the newest kernel
+/// 6.1.0-21-amd64 @ web01".
+///
+/// # Errors
+///
+/// If the command fails, if no kernel package is found, e.g. without `rpm` or `dpkg`, or on other
+/// systems than Linux, a critical result indicating the error is returned.
+pub fn kernel(
+    runner: &dyn CommandRunner,
+    server_name: &str,
+    platform: Platform,
+    on_outdated: Status,
+) -> Vec<CheckResult> {
+    if platform.os != Os::Linux {
+        return vec![CheckResult::new(
+            Status::Critical,
+            "Error: the kernel packages are only compared on Linux",
+        )];
+    }
+    let output = match runner.run(KERNEL_COMMAND) {
+        Ok(output) => output,
+        Err(e) => {
+            warn!("Could not list the kernels of {}: {}", server_name, e);
+            return vec![
+                CheckResult::new(Status::Critical, format!("Error: {}", e)).with_error(e.as_ref())
+            ];
+        }
+    };
+
+    let mut lines = output
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty());
+    let running = lines.next().unwrap_or_default();
+    let newest = lines
+        .filter(|version| kernel_flavor(version) == kernel_flavor(running))
+        .max_by(|a, b| compare_versions(a, b));
+    let result = match newest {
+        None => CheckResult::new(
+            Status::Critical,
+            format!(
+                "Error: no kernel package found with rpm or dpkg @ {}",
+                server_name
+            ),
+        ),
+        Some(newest) if compare_versions(newest, running).is_gt() => CheckResult::new(
+            on_outdated,
+            format!(
+                "running kernel {}, {} installed, reboot needed @ {}",
+                running, newest, server_name
+            ),
+        ),
+        Some(_) => CheckResult::new(
+            Status::Ok,
+            format!("running the newest kernel {} @ {}", running, server_name),
+        ),
+    };
+    vec![result]
+}
+
+/// Returns the flavor of a Debian or Ubuntu kernel, starting at the first dash followed by a
+/// letter, e.g. `-cloud-amd64` for `6.1.0-18-cloud-amd64`, or an empty string for RPM kernels.
+fn kernel_flavor(version: &str) -> &str {
+    version
+        .char_indices()
+        .find(|&(i, c)| c == '-' && version[i + 1..].starts_with(|c: char| c.is_alphabetic()))
+        .map_or("", |(i, _)| &version[i..])
+}
+
+/// Compares two package versions, their numbers numerically and the rest alphabetically, so that
+/// `6.1.0-21` is newer than `6.1.0-9`.
+fn compare_versions(a: &str, b: &str) -> std::cmp::Ordering {
+    let chunks = |version: &str| -> Vec<String> {
+        let mut chunks: Vec<String> = vec![];
+        let mut previous: Option<bool> = None;
+        for c in version.chars() {
+            let digit = c.is_ascii_digit();
+            match chunks.last_mut() {
+                Some(chunk) if previous == Some(digit) => chunk.push(c),
+                _ => chunks.push(c.to_string()),
+            }
+            previous = Some(digit);
+        }
+        chunks
+    };
+    for (a, b) in chunks(a).iter().zip(chunks(b).iter()) {
+        let ordering = match (a.parse::<u64>(), b.parse::<u64>()) {
+            (Ok(a), Ok(b)) => a.cmp(&b),
+            _ => a.cmp(b),
+        };
+        if ordering.is_ne() {
+            return ordering;
+        }
+    }
+    chunks(a).len().cmp(&chunks(b).len())
+}
+
+/// Command listing the OOM kills of the past day from the journal, or from the kernel ring buffer
+/// when journald is not available, with absolute timestamps.
+const OOM_KILLS_COMMAND: &str = "{ journalctl -k -q --no-pager -o short-unix --since '-1 day' \
+     2>/dev/null || dmesg --time-format iso; } | grep 'Killed process' || true";
+
+/// Lists the processes killed by the out-of-memory killer of a Linux server, the single signal
+/// explaining most of the outages that leave no trace in the other checks.
+///
+/// The kernel messages of the past day are read from the journal (`journalctl -k`), or from
+/// `dmesg` on servers without journald, which both require the user to be allowed to read the
+/// kernel log (e.g. by being in the `systemd-journal` or `adm` group). Each kill, e.g.
+/// `Out of memory: Killed process 1234 (java) total-vm:...`, is reported with its time.
+///
+/// # Arguments
+///
+/// * `runner` - Runs the commands on the server, e.g. an established SSH `Session`.
+/// * `server_name` - The name of the server, used in the messages of the results.
+/// * `platform` - The operating system of the server, only Linux being supported.
+/// * `on_kill` - Status of the results reporting a kill.
+///
+/// # Returns
+///
+/// Returns a result per kill, in chronological order, such as "java (1234) killed by the OOM
+/// killer 14 minutes ago @ web01", with the time of the kill as its observation so that the
+/// monitor only reports the kills that are new since the previous run (see `State::new_kill`).
+/// Returns a single `Ok` result if no process was killed.
+///
+/// # Errors
+///
+/// If the command fails, or on other systems than Linux, a critical result indicating the error
+/// is returned.
+pub fn oom_kills(
+    runner: &dyn CommandRunner,
+    server_name: &str,
+    platform: Platform,
+    on_kill: Status,
+) -> Vec<CheckResult> {
+    if platform.os != Os::Linux {
+        return vec![CheckResult::new(
+            Status::Critical,
+            "Error: OOM kills are only detected on Linux",
+        )];
+    }
+    let output = match runner.run(OOM_KILLS_COMMAND) {
+        Ok(output) => output,
+        Err(e) => {
+            warn!("Could not read the kernel log of {}: {}", server_name, e);
+            return vec![
+                CheckResult::new(Status::Critical, format!("Error: {}", e)).with_error(e.as_ref())
+            ];
+        }
+    };
+
+    let re = Regex::new(r"Killed process (\d+) \(([^)]*)\)").expect("valid regular expression");
+    let now = chrono::Utc::now();
+    let mut results: Vec<CheckResult> = output
+        .lines()
+        .filter_map(|line| {
+            let captures = re.captures(line)?;
+            let time = oom_kill_time(line.split_whitespace().next()?)?;
+            Some(
+                CheckResult::new(
+                    on_kill,
+                    format!(
+                        "{} ({}) killed by the OOM killer {} ago @ {}",
+                        &captures[2],
+                        &captures[1],
+                        utils::format_duration(now - time),
+                        server_name
+                    ),
+                )
+                .with_observation(Observation::Kill(time)),
+            )
+        })
+        .collect();
+    if results.is_empty() {
+        results.push(CheckResult::new(
+            Status::Ok,
+            format!("no OOM kills @ {}", server_name),
+        ));
+    }
+    results
+}
+
+/// Parses the timestamp of a kernel message, in seconds since the epoch as printed by
+/// `journalctl -o short-unix` (`1760000000.123456`), or in ISO 8601 as printed by `dmesg
+/// --time-format iso` (`2026-10-16T10:15:30,123456+02:00`).
+fn oom_kill_time(timestamp: &str) -> Option<chrono::DateTime<chrono::Utc>> {
+    if let Ok(seconds) = timestamp.parse::<f64>() {
+        return chrono::DateTime::from_timestamp_micros((seconds * 1e6).round() as i64);
+    }
+    chrono::DateTime::parse_from_rfc3339(&timestamp.replacen(',', ".", 1))
+        .ok()
+        .map(|time| time.to_utc())
+}
+
+/// Command listing the logins and the failed logins of the past day, the latter requiring root.
+const LOGINS_COMMAND: &str = "last -w --time-format iso --since -1day && { echo '-- failed'; \
+     lastb -w --time-format iso --since -1day -n 1000 2>/dev/null || echo '!lastb'; }";
+
+/// Lists the logins and the failed login attempts of the past day on a Linux server, with `last`
+/// and `lastb`, which only root can run.
+///
+/// # Arguments
+///
+/// * `runner` - Runs the commands on the server, e.g. an established SSH `Session`.
+/// * `server_name` - The name of the server, used in the messages of the results.
+/// * `platform` - The operating system of the server, only Linux being supported.
+/// * `allowed_users` - The users expected to log in.
+///
+/// # Returns
+///
+/// Returns a result per login, in chronological order, such as "alice logged in from 192.0.2.5 @
+/// web01", critical if the user is not allowed, with the time of the login as its observation,
+/// and a result per failed attempt, whose message is its source, with its time as a failed login
+/// observation. The monitor only keeps those that are new since the previous run, and summarizes
+/// them (see `State::last_login`). When `lastb` cannot be run, a warning observing that the failed
+/// logins are unreadable replaces the failed attempts.
+///
+/// # Errors
+///
+/// If `last` fails, or on other systems than Linux or with the `busybox` shell profile, a
+/// critical result indicating the error is returned.
+pub fn logins(
+    runner: &dyn CommandRunner,
+    server_name: &str,
+    platform: Platform,
+    allowed_users: &[String],
+) -> Vec<CheckResult> {
+    if platform.os != Os::Linux || platform.shell == ShellProfile::Busybox {
+        return vec![CheckResult::new(
+            Status::Critical,
+            "Error: the logins are only listed with the last of util-linux",
+        )];
+    }
+    let output = match runner.run(LOGINS_COMMAND) {
+        Ok(output) => output,
+        Err(e) => {
+            warn!("Could not list the logins of {}: {}", server_name, e);
+            return vec![
+                CheckResult::new(Status::Critical, format!("Error: {}", e)).with_error(e.as_ref())
+            ];
+        }
+    };
+
+    let (logins, failed) = output.split_once("-- failed").unwrap_or((&output, ""));
+    // alice    pts/0    192.0.2.5    2024-05-01T10:00:00+00:00 - 2024-05-01T11:00:00+00:00  (01:00)
+    let parse = |section: &str| -> Vec<(String, String, chrono::DateTime<chrono::Utc>)> {
+        let mut entries: Vec<(String, String, chrono::DateTime<chrono::Utc>)> = section
+            .lines()
+            .filter_map(|line| {
+                let columns: Vec<&str> = line.split_whitespace().collect();
+                if matches!(columns.first(), Some(&"reboot" | &"shutdown" | &"runlevel"))
+                    || columns.get(1) == Some(&"begins")
+                {
+                    return None;
+                }
+                let (index, time) = columns.iter().enumerate().find_map(|(i, column)| {
+                    Some((i, chrono::DateTime::parse_from_rfc3339(column).ok()?))
+                })?;
+                // The host is missing for the local logins, e.g. on tty1
+                let from = match index {
+                    i if i >= 3 => format!("from {}", columns[2]),
+                    _ => format!("on {}", columns.get(1).unwrap_or(&"")),
+                };
+                Some((columns[0].to_string(), from, time.to_utc()))
+            })
+            .collect();
+        // last lists the latest first
+        entries.reverse();
+        entries
+    };
+
+    let mut results: Vec<CheckResult> = parse(logins)
+        .into_iter()
+        .map(|(user, from, time)| {
+            let status = if allowed_users.contains(&user) {
+                Status::Ok
+            } else {
+                Status::Critical
+            };
+            CheckResult::new(
+                status,
+                format!("{} logged in {} @ {}", user, from, server_name),
+            )
+            .with_observation(Observation::Login(time))
+        })
+        .collect();
+    if failed.trim() == "!lastb" {
+        results.push(
+            CheckResult::new(
+                Status::Warning,
+                format!(
+                    "failed logins not readable, lastb requires root @ {}",
+                    server_name
+                ),
+            )
+            .with_observation(Observation::FailedLoginsUnreadable),
+        );
+    } else {
+        results.extend(parse(failed).into_iter().map(|(_, from, time)| {
+            let source = from.strip_prefix("from ").unwrap_or("unknown");
+            CheckResult::new(Status::Ok, source).with_observation(Observation::FailedLogin(time))
+        }));
+    }
+    results
+}
+
+/// Command reading the mode of SELinux, or the modes of the AppArmor profiles, followed by the
+/// number of denials logged in the journal in the past hour.
+const SECURITY_MODULE_COMMAND: &str = "if [ -r /sys/fs/selinux/enforce ]; then \
+     echo \"selinux $(cat /sys/fs/selinux/enforce)\"; \
+     elif [ \"$(cat /sys/module/apparmor/parameters/enabled 2>/dev/null)\" = Y ]; then echo apparmor; \
+     cat /sys/kernel/security/apparmor/profiles 2>/dev/null || echo '!profiles'; \
+     else echo disabled; fi; \
+     echo \"denials $(journalctl -q --no-pager --since '-1 hour' -o cat 2>/dev/null \
+     | grep -cE 'avc: +denied|apparmor=\"DENIED\"')\"";
+
+/// Verifies the mode of the mandatory access control of a Linux server, SELinux or AppArmor, and
+/// counts the denials of the past hour.
+///
+/// The mode of SELinux is read from `/sys/fs/selinux/enforce`, and the modes of the AppArmor
+/// profiles from `/sys/kernel/security/apparmor/profiles`, AppArmor complaining as soon as one of
+/// its profiles is in complain mode. The profiles are only readable by root. The denials, e.g.
+/// `avc:  denied { read }` or `apparmor="DENIED"`, are counted in the journal, which requires the
+/// user to be allowed to read it (e.g. by being in the `systemd-journal` or `adm` group).
+///
+/// # Arguments
+///
+/// * `runner` - Runs the commands on the server, e.g. an established SSH `Session`.
+/// * `server_name` - The name of the server, used in the messages of the results.
+/// * `platform` - The operating system of the server, only Linux being supported.
+/// * `expected` - The expected mode.
+/// * `denials_warning` - Number of denials in the past hour at which a warning is reported.
+/// * `max_denials` - Number of denials in the past hour at which a critical alert is reported.
+///
+/// # Returns
+///
+/// Returns a result for the mode, such as "SELinux enforcing @ web01", critical when it differs
+/// from the expected one or when neither SELinux nor AppArmor is enabled, e.g. "SELinux
+/// permissive, expected enforcing @ web01", with the `enforcing` metric (1 or 0), and a result
+/// for the denials, such as "3 denials in the past hour @ web01", with the `denials` metric.
+///
+/// # Errors
+///
+/// If the command fails, or on other systems than Linux, a critical result indicating the error
+/// is returned.
+pub fn security_module(
+    runner: &dyn CommandRunner,
+    server_name: &str,
+    platform: Platform,
+    expected: Enforcement,
+    denials_warning: Option<u32>,
+    max_denials: Option<u32>,
+) -> Vec<CheckResult> {
+    if platform.os != Os::Linux {
+        return vec![CheckResult::new(
+            Status::Critical,
+            "Error: SELinux and AppArmor are only verified on Linux",
+        )];
+    }
+    let output = match runner.run(SECURITY_MODULE_COMMAND) {
+        Ok(output) => output,
+        Err(e) => {
+            warn!(
+                "Could not read the security module of {}: {}",
+                server_name, e
+            );
+            return vec![
+                CheckResult::new(Status::Critical, format!("Error: {}", e)).with_error(e.as_ref())
+            ];
+        }
+    };
+
+    let expected_name = |module: &str| match (expected, module) {
+        (Enforcement::Enforcing, _) => "enforcing",
+        (Enforcement::Complaining, "SELinux") => "permissive",
+        (Enforcement::Complaining, _) => "complaining",
+    };
+    let mut lines = output.lines().map(str::trim);
+    let mode = match lines.next().unwrap_or_default() {
+        "selinux 1" => Some(("SELinux", Enforcement::Enforcing, "enforcing".to_string())),
+        "selinux 0" => Some((
+            "SELinux",
+            Enforcement::Complaining,
+            "permissive".to_string(),
+        )),
+        "apparmor" => {
+            // /sys/kernel/security/apparmor/profiles: "/usr/sbin/cupsd (enforce)"
+            let profiles: Vec<&str> = lines
+                .clone()
+                .take_while(|line| !line.starts_with("denials "))
+                .collect();
+            let complaining = profiles
+                .iter()
+                .filter(|profile| profile.ends_with("(complain)"))
+                .count();
+            match profiles.as_slice() {
+                ["!profiles"] => None,
+                [] => Some((
+                    "AppArmor",
+                    Enforcement::Complaining,
+                    "enabled without profiles".to_string(),
+                )),
+                _ if complaining > 0 => Some((
+                    "AppArmor",
+                    Enforcement::Complaining,
+                    format!(
+                        "complaining ({} of {} profiles)",
+                        complaining,
+                        profiles.len()
+                    ),
+                )),
+                _ => Some((
+                    "AppArmor",
+                    Enforcement::Enforcing,
+                    format!("enforcing ({} profiles)", profiles.len()),
+                )),
+            }
+        }
+        _ => Some((
+            "no SELinux or AppArmor",
+            Enforcement::Complaining,
+            String::new(),
+        )),
+    };
+    let mut results = vec![match mode {
+        Some((module, enforcement, description)) => {
+            let status = match enforcement == expected && !description.is_empty() {
+                true => Status::Ok,
+                false => Status::Critical,
+            };
+            let mut message = [module, &description].join(" ").trim().to_string();
+            if status != Status::Ok {
+                message = format!("{}, expected {}", message, expected_name(module));
+            }
+            CheckResult::new(status, format!("{} @ {}", message, server_name)).with_metric(
+                "enforcing",
+                f64::from(u8::from(enforcement == Enforcement::Enforcing)),
+            )
+        }
+        None => CheckResult::new(
+            Status::Warning,
+            format!(
+                "AppArmor enabled, its profiles are only readable by root @ {}",
+                server_name
+            ),
+        ),
+    }];
+
+    if let Some(denials) = output
+        .lines()
+        .find_map(|line| line.trim().strip_prefix("denials "))
+        .and_then(|count| count.parse::<u32>().ok())
+    {
+        let status = Status::from_thresholds(
+            f64::from(denials),
+            denials_warning.map(f64::from),
+            max_denials.map_or(f64::INFINITY, f64::from),
+        );
+        results.push(
+            CheckResult::new(
+                status,
+                format!(
+                    "{} denial{} in the past hour @ {}",
+                    denials,
+                    if denials == 1 { "" } else { "s" },
+                    server_name
+                ),
+            )
+            .with_metric("denials", f64::from(denials)),
+        );
+    }
+    results
+}
+
+/// Files compared by the `account_changes` checks without `files`.
+pub const ACCOUNT_FILES: &[&str] = &[
+    "/etc/passwd",
+    "/etc/group",
+    "/etc/sudoers",
+    "/etc/sudoers.d",
+];
+
+/// Reads the files defining the accounts and privileges of a server, e.g. `/etc/passwd` and
+/// `/etc/sudoers`, for the monitor to compare them with the hashes of their lines at the previous
+/// run (see `State::replace_file`). A directory stands for the regular files it holds, e.g. the rules of
+/// `/etc/sudoers.d`, and the files that do not exist are skipped. Windows is not supported.
+///
+/// # Arguments
+///
+/// * `runner` - Runs the commands on the server, e.g. an established SSH `Session`.
+/// * `server_name` - The name of the server, used in the messages of the results.
+/// * `platform` - The operating system of the server.
+/// * `files` - The paths of the files and directories read.
+///
+/// # Returns
+///
+/// Returns a result per file, whose message is its path and whose details are its content,
+/// observed as a file, which the monitor replaces by the changes since the previous run. A file
+/// that cannot be read, e.g. `/etc/sudoers` when not monitoring as root, gives a result whose
+/// message is its path, observed as an unreadable file.
+///
+/// # Errors
+///
+/// If the command fails, or on Windows, a critical result indicating the error is returned.
+pub fn account_files(
+    runner: &dyn CommandRunner,
+    server_name: &str,
+    platform: Platform,
+    files: &[String],
+) -> Vec<CheckResult> {
+    if platform.os == Os::Windows {
+        return vec![CheckResult::new(
+            Status::Critical,
+            "Error: the account files are not compared on Windows",
+        )];
+    }
+    // awk adds the final newline that a file may lack, so that the next header starts a line
+    let command = format!(
+        "for f in {}; do if [ -d \"$f\" ]; then [ -r \"$f\" ] || echo \"==! $f\"; \
+         set -- \"$f\"/*; else set -- \"$f\"; fi; \
+         for g in \"$@\"; do [ -f \"$g\" ] || continue; \
+         if [ -r \"$g\" ]; then echo \"==> $g\"; awk 1 \"$g\"; else echo \"==! $g\"; fi; \
+         done; done",
+        files
+            .iter()
+            .map(|file| platform::shell_quote(file))
+            .collect::<Vec<_>>()
+            .join(" ")
+    );
+    let output = match runner.run(&command) {
+        Ok(output) => output,
+        Err(e) => {
+            warn!("Could not read the account files of {}: {}", server_name, e);
+            return vec![
+                CheckResult::new(Status::Critical, format!("Error: {}", e)).with_error(e.as_ref())
+            ];
+        }
+    };
+
+    let mut results: Vec<CheckResult> = vec![];
+    let mut content: Option<(&str, Vec<&str>)> = None;
+    let flush = |content: Option<(&str, Vec<&str>)>, results: &mut Vec<CheckResult>| {
+        if let Some((path, lines)) = content {
+            results.push(
+                CheckResult::new(Status::Ok, path)
+                    .with_details(lines.join("\n"))
+                    .with_observation(Observation::File),
+            );
+        }
+    };
+    for line in output.lines() {
+        if let Some(path) = line.strip_prefix("==> ") {
+            flush(content.take(), &mut results);
+            content = Some((path, vec![]));
+        } else if let Some(path) = line.strip_prefix("==! ") {
+            flush(content.take(), &mut results);
+            results.push(
+                CheckResult::new(Status::Warning, path)
+                    .with_observation(Observation::UnreadableFile),
+            );
+        } else if let Some((_, lines)) = &mut content {
+            lines.push(line);
+        }
+    }
+    flush(content, &mut results);
+    if results.is_empty() {
+        return vec![CheckResult::new(
+            Status::Critical,
+            format!(
+                "Error: none of {} found @ {}",
+                files.join(", "),
+                server_name
+            ),
+        )];
+    }
+    results
+}
+
+/// Counts the zombie processes of a server, and those blocked in uninterruptible sleep (`D`
+/// state), usually waiting for a disk or an NFS server that stopped answering.
+///
+/// The states are listed with `ps -A -o stat=,pid=,comm=`, or read from `/proc/<pid>/stat` with
+/// the `busybox` shell profile, whose `ps` has no `-o` in its smallest builds. Windows is not
+/// supported.
+///
+/// # Arguments
+///
+/// * `runner` - Runs the commands on the server, e.g. an established SSH `Session`.
+/// * `server_name` - The name of the server, used in the messages of the results.
+/// * `platform` - The operating system and shell profile of the server, selecting the command.
+/// * `zombies_warning` - Optional number of zombies at which the result becomes a warning.
+/// * `max_zombies` - Number of zombies at which the result becomes critical.
+/// * `blocked_warning` - Optional number of blocked processes at which the result becomes a
+///   warning.
+/// * `max_blocked` - Optional number of blocked processes at which the result becomes critical.
+///
+/// # Returns
+///
+/// Returns two results, such as "2 zombie processes @ web01" and "0 blocked processes @ web01",
+/// with the `zombies` and `blocked` metrics. The results crossing a threshold list the processes
+/// (PID and command) as details.
+///
+/// # Errors
+///
+/// If the command fails, or on Windows, a critical result indicating the error is returned.
+pub fn process_states(
+    runner: &dyn CommandRunner,
+    server_name: &str,
+    platform: Platform,
+    zombies_warning: Option<u32>,
+    max_zombies: u32,
+    blocked_warning: Option<u32>,
+    max_blocked: Option<u32>,
+) -> Vec<CheckResult> {
+    let command = match platform.os {
+        Os::Windows => {
+            return vec![CheckResult::new(
+                Status::Critical,
+                "Error: process states are not supported on Windows",
+            )]
+        }
+        // The processes ending between the expansion of the glob and their read make `cat` fail
+        Os::Linux if platform.shell == ShellProfile::Busybox => {
+            "cat /proc/[0-9]*/stat 2>/dev/null; true"
+        }
+        Os::Linux | Os::Macos | Os::Freebsd => "ps -A -o stat=,pid=,comm=",
+    };
+    let output = match runner.run(command) {
+        Ok(output) => output,
+        Err(e) => {
+            warn!("Could not list the processes of {}: {}", server_name, e);
+            return vec![
+                CheckResult::new(Status::Critical, format!("Error: {}", e)).with_error(e.as_ref())
+            ];
+        }
+    };
+
+    // `Z 123 defunct` from `ps`, `123 (defunct) Z 1 ...` from `/proc/<pid>/stat`, whose command
+    // can hold spaces and parentheses
+    let processes: Vec<(char, String)> = output
+        .lines()
+        .filter_map(|line| match platform.shell {
+            ShellProfile::Busybox => {
+                let (pid, rest) = line.split_once(" (")?;
+                let (comm, rest) = rest.rsplit_once(')')?;
+                let state = rest.trim_start().chars().next()?;
+                Some((state, format!("{} {}", pid, comm)))
+            }
+            ShellProfile::Standard => {
+                let (state, process) = line.trim_start().split_once(char::is_whitespace)?;
+                Some((state.chars().next()?, process.trim().to_string()))
+            }
+        })
+        .collect();
+
+    [
+        ('Z', "zombie", "zombies", zombies_warning, Some(max_zombies)),
+        ('D', "blocked", "blocked", blocked_warning, max_blocked),
+    ]
+    .into_iter()
+    .map(|(state, adjective, metric, warning, critical)| {
+        let matching: Vec<&str> = processes
+            .iter()
+            .filter(|(s, _)| *s == state)
+            .map(|(_, process)| process.as_str())
+            .collect();
+        let count = matching.len();
+        let status = Status::from_thresholds(
+            count as f64,
+            warning.map(f64::from),
+            critical.map_or(f64::INFINITY, f64::from),
+        );
+        let message = format!(
+            "{} {} process{} @ {}",
+            count,
+            adjective,
+            if count == 1 { "" } else { "es" },
+            server_name
+        );
+        let result = CheckResult::new(status, message).with_metric(metric, count as f64);
+        match status {
+            Status::Ok => result,
+            _ => result.with_details(matching.join("\n")),
+        }
+    })
+    .collect()
+}
+
+/// Checks that network interfaces of a Linux server are up, reporting their error and drop
+/// counters, and optionally classifies their throughput against thresholds.
+///
+/// The state of every interface is read from `/sys/class/net/<interface>/operstate`, `up` and
+/// `unknown` (the state of the loopback and of many tunnels) counting as up. The counters are read
+/// from `/proc/net/dev`, twice one second apart when a threshold is set, to compute the
+/// throughput in both directions.
+///
+/// # Arguments
+///
+/// * `runner` - Runs the commands on the server, e.g. an established SSH `Session`.
+/// * `server_name` - The name of the server, used in the messages of the results.
+/// * `platform` - The operating system of the server, only Linux being supported.
+/// * `interfaces` - Names of the interfaces to check.
+/// * `warning` - Optional throughput in Mbit/s at which the result becomes a warning.
+/// * `critical` - Optional throughput in Mbit/s at which the result becomes critical.
+///
+/// # Returns
+///
+/// Returns a result per interface, critical if it is down or missing, such as "eth0 up,
+/// ↓ 12.30 Mbit/s ↑ 4.50 Mbit/s, 0 errors, 3 drops @ web01". When the throughput is sampled, the
+/// highest of both directions is the metric of the result, named after the interface.
+///
+/// # Errors
+///
+/// If a command fails, or on other systems than Linux, a critical result indicating the error is
+/// returned.
+pub fn network(
+    runner: &dyn CommandRunner,
+    server_name: &str,
+    platform: Platform,
+    interfaces: &[String],
+    warning: Option<f64>,
+    critical: Option<f64>,
+) -> Vec<CheckResult> {
+    if platform.os != Os::Linux {
+        return vec![CheckResult::new(
+            Status::Critical,
+            "Error: network interfaces are only checked on Linux",
+        )];
+    }
+    let sample = warning.is_some() || critical.is_some();
+    let states = format!(
+        "for i in {}; do echo \"$i\" $(cat \"/sys/class/net/$i/operstate\" 2>/dev/null || echo missing); done",
+        interfaces
+            .iter()
+            .map(|interface| platform::shell_quote(interface))
+            .collect::<Vec<_>>()
+            .join(" ")
+    );
+    let counters = match sample {
+        true => "cat /proc/net/dev; sleep 1; cat /proc/net/dev",
+        false => "cat /proc/net/dev",
+    };
+    let (states, counters) = match runner
+        .run(&states)
+        .and_then(|states| Ok((states, runner.run(counters)?)))
+    {
+        Ok(outputs) => outputs,
+        Err(e) => {
+            warn!("Could not read the interfaces of {}: {}", server_name, e);
+            return vec![
+                CheckResult::new(Status::Critical, format!("Error: {}", e)).with_error(e.as_ref())
+            ];
+        }
+    };
+
+    // `  eth0: rx_bytes rx_packets rx_errs rx_drop rx_fifo rx_frame rx_compressed rx_multicast
+    // tx_bytes tx_packets tx_errs tx_drop ...`, the interfaces being listed once per sample
+    let mut samples: std::collections::HashMap<&str, Vec<Vec<u64>>> = Default::default();
+    for line in counters.lines() {
+        if let Some((name, fields)) = line.split_once(':') {
+            let fields = fields
+                .split_whitespace()
+                .filter_map(|s| s.parse().ok())
+                .collect::<Vec<u64>>();
+            if fields.len() >= 12 {
+                samples.entry(name.trim()).or_default().push(fields);
+            }
+        }
+    }
+
+    interfaces
+        .iter()
+        .map(|interface| {
+            let state = states
+                .lines()
+                .find_map(|line| line.strip_prefix(&format!("{} ", interface)))
+                .unwrap_or("missing")
+                .trim();
+            match state {
+                "up" | "unknown" => {}
+                "missing" => {
+                    return CheckResult::new(
+                        Status::Critical,
+                        format!("{} not found @ {}", interface, server_name),
+                    )
+                }
+                _ => {
+                    return CheckResult::new(
+                        Status::Critical,
+                        format!("{} is {} @ {}", interface, state, server_name),
+                    )
+                }
+            }
+            let Some(sample) = samples.get(interface.as_str()) else {
+                return CheckResult::new(
+                    Status::Critical,
+                    format!("Error: no counters for {}", interface),
+                );
+            };
+            let last = &sample[sample.len() - 1];
+            let (errors, drops) = (last[2] + last[10], last[3] + last[11]);
+            let counts = format!(
+                "{} error{}, {} drop{}",
+                errors,
+                if errors == 1 { "" } else { "s" },
+                drops,
+                if drops == 1 { "" } else { "s" }
+            );
+            let throughput = match sample.as_slice() {
+                [first, second] => sample_throughput(first, second),
+                _ => None,
+            };
+            match throughput {
+                Some((rx, tx)) => {
+                    let throughput = rx.max(tx);
+                    CheckResult::new(
+                        Status::from_thresholds(
+                            throughput,
+                            warning,
+                            critical.unwrap_or(f64::INFINITY),
+                        ),
+                        format!(
+                            "{} up, ↓ {:.2} Mbit/s ↑ {:.2} Mbit/s, {} @ {}",
+                            interface, rx, tx, counts, server_name
+                        ),
+                    )
+                    .with_metric(interface.as_str(), throughput)
+                }
+                None => CheckResult::new(
+                    Status::Ok,
+                    format!("{} up, {} @ {}", interface, counts, server_name),
+                ),
+            }
+        })
+        .collect()
+}
+
+/// Computes the throughput received and sent, in Mbit/s, between two samples of the counters of
+/// an interface taken one second apart, or `None` if a counter went backwards (e.g. the
+/// interface was recreated in between).
+fn sample_throughput(first: &[u64], second: &[u64]) -> Option<(f64, f64)> {
+    let rate = |i: usize| {
+        let bytes = second[i].checked_sub(first[i])?;
+        Some(bytes as f64 * 8.0 / 1_000_000.0)
+    };
+    Some((rate(0)?, rate(8)?))
+}
+
+/// Counts the TCP connections of a server that are established and in `TIME_WAIT`, optionally
+/// those on a given port only, whether it is their local port (e.g. the connections to a web
+/// server) or their remote one (e.g. the connections of an application to an API).
+///
+/// The sockets are listed with `ss -tan` on Linux, with `netstat -tan` with the `busybox` shell
+/// profile, with `netstat -an -p tcp` on macOS and FreeBSD, whose addresses end with `.<port>`,
+/// and with `Get-NetTCPConnection` on Windows.
+///
+/// # Arguments
+///
+/// * `runner` - Runs the commands on the server, e.g. an established SSH `Session`.
+/// * `server_name` - The name of the server, used in the messages of the results.
+/// * `platform` - The operating system and shell profile of the server, selecting the command.
+/// * `port` - Optional port the connections are counted on.
+/// * `established_warning` - Optional number of established connections at which the result
+///   becomes a warning.
+/// * `max_established` - Number of established connections at which the result becomes critical.
+/// * `time_wait_warning` - Optional number of connections in `TIME_WAIT` at which the result
+///   becomes a warning.
+/// * `max_time_wait` - Optional number of connections in `TIME_WAIT` at which the result becomes
+///   critical.
+///
+/// # Returns
+///
+/// Returns two results, such as "120 established connections on port 443 @ web01" and
+/// "30 connections in TIME_WAIT on port 443 @ web01", with the `established` and `time_wait`
+/// metrics.
+///
+/// # Errors
+///
+/// If the command fails, a critical result indicating the error is returned.
+#[allow(clippy::too_many_arguments)]
+pub fn connections(
+    runner: &dyn CommandRunner,
+    server_name: &str,
+    platform: Platform,
+    port: Option<u16>,
+    established_warning: Option<u32>,
+    max_established: u32,
+    time_wait_warning: Option<u32>,
+    max_time_wait: Option<u32>,
+) -> Vec<CheckResult> {
+    // Columns of the state and of the local and remote addresses, and the separator of the port
+    let (command, state, addresses, separator) = match platform.os {
+        Os::Linux if platform.shell == ShellProfile::Busybox => {
+            ("netstat -tan".to_string(), 5, 3, ':')
+        }
+        Os::Linux => ("ss -tan".to_string(), 0, 3, ':'),
+        Os::Macos | Os::Freebsd => ("netstat -an -p tcp".to_string(), 5, 3, '.'),
+        Os::Windows => (
+            platform::powershell(
+                "Get-NetTCPConnection | \
+                 ForEach-Object { \"$($_.State) $($_.LocalPort) $($_.RemotePort)\" }",
+            ),
+            0,
+            1,
+            ':',
+        ),
+    };
+    let output = match runner.run(&command) {
+        Ok(output) => output,
+        Err(e) => {
+            warn!("Could not list the connections of {}: {}", server_name, e);
+            return vec![
+                CheckResult::new(Status::Critical, format!("Error: {}", e)).with_error(e.as_ref())
+            ];
+        }
+    };
+
+    let (mut established, mut time_wait) = (0, 0);
+    for line in output.lines() {
+        let columns: Vec<&str> = line.split_whitespace().collect();
+        let (Some(state), Some(local), Some(remote)) = (
+            columns.get(state),
+            columns.get(addresses),
+            columns.get(addresses + 1),
+        ) else {
+            continue;
+        };
+        let port_of = |address: &str| {
+            address
+                .rsplit_once(separator)
+                .map_or(address, |(_, port)| port)
+                .parse::<u16>()
+                .ok()
+        };
+        if port.is_some_and(|port| port_of(local) != Some(port) && port_of(remote) != Some(port)) {
+            continue;
+        }
+        match state.to_uppercase().replace(['-', '_'], "").as_str() {
+            "ESTAB" | "ESTABLISHED" => established += 1,
+            "TIMEWAIT" => time_wait += 1,
+            _ => {}
+        }
+    }
+
+    let on_port = port.map_or(String::new(), |port| format!(" on port {}", port));
+    let plural = |count: u32| if count == 1 { "" } else { "s" };
+    vec![
+        CheckResult::new(
+            Status::from_thresholds(
+                f64::from(established),
+                established_warning.map(f64::from),
+                f64::from(max_established),
+            ),
+            format!(
+                "{} established connection{}{} @ {}",
+                established,
+                plural(established),
+                on_port,
+                server_name
+            ),
+        )
+        .with_metric("established", f64::from(established)),
+        CheckResult::new(
+            Status::from_thresholds(
+                f64::from(time_wait),
+                time_wait_warning.map(f64::from),
+                max_time_wait.map_or(f64::INFINITY, f64::from),
+            ),
+            format!(
+                "{} connection{} in TIME_WAIT{} @ {}",
+                time_wait,
+                plural(time_wait),
+                on_port,
+                server_name
+            ),
+        )
+        .with_metric("time_wait", f64::from(time_wait)),
+    ]
+}
+
+/// Audits the TCP ports listened on by a server against an allowlist, reporting the unexpected
+/// listeners, e.g. a forgotten debug service or the backdoor of a compromise.
+///
+/// The listening sockets are listed with `ss -Htlnp` on Linux, with `netstat -tlnp` with the
+/// `busybox` shell profile, with `netstat -an -p tcp` on macOS and FreeBSD and with
+/// `Get-NetTCPConnection` on Windows. The processes are named on Linux and Windows, those of the
+/// other users only when monitoring as root.
+///
+/// # Arguments
+///
+/// * `runner` - Runs the commands on the server, e.g. an established SSH `Session`.
+/// * `server_name` - The name of the server, used in the messages of the results.
+/// * `platform` - The operating system and shell profile of the server, selecting the command.
+/// * `allowed_ports` - The ports expected to be listened on.
+/// * `ignore_loopback` - Whether the sockets listening on the loopback interface are ignored.
+///
+/// # Returns
+///
+/// Returns a critical result per unexpected port, such as "unexpected listener on port 8080
+/// (python3) @ web01", or a single result such as "4 listening ports, all allowed @ web01", with
+/// the number of unexpected ports as the `unexpected_ports` metric.
+///
+/// # Errors
+///
+/// If the command fails, a critical result indicating the error is returned.
+pub fn listening_ports(
+    runner: &dyn CommandRunner,
+    server_name: &str,
+    platform: Platform,
+    allowed_ports: &[u16],
+    ignore_loopback: bool,
+) -> Vec<CheckResult> {
+    // Columns of the state, of the local address and of the process, and the separator of the port
+    let (command, state, local, process, separator) = match platform.os {
+        Os::Linux if platform.shell == ShellProfile::Busybox => {
+            ("netstat -tlnp".to_string(), 5, 3, 6, ':')
+        }
+        Os::Linux => ("ss -Htlnp".to_string(), 0, 3, 5, ':'),
+        Os::Macos | Os::Freebsd => ("netstat -an -p tcp".to_string(), 5, 3, 6, '.'),
+        Os::Windows => (
+            platform::powershell(
+                "Get-NetTCPConnection -State Listen | ForEach-Object { \
+                 \"Listen $($_.LocalAddress):$($_.LocalPort) \
+                 $((Get-Process -Id $_.OwningProcess).ProcessName)\" }",
+            ),
+            0,
+            1,
+            2,
+            ':',
+        ),
+    };
+    let output = match runner.run(&command) {
+        Ok(output) => output,
+        Err(e) => {
+            warn!(
+                "Could not list the listening ports of {}: {}",
+                server_name, e
+            );
+            return vec![
+                CheckResult::new(Status::Critical, format!("Error: {}", e)).with_error(e.as_ref())
+            ];
+        }
+    };
+
+    // The processes listening on every port, a port being listened on by IPv4 and IPv6 sockets
+    let mut listeners: BTreeMap<u16, Vec<String>> = BTreeMap::new();
+    for line in output.lines() {
+        let columns: Vec<&str> = line.split_whitespace().collect();
+        let (Some(state), Some(local)) = (columns.get(state), columns.get(local)) else {
+            continue;
+        };
+        if !state.eq_ignore_ascii_case("LISTEN") {
+            continue;
+        }
+        let Some((address, port)) = local.rsplit_once(separator) else {
+            continue;
+        };
+        let Ok(port) = port.parse::<u16>() else {
+            continue;
+        };
+        let address = address.trim_start_matches('[').trim_end_matches(']');
+        if ignore_loopback && (address.starts_with("127.") || address == "::1") {
+            continue;
+        }
+        let names = listeners.entry(port).or_default();
+        // users:(("sshd",pid=812,fd=3)) with ss, 812/sshd with netstat
+        let name = columns
+            .get(process)
+            .map(|process| match process.split_once("((\"") {
+                Some((_, users)) => users.split('"').next().unwrap_or_default(),
+                None => process.rsplit('/').next().unwrap_or_default(),
+            });
+        if let Some(name) = name.filter(|name| !name.is_empty() && *name != "-") {
+            if !names.iter().any(|n| n == name) {
+                names.push(name.to_string());
+            }
+        }
+    }
+
+    let unexpected: Vec<(&u16, &Vec<String>)> = listeners
+        .iter()
+        .filter(|(port, _)| !allowed_ports.contains(port))
+        .collect();
+    let count = unexpected.len() as f64;
+    if unexpected.is_empty() {
+        return vec![CheckResult::new(
+            Status::Ok,
+            format!(
+                "{} listening ports, all allowed @ {}",
+                listeners.len(),
+                server_name
+            ),
+        )
+        .with_metric("unexpected_ports", 0.0)];
+    }
+    unexpected
+        .into_iter()
+        .map(|(port, names)| {
+            let names = if names.is_empty() {
+                String::new()
+            } else {
+                format!(" ({})", names.join(", "))
+            };
+            CheckResult::new(
+                Status::Critical,
+                format!(
+                    "unexpected listener on port {}{} @ {}",
+                    port, names, server_name
+                ),
+            )
+            .with_metric("unexpected_ports", count)
+        })
+        .collect()
+}
+
+/// Checks that mount points of a Linux server are mounted and respond, a hung NFS share blocking
+/// every process touching it while the other checks stay green.
+///
+/// Every mount point is looked up with `findmnt`, or in `/proc/mounts` with the `busybox` shell
+/// profile, then `stat` is run on it under `timeout`, killed once `stat_timeout` seconds have
+/// elapsed: the processes waiting for an NFS server can only be interrupted by `SIGKILL`.
+///
+/// # Arguments
+///
+/// * `runner` - Runs the commands on the server, e.g. an established SSH `Session`.
+/// * `server_name` - The name of the server, used in the messages of the results.
+/// * `platform` - The operating system and shell profile of the server, only Linux being
+///   supported.
+/// * `mounts` - The mount points to check.
+/// * `stat_timeout` - Time in seconds after which a mount point is considered hung.
+///
+/// # Returns
+///
+/// Returns a result per mount point, such as "/mnt/data mounted (nfs4 nas:/export) @ web01", or
+/// critical if it is not mounted or does not respond.
+///
+/// # Errors
+///
+/// If a command fails, or on other systems than Linux, a critical result indicating the error is
+/// returned.
+pub fn mounts(
+    runner: &dyn CommandRunner,
+    server_name: &str,
+    platform: Platform,
+    mounts: &[String],
+    stat_timeout: u64,
+) -> Vec<CheckResult> {
+    if platform.os != Os::Linux {
+        return vec![CheckResult::new(
+            Status::Critical,
+            "Error: mount points are only checked on Linux",
+        )];
+    }
+    mounts
+        .iter()
+        .map(|mount| {
+            let lookup = match platform.shell {
+                ShellProfile::Busybox => format!(
+                    "awk -v m={} '$2 == m {{ print $3, $1; found = 1 }} END {{ exit !found }}' /proc/mounts",
+                    platform::shell_quote(mount)
+                ),
+                ShellProfile::Standard => format!(
+                    "findmnt -n -o FSTYPE,SOURCE --mountpoint {}",
+                    platform::shell_quote(mount)
+                ),
+            };
+            let command = format!(
+                "if ! {}; then echo unmounted; \
+                 elif timeout -s KILL {} stat {} >/dev/null 2>&1; then echo responsive; \
+                 else echo hung; fi",
+                lookup,
+                stat_timeout,
+                platform::shell_quote(mount)
+            );
+            let output = match runner.run(&command) {
+                Ok(output) => output,
+                Err(e) => {
+                    return CheckResult::new(Status::Critical, format!("Error: {}", e))
+                        .with_error(e.as_ref())
+                }
+            };
+            let mut lines = output.lines().map(str::trim).filter(|l| !l.is_empty());
+            let (filesystem, verdict) = match (lines.next(), lines.next()) {
+                (Some(filesystem), Some(verdict)) => (filesystem, verdict),
+                (Some(verdict), None) => ("", verdict),
+                _ => ("", ""),
+            };
+            // `findmnt` aligns its columns
+            let filesystem = filesystem.split_whitespace().collect::<Vec<_>>().join(" ");
+            match verdict {
+                "responsive" => CheckResult::new(
+                    Status::Ok,
+                    format!("{} mounted ({}) @ {}", mount, filesystem, server_name),
+                ),
+                "hung" => CheckResult::new(
+                    Status::Critical,
+                    format!(
+                        "{} does not respond after {}s ({}) @ {}",
+                        mount, stat_timeout, filesystem, server_name
+                    ),
+                ),
+                "unmounted" => CheckResult::new(
+                    Status::Critical,
+                    format!("{} is not mounted @ {}", mount, server_name),
+                ),
+                _ => CheckResult::new(
+                    Status::Critical,
+                    format!("Error: Could not check the mount point {}", mount),
+                ),
+            }
+        })
+        .collect()
+}
+
+/// Lists the processes using the most CPU on a server, the header and the five busiest ones,
+/// to be attached to the results of the `load` and `cpu` checks crossing their thresholds.
+///
+/// `ps aux --sort` is a GNU extension: the BSDs and macOS sort with `-r`, BusyBox lists the
+/// processes with `top` in batch mode (after its summary lines), and Windows sorts the processes
+/// by their cumulated processor time.
+///
+/// # Errors
+///
+/// Returns an error if the command fails.
+pub fn top_processes(
+    runner: &dyn CommandRunner,
+    platform: Platform,
+) -> Result<String, Box<dyn std::error::Error>> {
+    let command = match platform.os {
+        Os::Linux if platform.shell == ShellProfile::Busybox => "top -b -n 1 | head -n 10",
+        Os::Linux => "ps aux --sort=-%cpu | head -n 6",
+        Os::Macos | Os::Freebsd => "ps aux -r | head -n 6",
+        Os::Windows => {
+            return runner.run(&platform::powershell(
+                "Get-Process | Sort-Object CPU -Descending | Select-Object -First 5 | \
+                 Format-Table -AutoSize Id, ProcessName, CPU, WorkingSet | Out-String -Width 200",
+            ))
+        }
+    };
+    runner.run(command)
+}
+
+/// Performs HTTP GET requests to a list of URLs constructed from a specified host and path segments.
+///
+/// This function iterates over a slice of URL path segments, appends each segment to the given host
+/// to form complete URLs, and then performs an HTTP GET request to each URL. The function collects
+/// the outcome of these requests into a `CheckResult` per URL. Successful requests are noted with a "✅", while failures due to
+/// either network errors or non-success HTTP status codes are marked with a "❌".
+///
+/// # Arguments
+///
+/// * `host` - A string slice representing the base host to which the URL path segments will be appended.
+/// * `urls` - A slice of `String` objects, each representing a path segment to be appended to the host
+///   to form complete URLs for the GET requests.
+///
+/// # Returns
+///
+/// Returns a `CheckResult` for each of the constructed URLs. Successful requests have an `Ok` status,
+/// the URL and response time as message, and the response time in milliseconds as metric, named
+/// after the path. Unsuccessful requests are `Critical`, with the URL and either the HTTP status code
+/// (for responses that were received but indicated failure) or the error message if the request failed
+/// to complete.
+///
+/// # Examples
+///
+/// ```rust
+/// let host = "http://example.com";
+/// let paths = vec![String::from("/api/health"), String::from("/api/status")];
+/// let results = ping(host, &paths);
+/// results.iter().for_each(|r| println!("{}", r));
+/// ```
+///
+/// This might print something like:
+///
+/// ```text
+/// ✅ http://example.com/api/health (87ms)
+/// ❌ http://example.com/api/status == `404 Not Found`
+/// ```
+///
+/// # Note
+///
+/// The function uses `reqwest::blocking::get` to perform synchronous HTTP GET requests. This means
+/// that each request will block the executing thread until a response is received or an error occurs.
+/// As such, the total execution time of this function will be at least the sum of the response times
+/// for all the requests, plus any additional overhead. For applications requiring non-blocking behavior
+/// or high levels of concurrency, consider using asynchronous requests or a different approach.
+///
+/// Error handling in this function distinguishes between two types of failures: HTTP errors, where a
+/// response was received but indicated an error through its status code, and network or other errors,
+/// where the request could not be completed at all. In the former case, the specific status code is
+/// included in the output; in the latter, the error message provided by the failure is included.
+pub fn ping(host: &str, urls: &[String]) -> Vec<CheckResult> {
+    urls.iter()
+        .map(|u| {
+            let request_url = format!("{}{}", host, u);
+            let start = Instant::now();
+            match reqwest::blocking::get(&request_url) {
+                Ok(response) => {
+                    let status = response.status();
+                    if status.is_success() {
+                        let latency = start.elapsed().as_millis();
+                        CheckResult::new(Status::Ok, format!("{} ({}ms)", request_url, latency))
+                            .with_metric(u.as_str(), latency as f64)
+                    } else {
+                        CheckResult::new(
+                            Status::Critical,
+                            format!("{} == `{}`", request_url, status),
+                        )
+                    }
+                }
+                Err(e) => CheckResult::new(
+                    Status::Critical,
+                    format!("{} is not accessible", request_url),
+                )
+                .with_details(e.to_string()),
+            }
+        })
+        .collect()
+}
+
+/// Reads and parses the temperature from a specified sensor file on a remote system via SSH.
+///
+/// This function executes a command to read the contents of a sensor file, where the temperature
+/// data is expected to be in a specific format, typically containing a string like "t=12345" where
+/// the digits represent the temperature in a unit such as millidegrees Celsius, or only the digits
+/// as in the `temp` files of the thermal zones of Linux. The function then
+/// parses this format to extract the temperature value, converts it to degrees Celsius, and returns
+/// a result classified against the warning and critical thresholds (by default, 30°C is critical).
+/// In case of errors at any step (e.g., command execution failure, regex compilation error, or parsing
+/// failure), an appropriate error message is returned.
+///
+/// # Arguments
+///
+/// * `runner` - Runs the commands on the server, e.g. an established SSH `Session`.
+/// * `platform` - The operating system of the server. On FreeBSD, `sensor` is the name of a `sysctl`
+///   variable (e.g. `dev.cpu.0.temperature`, printed as `45.0C`), while macOS is not supported.
+/// * `sensor` - The path to the sensor file on the remote system that contains the temperature data.
+/// * `warning` - Optional temperature, in °C, at which the result becomes a warning.
+/// * `critical` - Temperature, in °C, at which the result becomes critical.
+///
+/// # Returns
+///
+/// Returns a `CheckResult` that indicates the temperature reading and its status, rendered as:
+/// - "✅ XX°C" if the temperature is successfully read and below the thresholds.
+/// - "⚠️ XX°C" if the temperature is at or above the warning threshold.
+/// - "❌ XX°C" if the temperature is at or above the critical threshold.
+/// - "❌ Failed to parse temperature!" if the temperature value cannot be parsed from the file contents.
+/// - "❌ Cannot read temperature!" if the sensor data does not match the expected format.
+/// - "❌ Error: ..." if the command fails.
+///
+/// # Examples
+///
+/// Assuming the sensor file "/sys/class/thermal/thermal_zone0/temp" contains "t=29500":
+///
+/// ```rust
+/// let session = // Assume `session` is an established SSH `Session`.
+/// let sensor_path = "/sys/class/thermal/thermal_zone0/temp";
+/// let temperature_status = temperature(&session, Platform::default(), sensor_path, None, 30);
+/// temperature_status.iter().for_each(|r| println!("{}", r));
+/// ```
+///
+/// This might print:
+///
+/// ```text
+/// ✅ 29°C
+/// ```
+///
+/// # Note
+///
+/// This function assumes that the sensor data format and the command to read it ("cat /path/to/sensor") are consistent
+/// across the remote systems it is used with. Variations in sensor data format or the need to use a different
+/// command to access it may require modifications to the function.
+///
+/// Error handling in this function provides basic feedback through returned error messages for specific failure
+/// points. For production use, it may be beneficial to implement more detailed error reporting or logging,
+/// especially for debugging issues with sensor data retrieval or parsing.
+pub fn temperature(
+    runner: &dyn CommandRunner,
+    platform: Platform,
+    sensor: &str,
+    warning: Option<u32>,
+    critical: u32,
+) -> Vec<CheckResult> {
+    let command = match platform.os {
+        Os::Macos => {
+            return vec![CheckResult::new(
+                Status::Critical,
+                "Error: temperature sensors are not supported on macOS",
+            )]
+        }
+        Os::Freebsd => format!("sysctl -n {}", platform::shell_quote(sensor)),
+        Os::Linux | Os::Windows => format!("cat {}", platform::shell_quote(sensor)),
+    };
+    let output = match runner.run(&command) {
+        Ok(output) => output,
+        Err(e) => {
+            warn!("Could not read the temperature sensor {}: {}", sensor, e);
+            return vec![
+                CheckResult::new(Status::Critical, format!("Error: {}", e)).with_error(e.as_ref())
+            ];
+        }
+    };
+
+    if platform.os == Os::Freebsd {
+        return match output.trim().trim_end_matches('C').parse::<f64>() {
+            Ok(temperature) => {
+                let status = Status::from_thresholds(
+                    temperature,
+                    warning.map(f64::from),
+                    f64::from(critical),
+                );
+                vec![CheckResult::new(status, format!("{:.0}°C", temperature))
+                    .with_metric("temperature", temperature)]
+            }
+            Err(_) => vec![CheckResult::new(
+                Status::Critical,
+                "Failed to parse temperature!",
+            )],
+        };
+    }
+
+    // Compile the regular expression to match the temperature value, alone or after `t=`
+    let re = Regex::new(r"t=(\d+)|\A\s*(\d+)\s*\z").expect("valid regular expression");
+
+    if let Some(caps) = re.captures(&output) {
+        if let Some(matched) = caps.get(1).or_else(|| caps.get(2)) {
+            let temperature = match matched.as_str().parse::<u32>() {
+                Ok(temp) => temp / 1000, // Convert to degrees Celsius
+                Err(_) => {
+                    return vec![CheckResult::new(
+                        Status::Critical,
+                        "Failed to parse temperature!",
+                    )]
+                }
+            };
+
+            let status = Status::from_thresholds(
+                f64::from(temperature),
+                warning.map(f64::from),
+                f64::from(critical),
+            );
+            return vec![CheckResult::new(status, format!("{}°C", temperature))
+                .with_metric("temperature", f64::from(temperature))];
+        }
+    }
+    vec![CheckResult::new(
+        Status::Critical,
+        "Cannot read temperature!",
+    )]
+}
+
+/// Reads several labelled temperature sensors of a server, as `temperature` does for a single
+/// one, each sensor being compared to its own thresholds, or to those of the check if it has none.
+///
+/// # Arguments
+///
+/// * `runner` - Runs the commands on the server, e.g. an established SSH `Session`.
+/// * `platform` - The operating system of the server, selecting how the sensors are read.
+/// * `sensors` - The labelled sensors.
+/// * `warning` - Optional temperature, in °C, at which the sensors without their own warning
+///   threshold become a warning.
+/// * `critical` - Temperature, in °C, at which the sensors without their own `max` become
+///   critical.
+///
+/// # Returns
+///
+/// Returns one result per sensor, prefixed with its label, e.g. "CPU 41°C" and "HDD bay 52°C",
+/// with the temperature as metric named after the label.
+///
+/// # Errors
+///
+/// A sensor that cannot be read gives a critical result, prefixed with its label, the other
+/// sensors being still read.
+pub fn temperatures(
+    runner: &dyn CommandRunner,
+    platform: Platform,
+    sensors: &[TemperatureSensor],
+    warning: Option<u32>,
+    critical: u32,
+) -> Vec<CheckResult> {
+    sensors
+        .iter()
+        .flat_map(|sensor| {
+            temperature(
+                runner,
+                platform,
+                &sensor.sensor,
+                sensor.warning.or(warning),
+                sensor.max.unwrap_or(critical),
+            )
+            .into_iter()
+            .map(|mut result| {
+                result.message = format!("{} {}", sensor.label, result.message);
+                if let Some(metric) = &mut result.metric {
+                    metric.name = sensor.label.clone();
+                }
+                result
+            })
+        })
+        .collect()
+}
+
+/// Reads the temperatures, fans, voltages and other features of the hardware monitoring chips of
+/// a Linux server with lm-sensors, from the JSON output of `sensors -j`.
+///
+/// A feature is compared to the thresholds of its selector, or without them to the limits
+/// configured in lm-sensors: its `crit` limit is critical and its `max` limit a warning, or its
+/// `max` limit is critical if it has no `crit` one, and a value below its positive `min` limit is
+/// critical. A feature whose alarm or fault flag is raised by the chip is always critical.
+///
+/// # Arguments
+///
+/// * `runner` - Runs the commands on the server, e.g. an established SSH `Session`.
+/// * `server_name` - The name of the server, used in the messages of the results.
+/// * `selectors` - The chips and features read, with their thresholds.
+///
+/// # Returns
+///
+/// Returns one result per feature, such as "coretemp-isa-0000 Package id 0 45.0°C @ web01" or
+/// "nct6775-isa-0290 fan2 0 RPM (alarm) @ web01", with the value as metric named after the chip
+/// and the feature.
+///
+/// # Errors
+///
+/// If `sensors` fails (e.g. lm-sensors is not installed) or its output is not JSON, a critical
+/// result indicating the error is returned. A chip or a feature that is not found gives a critical
+/// result.
+pub fn lm_sensors(
+    runner: &dyn CommandRunner,
+    server_name: &str,
+    selectors: &[LmSensor],
+) -> Vec<CheckResult> {
+    let output = match runner.run("sensors -j") {
+        Ok(output) => output,
+        Err(e) => {
+            warn!("Could not read the sensors of {}: {}", server_name, e);
+            return vec![
+                CheckResult::new(Status::Critical, format!("Error: {}", e)).with_error(e.as_ref())
+            ];
+        }
+    };
+    let chips: serde_json::Map<String, serde_json::Value> = match serde_json::from_str(&output) {
+        Ok(chips) => chips,
+        Err(e) => {
+            return vec![CheckResult::new(
+                Status::Critical,
+                format!("Error: unexpected output of sensors: {}", e),
+            )]
+        }
+    };
+
+    let mut results = vec![];
+    for selector in selectors {
+        let matching: Vec<(&String, &serde_json::Map<String, serde_json::Value>)> = chips
+            .iter()
+            .filter(|(name, _)| {
+                **name == selector.chip || name.starts_with(&format!("{}-", selector.chip))
+            })
+            .filter_map(|(name, chip)| Some((name, chip.as_object()?)))
+            .collect();
+        if matching.is_empty() {
+            results.push(CheckResult::new(
+                Status::Critical,
+                format!("chip {} not found @ {}", selector.chip, server_name),
+            ));
+            continue;
+        }
+        for (chip_name, chip) in matching {
+            let features: Vec<(&String, &serde_json::Map<String, serde_json::Value>)> = chip
+                .iter()
+                .filter(|(name, _)| selector.feature.as_ref().is_none_or(|f| f == *name))
+                .filter_map(|(name, feature)| Some((name, feature.as_object()?)))
+                .collect();
+            if features.is_empty() {
+                results.push(CheckResult::new(
+                    Status::Critical,
+                    format!(
+                        "feature {} not found on {} @ {}",
+                        selector.feature.as_deref().unwrap_or_default(),
+                        chip_name,
+                        server_name
+                    ),
+                ));
+            }
+            for (feature_name, values) in features {
+                results.extend(lm_sensor_result(
+                    server_name,
+                    chip_name,
+                    feature_name,
+                    values,
+                    selector,
+                ));
+            }
+        }
+    }
+    results
+}
+
+/// Classifies a feature of the output of `sensors -j`, e.g. `{"temp1_input": 45.0,
+/// "temp1_max": 80.0, "temp1_crit": 100.0, "temp1_crit_alarm": 0.0}`, returning `None` for the
+/// features without value.
+fn lm_sensor_result(
+    server_name: &str,
+    chip: &str,
+    feature: &str,
+    values: &serde_json::Map<String, serde_json::Value>,
+    selector: &LmSensor,
+) -> Option<CheckResult> {
+    let (input_name, value) = values
+        .iter()
+        .find_map(|(name, value)| Some((name.strip_suffix("_input")?, value.as_f64()?)))?;
+    let limit = |name: &str| values.get(&format!("{}_{}", input_name, name))?.as_f64();
+    let (critical, warning) = match selector.max {
+        Some(max) => (Some(max), selector.warning),
+        None => match (limit("crit"), limit("max")) {
+            (Some(crit), max) => (Some(crit), selector.warning.or(max)),
+            (None, max) => (max, selector.warning),
+        },
+    };
+    let min = selector.min.or(limit("min").filter(|min| *min > 0.0));
+    let alarm = values.iter().any(|(name, flag)| {
+        (name.ends_with("_alarm") || name.ends_with("_fault")) && flag.as_f64() != Some(0.0)
+    });
+
+    let mut status = Status::from_thresholds(value, warning, critical.unwrap_or(f64::INFINITY));
+    if alarm || min.is_some_and(|min| value < min) {
+        status = Status::Critical;
+    }
+    let unit = match input_name.trim_end_matches(|c: char| c.is_ascii_digit()) {
+        "temp" => "°C",
+        "fan" => " RPM",
+        "in" => " V",
+        "power" => " W",
+        "curr" => " A",
+        _ => "",
+    };
+    let precision = match unit {
+        " RPM" => 0,
+        " V" | " A" => 2,
+        _ => 1,
+    };
+    Some(
+        CheckResult::new(
+            status,
+            format!(
+                "{} {} {:.*}{}{} @ {}",
+                chip,
+                feature,
+                precision,
+                value,
+                unit,
+                if alarm { " (alarm)" } else { "" },
+                server_name
+            ),
+        )
+        .with_metric(format!("{} {}", chip, feature), value),
+    )
+}
+
+/// Reads the sensors of the baseboard management controller (BMC) of a server with
+/// `ipmitool sdr elist`, reporting those that are not in a nominal state, e.g. a fan that stopped,
+/// a power supply that lost its input or a temperature above the thresholds of the BMC. Such
+/// hardware failures are often not visible to the operating system.
+///
+/// The state of a sensor is the one computed by the BMC: `ok` and `ns` (no reading, e.g. an empty
+/// slot) are nominal, `nc` (non-critical) is a warning, while `cr` (critical) and `nr`
+/// (non-recoverable) are critical.
+///
+/// # Arguments
+///
+/// * `runner` - Runs the commands on the server, e.g. an established SSH `Session`.
+/// * `server_name` - The name of the server, used in the messages of the results.
+/// * `ipmitool` - The command running `ipmitool`, e.g. `sudo -n ipmitool`.
+/// * `ignore` - The names of the sensors that are not reported.
+///
+/// # Returns
+///
+/// Returns a result per non-nominal sensor, such as "PS2 Status: Power Supply AC lost (critical)
+/// @ web01", or a single result such as "42 IPMI sensors nominal @ web01" when there is none, with
+/// the number of non-nominal sensors as the `not_nominal` metric.
+///
+/// # Errors
+///
+/// If `ipmitool` fails, e.g. without access to the BMC, or lists no sensor, a critical result
+/// indicating the error is returned.
+pub fn ipmi(
+    runner: &dyn CommandRunner,
+    server_name: &str,
+    ipmitool: &str,
+    ignore: &[String],
+) -> Vec<CheckResult> {
+    let output = match runner.run(&format!("{} sdr elist", ipmitool)) {
+        Ok(output) => output,
+        Err(e) => {
+            warn!("Could not read the IPMI sensors of {}: {}", server_name, e);
+            return vec![
+                CheckResult::new(Status::Critical, format!("Error: {}", e)).with_error(e.as_ref())
+            ];
+        }
+    };
+
+    // Name | record ID | state | entity | reading or asserted events
+    let sensors: Vec<Vec<&str>> = output
+        .lines()
+        .map(|line| line.split('|').map(str::trim).collect::<Vec<&str>>())
+        .filter(|fields| fields.len() >= 5 && !ignore.iter().any(|name| name == fields[0]))
+        .collect();
+    if sensors.is_empty() {
+        return vec![CheckResult::new(
+            Status::Critical,
+            format!("Error: no IPMI sensor found @ {}", server_name),
+        )];
+    }
+
+    let not_nominal: Vec<CheckResult> = sensors
+        .iter()
+        .filter_map(|fields| {
+            let (status, state) = match fields[2] {
+                "nc" => (Status::Warning, "non-critical"),
+                "cr" => (Status::Critical, "critical"),
+                "nr" => (Status::Critical, "non-recoverable"),
+                _ => return None,
+            };
+            Some(CheckResult::new(
+                status,
+                format!("{}: {} ({}) @ {}", fields[0], fields[4], state, server_name),
+            ))
+        })
+        .collect();
+    if not_nominal.is_empty() {
+        return vec![CheckResult::new(
+            Status::Ok,
+            format!("{} IPMI sensors nominal @ {}", sensors.len(), server_name),
+        )
+        .with_metric("not_nominal", 0.0)];
+    }
+    let count = not_nominal.len() as f64;
+    not_nominal
+        .into_iter()
+        .map(|result| result.with_metric("not_nominal", count))
+        .collect()
+}
+
+/// Queries a UPS monitored by Network UPS Tools (NUT) with `upsc`, reporting its status flags,
+/// battery charge and remaining runtime.
+///
+/// The UPS running on battery (`OB`), its battery being low (`LB`) and a forced shutdown (`FSD`)
+/// are critical, while a battery to replace (`RB`), an overload (`OVER`) or the bypass (`BYPASS`)
+/// are warnings. The loss of the communication with the UPS makes `upsc` fail (e.g. with "Data
+/// stale"), which is critical as well.
+///
+/// # Arguments
+///
+/// * `runner` - Runs the commands on the server, e.g. an established SSH `Session`.
+/// * `server_name` - The name of the server, used in the message of the result.
+/// * `ups` - The name of the UPS, e.g. `myups` or `myups@nas01`.
+/// * `charge_warning` - Optional battery charge, in percent, below which the result becomes a
+///   warning.
+/// * `min_charge` - Optional battery charge, in percent, below which the result becomes critical.
+///
+/// # Returns
+///
+/// Returns a single result, such as "myups on battery, 85% charge, 35 minutes of runtime @ nas01",
+/// with the battery charge as the `charge` metric.
+///
+/// # Errors
+///
+/// If `upsc` fails or reports no status, a critical result indicating the error is returned.
+pub fn ups(
+    runner: &dyn CommandRunner,
+    server_name: &str,
+    ups: &str,
+    charge_warning: Option<f64>,
+    min_charge: Option<f64>,
+) -> Vec<CheckResult> {
+    let output = match runner.run(&format!("upsc {}", platform::shell_quote(ups))) {
+        Ok(output) => output,
+        Err(e) => {
+            warn!("Could not query the UPS {} of {}: {}", ups, server_name, e);
+            return vec![CheckResult::new(
+                Status::Critical,
+                format!("Error: {} unreachable: {} @ {}", ups, e, server_name),
+            )
+            .with_error(e.as_ref())];
+        }
+    };
+    let variables: HashMap<&str, &str> = output
+        .lines()
+        .filter_map(|line| line.split_once(": "))
+        .map(|(name, value)| (name.trim(), value.trim()))
+        .collect();
+    let Some(flags) = variables.get("ups.status") else {
+        return vec![CheckResult::new(
+            Status::Critical,
+            format!("Error: no status reported by {} @ {}", ups, server_name),
+        )];
+    };
+
+    let mut status = Status::Ok;
+    let mut states = vec![];
+    for flag in flags.split_whitespace() {
+        let (flag_status, state) = match flag {
+            "OL" => (Status::Ok, "online"),
+            "OB" => (Status::Critical, "on battery"),
+            "LB" => (Status::Critical, "low battery"),
+            "FSD" => (Status::Critical, "forced shutdown"),
+            "OFF" => (Status::Critical, "off"),
+            "RB" => (Status::Warning, "battery to replace"),
+            "OVER" => (Status::Warning, "overloaded"),
+            "BYPASS" => (Status::Warning, "on bypass"),
+            "CHRG" => (Status::Ok, "charging"),
+            "DISCHRG" => continue,
+            other => (Status::Ok, other),
+        };
+        status = status.max(flag_status);
+        states.push(state.to_string());
+    }
+
+    let charge = variables
+        .get("battery.charge")
+        .and_then(|c| c.parse::<f64>().ok());
+    if let Some(charge) = charge {
+        if min_charge.is_some_and(|min| charge < min) {
+            status = Status::Critical;
+        } else if charge_warning.is_some_and(|warning| charge < warning) {
+            status = status.max(Status::Warning);
+        }
+        states.push(format!("{:.0}% charge", charge));
+    }
+    if let Some(runtime) = variables
+        .get("battery.runtime")
+        .and_then(|r| r.parse::<f64>().ok())
+    {
+        states.push(format!(
+            "{} of runtime",
+            utils::format_duration(chrono::Duration::seconds(runtime as i64))
+        ));
+    }
+
+    let result = CheckResult::new(
+        status,
+        format!("{} {} @ {}", ups, states.join(", "), server_name),
+    );
+    match charge {
+        Some(charge) => vec![result.with_metric("charge", charge)],
+        None => vec![result],
+    }
+}
+
+/// Executes a custom command on a remote server via SSH and formats the output.
+///
+/// This function sends a specified command to be executed on a remote server through an established
+/// SSH session. It returns an informational result with the command as message and the command output
+/// as details, rendered in a markdown code block. If the command execution fails, it logs the error and
+/// returns a critical result with the exit status and standard error of the command.
+///
+/// Outputs longer than `max_lines` lines or `max_chars` characters are truncated in the details,
+/// the full output being kept in the `output` of the result, e.g. to be uploaded to Slack.
+///
+/// # Arguments
+///
+/// * `runner` - Runs the command on the server, e.g. an established SSH `Session`.
+/// * `command` - A string slice that holds the command to be executed on the remote server.
+/// * `max_lines` - The maximum number of lines of the output shown, all of them if `None`.
+/// * `max_chars` - The maximum number of characters of the output shown, all of them if `None`.
+///
+/// # Returns
+///
+/// Returns a `CheckResult` with an `Info` status, the command itself in backticks as message and the
+/// command output as details. If an error occurs during command execution, a `Critical` result describing
+/// the error is returned instead, and the error is logged to standard error.
+///
+/// # Examples
+///
+/// ```rust
+/// let session = // Assume `session` is an established SSH `Session`.
+/// let command = "ls -la";
+/// let results = custom_command(&session, command, None, None);
+/// results.iter().for_each(|r| println!("{}", r));
+/// ```
+///
+/// This might output something like:
+///
+/// ```text
+/// ℹ️ `ls -la`
+/// ```
+/// ```text
+/// total 12
+/// drwxr-xr-x  2 user user 4096 Jul 21 12:00 .
+/// drwxr-xr-x  4 user user 4096 Jul 20 14:43 ..
+/// -rw-r--r--  1 user user   66 Jul 21 12:00 file.txt
+/// ```
+///
+/// # Note
+///
+/// This function is designed to execute arbitrary commands on a remote server, which can be potentially
+/// very dangerous if not used carefully. Ensure that the commands being executed are safe and that the
+/// `command` argument comes from a trusted source to prevent security risks such as command injection.
+pub fn custom_command(
+    runner: &dyn CommandRunner,
+    command: &str,
+    max_lines: Option<usize>,
+    max_chars: Option<usize>,
+) -> Vec<CheckResult> {
+    let output = match runner.run(command) {
+        Ok(output) => output,
+        Err(e) => {
+            warn!("Custom command `{}` failed: {}", command, e);
+            return vec![CheckResult::new(
+                Status::Critical,
+                format!("`{}` failed: {}", command, e),
+            )
+            .with_error(e.as_ref())];
+        }
+    };
+
+    let result = CheckResult::new(Status::Info, format!("`{}`", command));
+    match utils::truncate_output(&output, max_lines, max_chars) {
+        Some(truncated) => {
+            let mut result = result.with_details(truncated);
+            result.output = Some(output);
+            vec![result]
+        }
+        None => vec![result.with_details(output)],
+    }
+}
+
+/// Lists directories older than a specified number of days in a given location on a remote server.
+///
+/// This function executes a `find` command on a remote server via SSH to identify directories within
+/// a specified location (`loc`) that are older than a given number of days (`cutoff`). It formats the
+/// list of these directories into a critical result. If an error occurs during command execution,
+/// an error message is logged, and no result is returned. If no directories meet the criteria,
+/// a passing result indicating this is returned instead.
+///
+/// # Arguments
+///
+/// * `runner` - Runs the commands on the server, e.g. an established SSH `Session`.
+/// * `platform` - The operating system and shell profile of the server, `Get-ChildItem` being
+///   used on Windows.
+/// * `loc` - A string slice that specifies the location on the remote server to search for old directories.
+/// * `cutoff` - The number of days used as the threshold for determining if a directory is considered "old".
+///
+/// # Returns
+///
+/// Returns a `CheckResult` that either:
+/// - Lists the directories older than `cutoff` days in the specified location as details, rendered as
+///   a markdown code block for readability, or
+/// - Indicates that no directories older than `cutoff` days were found in the specified location.
+///
+/// No result is returned if an error occurred during command execution.
+///
+/// # Examples
+///
+/// ```rust
+/// let session = // Assume `session` is an established SSH `Session`.
+/// let location = "/var/log";
+/// let days_old = 30;
+/// let results = list_old_directories(&session, Platform::default(), location, days_old);
+/// results.iter().for_each(|r| println!("{}", r));
+/// ```
+///
+/// This might output something like:
+///
+/// ```text
 /// ❌ Directories older than 30 days:
 /// ```
 /// ```text
-/// /var/log/old_logs
-/// /var/log/archive
+/// /var/log/old_logs
+/// /var/log/archive
+/// ```
+/// Or, if no directories meet the criteria:
+///
+/// ```text
+/// ✅ No directories older than 30 days in `/var/log`
+/// ```
+///
+/// # Note
+///
+/// This function relies on the `find` command's `-mtime` option to determine the age of directories,
+/// which is based on the time of the last modification to the directory's contents. This approach focuses
+/// on when files within the directory were last added, removed, or renamed, rather than when their metadata
+/// was last changed. Ensure that the remote server's environment and filesystem support the commands and
+/// options used.
+///
+/// If the `find` command fails, e.g. because `loc` does not exist, a critical result with the error
+/// is returned.
+pub fn list_old_directories(
+    runner: &dyn CommandRunner,
+    platform: Platform,
+    loc: &str,
+    cutoff: u16,
+) -> Vec<CheckResult> {
+    let command = match platform.os {
+        Os::Linux if platform.shell == ShellProfile::Busybox => {
+            format!(
+                "find {}/. ! -name . -prune -type d -mtime +{}",
+                platform::shell_quote(loc),
+                cutoff
+            )
+        }
+        Os::Linux | Os::Macos | Os::Freebsd => {
+            format!(
+                "find {} -maxdepth 1 -type d -mtime +{}",
+                platform::shell_quote(loc),
+                cutoff
+            )
+        }
+        // `-mtime +N` matches the directories modified at least N+1 days ago
+        Os::Windows => platform::powershell(&format!(
+            "Get-ChildItem -Directory -Force -LiteralPath {} -ErrorAction Stop | \
+             Where-Object {{ $_.LastWriteTime -lt (Get-Date).AddDays(-{}) }} | \
+             ForEach-Object {{ $_.FullName }}",
+            platform::powershell_quote(loc),
+            u32::from(cutoff) + 1
+        )),
+    };
+    let output = match runner.run(&command) {
+        Ok(output) => output,
+        Err(e) => {
+            warn!("Could not list the old directories of {}: {}", loc, e);
+            return vec![
+                CheckResult::new(Status::Critical, format!("Error: {}", e)).with_error(e.as_ref())
+            ];
+        }
+    };
+
+    // The portable form of `find` lists the directories as `loc/./name`
+    let output = output.replace("/./", "/");
+    let files: Vec<&str> = output.split('\n').filter(|line| !line.is_empty()).collect();
+
+    if files.is_empty() {
+        return vec![CheckResult::new(
+            Status::Ok,
+            format!("No directories older than {} days in `{}`", cutoff, loc),
+        )
+        .with_metric("old_directories", 0.0)];
+    }
+
+    vec![CheckResult::new(
+        Status::Critical,
+        format!("Directories older than {} days:", cutoff),
+    )
+    .with_details(files.join("\n"))
+    .with_metric("old_directories", files.len() as f64)]
+}
+
+/// Checks the health of a Kubernetes cluster reachable from a remote server.
+///
+/// This function runs `kubectl` on the remote server with the given kubeconfig, first listing the
+/// nodes of the cluster and then its pods. Nodes whose status is not `Ready` and pods that are not
+/// `Running` or `Completed` (e.g. `CrashLoopBackOff`, `ImagePullBackOff`, `Pending`) are reported.
+/// Note that crash-looping pods are still in the `Running` phase, so the pod status column is
+/// inspected instead of relying on a `status.phase` field selector.
+///
+/// # Arguments
+///
+/// * `runner` - Runs the commands on the server, e.g. an established SSH `Session`.
+/// * `server_name` - The name of the server, used for reporting.
+/// * `kubeconfig` - Path to the kubeconfig file on the remote server.
+/// * `namespace` - Optional namespace to restrict the pod listing to; all namespaces are checked
+///   if `None`.
+/// * `context` - Optional kubeconfig context to use instead of the current one.
+///
+/// # Returns
+///
+/// Returns one `CheckResult` for the nodes and one for the pods, rendered as e.g.:
+///
+/// ```text
+/// ✅ 3/3 nodes Ready @ `server_name`
+/// ❌ 2 unhealthy pods @ `server_name`
+/// ```
+/// ```text
+/// default/web-7d4b9 CrashLoopBackOff
+/// kube-system/dns-5f8c Pending
+/// ```
+///
+/// If `kubectl` fails, a critical "Error:" result is returned for the corresponding listing.
+pub fn kubernetes(
+    runner: &dyn CommandRunner,
+    server_name: &str,
+    kubeconfig: &str,
+    namespace: Option<&str>,
+    context: Option<&str>,
+) -> Vec<CheckResult> {
+    let mut kubectl = format!("kubectl --kubeconfig {}", platform::shell_quote(kubeconfig));
+    if let Some(context) = context {
+        kubectl.push_str(&format!(" --context {}", platform::shell_quote(context)));
+    }
+
+    let nodes = match runner.run(&format!("{} get nodes --no-headers", kubectl)) {
+        Ok(output) => {
+            let total = output.lines().filter(|l| !l.trim().is_empty()).count();
+            let not_ready = parse_not_ready_nodes(&output);
+            let metric = not_ready.len() as f64;
+            let result = if not_ready.is_empty() {
+                CheckResult::new(
+                    Status::Ok,
+                    format!("{}/{} nodes Ready @ `{}`", total, total, server_name),
+                )
+            } else {
+                CheckResult::new(
+                    Status::Critical,
+                    format!(
+                        "{}/{} nodes NotReady @ `{}`: {}",
+                        not_ready.len(),
+                        total,
+                        server_name,
+                        not_ready.join(", ")
+                    ),
+                )
+            };
+            result.with_metric("not_ready_nodes", metric)
+        }
+        Err(e) => {
+            CheckResult::new(Status::Critical, format!("Error: {}", e)).with_error(e.as_ref())
+        }
+    };
+
+    let scope = match namespace {
+        Some(namespace) => format!("-n {}", platform::shell_quote(namespace)),
+        None => "-A".to_string(),
+    };
+    let pods_command = format!("{} get pods {} --no-headers", kubectl, scope);
+    let pods = match runner.run(&pods_command) {
+        Ok(output) => {
+            let unhealthy = parse_unhealthy_pods(&output, namespace);
+            let target = namespace.unwrap_or("all namespaces");
+            let metric = unhealthy.len() as f64;
+            let result = if unhealthy.is_empty() {
+                CheckResult::new(
+                    Status::Ok,
+                    format!("All pods healthy in {} @ `{}`", target, server_name),
+                )
+            } else {
+                CheckResult::new(
+                    Status::Critical,
+                    format!(
+                        "{} unhealthy pods in {} @ `{}`",
+                        unhealthy.len(),
+                        target,
+                        server_name
+                    ),
+                )
+                .with_details(unhealthy.join("\n"))
+            };
+            result.with_metric("unhealthy_pods", metric)
+        }
+        Err(e) => {
+            CheckResult::new(Status::Critical, format!("Error: {}", e)).with_error(e.as_ref())
+        }
+    };
+
+    vec![nodes, pods]
+}
+
+/// Returns the names of the nodes that are not `Ready` in the output of `kubectl get nodes --no-headers`.
+fn parse_not_ready_nodes(output: &str) -> Vec<String> {
+    output
+        .lines()
+        .filter_map(|line| {
+            let mut fields = line.split_whitespace();
+            let name = fields.next()?;
+            let status = fields.next()?;
+            // Cordoned nodes are reported as `Ready,SchedulingDisabled`
+            if status.split(',').any(|s| s == "Ready") {
+                None
+            } else {
+                Some(name.to_string())
+            }
+        })
+        .collect()
+}
+
+/// Returns the pods that are neither `Running` nor `Completed` in the output of
+/// `kubectl get pods --no-headers`, formatted as `namespace/name STATUS`.
+///
+/// When `namespace` is `None` the output is expected to come from `kubectl get pods -A`, which
+/// has an extra leading `NAMESPACE` column.
+fn parse_unhealthy_pods(output: &str, namespace: Option<&str>) -> Vec<String> {
+    output
+        .lines()
+        .filter_map(|line| {
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            let (ns, rest) = match namespace {
+                Some(ns) => (ns, &fields[..]),
+                None => (*fields.first()?, fields.get(1..)?),
+            };
+            // NAME READY STATUS RESTARTS AGE
+            let name = rest.first()?;
+            let status = rest.get(2)?;
+            match *status {
+                "Running" | "Completed" => None,
+                _ => Some(format!("{}/{} {}", ns, name, status)),
+            }
+        })
+        .collect()
+}
+
+/// Checks the disk usage of Docker on a remote server, as listed by `docker system df`, and
+/// optionally counts the dangling images and volumes, which `docker image prune` and `docker
+/// volume prune` would remove.
+///
+/// # Arguments
+///
+/// * `runner` - Runs the commands on the server, e.g. an established SSH `Session`.
+/// * `server_name` - The name of the server, used in the messages of the results.
+/// * `docker` - The command running Docker, e.g. `docker` or `sudo docker`.
+/// * `max_usage` - Disk usage in bytes at which a critical alert is reported.
+/// * `usage_warning` - Disk usage in bytes at which a warning is reported.
+/// * `dangling_warning` - Number of dangling images and volumes at which a warning is reported,
+///   which are only counted if set.
+///
+/// # Returns
+///
+/// Returns a result for the disk usage, such as "Docker uses 38.20 GiB (12.10 GiB reclaimable) @
+/// web01", with the usage of the images, containers, volumes and build cache as details when it
+/// is not `Ok` and the `docker_usage` metric, and a result such as "3 dangling images, 2 dangling
+/// volumes @ web01" with the `dangling` metric if `dangling_warning` is set.
+///
+/// # Errors
+///
+/// If the command fails, e.g. when the user may not access the Docker daemon, a critical result
+/// indicating the error is returned.
+pub fn docker_disk(
+    runner: &dyn CommandRunner,
+    server_name: &str,
+    docker: &str,
+    max_usage: u64,
+    usage_warning: Option<u64>,
+    dangling_warning: Option<u32>,
+) -> Vec<CheckResult> {
+    let mut command = format!(
+        "{} system df --format '{{{{.Type}}}}|{{{{.Size}}}}|{{{{.Reclaimable}}}}'",
+        docker
+    );
+    if dangling_warning.is_some() {
+        command.push_str(&format!(
+            " && echo \"dangling $({d} images -qf dangling=true | wc -l) \
+             $({d} volume ls -qf dangling=true | wc -l)\"",
+            d = docker
+        ));
+    }
+    let output = match runner.run(&command) {
+        Ok(output) => output,
+        Err(e) => {
+            warn!("Could not read the Docker usage of {}: {}", server_name, e);
+            return vec![
+                CheckResult::new(Status::Critical, format!("Error: {}", e)).with_error(e.as_ref())
+            ];
+        }
+    };
+
+    // Images|30.1GB|12GB (39%)
+    let mut total = 0;
+    let mut reclaimable = 0;
+    let mut usages = vec![];
+    for line in output.lines() {
+        let fields: Vec<&str> = line.trim().split('|').collect();
+        let [kind, size, freeable] = fields[..] else {
+            continue;
+        };
+        let size = docker_size(size).unwrap_or(0);
+        let freeable = docker_size(freeable.split_whitespace().next().unwrap_or_default());
+        total += size;
+        reclaimable += freeable.unwrap_or(0);
+        usages.push(format!(
+            "{}: {} ({} reclaimable)",
+            kind,
+            utils::format_size(size),
+            utils::format_size(freeable.unwrap_or(0))
+        ));
+    }
+    if usages.is_empty() {
+        return vec![CheckResult::new(
+            Status::Critical,
+            format!(
+                "Error: unexpected output of docker system df: {}",
+                output.trim()
+            ),
+        )];
+    }
+    let status = Status::from_thresholds(
+        total as f64,
+        usage_warning.map(|w| w as f64),
+        max_usage as f64,
+    );
+    let mut usage = CheckResult::new(
+        status,
+        format!(
+            "Docker uses {} ({} reclaimable) @ {}",
+            utils::format_size(total),
+            utils::format_size(reclaimable),
+            server_name
+        ),
+    )
+    .with_metric("docker_usage", total as f64);
+    if status != Status::Ok {
+        usage = usage.with_details(usages.join("\n"));
+    }
+    let mut results = vec![usage];
+
+    let dangling = output.lines().find_map(|line| {
+        let mut counts = line.strip_prefix("dangling ")?.split_whitespace();
+        let images: u32 = counts.next()?.parse().ok()?;
+        Some((images, counts.next()?.parse::<u32>().ok()?))
+    });
+    if let (Some(warning), Some((images, volumes))) = (dangling_warning, dangling) {
+        let plural = |count: u32| if count == 1 { "" } else { "s" };
+        let status = match images + volumes >= warning {
+            true => Status::Warning,
+            false => Status::Ok,
+        };
+        results.push(
+            CheckResult::new(
+                status,
+                format!(
+                    "{} dangling image{}, {} dangling volume{} @ {}",
+                    images,
+                    plural(images),
+                    volumes,
+                    plural(volumes),
+                    server_name
+                ),
+            )
+            .with_metric("dangling", f64::from(images + volumes)),
+        );
+    }
+    results
+}
+
+/// Detects the containers of a remote server that are in a restart loop: those Docker lists as
+/// `Restarting`, and those that stopped more than `max_restarts` times within the window, as
+/// counted from the `die` events of `docker events`. A crash-looping container is otherwise
+/// listed as running most of the time.
+///
+/// # Arguments
+///
+/// * `runner` - Runs the commands on the server, e.g. an established SSH `Session`.
+/// * `server_name` - The name of the server, used in the messages of the results.
+/// * `docker` - The command running Docker, e.g. `docker` or `sudo docker`.
+/// * `max_restarts` - Number of restarts within the window above which a container is reported.
+/// * `window` - Window in minutes over which the restarts are counted.
+///
+/// # Returns
+///
+/// Returns a critical result per container in a restart loop, such as "api restarted 12 times in
+/// 60 minutes (exit code 1) @ web01" or "worker restarting @ web01", with its number of restarts
+/// as a metric named after it, or a single `Ok` result if there is none.
+///
+/// # Errors
+///
+/// If the command fails, e.g. when the user may not access the Docker daemon, a critical result
+/// indicating the error is returned.
+pub fn container_restarts(
+    runner: &dyn CommandRunner,
+    server_name: &str,
+    docker: &str,
+    max_restarts: u32,
+    window: u32,
+) -> Vec<CheckResult> {
+    let command = format!(
+        "{d} ps -a --filter status=restarting --format '{{{{.Names}}}}' && echo --- && \
+         {d} events --since {w}m --until \"$(date +%s)\" --filter type=container --filter event=die \
+         --format '{{{{.Actor.Attributes.name}}}} {{{{.Actor.Attributes.exitCode}}}}'",
+        d = docker,
+        w = window
+    );
+    let output = match runner.run(&command) {
+        Ok(output) => output,
+        Err(e) => {
+            warn!("Could not list the containers of {}: {}", server_name, e);
+            return vec![
+                CheckResult::new(Status::Critical, format!("Error: {}", e)).with_error(e.as_ref())
+            ];
+        }
+    };
+
+    let (restarting, events) = output.split_once("---").unwrap_or((&output, ""));
+    // The number of restarts and the last exit code of each container, in chronological order
+    let mut restarts: BTreeMap<&str, (u32, &str)> = BTreeMap::new();
+    for event in events.lines() {
+        let mut fields = event.split_whitespace();
+        if let Some(name) = fields.next() {
+            let entry = restarts.entry(name).or_default();
+            entry.0 += 1;
+            entry.1 = fields.next().unwrap_or_default();
+        }
+    }
+    let restarting: Vec<&str> = restarting
+        .lines()
+        .map(str::trim)
+        .filter(|name| !name.is_empty())
+        .collect();
+
+    let mut looping: Vec<&str> = restarts
+        .iter()
+        .filter(|(_, (count, _))| *count > max_restarts)
+        .map(|(name, _)| *name)
+        .collect();
+    for name in restarting {
+        if !looping.contains(&name) {
+            looping.push(name);
+        }
+    }
+    if looping.is_empty() {
+        return vec![CheckResult::new(
+            Status::Ok,
+            format!("no container in a restart loop @ {}", server_name),
+        )];
+    }
+    looping
+        .into_iter()
+        .map(|name| {
+            let (count, exit_code) = restarts.get(name).copied().unwrap_or_default();
+            let message = match count {
+                0 => format!("{} restarting @ {}", name, server_name),
+                _ => format!(
+                    "{} restarted {} time{} in {} minutes{} @ {}",
+                    name,
+                    count,
+                    if count == 1 { "" } else { "s" },
+                    window,
+                    match exit_code {
+                        "" => String::new(),
+                        code => format!(" (exit code {})", code),
+                    },
+                    server_name
+                ),
+            };
+            CheckResult::new(Status::Critical, message).with_metric(name, f64::from(count))
+        })
+        .collect()
+}
+
+/// Checks the Docker Compose projects of a remote server, project by project: the services
+/// defined in each Compose file (`docker compose config --services`) are compared with the
+/// containers of the project (`docker compose ps -a --format json`).
+///
+/// A service is up when its containers are running and, if it has a health check, healthy. The
+/// services whose containers exited with code 0 are considered as completed one-off jobs, e.g. a
+/// migration, while those still starting their health check are a warning.
+///
+/// # Arguments
+///
+/// * `runner` - Runs the commands on the server, e.g. an established SSH `Session`.
+/// * `server_name` - The name of the server, used in the messages of the results.
+/// * `docker` - The command running Docker, e.g. `docker` or `sudo docker`.
+/// * `compose_files` - The paths of the Compose files of the projects.
+///
+/// # Returns
+///
+/// Returns a result per project, such as "app: 3/4 services up @ web01", critical when a service
+/// is down, unhealthy or has no container, which are listed as details, e.g. "db: exited (exit
+/// code 1)", with the number of services that are not up as a metric named after the project.
+///
+/// # Errors
+///
+/// If the commands fail for a project, e.g. when its Compose file is invalid, a critical result
+/// indicating the error is returned for it.
+pub fn compose(
+    runner: &dyn CommandRunner,
+    server_name: &str,
+    docker: &str,
+    compose_files: &[String],
+) -> Vec<CheckResult> {
+    compose_files
+        .iter()
+        .map(|file| {
+            let command = format!(
+                "{d} compose -f {f} config --services && echo --- && \
+                 {d} compose -f {f} ps -a --format json",
+                d = docker,
+                f = platform::shell_quote(file)
+            );
+            match runner.run(&command) {
+                Ok(output) => compose_project(server_name, file, &output),
+                Err(e) => {
+                    warn!(
+                        "Could not list the services of {} on {}: {}",
+                        file, server_name, e
+                    );
+                    CheckResult::new(
+                        Status::Critical,
+                        format!("Error: {} @ `{}:{}`", e, server_name, file),
+                    )
+                    .with_error(e.as_ref())
+                }
+            }
+        })
+        .collect()
+}
+
+/// Classifies the services of a Docker Compose project from the output of `docker compose config
+/// --services`, followed by `---` and the output of `docker compose ps -a --format json`, a JSON
+/// object per line since Compose 2.21 and a JSON array before.
+fn compose_project(server_name: &str, file: &str, output: &str) -> CheckResult {
+    let (services, containers) = output.split_once("---").unwrap_or((output, ""));
+    let containers: Vec<serde_json::Value> = match containers.trim() {
+        array if array.starts_with('[') => serde_json::from_str(array).unwrap_or_default(),
+        lines => lines
+            .lines()
+            .filter_map(|line| serde_json::from_str(line).ok())
+            .collect(),
+    };
+    // The project is named after the directory of its Compose file by default
+    let project = containers
+        .iter()
+        .find_map(|container| container["Project"].as_str())
+        .or_else(|| std::path::Path::new(file).parent()?.file_name()?.to_str())
+        .unwrap_or(file)
+        .to_string();
+
+    let mut status = Status::Ok;
+    let mut problems = vec![];
+    let services: Vec<&str> = services
+        .lines()
+        .map(str::trim)
+        .filter(|service| !service.is_empty())
+        .collect();
+    for service in &services {
+        let mut states: Vec<(Status, String)> = containers
+            .iter()
+            .filter(|container| container["Service"].as_str() == Some(service))
+            .filter_map(|container| {
+                let state = container["State"].as_str().unwrap_or("unknown");
+                let health = container["Health"].as_str().unwrap_or_default();
+                match (state, health) {
+                    ("running", "" | "healthy") => None,
+                    ("running", "starting") => Some((Status::Warning, "starting".to_string())),
+                    ("running", health) => Some((Status::Critical, health.to_string())),
+                    ("exited", _) if container["ExitCode"].as_i64() == Some(0) => None,
+                    ("exited", _) => Some((
+                        Status::Critical,
+                        format!(
+                            "exited (exit code {})",
+                            container["ExitCode"].as_i64().unwrap_or(-1)
+                        ),
+                    )),
+                    (state, _) => Some((Status::Critical, state.to_string())),
+                }
+            })
+            .collect();
+        if !containers
+            .iter()
+            .any(|container| container["Service"].as_str() == Some(service))
+        {
+            states.push((Status::Critical, "no container".to_string()));
+        }
+        if let Some((worst, _)) = states.iter().max_by_key(|(status, _)| *status) {
+            status = status.max(*worst);
+            let states: Vec<&str> = states.iter().map(|(_, state)| state.as_str()).collect();
+            problems.push(format!("{}: {}", service, states.join(", ")));
+        }
+    }
+
+    let up = services.len() - problems.len();
+    let mut result = CheckResult::new(
+        status,
+        format!(
+            "{}: {}/{} services up @ {}",
+            project,
+            up,
+            services.len(),
+            server_name
+        ),
+    )
+    .with_metric(&project, problems.len() as f64);
+    if !problems.is_empty() {
+        result = result.with_details(problems.join("\n"));
+    }
+    result
+}
+
+/// Parses a size printed by Docker, in decimal units, e.g. `1.5GB`, `512kB` or `0B`.
+fn docker_size(size: &str) -> Option<u64> {
+    let split = size.find(|c: char| !c.is_ascii_digit() && c != '.')?;
+    let (number, unit) = size.split_at(split);
+    let exponent = match unit {
+        "B" => 0,
+        "kB" | "KB" => 1,
+        "MB" => 2,
+        "GB" => 3,
+        "TB" => 4,
+        "PB" => 5,
+        _ => return None,
+    };
+    Some((number.parse::<f64>().ok()? * 1000f64.powi(exponent)).round() as u64)
+}
+
+/// Checks the health of a PostgreSQL or MySQL/MariaDB server using its command-line client on the
+/// remote server.
+///
+/// The liveness of the database is checked with a lightweight query (`psql -c 'select 1'` for
+/// PostgreSQL, `mysqladmin ping` for MySQL). Optionally, the number of open connections and the
+/// replication lag (on replicas) are compared against the given thresholds. Authentication is left
+/// to the client configuration of the remote user (`~/.pgpass`, `~/.my.cnf`, peer authentication...).
+///
+/// # Arguments
+///
+/// * `runner` - Runs the commands on the server, e.g. an established SSH `Session`.
+/// * `server_name` - The name of the server, used for reporting.
+/// * `engine` - The database engine, either `postgres` or `mysql`.
+/// * `user` - Optional database user to connect as.
+/// * `database` - Optional database name to connect to, only used for PostgreSQL.
+/// * `max_connections` - Optional maximum number of open connections.
+/// * `max_replication_lag` - Optional maximum replication lag, in seconds.
+///
+/// # Returns
+///
+/// Returns a `CheckResult` per performed check, rendered as e.g.:
+///
+/// ```text
+/// ✅ postgres is up @ `server_name`
+/// ❌ 120 connections (max 100) @ `server_name`
+/// ✅ replication lag 0s @ `server_name`
 /// ```
-/// Or, if no directories meet the criteria:
+///
+/// If the database is not reachable, only the critical "... is down" result is returned.
+pub fn database(
+    runner: &dyn CommandRunner,
+    server_name: &str,
+    engine: &str,
+    user: Option<&str>,
+    database: Option<&str>,
+    max_connections: Option<u32>,
+    max_replication_lag: Option<u64>,
+) -> Vec<CheckResult> {
+    let client = match DatabaseClient::new(engine, user, database) {
+        Some(client) => client,
+        None => {
+            return vec![CheckResult::new(
+                Status::Critical,
+                format!("Error: Unknown database engine `{}`", engine),
+            )]
+        }
+    };
+
+    if let Err(e) = runner.run(&client.ping_command()) {
+        return vec![CheckResult::new(
+            Status::Critical,
+            format!("{} is down @ `{}`: {}", engine, server_name, e),
+        )
+        .with_error(e.as_ref())];
+    }
+    let mut results = vec![CheckResult::new(
+        Status::Ok,
+        format!("{} is up @ `{}`", engine, server_name),
+    )];
+
+    if let Some(max) = max_connections {
+        let result = runner.run(&client.connections_command()).map_or_else(
+            |e| CheckResult::new(Status::Critical, format!("Error: {}", e)).with_error(e.as_ref()),
+            |output| match client.parse_connections(&output) {
+                Some(count) if count > max => CheckResult::new(
+                    Status::Critical,
+                    format!("{} connections (max {}) @ `{}`", count, max, server_name),
+                )
+                .with_metric("connections", count as f64),
+                Some(count) => CheckResult::new(
+                    Status::Ok,
+                    format!("{} connections @ `{}`", count, server_name),
+                )
+                .with_metric("connections", count as f64),
+                None => CheckResult::new(
+                    Status::Critical,
+                    "Error: Could not parse the number of connections",
+                ),
+            },
+        );
+        results.push(result);
+    }
+
+    if let Some(max) = max_replication_lag {
+        let result = runner.run(&client.replication_lag_command()).map_or_else(
+            |e| CheckResult::new(Status::Critical, format!("Error: {}", e)).with_error(e.as_ref()),
+            |output| match client.parse_replication_lag(&output) {
+                Some(lag) if lag > max => CheckResult::new(
+                    Status::Critical,
+                    format!(
+                        "replication lag {}s (max {}s) @ `{}`",
+                        lag, max, server_name
+                    ),
+                )
+                .with_metric("replication_lag", lag as f64),
+                Some(lag) => CheckResult::new(
+                    Status::Ok,
+                    format!("replication lag {}s @ `{}`", lag, server_name),
+                )
+                .with_metric("replication_lag", lag as f64),
+                None => CheckResult::new(Status::Ok, format!("not a replica @ `{}`", server_name)),
+            },
+        );
+        results.push(result);
+    }
+
+    results
+}
+
+/// Builds the client commands used by the `database` check for a given engine.
+enum DatabaseClient {
+    Postgres(String),
+    Mysql(String),
+}
+
+impl DatabaseClient {
+    fn new(engine: &str, user: Option<&str>, database: Option<&str>) -> Option<Self> {
+        match engine {
+            "postgres" | "postgresql" => {
+                let mut args = String::new();
+                if let Some(user) = user {
+                    args.push_str(&format!(" -U {}", platform::shell_quote(user)));
+                }
+                if let Some(database) = database {
+                    args.push_str(&format!(" -d {}", platform::shell_quote(database)));
+                }
+                Some(DatabaseClient::Postgres(args))
+            }
+            // All the MySQL queries are server-wide, so the database is not needed
+            "mysql" | "mariadb" => {
+                let args = user.map_or(String::new(), |user| {
+                    format!(" -u {}", platform::shell_quote(user))
+                });
+                Some(DatabaseClient::Mysql(args))
+            }
+            _ => None,
+        }
+    }
+
+    fn ping_command(&self) -> String {
+        match self {
+            DatabaseClient::Postgres(args) => format!("psql{} -tAc 'select 1'", args),
+            DatabaseClient::Mysql(args) => format!("mysqladmin{} ping", args),
+        }
+    }
+
+    fn connections_command(&self) -> String {
+        match self {
+            DatabaseClient::Postgres(args) => {
+                format!("psql{} -tAc 'select count(*) from pg_stat_activity'", args)
+            }
+            DatabaseClient::Mysql(args) => {
+                format!(
+                    "mysql{} -N -e \"SHOW STATUS LIKE 'Threads_connected'\"",
+                    args
+                )
+            }
+        }
+    }
+
+    fn replication_lag_command(&self) -> String {
+        match self {
+            DatabaseClient::Postgres(args) => format!(
+                "psql{} -tAc 'select extract(epoch from now() - pg_last_xact_replay_timestamp())::int'",
+                args
+            ),
+            DatabaseClient::Mysql(args) => format!("mysql{} -e 'SHOW REPLICA STATUS\\G'", args),
+        }
+    }
+
+    fn parse_connections(&self, output: &str) -> Option<u32> {
+        match self {
+            DatabaseClient::Postgres(_) => output.trim().parse().ok(),
+            // Threads_connected\t5
+            DatabaseClient::Mysql(_) => output.split_whitespace().nth(1)?.parse().ok(),
+        }
+    }
+
+    /// Returns `None` if the server is not a replica.
+    fn parse_replication_lag(&self, output: &str) -> Option<u64> {
+        match self {
+            DatabaseClient::Postgres(_) => output.trim().parse().ok(),
+            DatabaseClient::Mysql(_) => output.lines().find_map(|line| {
+                let (key, value) = line.trim().split_once(':')?;
+                match key {
+                    "Seconds_Behind_Source" | "Seconds_Behind_Master" => value.trim().parse().ok(),
+                    _ => None,
+                }
+            }),
+        }
+    }
+}
+
+/// Checks that a Redis server is up and, optionally, that its memory usage and number of
+/// connected clients are below the given thresholds.
+///
+/// The check runs `redis-cli -u <redis_url> ping` on the remote server and, if any threshold is
+/// given, parses the output of `redis-cli INFO` to retrieve `used_memory` and `connected_clients`.
+///
+/// # Arguments
+///
+/// * `runner` - Runs the commands on the server, e.g. an established SSH `Session`.
+/// * `server_name` - The name of the server, used for reporting.
+/// * `redis_url` - The URL of the Redis server as seen from the remote server, e.g. `redis://localhost:6379`.
+/// * `max_memory_mb` - Optional maximum memory usage, in megabytes.
+/// * `max_clients` - Optional maximum number of connected clients.
+///
+/// # Returns
+///
+/// Returns a `CheckResult` per performed check, rendered as e.g.:
 ///
 /// ```text
-/// ✅ No directories older than 30 days in `/var/log`
+/// ✅ redis is up @ `server_name`
+/// ❌ redis memory 600MB (max 512MB) @ `server_name`
+/// ✅ 12 redis clients @ `server_name`
 /// ```
+pub fn redis(
+    runner: &dyn CommandRunner,
+    server_name: &str,
+    redis_url: &str,
+    max_memory_mb: Option<u64>,
+    max_clients: Option<u64>,
+) -> Vec<CheckResult> {
+    let redis_cli = format!("redis-cli -u {}", platform::shell_quote(redis_url));
+    let down = |reason: String| {
+        vec![CheckResult::new(
+            Status::Critical,
+            format!("redis is down @ `{}`: {}", server_name, reason),
+        )]
+    };
+    match runner.run(&format!("{} ping", redis_cli)) {
+        Ok(output) if output.trim() == "PONG" => {}
+        Ok(output) => return down(output.trim().to_string()),
+        Err(e) => return down(e.to_string()),
+    }
+    let mut results = vec![CheckResult::new(
+        Status::Ok,
+        format!("redis is up @ `{}`", server_name),
+    )];
+
+    if max_memory_mb.is_none() && max_clients.is_none() {
+        return results;
+    }
+
+    let info = match runner.run(&format!("{} INFO", redis_cli)) {
+        Ok(info) => info,
+        Err(e) => {
+            results.push(
+                CheckResult::new(Status::Critical, format!("Error: {}", e)).with_error(e.as_ref()),
+            );
+            return results;
+        }
+    };
+
+    if let Some(max) = max_memory_mb {
+        let result = match redis_info_field(&info, "used_memory") {
+            Some(bytes) => {
+                let used = bytes / 1024 / 1024;
+                let result = if used > max {
+                    CheckResult::new(
+                        Status::Critical,
+                        format!(
+                            "redis memory {}MB (max {}MB) @ `{}`",
+                            used, max, server_name
+                        ),
+                    )
+                } else {
+                    CheckResult::new(
+                        Status::Ok,
+                        format!("redis memory {}MB @ `{}`", used, server_name),
+                    )
+                };
+                result.with_metric("memory_mb", used as f64)
+            }
+            None => CheckResult::new(
+                Status::Critical,
+                "Error: Could not parse redis memory usage",
+            ),
+        };
+        results.push(result);
+    }
+
+    if let Some(max) = max_clients {
+        let result = match redis_info_field(&info, "connected_clients") {
+            Some(clients) if clients > max => CheckResult::new(
+                Status::Critical,
+                format!(
+                    "{} redis clients (max {}) @ `{}`",
+                    clients, max, server_name
+                ),
+            )
+            .with_metric("clients", clients as f64),
+            Some(clients) => CheckResult::new(
+                Status::Ok,
+                format!("{} redis clients @ `{}`", clients, server_name),
+            )
+            .with_metric("clients", clients as f64),
+            None => CheckResult::new(
+                Status::Critical,
+                "Error: Could not parse redis connected clients",
+            ),
+        };
+        results.push(result);
+    }
+
+    results
+}
+
+/// Retrieves a numeric `field:value` entry from the output of `redis-cli INFO`.
+fn redis_info_field(info: &str, field: &str) -> Option<u64> {
+    info.lines().find_map(|line| {
+        let (key, value) = line.trim().split_once(':')?;
+        if key == field {
+            value.trim().parse().ok()
+        } else {
+            None
+        }
+    })
+}
+
+/// Reads the status page of a web server, fetched with `curl` on the server itself: the
+/// `stub_status` of nginx or the machine-readable `mod_status` of Apache (`server-status?auto`).
 ///
-/// # Note
+/// The page is fetched twice, one second apart, to compute the request rate from the total number
+/// of requests served. The kind of web server is recognized from the page.
 ///
-/// This function relies on the `find` command's `-mtime` option to determine the age of directories,
-/// which is based on the time of the last modification to the directory's contents. This approach focuses
-/// on when files within the directory were last added, removed, or renamed, rather than when their metadata
-/// was last changed. Ensure that the remote server's environment and filesystem support the commands and
-/// options used.
+/// # Arguments
+///
+/// * `runner` - Runs the commands on the server, e.g. an established SSH `Session`.
+/// * `server_name` - The name of the server, used in the messages of the results.
+/// * `status_url` - The URL of the status page as seen from the server.
+/// * `connections_warning` - Optional number of active connections at which the result becomes a
+///   warning.
+/// * `max_connections` - Optional number of active connections at which the result becomes
+///   critical.
+/// * `busy_workers_warning` - Optional share of busy Apache workers, in percent, at which the
+///   result becomes a warning.
+/// * `max_busy_workers` - Optional share of busy Apache workers, in percent, at which the result
+///   becomes critical.
+///
+/// # Returns
+///
+/// Returns the results "nginx: 291 active connections @ web01" and "nginx: 12.0 requests/s @
+/// web01", with the `connections` and `requests_per_second` metrics, and for Apache "apache: 10
+/// of 150 workers busy (7%) @ web01", with the `busy_workers` metric.
 ///
-/// Error handling in this function logs command execution errors to standard error and returns an
-/// empty string. This approach is suitable for command-line applications but may need adjustment for
-/// use in other contexts where error logging or handling might be implemented differently.
-pub fn list_old_directories(sess: &Session, loc: &str, cutoff: u16) -> String {
-    let command = format!("find {} -maxdepth 1 -type d -mtime +{}", loc, cutoff);
-    let output = match ssh::run_ssh_command(sess, &command) {
+/// # Errors
+///
+/// If `curl` fails, e.g. the web server is down, or the page is not a status page, a critical
+/// result indicating the error is returned.
+#[allow(clippy::too_many_arguments)]
+pub fn web_server(
+    runner: &dyn CommandRunner,
+    server_name: &str,
+    status_url: &str,
+    connections_warning: Option<u32>,
+    max_connections: Option<u32>,
+    busy_workers_warning: Option<f64>,
+    max_busy_workers: Option<f64>,
+) -> Vec<CheckResult> {
+    let fetch = format!(
+        "curl -fsS --max-time 5 {}",
+        platform::shell_quote(status_url)
+    );
+    let command = format!("{} && echo --- && sleep 1 && {}", fetch, fetch);
+    let output = match runner.run(&command) {
+        Ok(output) => output,
+        Err(e) => {
+            warn!("Could not fetch the status of {}: {}", status_url, e);
+            return vec![
+                CheckResult::new(Status::Critical, format!("Error: {}", e)).with_error(e.as_ref())
+            ];
+        }
+    };
+    let samples: Vec<WebServerStatus> = output
+        .split("\n---\n")
+        .filter_map(WebServerStatus::parse)
+        .collect();
+    let [first, second] = samples.as_slice() else {
+        return vec![CheckResult::new(
+            Status::Critical,
+            format!(
+                "Error: {} is not an nginx stub_status or Apache server-status?auto page @ {}",
+                status_url, server_name
+            ),
+        )];
+    };
+
+    let status = Status::from_thresholds(
+        second.connections as f64,
+        connections_warning.map(f64::from),
+        max_connections.map_or(f64::INFINITY, f64::from),
+    );
+    let mut results = vec![CheckResult::new(
+        status,
+        format!(
+            "{}: {} active connections @ {}",
+            second.kind, second.connections, server_name
+        ),
+    )
+    .with_metric("connections", second.connections as f64)];
+    let rate = second.requests.saturating_sub(first.requests) as f64;
+    results.push(
+        CheckResult::new(
+            Status::Ok,
+            format!("{}: {:.1} requests/s @ {}", second.kind, rate, server_name),
+        )
+        .with_metric("requests_per_second", rate),
+    );
+    if let Some((busy, total)) = second.workers.filter(|(_, total)| *total > 0) {
+        let share = busy as f64 / total as f64 * 100.0;
+        let status = Status::from_thresholds(
+            share,
+            busy_workers_warning,
+            max_busy_workers.unwrap_or(f64::INFINITY),
+        );
+        results.push(
+            CheckResult::new(
+                status,
+                format!(
+                    "{}: {} of {} workers busy ({:.0}%) @ {}",
+                    second.kind, busy, total, share, server_name
+                ),
+            )
+            .with_metric("busy_workers", share),
+        );
+    }
+    results
+}
+
+/// A sample of the status page of a web server.
+struct WebServerStatus {
+    kind: &'static str,
+    connections: u64,
+    requests: u64,
+    /// The busy workers and the worker slots, for Apache.
+    workers: Option<(u64, u64)>,
+}
+
+impl WebServerStatus {
+    /// Parses the `stub_status` page of nginx or the `server-status?auto` page of Apache.
+    fn parse(page: &str) -> Option<WebServerStatus> {
+        if let Some((_, active)) = page.split_once("Active connections:") {
+            // Active connections: 291
+            // server accepts handled requests
+            //  16630948 16630948 31070465
+            let connections = active.split_whitespace().next()?.parse().ok()?;
+            let counters = page.lines().nth(2)?;
+            return Some(WebServerStatus {
+                kind: "nginx",
+                connections,
+                requests: counters.split_whitespace().nth(2)?.parse().ok()?,
+                workers: None,
+            });
+        }
+        let field = |name: &str| {
+            page.lines()
+                .find_map(|line| line.strip_prefix(name)?.strip_prefix(':'))
+                .map(str::trim)
+        };
+        let requests = field("Total Accesses")?.parse().ok()?;
+        let busy: u64 = field("BusyWorkers")?.parse().ok()?;
+        // Every character of the scoreboard is a worker slot
+        let slots = field("Scoreboard").map_or(0, |s| s.len() as u64);
+        Some(WebServerStatus {
+            kind: "apache",
+            connections: field("ConnsTotal")
+                .and_then(|c| c.parse().ok())
+                .unwrap_or(busy),
+            requests,
+            workers: Some((busy, slots)),
+        })
+    }
+}
+
+/// Checks that fail2ban is running and reports the addresses banned by its jails, as listed by
+/// `fail2ban-client status`.
+///
+/// The addresses currently banned are those banned within the ban time of the jail, so their
+/// number spikes with an attack, which the thresholds catch.
+///
+/// # Arguments
+///
+/// * `runner` - Runs the commands on the server, e.g. an established SSH `Session`.
+/// * `server_name` - The name of the server, used in the messages of the results.
+/// * `fail2ban_client` - The command running `fail2ban-client`, e.g. `sudo -n fail2ban-client`.
+/// * `jails` - Optional jails reported, all of them if `None`.
+/// * `banned_warning` - Optional number of addresses currently banned by a jail at which its
+///   result becomes a warning.
+/// * `max_banned` - Optional number of addresses currently banned by a jail at which its result
+///   becomes critical.
+///
+/// # Returns
+///
+/// Returns one result per jail, such as "sshd: 5 banned, 87 in total, 3 failing @ web01", with
+/// the number of addresses currently banned as metric named after the jail.
+///
+/// # Errors
+///
+/// If fail2ban is not running or cannot be queried, a critical result indicating the error is
+/// returned. A configured jail that does not exist gives a critical result.
+pub fn fail2ban(
+    runner: &dyn CommandRunner,
+    server_name: &str,
+    fail2ban_client: &str,
+    jails: Option<&[String]>,
+    banned_warning: Option<u32>,
+    max_banned: Option<u32>,
+) -> Vec<CheckResult> {
+    let command = format!(
+        "status=$({client} status) && for jail in $(echo \"$status\" \
+         | sed -n 's/.*Jail list://p' | tr -d ','); do {client} status \"$jail\"; done",
+        client = fail2ban_client
+    );
+    let output = match runner.run(&command) {
+        Ok(output) => output,
+        Err(e) => {
+            warn!("Could not query fail2ban on {}: {}", server_name, e);
+            return vec![CheckResult::new(
+                Status::Critical,
+                format!("Error: fail2ban is not running: {} @ {}", e, server_name),
+            )
+            .with_error(e.as_ref())];
+        }
+    };
+
+    // Status for the jail: sshd
+    // |- Filter
+    // |  |- Currently failed:	3
+    // ...
+    let field = |section: &str, name: &str| -> u64 {
+        section
+            .lines()
+            .find_map(|line| line.split_once(name)?.1.strip_prefix(':'))
+            .and_then(|value| value.trim().parse().ok())
+            .unwrap_or(0)
+    };
+    let found: Vec<(&str, &str)> = output
+        .split("Status for the jail:")
+        .skip(1)
+        .map(|section| {
+            let (name, section) = section.split_once('\n').unwrap_or((section, ""));
+            (name.trim(), section)
+        })
+        .collect();
+
+    let mut results = vec![];
+    if let Some(jails) = jails {
+        for jail in jails {
+            if !found.iter().any(|(name, _)| name == jail) {
+                results.push(CheckResult::new(
+                    Status::Critical,
+                    format!("jail {} not found @ {}", jail, server_name),
+                ));
+            }
+        }
+    }
+    for (name, section) in found {
+        if jails.is_some_and(|jails| !jails.iter().any(|jail| jail == name)) {
+            continue;
+        }
+        let banned = field(section, "Currently banned");
+        let status = Status::from_thresholds(
+            banned as f64,
+            banned_warning.map(f64::from),
+            max_banned.map_or(f64::INFINITY, f64::from),
+        );
+        results.push(
+            CheckResult::new(
+                status,
+                format!(
+                    "{}: {} banned, {} in total, {} failing @ {}",
+                    name,
+                    banned,
+                    field(section, "Total banned"),
+                    field(section, "Currently failed"),
+                    server_name
+                ),
+            )
+            .with_metric(name, banned as f64),
+        );
+    }
+    if results.is_empty() {
+        results.push(CheckResult::new(
+            Status::Warning,
+            format!("fail2ban runs without jail @ {}", server_name),
+        ));
+    }
+    results
+}
+
+/// Checks that the number of messages waiting in RabbitMQ queues is below a threshold.
+///
+/// This function runs `rabbitmqctl list_queues name messages` on the remote server and reports
+/// every queue holding more than `max_depth` messages. A growing queue usually means that the
+/// consumers are down or cannot keep up.
+///
+/// # Arguments
+///
+/// * `runner` - Runs the commands on the server, e.g. an established SSH `Session`.
+/// * `server_name` - The name of the server, used for reporting.
+/// * `max_depth` - Maximum number of messages allowed in a queue.
+/// * `vhost` - Optional virtual host to list the queues of, the default one is used if `None`.
+/// * `queues` - Optional list of queue names to restrict the check to; all queues are checked if `None`.
+///
+/// # Returns
+///
+/// Returns a critical `CheckResult` per queue above the threshold, rendered as e.g.:
+///
+/// ```text
+/// ❌ queue `emails` has 1500 messages (max 1000) @ `server_name`
+/// ```
+///
+/// or a single passing result if all queues are below the threshold.
+pub fn queue_depth(
+    runner: &dyn CommandRunner,
+    server_name: &str,
+    max_depth: u64,
+    vhost: Option<&str>,
+    queues: Option<&[String]>,
+) -> Vec<CheckResult> {
+    let mut command = "rabbitmqctl list_queues --quiet --no-table-headers".to_string();
+    if let Some(vhost) = vhost {
+        command.push_str(&format!(" -p {}", platform::shell_quote(vhost)));
+    }
+    command.push_str(" name messages");
+
+    let output = match runner.run(&command) {
         Ok(output) => output,
-        Err(e) => {
-            eprintln!("Error: {}", e);
-            return "".to_string();
-        }
+        Err(e) => return vec![CheckResult::new(Status::Critical, format!("Error: {}", e))],
     };
 
-    let files: Vec<&str> = output.split('\n').filter(|line| !line.is_empty()).collect();
+    let depths: Vec<(String, u64)> = parse_queue_depths(&output)
+        .into_iter()
+        .filter(|(name, _)| queues.is_none_or(|queues| queues.contains(name)))
+        .collect();
 
-    if files.is_empty() {
-        return format!("✅ No directories older than {} days in `{}`", cutoff, loc);
-    }
+    let full: Vec<CheckResult> = depths
+        .iter()
+        .filter(|(_, depth)| *depth > max_depth)
+        .map(|(name, depth)| {
+            CheckResult::new(
+                Status::Critical,
+                format!(
+                    "queue `{}` has {} messages (max {}) @ `{}`",
+                    name, depth, max_depth, server_name
+                ),
+            )
+            .with_metric(name.as_str(), *depth as f64)
+        })
+        .collect();
 
-    let mut result = format!("❌ Directories older than {} days:", cutoff);
-    result.push_str("\n```");
-    for file in files {
-        result.push('\n');
-        result.push_str(file);
+    if full.is_empty() {
+        vec![CheckResult::new(
+            Status::Ok,
+            format!(
+                "{} queues below {} messages @ `{}`",
+                depths.len(),
+                max_depth,
+                server_name
+            ),
+        )]
+    } else {
+        full
     }
-    result.push_str("```");
+}
 
-    result
+/// Parses the `name messages` table printed by `rabbitmqctl list_queues`, ignoring any header or
+/// informational line.
+fn parse_queue_depths(output: &str) -> Vec<(String, u64)> {
+    output
+        .lines()
+        .filter_map(|line| {
+            let mut fields = line.split_whitespace();
+            let name = fields.next()?;
+            let depth = fields.next()?.parse().ok()?;
+            Some((name.to_string(), depth))
+        })
+        .collect()
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::runner::MockRunner;
+
+    const UPTIME: &str =
+        " 10:15:02 up 12 days,  3:04,  2 users,  load average: 0.52, 21.30, 60.00\n";
+
+    #[test]
+    fn test_number_of_files() {
+        let runner = MockRunner::new()
+            .ok("find /spool -maxdepth 1 -type f | wc -l", "1\n")
+            .ok("find /queue/. ! -name . -prune -type f | wc -l", "600\n");
+        let results = number_of_files(
+            &runner,
+            "web01",
+            Platform::default(),
+            &["/spool".into()],
+            1000,
+            Some(500),
+        );
+        assert_eq!(results[0].status, Status::Ok);
+        assert_eq!(results[0].message, "1 file @ `web01:/spool`");
+        let busybox = Platform {
+            os: Os::Linux,
+            shell: ShellProfile::Busybox,
+        };
+        let results = number_of_files(
+            &runner,
+            "router",
+            busybox,
+            &["/queue".into()],
+            1000,
+            Some(500),
+        );
+        assert_eq!(results[0].status, Status::Warning);
+        assert_eq!(results[0].message, "600 files @ `router:/queue`");
+    }
+
+    #[test]
+    fn test_dir_size() {
+        let runner = MockRunner::new()
+            .ok("du -sk /var/log", "4404020\t/var/log\n")
+            .ok("du -sk /scratch", "12\t/scratch\n")
+            .fail("du -sk /missing", 1, "du: cannot access '/missing'");
+        let paths = ["/var/log", "/scratch", "/missing"].map(String::from);
+        let gib = 1024 * 1024 * 1024;
+        let results = dir_size(
+            &runner,
+            "web01",
+            Platform::default(),
+            &paths,
+            10 * gib,
+            Some(4 * gib),
+        );
+        assert_eq!(results[0].status, Status::Warning);
+        assert_eq!(results[0].message, "4.20 GiB @ `web01:/var/log`");
+        assert_eq!(
+            results[0].metric.as_ref().unwrap().value,
+            4404020.0 * 1024.0
+        );
+        assert_eq!(results[1].status, Status::Ok);
+        assert_eq!(results[1].message, "12.00 KiB @ `web01:/scratch`");
+        assert_eq!(results[2].status, Status::Critical);
+    }
+
+    #[test]
+    fn test_quoted_arguments() {
+        // The mock only knows the commands with the arguments quoted, failing on any other
+        let ran = |results: Vec<CheckResult>| {
+            assert!(
+                !results[0].message.contains("unexpected command"),
+                "{}",
+                results[0].message
+            );
+        };
+        let runner = MockRunner::new()
+            .ok("du -sk '/srv/$(reboot)'", "12\t/srv\n")
+            .ok("find '/spool; reboot' -maxdepth 1 -type f | wc -l", "1\n")
+            .ok(
+                "openssl x509 -enddate -noout -in '/etc/ssl/`reboot`.pem'",
+                "notAfter=Jan  1 00:00:00 2100 GMT\n",
+            )
+            .ok(
+                "if ! findmnt -n -o FSTYPE,SOURCE --mountpoint '/data > /etc/passwd'; \
+                 then echo unmounted; \
+                 elif timeout -s KILL 5 stat '/data > /etc/passwd' >/dev/null 2>&1; \
+                 then echo responsive; else echo hung; fi",
+                "unmounted\n",
+            )
+            .ok(
+                r"upsc 'ups'\''; reboot; '\'''",
+                "battery.charge: 100\nups.status: OL\n",
+            )
+            .ok(
+                "kubectl --kubeconfig '/etc/k.conf && reboot' get nodes --no-headers",
+                "node1 Ready control-plane 10d v1.30.0\n",
+            )
+            .ok("redis-cli -u 'redis://cache/0|reboot' ping", "PONG\n")
+            .ok(
+                "curl -fsS --max-time 5 'http://localhost/status?$(reboot)' && echo --- && \
+                 sleep 1 && curl -fsS --max-time 5 'http://localhost/status?$(reboot)'",
+                "<html></html>\n---\n<html></html>\n",
+            )
+            .ok(
+                "docker compose -f '/srv/$(reboot)/compose.yaml' config --services && echo --- && \
+                 docker compose -f '/srv/$(reboot)/compose.yaml' ps -a --format json",
+                "web\n---\n",
+            );
+        let default = Platform::default();
+        ran(dir_size(
+            &runner,
+            "web01",
+            default,
+            &["/srv/$(reboot)".into()],
+            1,
+            None,
+        ));
+        ran(number_of_files(
+            &runner,
+            "web01",
+            default,
+            &["/spool; reboot".into()],
+            1,
+            None,
+        ));
+        ran(certificate_files(
+            &runner,
+            "web01",
+            &["/etc/ssl/`reboot`.pem".into()],
+            30,
+            7,
+        ));
+        ran(mounts(
+            &runner,
+            "web01",
+            default,
+            &["/data > /etc/passwd".into()],
+            5,
+        ));
+        ran(ups(&runner, "nas01", "ups'; reboot; '", None, None));
+        ran(kubernetes(
+            &runner,
+            "k8s",
+            "/etc/k.conf && reboot",
+            None,
+            None,
+        ));
+        ran(redis(
+            &runner,
+            "cache01",
+            "redis://cache/0|reboot",
+            None,
+            None,
+        ));
+        let url = "http://localhost/status?$(reboot)";
+        ran(web_server(&runner, "web01", url, None, None, None, None));
+        let files = ["/srv/$(reboot)/compose.yaml".to_string()];
+        ran(compose(&runner, "web01", "docker", &files));
+    }
+
+    #[test]
+    fn test_top_directories() {
+        let du = "188743680\t/home\n125829120\t/home/alice\n52428800\t/home/bob\n";
+        let runner =
+            MockRunner::new().ok("du -k -d 1 /home 2>/dev/null | sort -rn | head -n 3", du);
+        let gib = 1024 * 1024 * 1024;
+        let platform = Platform::default();
+        let results = top_directories(&runner, "web01", platform, "/home", 2, 100 * gib, None);
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].status, Status::Critical);
+        assert_eq!(
+            results[0].message,
+            "largest under `web01:/home`: /home/alice (120.00 GiB)"
+        );
+        assert_eq!(
+            results[0].details.as_deref(),
+            Some("  120.00 GiB  /home/alice\n   50.00 GiB  /home/bob")
+        );
+
+        let results = top_directories(&runner, "web01", platform, "/home", 2, 200 * gib, None);
+        assert_eq!(results[0].status, Status::Ok);
+        assert_eq!(results[0].details, None);
+    }
+
+    #[test]
+    fn test_certificate_files() {
+        let end = |days: i64| {
+            let date =
+                chrono::Utc::now() + chrono::Duration::days(days) + chrono::Duration::hours(1);
+            format!("notAfter={} GMT\n", date.format("%b %e %H:%M:%S %Y"))
+        };
+        let runner = MockRunner::new()
+            .ok(
+                "openssl x509 -enddate -noout -in /etc/ssl/ca.pem",
+                &end(400),
+            )
+            .ok(
+                "openssl x509 -enddate -noout -in /etc/ssl/mail.pem",
+                &end(12),
+            )
+            .ok(
+                "openssl x509 -enddate -noout -in /etc/ssl/old.pem",
+                &end(-3),
+            )
+            .fail(
+                "openssl x509 -enddate -noout -in /etc/ssl/missing.pem",
+                1,
+                "Could not open file or uri for loading certificate\n",
+            );
+        let paths: Vec<String> = ["ca", "mail", "old", "missing"]
+            .iter()
+            .map(|name| format!("/etc/ssl/{}.pem", name))
+            .collect();
+        let results = certificate_files(&runner, "mail01", &paths, 30, 7);
+        let statuses: Vec<Status> = results.iter().map(|r| r.status).collect();
+        assert_eq!(
+            statuses,
+            [
+                Status::Ok,
+                Status::Warning,
+                Status::Critical,
+                Status::Critical
+            ]
+        );
+        assert!(results[1]
+            .message
+            .starts_with("/etc/ssl/mail.pem expires in 12 days ("));
+        assert!(results[2]
+            .message
+            .starts_with("/etc/ssl/old.pem expired 2 days ago ("));
+        assert_eq!(results[1].metric.as_ref().unwrap().value, 12.0);
+        assert_eq!(results[3].exit_code, Some(1));
+    }
+
+    #[test]
+    fn test_certbot() {
+        let end = |days: i64| {
+            let date =
+                chrono::Utc::now() + chrono::Duration::days(days) + chrono::Duration::hours(1);
+            format!("notAfter={} GMT", date.format("%b %e %H:%M:%S %Y"))
+        };
+        let output = format!(
+            "example.com {}\nmail.example.com {}\nold.example.com {}\n\
+             2024-05-01 10:00:00,123:ERROR:certbot._internal.renewal:Failed to renew certificate \
+             mail.example.com with error: Some challenges have failed.\n",
+            end(75),
+            end(12),
+            "Could not open file or uri for loading certificate",
+        );
+        let runner = MockRunner::new().ok(
+            "for conf in /etc/letsencrypt/renewal/*.conf; do [ -e \"$conf\" ] || continue; \
+             name=$(basename \"$conf\" .conf); \
+             echo \"$name $(openssl x509 -enddate -noout -in /etc/letsencrypt/live/$name/cert.pem 2>&1 | head -n 1)\"; \
+             done; \
+             log=$(ls -t /var/log/letsencrypt/letsencrypt.log* 2>/dev/null \
+             | xargs -r grep -l 'Processing '/etc/letsencrypt/renewal 2>/dev/null | head -n 1); \
+             [ -z \"$log\" ] || grep -h 'Failed to renew certificate' \"$log\" || true",
+            &output,
+        );
+        let results = certbot(
+            &runner,
+            "web01",
+            "/etc/letsencrypt/",
+            "/var/log/letsencrypt",
+            20,
+        );
+        assert_eq!(results.len(), 3);
+        assert_eq!(results[0].status, Status::Ok);
+        assert!(results[0]
+            .message
+            .starts_with("example.com expires in 75 days ("));
+        assert_eq!(results[1].status, Status::Critical);
+        assert!(results[1]
+            .message
+            .ends_with("), renewal overdue, latest renewal failed @ web01"));
+        assert_eq!(
+            results[1].details.as_deref(),
+            Some("Some challenges have failed.")
+        );
+        assert_eq!(results[2].status, Status::Critical);
+        assert!(results[2].message.ends_with("@ `web01:old.example.com`"));
+    }
+
+    #[test]
+    fn test_permissions() {
+        let ls = "\
+-rw-rw-rw- 1 www-data www-data 120 Jan  1  2024 /srv/app/config.yml
+drwxrwxrwt 2 root root 4096 May  1 10:00 /srv/tmp
+-rw-r--r-- 1 1005 www-data 5 May  1 10:00 /srv/app/old file.txt
+-rw-r--r-- 1 bob bob 5 May  1 10:00 /srv/app/notes
+";
+        let runner = MockRunner::new()
+            .ok(
+                "find /srv -xdev \\( -type f -o -type d \\) \\( -perm -0002 ! -perm -1000 -o -nouser \
+                 -o -nogroup -o ! -user www-data \\) -exec ls -ld {} + 2>/dev/null | head -n 4",
+                ls,
+            )
+            .ok(
+                "find /srv -xdev \\( -type f -o -type d \\) \\( -perm -0002 ! -perm -1000 \\) \
+                 -exec ls -ld {} + 2>/dev/null | head -n 21",
+                "",
+            );
+        let paths = ["/srv".to_string()];
+        let platform = Platform::default();
+        let results = permissions(&runner, "web01", platform, &paths, Some("www-data"), 3);
+        assert_eq!(results[0].status, Status::Critical);
+        assert_eq!(
+            results[0].message,
+            "more than 3 files with wrong permissions under /srv @ web01"
+        );
+        assert_eq!(
+            results[0].details.as_deref(),
+            Some(
+                "world-writable  -rw-rw-rw- www-data www-data /srv/app/config.yml\n\
+                 wrong owner     drwxrwxrwt root root /srv/tmp\n\
+                 unknown user    -rw-r--r-- 1005 www-data /srv/app/old file.txt"
+            )
+        );
+
+        let platform = Platform {
+            shell: ShellProfile::Busybox,
+            ..Platform::default()
+        };
+        let results = permissions(&runner, "web01", platform, &paths, None, 20);
+        assert_eq!(results[0].status, Status::Ok);
+    }
+
+    #[test]
+    fn test_quota() {
+        let report = "*** Report for user quotas on device /dev/sda1\n\
+                      Block grace time: 7days; Inode grace time: 7days\n\
+                      \x20                       Block limits                File limits\n\
+                      User            used    soft    hard  grace    used  soft  hard  grace\n\
+                      ----------------------------------------------------------------------\n\
+                      root      --      20       0       0              2     0     0\n\
+                      alice     +-  1050000 1000000 1100000  6days     120     0     0\n\
+                      bob       --   850000       0 1000000             12     0     0\n\
+                      carol     --   100000 1000000 1100000             12     0     0\n";
+        let runner = MockRunner::new().ok("repquota -u -a", report);
+        let results = quota(
+            &runner,
+            "hpc01",
+            QuotaKind::Users,
+            None,
+            false,
+            Some(80.0),
+            100.0,
+        );
+        assert_eq!(results.len(), 2);
+        assert_eq!(results[0].status, Status::Critical);
+        assert_eq!(
+            results[0].message,
+            "alice uses 105% of their quota on /dev/sda1 (1.00 GiB of 976.56 MiB) @ hpc01"
+        );
+        assert_eq!(results[1].status, Status::Warning);
+        assert!(results[1].message.starts_with("bob uses 85% "));
+
+        let runner = MockRunner::new().ok(
+            "xfs_quota -x -c 'report -N -n -p' /scratch",
+            "#0 0 0 0 00 [--------]\n#42 100 1000 2000 00 [--------]\n",
+        );
+        let results = quota(
+            &runner,
+            "hpc01",
+            QuotaKind::Projects,
+            Some("/scratch"),
+            true,
+            Some(80.0),
+            100.0,
+        );
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].status, Status::Ok);
+        assert_eq!(results[0].message, "every quota below 80% @ hpc01");
+    }
+
+    #[test]
+    fn test_number_of_folder() {
+        let runner = MockRunner::new()
+            .ok(
+                "find /data -maxdepth 1 -type d | tail -n +2 | wc -l",
+                "120\n",
+            )
+            .ok(
+                "find /empty -maxdepth 1 -type d | tail -n +2 | wc -l",
+                "0\n",
+            )
+            .fail(
+                "find /missing -maxdepth 1 -type d | tail -n +2 | wc -l",
+                1,
+                "find: '/missing': No such file or directory",
+            );
+        let paths = ["/data", "/empty", "/missing"].map(String::from);
+        let results = number_of_folders(
+            &runner,
+            "web01",
+            Platform::default(),
+            &paths,
+            &100,
+            Some(50),
+        );
+        assert_eq!(results.len(), 3);
+        assert_eq!(results[0].status, Status::Critical);
+        assert_eq!(results[0].message, "120 folders @ `web01:/data`");
+        assert_eq!(results[1].status, Status::Ok);
+        assert_eq!(results[1].message, "No folders @ `web01:/empty`");
+        assert_eq!(results[2].status, Status::Critical);
+        assert!(results[2].message.contains("No such file or directory"));
+    }
+
+    #[test]
+    fn test_load() {
+        let runner = MockRunner::new().ok("uptime", UPTIME);
+        let results = load(&runner, "web01", Platform::default(), 1, Some(20.0), 50.0);
+        assert_eq!(results[0].status, Status::Ok);
+        assert_eq!(results[0].message, "load 0.52 (1min) @ web01");
+        assert_eq!(
+            results[0].metric,
+            Some(crate::report::Metric {
+                name: "load1".to_string(),
+                value: 0.52
+            })
+        );
+
+        let results = load(&runner, "web01", Platform::default(), 5, Some(20.0), 50.0);
+        assert_eq!(results[0].status, Status::Warning);
+        let results = load(&runner, "web01", Platform::default(), 15, Some(20.0), 50.0);
+        assert_eq!(results[0].status, Status::Critical);
+
+        let results = load(
+            &MockRunner::new(),
+            "web01",
+            Platform::default(),
+            1,
+            None,
+            50.0,
+        );
+        assert_eq!(results[0].status, Status::Critical);
+        assert_eq!(results[0].message, "Error: unexpected command `uptime`");
+
+        let runner = MockRunner::new().ok("uptime", "garbage");
+        let results = load(&runner, "web01", Platform::default(), 1, None, 50.0);
+        assert_eq!(results[0].message, "Error: Could not parse load average");
+
+        let macos = "10:15  up 12 days,  3:04, 2 users, load averages: 1.52 2.30 3.00\n";
+        let runner = MockRunner::new().ok("uptime", macos);
+        let results = load(&runner, "mac01", Os::Macos.into(), 5, None, 50.0);
+        assert_eq!(results[0].message, "load 2.30 (5min) @ mac01");
+    }
+
+    #[test]
+    fn test_cpu() {
+        const COMMAND: &str = "head -n 1 /proc/stat; sleep 1; head -n 1 /proc/stat";
+        let stat = "cpu  1000 0 500 8000 500 0 0 0 0 0\n\
+                    cpu  1060 0 520 8080 540 0 0 0 0 0\n";
+        let runner = MockRunner::new().ok(COMMAND, stat);
+        let results = cpu(
+            &runner,
+            "web01",
+            Platform::default(),
+            Some(20.0),
+            90.0,
+            None,
+            None,
+        );
+        assert_eq!(results.len(), 2);
+        assert_eq!(results[0].status, Status::Warning);
+        assert_eq!(results[0].message, "cpu 40.0% @ web01");
+        assert_eq!(results[1].status, Status::Ok);
+        assert_eq!(results[1].message, "iowait 20.0% @ web01");
+        assert_eq!(results[1].metric.as_ref().unwrap().name, "iowait");
+        let results = cpu(
+            &runner,
+            "web01",
+            Platform::default(),
+            None,
+            90.0,
+            None,
+            Some(20.0),
+        );
+        assert_eq!(results[1].status, Status::Critical);
+
+        let runner = MockRunner::new().ok(COMMAND, "cpu  1000 0 500 8000 500 0 0 0 0 0\n");
+        let results = cpu(
+            &runner,
+            "web01",
+            Platform::default(),
+            None,
+            90.0,
+            None,
+            None,
+        );
+        assert_eq!(results[0].message, "Error: Could not parse CPU usage");
+
+        let runner = MockRunner::new().ok(
+            "sysctl -n kern.cp_time; sleep 1; sysctl -n kern.cp_time",
+            "100 0 50 10 840\n150 0 100 10 940\n",
+        );
+        let results = cpu(&runner, "bsd01", Os::Freebsd.into(), None, 90.0, None, None);
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].message, "cpu 50.0% @ bsd01");
+
+        let top = "CPU usage: 10.0% user, 5.0% sys, 85.0% idle\n\
+                   CPU usage: 70.5% user, 25.0% sys, 4.5% idle\n";
+        let runner = MockRunner::new().ok("top -l 2 -n 0 -s 1 | grep 'CPU usage'", top);
+        let results = cpu(&runner, "mac01", Os::Macos.into(), None, 90.0, None, None);
+        assert_eq!(results[0].status, Status::Critical);
+        assert_eq!(results[0].message, "cpu 95.5% @ mac01");
+    }
+
+    #[test]
+    fn test_uptime() {
+        let boot = chrono::Utc::now() - chrono::Duration::days(12);
+        let runner = MockRunner::new()
+            .ok(
+                "grep btime /proc/stat",
+                &format!("btime {}\n", boot.timestamp()),
+            )
+            .ok(
+                "sysctl -n kern.boottime",
+                &format!("{{ sec = {}, usec = 42 }} Thu Oct  9\n", boot.timestamp()),
+            );
+        let results = uptime(&runner, "web01", Platform::default());
+        assert_eq!(results[0].status, Status::Ok);
+        assert_eq!(results[0].message, "up 12 days @ web01");
+        assert!(matches!(
+            results[0].observation,
+            Some(Observation::Boot(time)) if time.timestamp() == boot.timestamp()
+        ));
+        let results = uptime(&runner, "bsd01", Os::Freebsd.into());
+        assert_eq!(results[0].message, "up 12 days @ bsd01");
+
+        let runner = MockRunner::new().ok("grep btime /proc/stat", "");
+        let results = uptime(&runner, "web01", Platform::default());
+        assert_eq!(results[0].message, "Error: Could not parse boot time");
+    }
+
+    #[test]
+    fn test_entropy() {
+        let command = "cat /proc/sys/kernel/random/entropy_avail /proc/sys/kernel/random/poolsize";
+        let check = |output: &str| {
+            let runner = MockRunner::new().ok(command, output);
+            let result = entropy(&runner, "web01", Platform::default(), 200, Some(1000)).remove(0);
+            (result.status, result.message)
+        };
+        assert_eq!(
+            check("3012\n4096\n"),
+            (Status::Ok, "entropy 3012/4096 bits @ web01".to_string())
+        );
+        assert_eq!(check("512\n4096\n").0, Status::Warning);
+        assert_eq!(check("150\n4096\n").0, Status::Critical);
+        assert_eq!(check("").0, Status::Critical);
+    }
+
+    #[test]
+    fn test_kernel() {
+        let check = |output: &str| {
+            let runner = MockRunner::new().ok(KERNEL_COMMAND, output);
+            let result = kernel(&runner, "web01", Platform::default(), Status::Warning).remove(0);
+            (result.status, result.message)
+        };
+        assert_eq!(
+            check("6.1.0-18-amd64\n6.1.0-9-amd64\n6.1.0-18-amd64\n6.1.0-21-amd64\n6.1.0-25-cloud-amd64\n"),
+            (
+                Status::Warning,
+                "running kernel 6.1.0-18-amd64, 6.1.0-21-amd64 installed, reboot needed @ web01"
+                    .to_string()
+            )
+        );
+        assert_eq!(
+            check("5.14.0-362.8.1.el9_3.x86_64\n5.14.0-284.11.1.el9_2.x86_64\n5.14.0-362.8.1.el9_3.x86_64\n"),
+            (
+                Status::Ok,
+                "running the newest kernel 5.14.0-362.8.1.el9_3.x86_64 @ web01".to_string()
+            )
+        );
+        assert_eq!(check("6.6.8-arch1-1\n").0, Status::Critical);
+    }
+
+    #[test]
+    fn test_oom_kills() {
+        let log = "1760000000.123456 web01 kernel: Out of memory: Killed process 1234 (java) \
+                   total-vm:9000kB, anon-rss:8000kB\n\
+                   2025-10-09T10:15:30,500000+02:00 Memory cgroup out of memory: \
+                   Killed process 99 (python3) total-vm:100kB\n";
+        let runner = MockRunner::new().ok(OOM_KILLS_COMMAND, log);
+        let results = oom_kills(&runner, "web01", Platform::default(), Status::Critical);
+        assert_eq!(results.len(), 2);
+        assert_eq!(results[0].status, Status::Critical);
+        assert!(results[0]
+            .message
+            .starts_with("java (1234) killed by the OOM killer "));
+        assert_eq!(
+            results[0].observation,
+            chrono::DateTime::from_timestamp_micros(1760000000123456).map(Observation::Kill)
+        );
+        assert!(results[1].message.starts_with("python3 (99) killed"));
+        assert_eq!(
+            results[1].observation,
+            chrono::DateTime::from_timestamp_micros(1759997730500000).map(Observation::Kill)
+        );
+
+        let runner = MockRunner::new().ok(OOM_KILLS_COMMAND, "");
+        let results = oom_kills(&runner, "web01", Platform::default(), Status::Critical);
+        assert_eq!(results[0].status, Status::Ok);
+        assert_eq!(results[0].message, "no OOM kills @ web01");
+
+        let results = oom_kills(&runner, "mac01", Os::Macos.into(), Status::Critical);
+        assert_eq!(
+            results[0].message,
+            "Error: OOM kills are only detected on Linux"
+        );
+    }
+
+    #[test]
+    fn test_process_states() {
+        let ps = "Ss       1 systemd\nZ      412 defunct\nD     1031 rsync\nD+    1032 ls\nR+    2000 ps\n";
+        let runner = MockRunner::new().ok("ps -A -o stat=,pid=,comm=", ps);
+        let results = process_states(
+            &runner,
+            "nfs01",
+            Platform::default(),
+            None,
+            5,
+            Some(2),
+            None,
+        );
+        assert_eq!(results.len(), 2);
+        assert_eq!(results[0].status, Status::Ok);
+        assert_eq!(results[0].message, "1 zombie process @ nfs01");
+        assert_eq!(results[0].details, None);
+        assert_eq!(results[1].status, Status::Warning);
+        assert_eq!(results[1].message, "2 blocked processes @ nfs01");
+        assert_eq!(results[1].details.as_deref(), Some("1031 rsync\n1032 ls"));
+    }
+
+    #[test]
+    fn test_process_states_busybox() {
+        // A process ending during the read makes `cat` fail, which the command must ignore
+        let stat = "1 (init) S 0 1\n77 (my (odd) app) D 1 77\n";
+        let runner = MockRunner::new().ok("cat /proc/[0-9]*/stat 2>/dev/null; true", stat);
+        let busybox = Platform {
+            os: Os::Linux,
+            shell: ShellProfile::Busybox,
+        };
+        let results = process_states(&runner, "router", busybox, None, 1, None, Some(1));
+        assert_eq!(results[0].message, "0 zombie processes @ router");
+        assert_eq!(results[1].status, Status::Critical);
+        assert_eq!(results[1].details.as_deref(), Some("77 my (odd) app"));
+    }
+
+    #[test]
+    fn test_network() {
+        let states =
+            "for i in eth0 wg0 eth1; do echo \"$i\" $(cat \"/sys/class/net/$i/operstate\" \
+                      2>/dev/null || echo missing); done";
+        let dev = "Inter-|   Receive |  Transmit\n \
+                   face |bytes packets errs drop fifo frame compressed multicast|bytes\n";
+        let sample = |rx: u64, tx: u64| {
+            format!(
+                "    lo: 100 1 0 0 0 0 0 0 100 1 0 0 0 0 0 0\n  \
+                 eth0: {} 10 2 3 0 0 0 0 {} 10 1 0 0 0 0 0\n   \
+                 wg0: 0 0 0 0 0 0 0 0 0 0 0 0 0 0 0 0\n",
+                rx, tx
+            )
+        };
+        let runner = MockRunner::new()
+            .ok(states, "eth0 up\nwg0 down\neth1 missing\n")
+            .ok(
+                "cat /proc/net/dev; sleep 1; cat /proc/net/dev",
+                &format!(
+                    "{}{}{}{}",
+                    dev,
+                    sample(0, 0),
+                    dev,
+                    sample(12_500_000, 250_000)
+                ),
+            )
+            .ok("cat /proc/net/dev", &format!("{}{}", dev, sample(0, 0)));
+        let interfaces = ["eth0", "wg0", "eth1"].map(String::from);
+
+        let results = network(
+            &runner,
+            "web01",
+            Platform::default(),
+            &interfaces,
+            Some(80.0),
+            None,
+        );
+        assert_eq!(results.len(), 3);
+        assert_eq!(results[0].status, Status::Warning);
+        assert_eq!(
+            results[0].message,
+            "eth0 up, ↓ 100.00 Mbit/s ↑ 2.00 Mbit/s, 3 errors, 3 drops @ web01"
+        );
+        assert_eq!(results[0].metric.as_ref().unwrap().value, 100.0);
+        assert_eq!(results[1].status, Status::Critical);
+        assert_eq!(results[1].message, "wg0 is down @ web01");
+        assert_eq!(results[2].message, "eth1 not found @ web01");
+
+        let results = network(
+            &runner,
+            "web01",
+            Platform::default(),
+            &interfaces,
+            None,
+            None,
+        );
+        assert_eq!(results[0].status, Status::Ok);
+        assert_eq!(results[0].message, "eth0 up, 3 errors, 3 drops @ web01");
+        assert_eq!(results[0].metric, None);
+    }
+
+    #[test]
+    fn test_connections() {
+        let ss = "State      Recv-Q Send-Q Local Address:Port  Peer Address:Port\n\
+                  LISTEN     0      128    0.0.0.0:443         0.0.0.0:*\n\
+                  ESTAB      0      0      10.0.0.1:443        192.0.2.7:51000\n\
+                  ESTAB      0      0      10.0.0.1:443        192.0.2.8:51001\n\
+                  ESTAB      0      0      10.0.0.1:40000      198.51.100.1:443\n\
+                  ESTAB      0      0      [::1]:5432          [::1]:40001\n\
+                  TIME-WAIT  0      0      10.0.0.1:443        192.0.2.9:51002\n";
+        let runner = MockRunner::new().ok("ss -tan", ss);
+        let platform = Platform::default();
+        let results = connections(
+            &runner,
+            "web01",
+            platform,
+            Some(443),
+            Some(3),
+            10,
+            None,
+            None,
+        );
+        assert_eq!(results[0].status, Status::Warning);
+        assert_eq!(
+            results[0].message,
+            "3 established connections on port 443 @ web01"
+        );
+        assert_eq!(results[1].status, Status::Ok);
+        assert_eq!(
+            results[1].message,
+            "1 connection in TIME_WAIT on port 443 @ web01"
+        );
+        let results = connections(&runner, "web01", platform, None, None, 4, None, Some(1));
+        assert_eq!(results[0].status, Status::Critical);
+        assert_eq!(results[0].message, "4 established connections @ web01");
+        assert_eq!(results[1].status, Status::Critical);
+
+        let netstat = "Active Internet connections (including servers)\n\
+                       Proto Recv-Q Send-Q  Local Address          Foreign Address        (state)\n\
+                       tcp4       0      0  10.0.0.1.443           192.0.2.7.51000        ESTABLISHED\n\
+                       tcp4       0      0  10.0.0.1.443           192.0.2.9.51002        TIME_WAIT\n";
+        let runner = MockRunner::new().ok("netstat -an -p tcp", netstat);
+        let results = connections(
+            &runner,
+            "bsd01",
+            Os::Freebsd.into(),
+            Some(443),
+            None,
+            10,
+            None,
+            None,
+        );
+        assert_eq!(
+            results[0].message,
+            "1 established connection on port 443 @ bsd01"
+        );
+        assert_eq!(
+            results[1].message,
+            "1 connection in TIME_WAIT on port 443 @ bsd01"
+        );
+    }
+
+    #[test]
+    fn test_listening_ports() {
+        let ss = "\
+LISTEN 0      4096   127.0.0.53%lo:53        0.0.0.0:*    users:((\"systemd-resolve\",pid=601,fd=14))
+LISTEN 0      128          0.0.0.0:22        0.0.0.0:*    users:((\"sshd\",pid=812,fd=3))
+LISTEN 0      5            0.0.0.0:8080      0.0.0.0:*    users:((\"python3\",pid=4242,fd=3))
+LISTEN 0      128             [::]:22           [::]:*    users:((\"sshd\",pid=812,fd=4))
+LISTEN 0      511            [::1]:6379         [::]:*
+";
+        let netstat = "\
+Active Internet connections (including servers)
+Proto Recv-Q Send-Q Local Address          Foreign Address        (state)
+tcp4       0      0 *.22                   *.*                    LISTEN
+tcp4       0      0 192.0.2.10.52341       192.0.2.1.443          ESTABLISHED
+";
+        let runner = MockRunner::new()
+            .ok("ss -Htlnp", ss)
+            .ok("netstat -an -p tcp", netstat);
+        let results = listening_ports(&runner, "web01", Platform::default(), &[22], false);
+        let messages: Vec<&str> = results.iter().map(|r| r.message.as_str()).collect();
+        assert_eq!(
+            messages,
+            [
+                "unexpected listener on port 53 (systemd-resolve) @ web01",
+                "unexpected listener on port 6379 @ web01",
+                "unexpected listener on port 8080 (python3) @ web01",
+            ]
+        );
+        assert_eq!(results[0].status, Status::Critical);
+        assert_eq!(results[0].metric.as_ref().unwrap().value, 3.0);
+
+        let results = listening_ports(&runner, "web01", Platform::default(), &[22, 8080], true);
+        assert_eq!(results[0].status, Status::Ok);
+        assert_eq!(results[0].message, "2 listening ports, all allowed @ web01");
+
+        let results = listening_ports(&runner, "web01", Os::Freebsd.into(), &[22], false);
+        assert_eq!(results[0].message, "1 listening ports, all allowed @ web01");
+    }
+
+    #[test]
+    fn test_logins() {
+        let output = "\
+bob      pts/1        203.0.113.9      2024-05-01T12:00:00+00:00   still logged in
+reboot   system boot  6.1.0-18-amd64   2024-05-01T09:00:00+00:00   still running
+alice    pts/0        192.0.2.5        2024-05-01T10:00:00+00:00 - 2024-05-01T11:00:00+00:00  (01:00)
+root     tty1                          2024-05-01T08:00:00+00:00 - 2024-05-01T08:05:00+00:00  (00:05)
+
+wtmp begins 2024-04-01T00:00:00+00:00
+-- failed
+admin    ssh:notty    203.0.113.7      2024-05-01T10:30:00+00:00 - 2024-05-01T10:30:00+00:00  (00:00)
+
+btmp begins 2024-04-01T00:00:00+00:00
+";
+        let runner = MockRunner::new().ok(LOGINS_COMMAND, output);
+        let allowed = ["alice".to_string(), "root".to_string()];
+        let results = logins(&runner, "web01", Platform::default(), &allowed);
+        let messages: Vec<(Status, &str)> = results
+            .iter()
+            .map(|r| (r.status, r.message.as_str()))
+            .collect();
+        assert_eq!(
+            messages,
+            [
+                (Status::Ok, "root logged in on tty1 @ web01"),
+                (Status::Ok, "alice logged in from 192.0.2.5 @ web01"),
+                (Status::Critical, "bob logged in from 203.0.113.9 @ web01"),
+                (Status::Ok, "203.0.113.7"),
+            ]
+        );
+        assert!(matches!(
+            results[3].observation,
+            Some(Observation::FailedLogin(_))
+        ));
+
+        let runner = MockRunner::new().ok(LOGINS_COMMAND, "-- failed\n!lastb\n");
+        let results = logins(&runner, "web01", Platform::default(), &allowed);
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].status, Status::Warning);
+    }
+
+    #[test]
+    fn test_security_module() {
+        let check = |output: &str, expected: Enforcement| {
+            let runner = MockRunner::new().ok(SECURITY_MODULE_COMMAND, output);
+            security_module(
+                &runner,
+                "web01",
+                Platform::default(),
+                expected,
+                Some(5),
+                Some(50),
+            )
+            .into_iter()
+            .map(|r| (r.status, r.message))
+            .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            check("selinux 1\ndenials 0\n", Enforcement::Enforcing),
+            [
+                (Status::Ok, "SELinux enforcing @ web01".to_string()),
+                (Status::Ok, "0 denials in the past hour @ web01".to_string()),
+            ]
+        );
+        assert_eq!(
+            check("selinux 0\ndenials 7\n", Enforcement::Enforcing),
+            [
+                (
+                    Status::Critical,
+                    "SELinux permissive, expected enforcing @ web01".to_string()
+                ),
+                (
+                    Status::Warning,
+                    "7 denials in the past hour @ web01".to_string()
+                ),
+            ]
+        );
+        let apparmor = "apparmor\n/usr/sbin/cupsd (enforce)\nnginx (complain)\ndenials 1\n";
+        assert_eq!(
+            check(apparmor, Enforcement::Complaining)[0],
+            (
+                Status::Ok,
+                "AppArmor complaining (1 of 2 profiles) @ web01".to_string()
+            )
+        );
+        assert_eq!(
+            check(apparmor, Enforcement::Enforcing)[0],
+            (
+                Status::Critical,
+                "AppArmor complaining (1 of 2 profiles), expected enforcing @ web01".to_string()
+            )
+        );
+        assert_eq!(
+            check("apparmor\n!profiles\ndenials 0\n", Enforcement::Enforcing)[0].0,
+            Status::Warning
+        );
+        assert_eq!(
+            check("disabled\ndenials 0\n", Enforcement::Complaining)[0],
+            (
+                Status::Critical,
+                "no SELinux or AppArmor, expected complaining @ web01".to_string()
+            )
+        );
+    }
+
+    #[test]
+    fn test_docker_disk() {
+        let command = "docker system df --format '{{.Type}}|{{.Size}}|{{.Reclaimable}}' \
+                       && echo \"dangling $(docker images -qf dangling=true | wc -l) \
+                       $(docker volume ls -qf dangling=true | wc -l)\"";
+        let output = "Images|30GB|12GB (40%)\nContainers|1.5GB|0B (0%)\n\
+                      Local Volumes|8.5GB|500MB (5%)\nBuild Cache|0B|0B\ndangling 3 2\n";
+        let runner = MockRunner::new().ok(command, output);
+        let results = docker_disk(
+            &runner,
+            "web01",
+            "docker",
+            50_000_000_000,
+            Some(35_000_000_000),
+            Some(5),
+        );
+        assert_eq!(results[0].status, Status::Warning);
+        assert_eq!(
+            results[0].message,
+            "Docker uses 37.25 GiB (11.64 GiB reclaimable) @ web01"
+        );
+        assert_eq!(
+            results[0].details.as_deref().unwrap().lines().next(),
+            Some("Images: 27.94 GiB (11.18 GiB reclaimable)")
+        );
+        assert_eq!(results[1].status, Status::Warning);
+        assert_eq!(
+            results[1].message,
+            "3 dangling images, 2 dangling volumes @ web01"
+        );
+
+        let runner = MockRunner::new().fail(
+            "docker system df --format '{{.Type}}|{{.Size}}|{{.Reclaimable}}'",
+            1,
+            "permission denied while trying to connect to the Docker daemon socket",
+        );
+        let results = docker_disk(&runner, "web01", "docker", 50_000_000_000, None, None);
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].status, Status::Critical);
+    }
 
     #[test]
-    #[ignore] // TODO
-    fn test_number_of_folder() {}
+    fn test_container_restarts() {
+        let command =
+            "docker ps -a --filter status=restarting --format '{{.Names}}' && echo --- && \
+                       docker events --since 60m --until \"$(date +%s)\" --filter type=container \
+                       --filter event=die --format '{{.Actor.Attributes.name}} \
+                       {{.Actor.Attributes.exitCode}}'";
+        let output = "worker\n---\napi 1\nweb 0\napi 1\napi 137\n";
+        let runner = MockRunner::new().ok(command, output);
+        let messages: Vec<(Status, String)> = container_restarts(&runner, "web01", "docker", 2, 60)
+            .into_iter()
+            .map(|r| (r.status, r.message))
+            .collect();
+        assert_eq!(
+            messages,
+            [
+                (
+                    Status::Critical,
+                    "api restarted 3 times in 60 minutes (exit code 137) @ web01".to_string()
+                ),
+                (Status::Critical, "worker restarting @ web01".to_string()),
+            ]
+        );
+
+        let runner = MockRunner::new().ok(command, "---\nweb 0\n");
+        let results = container_restarts(&runner, "web01", "docker", 2, 60);
+        assert_eq!(results[0].status, Status::Ok);
+        assert_eq!(results[0].message, "no container in a restart loop @ web01");
+    }
+
+    #[test]
+    fn test_compose() {
+        let command = "docker compose -f /srv/app/compose.yaml config --services && echo --- && \
+                       docker compose -f /srv/app/compose.yaml ps -a --format json";
+        let output = r#"web
+db
+migrate
+worker
+cache
+---
+{"Project":"app","Service":"web","State":"running","Health":"healthy","ExitCode":0}
+{"Project":"app","Service":"db","State":"exited","Health":"","ExitCode":1}
+{"Project":"app","Service":"migrate","State":"exited","Health":"","ExitCode":0}
+{"Project":"app","Service":"cache","State":"running","Health":"starting","ExitCode":0}
+"#;
+        let runner = MockRunner::new().ok(command, output);
+        let files = ["/srv/app/compose.yaml".to_string()];
+        let results = compose(&runner, "web01", "docker", &files);
+        assert_eq!(results[0].status, Status::Critical);
+        assert_eq!(results[0].message, "app: 2/5 services up @ web01");
+        assert_eq!(
+            results[0].details.as_deref(),
+            Some("db: exited (exit code 1)\nworker: no container\ncache: starting")
+        );
+        assert_eq!(results[0].metric.as_ref().unwrap().value, 3.0);
+
+        // Compose before 2.21 prints an array
+        let output =
+            "web\n---\n[{\"Project\":\"app\",\"Service\":\"web\",\"State\":\"running\"}]\n";
+        let runner = MockRunner::new().ok(command, output);
+        let results = compose(&runner, "web01", "docker", &files);
+        assert_eq!(results[0].status, Status::Ok);
+        assert_eq!(results[0].message, "app: 1/1 services up @ web01");
+    }
+
+    #[test]
+    fn test_account_files() {
+        let files = ["/etc/passwd".to_string(), "/etc/sudoers.d".to_string()];
+        let command =
+            "for f in /etc/passwd /etc/sudoers.d; do if [ -d \"$f\" ]; then \
+             [ -r \"$f\" ] || echo \"==! $f\"; set -- \"$f\"/*; else set -- \"$f\"; fi; for g in \"$@\"; do [ -f \"$g\" ] || continue; \
+             if [ -r \"$g\" ]; then echo \"==> $g\"; awk 1 \"$g\"; else echo \"==! $g\"; fi; \
+             done; done";
+        let output = "==> /etc/passwd\nroot:x:0:0:root:/root:/bin/bash\n\
+                      bob:x:1001:1001::/home/bob:/bin/bash\n==! /etc/sudoers.d/bob\n";
+        let runner = MockRunner::new().ok(command, output);
+        let results = account_files(&runner, "web01", Platform::default(), &files);
+        assert_eq!(results.len(), 2);
+        assert_eq!(results[0].message, "/etc/passwd");
+        assert_eq!(
+            results[0].details.as_deref(),
+            Some("root:x:0:0:root:/root:/bin/bash\nbob:x:1001:1001::/home/bob:/bin/bash")
+        );
+        assert_eq!(results[0].observation, Some(Observation::File));
+        assert_eq!(results[1].message, "/etc/sudoers.d/bob");
+        assert_eq!(results[1].observation, Some(Observation::UnreadableFile));
+
+        let runner = MockRunner::new().ok(command, "");
+        let results = account_files(&runner, "web01", Platform::default(), &files);
+        assert_eq!(results[0].status, Status::Critical);
+    }
+
+    #[test]
+    fn test_mounts() {
+        let command = |mount: &str| {
+            format!(
+                "if ! findmnt -n -o FSTYPE,SOURCE --mountpoint {0}; then echo unmounted; \
+                 elif timeout -s KILL 5 stat {0} >/dev/null 2>&1; then echo responsive; \
+                 else echo hung; fi",
+                mount
+            )
+        };
+        let runner = MockRunner::new()
+            .ok(&command("/data"), "nfs4   nas:/export\nresponsive\n")
+            .ok(&command("/scratch"), "nfs4   nas:/scratch\nhung\n")
+            .ok(&command("/backup"), "unmounted\n");
+        let points = ["/data", "/scratch", "/backup"].map(String::from);
+        let results = mounts(&runner, "web01", Platform::default(), &points, 5);
+        assert_eq!(results[0].status, Status::Ok);
+        assert_eq!(
+            results[0].message,
+            "/data mounted (nfs4 nas:/export) @ web01"
+        );
+        assert_eq!(results[1].status, Status::Critical);
+        assert_eq!(
+            results[1].message,
+            "/scratch does not respond after 5s (nfs4 nas:/scratch) @ web01"
+        );
+        assert_eq!(results[2].message, "/backup is not mounted @ web01");
+
+        let results = mounts(&runner, "mac01", Os::Macos.into(), &points, 5);
+        assert_eq!(
+            results[0].message,
+            "Error: mount points are only checked on Linux"
+        );
+    }
 
     #[test]
-    #[ignore] // TODO
-    fn test_load() {}
+    fn test_top_processes() {
+        let ps = "USER PID %CPU %MEM COMMAND\nroot 1 99.0 0.1 yes\n";
+        let runner = MockRunner::new()
+            .ok("ps aux --sort=-%cpu | head -n 6", ps)
+            .ok("ps aux -r | head -n 6", ps);
+        assert_eq!(top_processes(&runner, Platform::default()).unwrap(), ps);
+        assert_eq!(top_processes(&runner, Os::Freebsd.into()).unwrap(), ps);
+        let busybox = Platform {
+            os: Os::Linux,
+            shell: ShellProfile::Busybox,
+        };
+        assert!(top_processes(&runner, busybox).is_err());
+    }
+
+    #[test]
+    fn test_busybox() {
+        let busybox = Platform {
+            os: Os::Linux,
+            shell: ShellProfile::Busybox,
+        };
+        let runner = MockRunner::new()
+            .ok("cat /proc/loadavg", "0.52 21.30 60.00 1/123 4567\n")
+            .ok("find /data/. ! -name . -prune -type d | wc -l", "12\n")
+            .ok(
+                "find /backups/. ! -name . -prune -type d -mtime +2",
+                "/backups/./2024-01-01\n",
+            );
+        let results = load(&runner, "router", busybox, 5, Some(20.0), 50.0);
+        assert_eq!(results[0].message, "load 21.30 (5min) @ router");
+        let results = number_of_folders(&runner, "router", busybox, &["/data".into()], &100, None);
+        assert_eq!(results[0].message, "12 folders @ `router:/data`");
+        let results = list_old_directories(&runner, busybox, "/backups", 2);
+        assert_eq!(results[0].details.as_deref(), Some("/backups/2024-01-01"));
+    }
+
+    #[test]
+    fn test_windows() {
+        let folders = platform::powershell(
+            "(Get-ChildItem -Directory -Force -LiteralPath 'D:\\data' -ErrorAction Stop).Count",
+        );
+        let queue = platform::powershell(
+            r"(Get-Counter '\System\Processor Queue Length').CounterSamples[0].CookedValue",
+        );
+        let runner = MockRunner::new().ok(&folders, "12\r\n").ok(&queue, "3\r\n");
+        let paths = ["D:\\data".to_string()];
+        let results = number_of_folders(&runner, "ad01", Os::Windows.into(), &paths, &100, None);
+        assert_eq!(results[0].message, "12 folders @ `ad01:D:\\data`");
+
+        let results = load(&runner, "ad01", Os::Windows.into(), 5, Some(2.0), 10.0);
+        assert_eq!(results[0].status, Status::Warning);
+        assert_eq!(results[0].message, "processor queue 3.00 @ ad01");
+    }
 
     #[test]
     fn test_ping() {
@@ -493,13 +5665,14 @@ mod tests {
         let urls = vec![String::from("/test")];
 
         let result = ping(host.as_str(), &urls);
-        // Check if there ✅ is in the result
-        assert!(result.contains('✅'));
+        // Check if the result is ✅
+        assert!(result.iter().all(|r| r.status == Status::Ok));
+        assert_eq!(result[0].metric.as_ref().unwrap().name, "/test");
 
         let result = ping("does-not-exist", &urls);
-        // Check if there ❌ is in the result
-        println!("{}", result);
-        assert!(result.contains('❌'));
+        // Check if the result is ❌
+        println!("{:?}", result);
+        assert!(result.iter().all(|r| r.status == Status::Critical));
     }
 
     #[test]
@@ -508,19 +5681,584 @@ mod tests {
         let urls = vec![String::from("/test")];
 
         let result = ping(host, &urls);
-        // Check if there ❌ is in the result
-        assert!(result.contains('❌'));
+        // Check if the result is ❌
+        assert!(result.iter().all(|r| r.status == Status::Critical));
+    }
+
+    #[test]
+    fn test_temperature() {
+        let sensor = "/sys/bus/w1/devices/28-01/w1_slave";
+        let runner = MockRunner::new().ok(
+            &format!("cat {}", sensor),
+            "72 01 4b 46 7f ff 0e 10 57 : crc=57 YES\n72 01 4b 46 7f ff 0e 10 57 t=23125\n",
+        );
+        let results = temperature(&runner, Platform::default(), sensor, Some(20), 30);
+        assert_eq!(results[0].status, Status::Warning);
+        assert_eq!(results[0].message, "23°C");
+
+        let runner = MockRunner::new().ok(&format!("cat {}", sensor), "no reading");
+        let results = temperature(&runner, Platform::default(), sensor, None, 30);
+        assert_eq!(results[0].message, "Cannot read temperature!");
+
+        let sensor = "/sys/class/thermal/thermal_zone0/temp";
+        let runner = MockRunner::new().ok(&format!("cat {}", sensor), "41000\n");
+        let results = temperature(&runner, Platform::default(), sensor, None, 80);
+        assert_eq!(results[0].message, "41°C");
+
+        let runner = MockRunner::new().ok("sysctl -n dev.cpu.0.temperature", "45.0C\n");
+        let results = temperature(
+            &runner,
+            Os::Freebsd.into(),
+            "dev.cpu.0.temperature",
+            None,
+            40,
+        );
+        assert_eq!(results[0].status, Status::Critical);
+        assert_eq!(results[0].message, "45°C");
+        let results = temperature(&runner, Os::Macos.into(), "dev.cpu.0.temperature", None, 40);
+        assert!(results[0].message.contains("not supported on macOS"));
+    }
+
+    #[test]
+    fn test_temperatures() {
+        let runner = MockRunner::new()
+            .ok("cat /sys/cpu", "a1 : crc=57 YES\na1 t=41000\n")
+            .ok("cat /sys/hdd", "a1 : crc=57 YES\na1 t=52500\n");
+        let sensor = |label: &str, sensor: &str, max| TemperatureSensor {
+            label: label.to_string(),
+            sensor: sensor.to_string(),
+            max,
+            warning: None,
+        };
+        let sensors = [
+            sensor("CPU", "/sys/cpu", Some(80)),
+            sensor("HDD bay", "/sys/hdd", None),
+            sensor("GPU", "/sys/gpu", None),
+        ];
+        let results = temperatures(&runner, Platform::default(), &sensors, None, 45);
+        assert_eq!(results[0].status, Status::Ok);
+        assert_eq!(results[0].message, "CPU 41°C");
+        assert_eq!(results[0].metric.as_ref().unwrap().name, "CPU");
+        assert_eq!(results[1].status, Status::Critical);
+        assert_eq!(results[1].message, "HDD bay 52°C");
+        assert_eq!(results[2].status, Status::Critical);
+        assert!(results[2].message.starts_with("GPU Error: "));
+    }
+
+    #[test]
+    fn test_lm_sensors() {
+        let output = r#"{
+            "coretemp-isa-0000": {
+                "Adapter": "ISA adapter",
+                "Package id 0": {"temp1_input": 85.0, "temp1_max": 80.0, "temp1_crit": 100.0},
+                "Core 0": {"temp2_input": 45.0, "temp2_max": 80.0, "temp2_crit": 100.0}
+            },
+            "nct6775-isa-0290": {
+                "Adapter": "ISA adapter",
+                "fan2": {"fan2_input": 0.0, "fan2_min": 0.0, "fan2_alarm": 1.0},
+                "in0": {"in0_input": 1.04, "in0_min": 0.8, "in0_max": 1.2, "in0_alarm": 0.0}
+            }
+        }"#;
+        let runner = MockRunner::new().ok("sensors -j", output);
+        let selector = |chip: &str, feature: Option<&str>| LmSensor {
+            chip: chip.to_string(),
+            feature: feature.map(str::to_string),
+            max: None,
+            warning: None,
+            min: None,
+        };
+        let results = lm_sensors(
+            &runner,
+            "web01",
+            &[
+                selector("coretemp", None),
+                selector("nct6775-isa-0290", Some("fan2")),
+                selector("nct6775", Some("in0")),
+                selector("nct6775", Some("fan9")),
+                selector("k10temp", None),
+            ],
+        );
+        let messages: Vec<(Status, &str)> = results
+            .iter()
+            .map(|r| (r.status, r.message.as_str()))
+            .collect();
+        assert_eq!(
+            messages,
+            [
+                (Status::Ok, "coretemp-isa-0000 Core 0 45.0°C @ web01"),
+                (
+                    Status::Warning,
+                    "coretemp-isa-0000 Package id 0 85.0°C @ web01"
+                ),
+                (
+                    Status::Critical,
+                    "nct6775-isa-0290 fan2 0 RPM (alarm) @ web01"
+                ),
+                (Status::Ok, "nct6775-isa-0290 in0 1.04 V @ web01"),
+                (
+                    Status::Critical,
+                    "feature fan9 not found on nct6775-isa-0290 @ web01"
+                ),
+                (Status::Critical, "chip k10temp not found @ web01"),
+            ]
+        );
+        assert_eq!(
+            results[0].metric.as_ref().unwrap().name,
+            "coretemp-isa-0000 Core 0"
+        );
+
+        let strict = LmSensor {
+            max: Some(40.0),
+            ..selector("coretemp", Some("Core 0"))
+        };
+        let results = lm_sensors(&runner, "web01", &[strict]);
+        assert_eq!(results[0].status, Status::Critical);
+    }
+
+    #[test]
+    fn test_ipmi() {
+        let output = "\
+CPU1 Temp        | 30h | ok  |  3.1 | 45 degrees C
+FAN1             | 41h | ok  | 29.1 | 4200 RPM
+FAN2             | 42h | cr  | 29.2 | 0 RPM
+PS1 Status       | 74h | ok  | 10.1 | Presence detected
+PS2 Status       | 75h | cr  | 10.2 | Presence detected, Power Supply AC lost
+PS3 Status       | 76h | ns  | 10.3 | No Reading
+Inlet Temp       | 04h | nc  |  7.1 | 38 degrees C
+";
+        let runner = MockRunner::new()
+            .ok("sudo -n ipmitool sdr elist", output)
+            .fail(
+                "ipmitool sdr elist",
+                1,
+                "Could not open device at /dev/ipmi0\n",
+            );
+        let results = ipmi(&runner, "web01", "sudo -n ipmitool", &["FAN2".to_string()]);
+        assert_eq!(results.len(), 2);
+        assert_eq!(results[0].status, Status::Critical);
+        assert_eq!(
+            results[0].message,
+            "PS2 Status: Presence detected, Power Supply AC lost (critical) @ web01"
+        );
+        assert_eq!(results[1].status, Status::Warning);
+        assert_eq!(
+            results[1].message,
+            "Inlet Temp: 38 degrees C (non-critical) @ web01"
+        );
+        assert_eq!(results[1].metric.as_ref().unwrap().value, 2.0);
+
+        let ignore = [
+            "PS2 Status".to_string(),
+            "Inlet Temp".to_string(),
+            "FAN2".to_string(),
+        ];
+        let results = ipmi(&runner, "web01", "sudo -n ipmitool", &ignore);
+        assert_eq!(results[0].status, Status::Ok);
+        assert_eq!(results[0].message, "4 IPMI sensors nominal @ web01");
+
+        let results = ipmi(&runner, "web01", "ipmitool", &[]);
+        assert_eq!(results[0].status, Status::Critical);
+        assert_eq!(results[0].exit_code, Some(1));
+    }
+
+    #[test]
+    fn test_ups() {
+        let runner = MockRunner::new()
+            .ok(
+                "upsc myups",
+                "battery.charge: 85\nbattery.runtime: 2100\nups.status: OB DISCHRG\n",
+            )
+            .ok(
+                "upsc eaton@nas02",
+                "battery.charge: 100\nups.status: OL CHRG RB\n",
+            )
+            .fail("upsc apc", 1, "Error: Data stale\n");
+        let results = ups(&runner, "nas01", "myups", Some(90.0), None);
+        assert_eq!(results[0].status, Status::Critical);
+        assert_eq!(
+            results[0].message,
+            "myups on battery, 85% charge, 35 minutes of runtime @ nas01"
+        );
+        assert_eq!(results[0].metric.as_ref().unwrap().value, 85.0);
+
+        let results = ups(&runner, "nas01", "eaton@nas02", None, Some(50.0));
+        assert_eq!(results[0].status, Status::Warning);
+        assert_eq!(
+            results[0].message,
+            "eaton@nas02 online, charging, battery to replace, 100% charge @ nas01"
+        );
+
+        let results = ups(&runner, "nas01", "apc", None, None);
+        assert_eq!(results[0].status, Status::Critical);
+        assert!(results[0].message.contains("Data stale"));
+    }
+
+    #[test]
+    fn test_web_server() {
+        let nginx = |requests: u64| {
+            format!(
+                "Active connections: 291 \nserver accepts handled requests\n \
+                 16630948 16630948 {} \nReading: 6 Writing: 179 Waiting: 106 \n",
+                requests
+            )
+        };
+        let apache = |accesses: u64| {
+            format!(
+                "localhost\nServerVersion: Apache/2.4.58\nTotal Accesses: {}\nReqPerSec: 2.3\n\
+                 BusyWorkers: 9\nIdleWorkers: 1\nConnsTotal: 42\nScoreboard: WWWWWWWWW_\n",
+                accesses
+            )
+        };
+        let fetch = |url: &str| {
+            format!(
+                "curl -fsS --max-time 5 {url} && echo --- && sleep 1 && curl -fsS --max-time 5 {url}"
+            )
+        };
+        let runner = MockRunner::new()
+            .ok(
+                &fetch("http://localhost/nginx_status"),
+                &format!("{}---\n{}", nginx(31070465), nginx(31070477)),
+            )
+            .ok(
+                &fetch("'http://localhost/server-status?auto'"),
+                &format!("{}---\n{}", apache(1000), apache(1023)),
+            )
+            .ok(
+                &fetch("http://localhost/"),
+                "<html></html>\n---\n<html></html>\n",
+            );
+
+        let url = "http://localhost/nginx_status";
+        let results = web_server(&runner, "web01", url, None, Some(200), None, None);
+        assert_eq!(results.len(), 2);
+        assert_eq!(results[0].status, Status::Critical);
+        assert_eq!(results[0].message, "nginx: 291 active connections @ web01");
+        assert_eq!(results[1].message, "nginx: 12.0 requests/s @ web01");
+
+        let url = "http://localhost/server-status?auto";
+        let results = web_server(&runner, "web01", url, None, None, Some(80.0), Some(95.0));
+        assert_eq!(results[0].message, "apache: 42 active connections @ web01");
+        assert_eq!(results[1].metric.as_ref().unwrap().value, 23.0);
+        assert_eq!(results[2].status, Status::Warning);
+        assert_eq!(
+            results[2].message,
+            "apache: 9 of 10 workers busy (90%) @ web01"
+        );
+
+        let results = web_server(
+            &runner,
+            "web01",
+            "http://localhost/",
+            None,
+            None,
+            None,
+            None,
+        );
+        assert_eq!(results[0].status, Status::Critical);
     }
 
     #[test]
-    #[ignore] // TODO
-    fn test_temperature() {}
+    fn test_fail2ban() {
+        let status = |jail: &str, failed: u32, banned: u32, total: u32| {
+            format!(
+                "Status for the jail: {}\n|- Filter\n|  |- Currently failed:\t{}\n\
+                 |  |- Total failed:\t1234\n|  `- File list:\t/var/log/auth.log\n\
+                 `- Actions\n   |- Currently banned:\t{}\n   |- Total banned:\t{}\n\
+                 \x20  `- Banned IP list:\t192.0.2.1\n",
+                jail, failed, banned, total
+            )
+        };
+        let command = |client: &str| {
+            format!(
+                "status=$({client} status) && for jail in $(echo \"$status\" \
+                 | sed -n 's/.*Jail list://p' | tr -d ','); do {client} status \"$jail\"; done"
+            )
+        };
+        let runner = MockRunner::new()
+            .ok(
+                &command("sudo -n fail2ban-client"),
+                &format!(
+                    "{}{}",
+                    status("sshd", 3, 5, 87),
+                    status("nginx-http-auth", 0, 40, 41)
+                ),
+            )
+            .fail(
+                &command("fail2ban-client"),
+                255,
+                "Failed to access socket path: /var/run/fail2ban/fail2ban.sock. \
+                 Is fail2ban running?\n",
+            );
+        let client = "sudo -n fail2ban-client";
+        let results = fail2ban(&runner, "web01", client, None, Some(10), Some(50));
+        assert_eq!(results.len(), 2);
+        assert_eq!(results[0].status, Status::Ok);
+        assert_eq!(
+            results[0].message,
+            "sshd: 5 banned, 87 in total, 3 failing @ web01"
+        );
+        assert_eq!(results[1].status, Status::Warning);
+        assert_eq!(results[1].metric.as_ref().unwrap().name, "nginx-http-auth");
+
+        let jails = ["sshd".to_string(), "postfix".to_string()];
+        let results = fail2ban(&runner, "web01", client, Some(&jails), None, None);
+        assert_eq!(results.len(), 2);
+        assert_eq!(results[0].message, "jail postfix not found @ web01");
+        assert_eq!(results[1].status, Status::Ok);
+
+        let results = fail2ban(&runner, "web01", "fail2ban-client", None, None, None);
+        assert_eq!(results[0].status, Status::Critical);
+        assert!(results[0].message.contains("Is fail2ban running?"));
+    }
+
+    #[test]
+    fn test_custom_command() {
+        let runner = MockRunner::new()
+            .ok("df -h /", "/dev/sda1  50G  20G  30G  40% /\n")
+            .fail("./backup.sh", 2, "backup: disk full\n");
+        let results = custom_command(&runner, "df -h /", None, None);
+        assert_eq!(results[0].status, Status::Info);
+        assert_eq!(results[0].message, "`df -h /`");
+        assert!(results[0].details.as_ref().unwrap().contains("40%"));
+        assert_eq!(results[0].output, None);
+
+        let results = custom_command(&runner, "df -h /", None, Some(10));
+        assert_eq!(results[0].details.as_deref(), Some("/dev/sda1…"));
+        assert!(results[0].output.as_ref().unwrap().ends_with("40% /\n"));
+
+        let results = custom_command(&runner, "./backup.sh", None, None);
+        assert_eq!(results[0].status, Status::Critical);
+        assert_eq!(
+            results[0].message,
+            "`./backup.sh` failed: Command './backup.sh' exited with status 2: backup: disk full"
+        );
+        assert_eq!(results[0].exit_code, Some(2));
+        assert_eq!(results[0].stderr.as_deref(), Some("backup: disk full\n"));
+    }
+
+    #[test]
+    fn test_list_old_directories() {
+        let command = "find /backups -maxdepth 1 -type d -mtime +2";
+        let runner = MockRunner::new().ok(command, "/backups/2024-01-01\n/backups/2024-01-02\n");
+        let results = list_old_directories(&runner, Platform::default(), "/backups", 2);
+        assert_eq!(results[0].status, Status::Critical);
+        assert_eq!(
+            results[0].details.as_deref(),
+            Some("/backups/2024-01-01\n/backups/2024-01-02")
+        );
+
+        let runner = MockRunner::new().ok(command, "");
+        let results = list_old_directories(&runner, Platform::default(), "/backups", 2);
+        assert_eq!(results[0].status, Status::Ok);
+
+        let runner = MockRunner::new().fail(command, 1, "find: '/backups': No such file");
+        let results = list_old_directories(&runner, Platform::default(), "/backups", 2);
+        assert_eq!(results[0].status, Status::Critical);
+        assert_eq!(results[0].exit_code, Some(1));
+    }
+
+    #[test]
+    fn test_kubernetes() {
+        let runner = MockRunner::new()
+            .ok(
+                "kubectl --kubeconfig ~/.kube/config get nodes --no-headers",
+                "node1   Ready   control-plane   10d   v1.29.1\n",
+            )
+            .fail(
+                "kubectl --kubeconfig ~/.kube/config get pods -n prod --no-headers",
+                1,
+                "error: You must be logged in to the server (Unauthorized)",
+            );
+        let results = kubernetes(&runner, "k8s", "~/.kube/config", Some("prod"), None);
+        assert_eq!(results[0].status, Status::Ok);
+        assert_eq!(results[0].message, "1/1 nodes Ready @ `k8s`");
+        assert_eq!(results[1].status, Status::Critical);
+        assert!(results[1].message.contains("Unauthorized"));
+        assert_eq!(results[1].exit_code, Some(1));
+    }
+
+    #[test]
+    fn test_database() {
+        let runner = MockRunner::new()
+            .ok("psql -U monitor -tAc 'select 1'", "1\n")
+            .ok(
+                "psql -U monitor -tAc 'select count(*) from pg_stat_activity'",
+                "120\n",
+            );
+        let results = database(
+            &runner,
+            "db01",
+            "postgres",
+            Some("monitor"),
+            None,
+            Some(100),
+            None,
+        );
+        assert_eq!(results.len(), 2);
+        assert_eq!(results[0].message, "postgres is up @ `db01`");
+        assert_eq!(results[1].status, Status::Critical);
+        assert_eq!(results[1].message, "120 connections (max 100) @ `db01`");
+
+        let runner = MockRunner::new().fail("mysqladmin ping", 1, "Access denied");
+        let results = database(&runner, "db01", "mysql", None, None, Some(100), Some(10));
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].status, Status::Critical);
+        assert!(results[0].message.starts_with("mysql is down @ `db01`"));
+    }
+
+    #[test]
+    fn test_redis() {
+        let runner = MockRunner::new()
+            .ok("redis-cli -u redis://localhost ping", "PONG\n")
+            .ok(
+                "redis-cli -u redis://localhost INFO",
+                "connected_clients:7\r\nused_memory:2097152\r\n",
+            );
+        let results = redis(&runner, "cache", "redis://localhost", Some(1), Some(10));
+        let statuses: Vec<Status> = results.iter().map(|r| r.status).collect();
+        assert_eq!(statuses, [Status::Ok, Status::Critical, Status::Ok]);
+        assert_eq!(results[1].message, "redis memory 2MB (max 1MB) @ `cache`");
+
+        let runner = MockRunner::new().ok("redis-cli -u redis://localhost ping", "LOADING\n");
+        let results = redis(&runner, "cache", "redis://localhost", None, None);
+        assert_eq!(results[0].message, "redis is down @ `cache`: LOADING");
+    }
+
+    #[test]
+    fn test_queue_depth() {
+        let runner = MockRunner::new().ok(
+            "rabbitmqctl list_queues --quiet --no-table-headers -p /app name messages",
+            "emails\t1500\nreports\t3\n",
+        );
+        let results = queue_depth(&runner, "mq", 1000, Some("/app"), None);
+        assert_eq!(results.len(), 1);
+        assert_eq!(
+            results[0].message,
+            "queue `emails` has 1500 messages (max 1000) @ `mq`"
+        );
+
+        let queues = vec!["reports".to_string()];
+        let results = queue_depth(&runner, "mq", 1000, Some("/app"), Some(&queues));
+        assert_eq!(results[0].status, Status::Ok);
+        assert_eq!(runner.commands().len(), 2);
+    }
+
+    #[test]
+    fn test_parse_not_ready_nodes() {
+        let output = "\
+node1   Ready                      control-plane   10d   v1.29.1
+node2   NotReady                   <none>          10d   v1.29.1
+node3   Ready,SchedulingDisabled   <none>          10d   v1.29.1
+";
+        assert_eq!(parse_not_ready_nodes(output), vec!["node2"]);
+    }
+
+    #[test]
+    fn test_parse_unhealthy_pods() {
+        let output = "\
+default       web-7d4b9   0/1   CrashLoopBackOff   12 (2m ago)   1h
+default       job-x1      0/1   Completed          0             1h
+kube-system   dns-5f8c    1/1   Running            0             10d
+";
+        assert_eq!(
+            parse_unhealthy_pods(output, None),
+            vec!["default/web-7d4b9 CrashLoopBackOff"]
+        );
+
+        let output = "web-7d4b9   0/1   Pending   0   1m\n";
+        assert_eq!(
+            parse_unhealthy_pods(output, Some("prod")),
+            vec!["prod/web-7d4b9 Pending"]
+        );
+    }
+
+    #[test]
+    fn test_database_client_commands() {
+        let client = DatabaseClient::new("postgres", Some("monitor"), Some("app")).unwrap();
+        assert_eq!(
+            client.ping_command(),
+            "psql -U monitor -d app -tAc 'select 1'"
+        );
+
+        let client = DatabaseClient::new("mysql", Some("monitor"), Some("app")).unwrap();
+        assert_eq!(client.ping_command(), "mysqladmin -u monitor ping");
+
+        assert!(DatabaseClient::new("oracle", None, None).is_none());
+    }
+
+    #[test]
+    fn test_database_client_parsing() {
+        let postgres = DatabaseClient::new("postgres", None, None).unwrap();
+        assert_eq!(postgres.parse_connections("42\n"), Some(42));
+        assert_eq!(postgres.parse_replication_lag("\n"), None);
+        assert_eq!(postgres.parse_replication_lag("7\n"), Some(7));
+
+        let mysql = DatabaseClient::new("mysql", None, None).unwrap();
+        assert_eq!(mysql.parse_connections("Threads_connected\t5\n"), Some(5));
+        let status = "*** 1. row ***\n   Replica_IO_Running: Yes\n   Seconds_Behind_Source: 12\n";
+        assert_eq!(mysql.parse_replication_lag(status), Some(12));
+        let status = "   Seconds_Behind_Master: NULL\n";
+        assert_eq!(mysql.parse_replication_lag(status), None);
+        assert_eq!(mysql.parse_replication_lag(""), None);
+    }
+
+    #[test]
+    fn test_redis_info_field() {
+        let info = "# Clients\r\nconnected_clients:7\r\n# Memory\r\nused_memory:1048576\r\nused_memory_human:1.00M\r\n";
+        assert_eq!(redis_info_field(info, "connected_clients"), Some(7));
+        assert_eq!(redis_info_field(info, "used_memory"), Some(1048576));
+        assert_eq!(redis_info_field(info, "blocked_clients"), None);
+    }
 
     #[test]
-    #[ignore] // TODO
-    fn test_custom_command() {}
+    fn test_parse_queue_depths() {
+        let output = "name\tmessages\nemails\t1500\nreports\t0\n";
+        assert_eq!(
+            parse_queue_depths(output),
+            vec![("emails".to_string(), 1500), ("reports".to_string(), 0)]
+        );
+    }
 
     #[test]
-    #[ignore] // TODO
-    fn test_list_old_directories() {}
+    #[ignore] // Requires the SSH server of tests/sshd/run.sh
+    fn test_checks_over_ssh() {
+        let sess = crate::ssh::create_session(&crate::ssh::test_server()).unwrap();
+
+        let paths = vec!["/srv/data".to_string()];
+        let results = number_of_folders(&sess, "sshd", Platform::default(), &paths, &2, None);
+        assert_eq!(results[0].message, "3 folders @ `sshd:/srv/data`");
+        assert_eq!(results[0].status, Status::Critical);
+
+        let results = load(&sess, "sshd", Platform::default(), 1, None, 1000.0);
+        assert_eq!(results[0].status, Status::Ok);
+
+        let results = temperature(&sess, Platform::default(), "/srv/w1_slave", None, 30);
+        assert_eq!(results[0].message, "23°C");
+
+        let results = custom_command(&sess, "ls /srv/missing", None, None);
+        assert_eq!(results[0].status, Status::Critical);
+        assert!(results[0].exit_code.is_some_and(|c| c != 0));
+        assert!(results[0].stderr.as_ref().unwrap().contains("/srv/missing"));
+
+        let results = list_old_directories(&sess, Platform::default(), "/srv/backups", 2);
+        assert_eq!(results[0].details.as_deref(), Some("/srv/backups/old"));
+
+        let results = kubernetes(&sess, "sshd", "/etc/kubeconfig", None, None);
+        assert_eq!(results[0].message, "1/2 nodes NotReady @ `sshd`: node2");
+        assert_eq!(
+            results[1].details.as_deref(),
+            Some("default/job-5c7b9d8f4-k9l2m CrashLoopBackOff")
+        );
+
+        let results = database(&sess, "sshd", "postgres", None, None, Some(100), None);
+        assert_eq!(results[1].message, "12 connections @ `sshd`");
+
+        let results = redis(&sess, "sshd", "redis://localhost", None, Some(5));
+        assert_eq!(results[0].status, Status::Ok);
+        assert_eq!(results[1].status, Status::Critical);
+
+        let results = queue_depth(&sess, "sshd", 1000, None, None);
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].status, Status::Critical);
+    }
 }
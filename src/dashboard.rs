@@ -0,0 +1,880 @@
+use crate::monitor::Recheck;
+use crate::report::{CheckResult, Markers, Report, Status};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::sync::mpsc::{SyncSender, TrySendError};
+use std::sync::{Arc, Mutex};
+
+/// Settings of the web dashboard served in daemon mode, which requires the `dashboard` feature.
+///
+/// ```yaml
+/// general:
+///   dashboard:
+///     listen: 127.0.0.1:8080
+///     token_env: DASHBOARD_TOKEN
+///     slack_signing_secret_env: SLACK_SIGNING_SECRET
+/// ```
+#[derive(Debug, Serialize, Deserialize)]
+pub struct DashboardConfig {
+    /// Address and port the dashboard listens on, e.g. `0.0.0.0:8080` for all interfaces. Without
+    /// a token, the dashboard only listens on `127.0.0.1` whatever the address (see `serve`).
+    pub listen: String,
+    /// Token required to trigger runs with `POST /api/run`, as `Authorization: Bearer <token>`.
+    /// Anyone reaching the dashboard can trigger runs if neither it nor `token_env` is set.
+    pub token: Option<String>,
+    /// Name of the environment variable holding the token, instead of `token`.
+    pub token_env: Option<String>,
+    /// Signing secret of the Slack app whose `/recheck` slash command is sent to
+    /// `/slack/command`, which is disabled if neither it nor `slack_signing_secret_env` is set.
+    pub slack_signing_secret: Option<String>,
+    /// Name of the environment variable holding the signing secret, instead of
+    /// `slack_signing_secret`.
+    pub slack_signing_secret_env: Option<String>,
+}
+
+impl DashboardConfig {
+    /// Returns the token required to trigger runs, if any.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if `token_env` is set to a missing environment variable.
+    pub fn token(&self) -> Result<Option<String>, Box<dyn std::error::Error>> {
+        if self.token.is_none() && self.token_env.is_none() {
+            return Ok(None);
+        }
+        crate::notify::secret(self.token.as_deref(), self.token_env.as_deref(), "token").map(Some)
+    }
+
+    /// Returns the signing secret of the Slack app, if the slash command is enabled.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if `slack_signing_secret_env` is set to a missing environment variable.
+    pub fn slack_signing_secret(&self) -> Result<Option<String>, Box<dyn std::error::Error>> {
+        if self.slack_signing_secret.is_none() && self.slack_signing_secret_env.is_none() {
+            return Ok(None);
+        }
+        crate::notify::secret(
+            self.slack_signing_secret.as_deref(),
+            self.slack_signing_secret_env.as_deref(),
+            "slack_signing_secret",
+        )
+        .map(Some)
+    }
+}
+
+/// The latest run, as shown by the dashboard.
+#[derive(Debug, Clone)]
+pub struct Snapshot {
+    /// Report of the run.
+    pub report: Report,
+    /// When the run started.
+    pub time: DateTime<Utc>,
+}
+
+impl Snapshot {
+    /// Updates the snapshot with the report of a run. The report of a `recheck` only replaces the
+    /// results it covers, i.e. the servers it ran on or only the results of its check, keeping the
+    /// time of the last full run.
+    pub fn update(&mut self, report: Report, recheck: Option<&Recheck>, time: DateTime<Utc>) {
+        let Some(recheck) = recheck else {
+            *self = Snapshot { report, time };
+            return;
+        };
+        for server in report.servers {
+            let Some(current) = self
+                .report
+                .servers
+                .iter_mut()
+                .find(|s| s.name == server.name)
+            else {
+                self.report.servers.push(server);
+                continue;
+            };
+            match &recheck.check {
+                None => *current = server,
+                Some(check) => {
+                    // In place of the previous results of the check, to keep the order of the report
+                    let at = current
+                        .results
+                        .iter()
+                        .position(|r| &r.check == check)
+                        .unwrap_or(current.results.len());
+                    current.results.retain(|r| &r.check != check);
+                    let at = at.min(current.results.len());
+                    current.results.splice(at..at, server.results);
+                }
+            }
+        }
+    }
+}
+
+/// The latest run, shared between the daemon updating it and the dashboard serving it.
+pub type Latest = Arc<Mutex<Option<Snapshot>>>;
+
+/// Maximum number of runs waiting in the queue of the daemon, further requests to trigger one
+/// being refused with `429 Too Many Requests` until the daemon catches up.
+pub const MAX_QUEUED_RUNS: usize = 16;
+
+/// State of the daemon shared with the dashboard.
+#[derive(Debug, Clone)]
+pub struct Dashboard {
+    /// The latest run.
+    pub latest: Latest,
+    /// Queue of the runs triggered with `POST /api/run`, consumed by the daemon, holding at most
+    /// `MAX_QUEUED_RUNS` runs.
+    pub runs: SyncSender<Recheck>,
+    /// Token required to trigger runs, see `DashboardConfig::token`.
+    pub token: Option<String>,
+    /// Signing secret of the Slack app, see `DashboardConfig::slack_signing_secret`.
+    pub slack_signing_secret: Option<String>,
+    /// Markers of the statuses shown on the HTML page, see `GeneralConfig::markers`.
+    pub markers: Markers,
+}
+
+/// A request to the dashboard.
+#[derive(Debug, Default)]
+pub struct Request {
+    /// Method of the request, e.g. `GET`.
+    pub method: String,
+    /// Path and query string of the request.
+    pub url: String,
+    /// Headers of the request, as names and values.
+    pub headers: Vec<(String, String)>,
+    /// Body of the request, empty for `GET` requests.
+    pub body: String,
+}
+
+impl Request {
+    /// Returns the value of a header, whose name is case insensitive.
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(n, _)| n.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    }
+}
+
+/// Maximum size of the body of a request, e.g. of a Slack slash command.
+#[cfg(feature = "dashboard")]
+const MAX_BODY_SIZE: u64 = 64 * 1024;
+
+/// A response of the dashboard: status code, content type and body.
+pub type Response = (u16, &'static str, String);
+
+/// Answers a request of the dashboard:
+///
+/// - `GET /` renders the latest run as an HTML page, refreshed every minute;
+/// - `GET /api/status` returns the latest run as JSON, see `status_json`;
+/// - `GET /badge/<server>.svg` returns a badge of the status of a server, and
+///   `GET /api/badge/<server>` the same as a shields.io endpoint, see `badge`;
+/// - `POST /api/run` triggers a run right away, see `trigger`;
+/// - `POST /slack/command` answers the `/recheck` Slack slash command, see `slack_command`.
+///
+/// They answer `503 Service Unavailable` until the first run is over, except for the badges which
+/// are grey until then.
+pub fn respond(request: &Request, dashboard: &Dashboard) -> Response {
+    let method = request.method.as_str();
+    let (path, query) = request.url.split_once('?').unwrap_or((&request.url, ""));
+    let snapshot = dashboard
+        .latest
+        .lock()
+        .map(|s| s.clone())
+        .unwrap_or_default();
+    match (method, path) {
+        ("POST", "/api/run") => {
+            return trigger(
+                query,
+                request.header("Authorization"),
+                snapshot.as_ref(),
+                dashboard,
+            )
+        }
+        ("POST", "/slack/command") => return slack_command(request, snapshot.as_ref(), dashboard),
+        _ => {}
+    }
+    if method == "GET" {
+        let badge_of = |server: &str| badge(snapshot.as_ref(), &percent_decode(server));
+        if let Some(server) = path
+            .strip_prefix("/badge/")
+            .and_then(|s| s.strip_suffix(".svg"))
+        {
+            return (200, "image/svg+xml", badge_of(server).svg());
+        }
+        if let Some(server) = path.strip_prefix("/api/badge/") {
+            return (
+                200,
+                "application/json",
+                badge_of(server).endpoint().to_string(),
+            );
+        }
+    }
+    match (method, path, snapshot) {
+        ("GET", "/" | "/api/status", None) => {
+            (503, "text/plain", "No run completed yet".to_string())
+        }
+        ("GET", "/", Some(snapshot)) => (
+            200,
+            "text/html; charset=utf-8",
+            html(&snapshot, &dashboard.markers),
+        ),
+        ("GET", "/api/status", Some(snapshot)) => {
+            (200, "application/json", status_json(&snapshot).to_string())
+        }
+        ("GET", _, _) => (404, "text/plain", "Not found".to_string()),
+        _ => (405, "text/plain", "Method not allowed".to_string()),
+    }
+}
+
+/// Queues a run of the checks, answering `202 Accepted` before the run starts. The query string
+/// optionally restricts the run to a server and check, e.g. `?server=web01&check=load`.
+///
+/// When the dashboard has a token, requests without it are refused with `401 Unauthorized`.
+/// Servers missing from the latest run are refused with `404 Not Found`, and runs beyond
+/// `MAX_QUEUED_RUNS` with `429 Too Many Requests`.
+fn trigger(
+    query: &str,
+    authorization: Option<&str>,
+    snapshot: Option<&Snapshot>,
+    dashboard: &Dashboard,
+) -> Response {
+    if let Some(token) = &dashboard.token {
+        let bearer = authorization.and_then(|a| a.strip_prefix("Bearer "));
+        if !bearer.is_some_and(|b| same_bytes(b.as_bytes(), token.as_bytes())) {
+            return (401, "text/plain", "Unauthorized".to_string());
+        }
+    }
+    let mut recheck = Recheck::default();
+    for (key, value) in form(query) {
+        match key.as_str() {
+            "server" => recheck.server = Some(value),
+            "check" => recheck.check = Some(value),
+            _ => {}
+        }
+    }
+    if let Some(server) = unknown_server(&recheck, snapshot) {
+        return (404, "text/plain", format!("Unknown server {}", server));
+    }
+    let body = json!({"queued": true, "server": recheck.server, "check": recheck.check});
+    match dashboard.runs.try_send(recheck) {
+        Ok(()) => (202, "application/json", body.to_string()),
+        Err(TrySendError::Full(_)) => (429, "text/plain", "Too many runs queued".to_string()),
+        Err(TrySendError::Disconnected(_)) => {
+            (503, "text/plain", "The daemon is not running".to_string())
+        }
+    }
+}
+
+/// Compares two byte strings in a time that does not depend on where they differ, so that the
+/// time taken to refuse a token does not tell how much of it was guessed.
+fn same_bytes(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |diff, (x, y)| diff | (x ^ y)) == 0
+}
+
+/// Answers the `/recheck <server> [check]` Slack slash command by queuing a run of the checks of
+/// the server, whose results are then posted back to the conversation of the command through its
+/// `response_url`.
+///
+/// Requests are only accepted with a valid signature of the Slack app (see
+/// `slack::verify_signature`), and the endpoint does not exist without a signing secret. Errors
+/// in the command, e.g. an unknown server, are answered to its author only.
+fn slack_command(
+    request: &Request,
+    snapshot: Option<&Snapshot>,
+    dashboard: &Dashboard,
+) -> Response {
+    let Some(secret) = &dashboard.slack_signing_secret else {
+        return (404, "text/plain", "Not found".to_string());
+    };
+    let verified = match (
+        request.header("X-Slack-Request-Timestamp"),
+        request.header("X-Slack-Signature"),
+    ) {
+        (Some(timestamp), Some(signature)) => crate::slack::verify_signature(
+            secret,
+            timestamp,
+            &request.body,
+            signature,
+            Utc::now().timestamp(),
+        ),
+        _ => false,
+    };
+    if !verified {
+        return (401, "text/plain", "Invalid signature".to_string());
+    }
+
+    let mut text = String::new();
+    let mut response_url = None;
+    for (key, value) in form(&request.body) {
+        match key.as_str() {
+            "text" => text = value,
+            "response_url" => response_url = Some(value),
+            _ => {}
+        }
+    }
+    let ephemeral = |text: String| {
+        let body = json!({"response_type": "ephemeral", "text": text});
+        (200, "application/json", body.to_string())
+    };
+    let mut words = text.split_whitespace().map(str::to_string);
+    let recheck = Recheck {
+        server: words.next(),
+        check: words.next(),
+        response_url,
+    };
+    if recheck.server.is_none() {
+        return ephemeral("Usage: `/recheck <server> [check]`".to_string());
+    }
+    if let Some(server) = unknown_server(&recheck, snapshot) {
+        return ephemeral(format!("Unknown server `{}`", server));
+    }
+    let text = format!("🔄 Running the {} again", recheck);
+    match dashboard.runs.try_send(recheck) {
+        Ok(()) => {
+            let body = json!({"response_type": "in_channel", "text": text});
+            (200, "application/json", body.to_string())
+        }
+        Err(TrySendError::Full(_)) => {
+            ephemeral("Too many runs queued, try again later".to_string())
+        }
+        Err(TrySendError::Disconnected(_)) => ephemeral("The daemon is not running".to_string()),
+    }
+}
+
+/// Returns the server of a recheck if it is not part of the latest run.
+fn unknown_server<'a>(recheck: &'a Recheck, snapshot: Option<&Snapshot>) -> Option<&'a str> {
+    let server = recheck.server.as_deref()?;
+    let snapshot = snapshot?;
+    (!snapshot.report.servers.iter().any(|s| s.name == server)).then_some(server)
+}
+
+/// Decodes a query string or a form-encoded body into its keys and values.
+fn form(encoded: &str) -> Vec<(String, String)> {
+    encoded
+        .split('&')
+        .filter_map(|p| p.split_once('='))
+        .map(|(key, value)| {
+            (
+                percent_decode(&key.replace('+', " ")),
+                percent_decode(&value.replace('+', " ")),
+            )
+        })
+        .collect()
+}
+
+/// Returns the latest run as JSON, e.g.:
+///
+/// ```json
+/// {
+///   "time": "2024-05-01T10:00:00Z",
+///   "status": "warning",
+///   "notes": [],
+///   "servers": [{
+///     "name": "web01", "host": "web01.example.com", "status": "warning",
+///     "results": [{
+///       "check": "load", "status": "warning", "message": "load 30.00 (5min) @ web01",
+///       "muted": false, "metric": {"name": "load5", "value": 30.0}
+///     }]
+///   }]
+/// }
+/// ```
+pub fn status_json(snapshot: &Snapshot) -> Value {
+    let result = |r: &CheckResult| {
+        json!({
+            "check": r.check,
+            "status": r.status,
+            "message": r.message,
+            "muted": r.muted,
+            "metric": r.metric,
+        })
+    };
+    json!({
+        "time": snapshot.time,
+        "status": snapshot.report.status(),
+        "notes": snapshot.report.notes.iter().map(result).collect::<Vec<_>>(),
+        "servers": snapshot.report.servers.iter().map(|s| json!({
+            "name": s.name,
+            "host": s.host,
+            "status": s.status(),
+            "results": s.results.iter().map(result).collect::<Vec<_>>(),
+        })).collect::<Vec<_>>(),
+    })
+}
+
+/// A status badge, with the name of a server as label and its status as message.
+#[derive(Debug, PartialEq)]
+pub struct Badge {
+    /// Left part of the badge, the name of the server.
+    pub label: String,
+    /// Right part of the badge, e.g. `ok` or `critical`.
+    pub message: &'static str,
+    /// Background color of the message, as a hexadecimal RGB color.
+    pub color: &'static str,
+}
+
+/// Returns the badge of a server in the latest run: green when its checks pass, orange with a
+/// ⚠️, red with a ❌ and grey for `unknown` servers (or before the first run).
+pub fn badge(snapshot: Option<&Snapshot>, server: &str) -> Badge {
+    let status = snapshot.and_then(|s| s.report.servers.iter().find(|r| r.name == server));
+    let (message, color) = match status.map(|s| s.status()) {
+        Some(Status::Ok | Status::Info) => ("ok", "#4c1"),
+        Some(Status::Warning) => ("warning", "#fe7d37"),
+        Some(Status::Critical) => ("critical", "#e05d44"),
+        None => ("unknown", "#9f9f9f"),
+    };
+    Badge {
+        label: server.to_string(),
+        message,
+        color,
+    }
+}
+
+impl Badge {
+    /// Renders the badge as a flat SVG image, in the style of shields.io.
+    pub fn svg(&self) -> String {
+        // Approximate width of the characters of Verdana 11px
+        let width = |text: &str| text.chars().count() * 7 + 10;
+        let (left, right) = (width(&self.label), width(self.message));
+        let label = escape(&self.label);
+        format!(
+            "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{total}\" height=\"20\" \
+             role=\"img\" aria-label=\"{label}: {message}\">\
+             <title>{label}: {message}</title>\
+             <rect width=\"{left}\" height=\"20\" fill=\"#555\"/>\
+             <rect x=\"{left}\" width=\"{right}\" height=\"20\" fill=\"{color}\"/>\
+             <g fill=\"#fff\" text-anchor=\"middle\" font-family=\"Verdana,sans-serif\" \
+             font-size=\"11\">\
+             <text x=\"{label_x}\" y=\"14\">{label}</text>\
+             <text x=\"{message_x}\" y=\"14\">{message}</text></g></svg>",
+            total = left + right,
+            left = left,
+            right = right,
+            color = self.color,
+            label = label,
+            message = self.message,
+            label_x = left / 2,
+            message_x = left + right / 2,
+        )
+    }
+
+    /// Returns the badge as a shields.io endpoint, to be styled by shields.io with
+    /// `https://img.shields.io/endpoint?url=<url of the endpoint>`.
+    pub fn endpoint(&self) -> Value {
+        json!({
+            "schemaVersion": 1,
+            "label": self.label,
+            "message": self.message,
+            "color": self.color.trim_start_matches('#'),
+        })
+    }
+}
+
+/// Decodes the percent-encoded characters of a path, e.g. `%20` for a space.
+fn percent_decode(path: &str) -> String {
+    let bytes = path.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let hex = bytes
+            .get(i + 1..i + 3)
+            .and_then(|h| std::str::from_utf8(h).ok());
+        match (bytes[i], hex.and_then(|h| u8::from_str_radix(h, 16).ok())) {
+            (b'%', Some(byte)) => {
+                decoded.push(byte);
+                i += 3;
+            }
+            (byte, _) => {
+                decoded.push(byte);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+/// Renders the latest run as a self-contained HTML page, with a table of results per server and
+/// the statuses shown with their `markers`.
+fn html(snapshot: &Snapshot, markers: &Markers) -> String {
+    let report = &snapshot.report;
+    let mut body = format!(
+        "<h1>{} RemoteSysMonitor</h1>\n<p>Last run: {}</p>\n",
+        escape(markers.status(report.status())),
+        snapshot.time.format("%Y-%m-%d %H:%M:%S UTC")
+    );
+    for note in &report.notes {
+        body.push_str(&format!("<p>{}</p>\n", escape(&note.headline())));
+    }
+    for server in &report.servers {
+        body.push_str(&format!(
+            "<h2 class=\"{}\">{} {} <small>{}</small></h2>\n<table>\n",
+            class(server.status()),
+            escape(markers.status(server.status())),
+            escape(&server.name),
+            escape(&server.host)
+        ));
+        for result in &server.results {
+            body.push_str(&format!(
+                "<tr class=\"{}\"><td>{}</td><td>{}</td><td>{}</td></tr>\n",
+                if result.muted {
+                    "muted"
+                } else {
+                    class(result.status)
+                },
+                escape(markers.status(result.status)),
+                escape(&result.check),
+                escape(&result.message)
+            ));
+        }
+        body.push_str("</table>\n");
+    }
+    format!(
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n\
+         <meta http-equiv=\"refresh\" content=\"60\">\n<title>RemoteSysMonitor</title>\n\
+         <style>{}</style>\n</head>\n<body>\n{}</body>\n</html>\n",
+        STYLE, body
+    )
+}
+
+const STYLE: &str = "body{font-family:sans-serif;margin:2em}table{border-collapse:collapse}\
+td{padding:.2em .8em;border-bottom:1px solid #ddd}small{color:#888;font-weight:normal}\
+.critical{color:#b00}.warning{color:#a60}.muted{color:#888}";
+
+fn class(status: Status) -> &'static str {
+    match status {
+        Status::Critical => "critical",
+        Status::Warning => "warning",
+        Status::Ok | Status::Info => "ok",
+    }
+}
+
+/// Escapes the characters with a meaning in HTML.
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// Returns the address the dashboard listens on: its `listen` address, or `127.0.0.1` on the same
+/// port when it has no token and the address is not a loopback one, since anyone reaching the
+/// dashboard could then trigger runs.
+#[cfg(feature = "dashboard")]
+fn listen_address(listen: &str, token: bool) -> String {
+    use std::net::ToSocketAddrs;
+    let loopback = listen
+        .to_socket_addrs()
+        .map(|mut addresses| addresses.all(|a| a.ip().is_loopback()))
+        .unwrap_or(true);
+    match listen.rsplit_once(':') {
+        Some((_, port)) if !token && !loopback => {
+            log::warn!(
+                "The dashboard has no token, listening on 127.0.0.1:{} instead of {}",
+                port,
+                listen
+            );
+            format!("127.0.0.1:{}", port)
+        }
+        _ => listen.to_string(),
+    }
+}
+
+/// Starts serving the dashboard in a background thread, only on `127.0.0.1` if it has no token
+/// (see `listen_address`).
+///
+/// # Errors
+///
+/// Returns an error if the address cannot be listened on.
+#[cfg(feature = "dashboard")]
+pub fn serve(
+    config: &DashboardConfig,
+    dashboard: Dashboard,
+) -> Result<std::net::SocketAddr, Box<dyn std::error::Error>> {
+    let listen = listen_address(&config.listen, dashboard.token.is_some());
+    let server = tiny_http::Server::http(&listen).map_err(|e| e.to_string())?;
+    let address = server
+        .server_addr()
+        .to_ip()
+        .ok_or("the dashboard must listen on an IP address")?;
+    std::thread::spawn(move || {
+        for mut incoming in server.incoming_requests() {
+            let mut request = Request {
+                method: incoming.method().as_str().to_string(),
+                url: incoming.url().to_string(),
+                headers: incoming
+                    .headers()
+                    .iter()
+                    .map(|h| (h.field.to_string(), h.value.to_string()))
+                    .collect(),
+                body: String::new(),
+            };
+            // Bodies are cut at `MAX_BODY_SIZE`, and ignored if not in UTF-8
+            let mut body = std::io::Read::take(incoming.as_reader(), MAX_BODY_SIZE);
+            if std::io::Read::read_to_string(&mut body, &mut request.body).is_err() {
+                request.body.clear();
+            }
+            let (code, content_type, body) = respond(&request, &dashboard);
+            let header =
+                tiny_http::Header::from_bytes("Content-Type", content_type).expect("valid header");
+            let response = tiny_http::Response::from_string(body)
+                .with_status_code(code)
+                .with_header(header);
+            if let Err(e) = incoming.respond(response) {
+                log::debug!("Could not answer a dashboard request: {}", e);
+            }
+        }
+    });
+    Ok(address)
+}
+
+/// Fails, as the dashboard requires the `dashboard` feature.
+#[cfg(not(feature = "dashboard"))]
+pub fn serve(
+    _config: &DashboardConfig,
+    _dashboard: Dashboard,
+) -> Result<std::net::SocketAddr, Box<dyn std::error::Error>> {
+    Err("the dashboard requires building with `--features dashboard`".into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::report::ServerReport;
+    use std::sync::mpsc::{sync_channel, Receiver};
+
+    fn snapshot() -> Snapshot {
+        let mut server = ServerReport::new("web01", "web01.example.com");
+        server.results.push(
+            CheckResult::new(Status::Warning, "load 30.00 (5min) @ <web01>")
+                .with_check("load")
+                .with_metric("load5", 30.0),
+        );
+        Snapshot {
+            report: Report {
+                notes: vec![],
+                servers: vec![server],
+            },
+            time: "2024-05-01T10:00:00Z".parse().unwrap(),
+        }
+    }
+
+    fn dashboard(snapshot: Option<Snapshot>) -> (Dashboard, Receiver<Recheck>) {
+        let (runs, receiver) = sync_channel(MAX_QUEUED_RUNS);
+        let dashboard = Dashboard {
+            latest: Arc::new(Mutex::new(snapshot)),
+            runs,
+            token: None,
+            slack_signing_secret: None,
+            markers: Markers::default(),
+        };
+        (dashboard, receiver)
+    }
+
+    fn request(method: &str, url: &str) -> Request {
+        Request {
+            method: method.to_string(),
+            url: url.to_string(),
+            ..Request::default()
+        }
+    }
+
+    fn post(url: &str, authorization: Option<&str>, dashboard: &Dashboard) -> Response {
+        let mut request = request("POST", url);
+        if let Some(authorization) = authorization {
+            request
+                .headers
+                .push(("authorization".to_string(), authorization.to_string()));
+        }
+        respond(&request, dashboard)
+    }
+
+    #[test]
+    fn test_respond() {
+        let (latest, _) = dashboard(Some(snapshot()));
+        let (empty, _) = dashboard(None);
+        let respond = |method, url, dashboard| respond(&request(method, url), dashboard);
+        let (code, content_type, body) = respond("GET", "/api/status?pretty", &latest);
+        assert_eq!((code, content_type), (200, "application/json"));
+        let status: Value = serde_json::from_str(&body).unwrap();
+        assert_eq!(status["status"], "warning");
+        assert_eq!(status["time"], "2024-05-01T10:00:00Z");
+        assert_eq!(status["servers"][0]["results"][0]["metric"]["value"], 30.0);
+
+        let (code, _, body) = respond("GET", "/", &latest);
+        assert_eq!(code, 200);
+        assert!(body.contains("<tr class=\"warning\"><td>⚠️</td><td>load</td>"));
+        assert!(body.contains("@ &lt;web01&gt;"));
+        let mut custom = latest.clone();
+        custom.markers.warning = "<warn>".to_string();
+        let (_, _, body) = respond("GET", "/", &custom);
+        assert!(body.contains("<h1>&lt;warn&gt; RemoteSysMonitor</h1>"));
+        assert!(body.contains("<td>&lt;warn&gt;</td><td>load</td>"));
+
+        let (code, content_type, body) = respond("GET", "/badge/web01.svg", &latest);
+        assert_eq!((code, content_type), (200, "image/svg+xml"));
+        assert!(body.contains("<title>web01: warning</title>"));
+        let (_, _, body) = respond("GET", "/api/badge/my%20server", &latest);
+        assert_eq!(
+            serde_json::from_str::<Value>(&body).unwrap(),
+            json!({"schemaVersion": 1, "label": "my server", "message": "unknown", "color": "9f9f9f"})
+        );
+        assert!(respond("GET", "/badge/web01.svg", &empty)
+            .2
+            .contains("unknown"));
+
+        assert_eq!(respond("GET", "/nothing", &latest).0, 404);
+        assert_eq!(respond("DELETE", "/", &latest).0, 405);
+        assert_eq!(respond("GET", "/", &empty).0, 503);
+    }
+
+    #[test]
+    fn test_trigger() {
+        let (mut dashboard, runs) = dashboard(Some(snapshot()));
+        let (code, _, body) = post("/api/run", None, &dashboard);
+        assert_eq!(code, 202);
+        assert_eq!(
+            serde_json::from_str::<Value>(&body).unwrap(),
+            json!({"queued": true, "server": null, "check": null})
+        );
+        assert_eq!(runs.try_recv(), Ok(Recheck::default()));
+
+        let (code, _, _) = post("/api/run?server=web01&check=disk%20usage", None, &dashboard);
+        assert_eq!(code, 202);
+        assert_eq!(
+            runs.try_recv(),
+            Ok(Recheck {
+                server: Some("web01".to_string()),
+                check: Some("disk usage".to_string()),
+                response_url: None,
+            })
+        );
+        assert_eq!(post("/api/run?server=db01", None, &dashboard).0, 404);
+
+        dashboard.token = Some("secret".to_string());
+        assert_eq!(post("/api/run", None, &dashboard).0, 401);
+        assert_eq!(post("/api/run", Some("Bearer wrong"), &dashboard).0, 401);
+        assert_eq!(post("/api/run", Some("Bearer secre"), &dashboard).0, 401);
+        assert_eq!(post("/api/run", Some("Bearer secret"), &dashboard).0, 202);
+        for _ in 1..MAX_QUEUED_RUNS {
+            assert_eq!(post("/api/run", Some("Bearer secret"), &dashboard).0, 202);
+        }
+        assert_eq!(post("/api/run", Some("Bearer secret"), &dashboard).0, 429);
+        drop(runs);
+        assert_eq!(post("/api/run", Some("Bearer secret"), &dashboard).0, 503);
+    }
+
+    #[test]
+    fn test_slack_command() {
+        let (mut dashboard, runs) = dashboard(Some(snapshot()));
+        let command = |text: &str, secret: &str, dashboard: &Dashboard| {
+            let body = format!(
+                "command=%2Frecheck&text={}&response_url=https%3A%2F%2Fhooks.slack.com%2Fcommands%2F1",
+                text
+            );
+            let timestamp = Utc::now().timestamp().to_string();
+            let mac = hmac_sha256::HMAC::mac(format!("v0:{}:{}", timestamp, body), secret);
+            let signature: String = mac.iter().map(|b| format!("{:02x}", b)).collect();
+            let mut request = request("POST", "/slack/command");
+            request.headers = vec![
+                ("X-Slack-Request-Timestamp".to_string(), timestamp),
+                ("X-Slack-Signature".to_string(), format!("v0={}", signature)),
+            ];
+            request.body = body;
+            respond(&request, dashboard)
+        };
+        assert_eq!(command("web01", "secret", &dashboard).0, 404);
+
+        dashboard.slack_signing_secret = Some("secret".to_string());
+        let (code, _, body) = command("web01+load", "secret", &dashboard);
+        assert_eq!(code, 200);
+        let body: Value = serde_json::from_str(&body).unwrap();
+        assert_eq!(body["response_type"], "in_channel");
+        assert_eq!(body["text"], "🔄 Running the check load of web01 again");
+        assert_eq!(
+            runs.try_recv(),
+            Ok(Recheck {
+                server: Some("web01".to_string()),
+                check: Some("load".to_string()),
+                response_url: Some("https://hooks.slack.com/commands/1".to_string()),
+            })
+        );
+
+        assert_eq!(command("web01", "wrong", &dashboard).0, 401);
+        let (_, _, body) = command("db01", "secret", &dashboard);
+        assert!(body.contains("\"ephemeral\""));
+        assert!(body.contains("Unknown server `db01`"));
+        assert!(command("", "secret", &dashboard).2.contains("Usage"));
+        assert!(runs.try_recv().is_err());
+    }
+
+    #[test]
+    fn test_snapshot_update() {
+        let mut snapshot = snapshot();
+        snapshot.report.servers[0]
+            .results
+            .push(CheckResult::new(Status::Ok, "disk ok").with_check("disk"));
+        let time = "2024-05-01T10:02:00Z".parse().unwrap();
+
+        let mut web = ServerReport::new("web01", "web01.example.com");
+        web.results
+            .push(CheckResult::new(Status::Ok, "load 1.00 (5min) @ web01").with_check("load"));
+        let recheck = Recheck {
+            server: Some("web01".to_string()),
+            check: Some("load".to_string()),
+            response_url: None,
+        };
+        let report = Report {
+            notes: vec![],
+            servers: vec![web],
+        };
+        snapshot.update(report.clone(), Some(&recheck), time);
+        let results = &snapshot.report.servers[0].results;
+        assert_eq!(results.len(), 2);
+        assert_eq!(results[0].message, "load 1.00 (5min) @ web01");
+        assert_eq!(results[1].check, "disk");
+        assert_eq!(snapshot.time.to_rfc3339(), "2024-05-01T10:00:00+00:00");
+
+        let recheck = Recheck {
+            server: Some("web01".to_string()),
+            check: None,
+            response_url: None,
+        };
+        snapshot.update(report.clone(), Some(&recheck), time);
+        assert_eq!(snapshot.report.servers[0].results.len(), 1);
+
+        snapshot.update(Report::default(), None, time);
+        assert!(snapshot.report.servers.is_empty());
+        assert_eq!(snapshot.time, time);
+    }
+
+    #[cfg(feature = "dashboard")]
+    #[test]
+    fn test_serve() {
+        let config = DashboardConfig {
+            listen: "127.0.0.1:0".to_string(),
+            token: None,
+            token_env: None,
+            slack_signing_secret: None,
+            slack_signing_secret_env: None,
+        };
+        assert_eq!(listen_address("0.0.0.0:8080", false), "127.0.0.1:8080");
+        assert_eq!(listen_address("0.0.0.0:8080", true), "0.0.0.0:8080");
+        assert_eq!(listen_address("127.0.0.1:8080", false), "127.0.0.1:8080");
+        assert_eq!(listen_address("[::1]:8080", false), "[::1]:8080");
+
+        let (mut dashboard, runs) = dashboard(Some(snapshot()));
+        dashboard.token = Some("secret".to_string());
+        let address = serve(&config, dashboard).unwrap();
+        let response = reqwest::blocking::get(format!("http://{}/api/status", address)).unwrap();
+        assert_eq!(response.status(), 200);
+        assert!(response.text().unwrap().contains("web01.example.com"));
+
+        let response = reqwest::blocking::Client::new()
+            .post(format!("http://{}/api/run?server=web01", address))
+            .bearer_auth("secret")
+            .send()
+            .unwrap();
+        assert_eq!(response.status(), 202);
+        assert_eq!(runs.recv().unwrap().server.as_deref(), Some("web01"));
+    }
+}
@@ -0,0 +1,245 @@
+use crate::config::Server;
+use crate::history::Record;
+use crate::report::{format_value, CheckResult, Report, ServerReport, Status};
+use chrono::Duration;
+use std::collections::BTreeMap;
+
+/// Parses a period, such as the period of a digest or the `every` of a check: a number followed
+/// by `s` (seconds), `m` (minutes), `h` (hours), `d` (days) or `w` (weeks), e.g. `24h` or `7d`.
+///
+/// # Errors
+///
+/// Returns an error if the period is not in this format.
+pub fn parse_period(period: &str) -> Result<Duration, String> {
+    let invalid = || {
+        format!(
+            "invalid period `{}`, expected e.g. `10m`, `24h`, `1d` or `1w`",
+            period
+        )
+    };
+    let Some((last, _)) = period.char_indices().last() else {
+        return Err(invalid());
+    };
+    let (count, unit) = period.split_at(last);
+    let count: i64 = count.parse().map_err(|_| invalid())?;
+    match unit {
+        "s" => Ok(Duration::seconds(count)),
+        "m" => Ok(Duration::minutes(count)),
+        "h" => Ok(Duration::hours(count)),
+        "d" => Ok(Duration::days(count)),
+        "w" => Ok(Duration::weeks(count)),
+        _ => Err(invalid()),
+    }
+}
+
+/// Summarizes the history of the checks into a report, e.g. for a daily digest.
+///
+/// The report starts with a note counting the incidents (a check starting to fail) of the period,
+/// followed by one result per check of every server giving its number of incidents and the
+/// maximum of its measured values, e.g.:
+///
+/// ```text
+/// 📋 last 24h: 3 incidents on 1 server over 288 runs, worst load5 42.00 on gpu01
+/// 🖥️ gpu01
+/// ✅ load: 3 incidents, max load5 42.00
+/// ✅ backups: max old_directories 0
+/// ```
+///
+/// Each result has the latest status of its check, so that a digest only looks alarming when
+/// something is still failing. Results in maintenance windows are not counted as incidents. The
+/// hosts of the servers are taken from `servers`, when they are still configured.
+pub fn digest(records: &[Record], period: &str, servers: &[Server]) -> Report {
+    // Results by server and check, in the order of the history
+    let mut checks: BTreeMap<&str, BTreeMap<&str, Vec<&Record>>> = BTreeMap::new();
+    for record in records {
+        checks
+            .entry(&record.server)
+            .or_default()
+            .entry(&record.check)
+            .or_default()
+            .push(record);
+    }
+
+    let mut runs: Vec<_> = records.iter().map(|r| r.time).collect();
+    runs.sort();
+    runs.dedup();
+
+    let mut incidents = 0;
+    let mut failing_servers = 0;
+    let mut worst: Option<(&str, &str, f64)> = None;
+    let mut reports = vec![];
+    for (server, server_checks) in &checks {
+        let host = servers
+            .iter()
+            .find(|s| s.name == *server)
+            .map_or("", |s| s.host.first());
+        let mut report = ServerReport::new(server, host);
+        let mut server_incidents = 0;
+        for (check, history) in server_checks {
+            let check_incidents = count_incidents(history);
+            server_incidents += check_incidents;
+
+            // Maximum of every metric, by name
+            let mut maxima: BTreeMap<&str, f64> = BTreeMap::new();
+            for record in history {
+                if let (Some(metric), Some(value)) = (&record.metric, record.value) {
+                    let max = maxima.entry(metric).or_insert(value);
+                    *max = max.max(value);
+                }
+            }
+            if check_incidents > 0 {
+                for (metric, value) in &maxima {
+                    if worst.is_none_or(|(_, _, w)| *value > w) {
+                        worst = Some((server, metric, *value));
+                    }
+                }
+            }
+
+            let mut parts = vec![];
+            if check_incidents > 0 {
+                parts.push(plural(check_incidents, "incident"));
+            }
+            parts.extend(
+                maxima
+                    .iter()
+                    .map(|(metric, value)| format!("max {} {}", metric, format_value(*value))),
+            );
+            if parts.is_empty() {
+                parts.push(plural(history.len(), "run"));
+            }
+            let mut result =
+                CheckResult::new(Status::Ok, format!("{}: {}", check, parts.join(", ")))
+                    .with_check(check);
+            if let Some(latest) = history.last() {
+                result.status = latest.status;
+                result.muted = latest.muted;
+            }
+            report.results.push(result);
+        }
+        if server_incidents > 0 {
+            failing_servers += 1;
+        }
+        incidents += server_incidents;
+        reports.push(report);
+    }
+
+    let mut summary = format!("📋 last {}: ", period);
+    if records.is_empty() {
+        summary.push_str("no results recorded");
+    } else if incidents == 0 {
+        summary.push_str(&format!("no incident over {}", plural(runs.len(), "run")));
+    } else {
+        summary.push_str(&format!(
+            "{} on {} over {}",
+            plural(incidents, "incident"),
+            plural(failing_servers, "server"),
+            plural(runs.len(), "run")
+        ));
+        if let Some((server, metric, value)) = worst {
+            summary.push_str(&format!(
+                ", worst {} {} on {}",
+                metric,
+                format_value(value),
+                server
+            ));
+        }
+    }
+
+    Report {
+        notes: vec![CheckResult::new(Status::Info, summary)],
+        servers: reports,
+    }
+}
+
+/// Counts the times a check started failing, i.e. the results that are critical while the previous
+/// one was not. Muted results are ignored.
+fn count_incidents(history: &[&Record]) -> usize {
+    let mut failing = false;
+    let mut incidents = 0;
+    for record in history.iter().filter(|r| !r.muted) {
+        let critical = record.status == Status::Critical;
+        if critical && !failing {
+            incidents += 1;
+        }
+        failing = critical;
+    }
+    incidents
+}
+
+/// Formats a count with its noun, e.g. `1 incident` or `3 incidents`.
+fn plural(count: usize, noun: &str) -> String {
+    match count {
+        1 => format!("1 {}", noun),
+        _ => format!("{} {}s", count, noun),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record(minute: u32, server: &str, check: &str, status: Status, value: f64) -> Record {
+        Record {
+            time: format!("2024-05-01T10:{:02}:00Z", minute).parse().unwrap(),
+            server: server.to_string(),
+            check: check.to_string(),
+            status,
+            message: String::new(),
+            metric: Some("load5".to_string()),
+            value: Some(value),
+            muted: false,
+        }
+    }
+
+    #[test]
+    fn test_parse_period() {
+        assert_eq!(parse_period("24h"), Ok(Duration::hours(24)));
+        assert_eq!(parse_period("7d"), Ok(Duration::days(7)));
+        assert_eq!(parse_period("1w"), Ok(Duration::weeks(1)));
+        assert_eq!(parse_period("10m"), Ok(Duration::minutes(10)));
+        assert!(parse_period("d").is_err());
+        assert!(parse_period("1y").is_err());
+    }
+
+    #[test]
+    fn test_digest() {
+        let mut muted = record(15, "db01", "load", Status::Critical, 90.0);
+        muted.muted = true;
+        let records = vec![
+            record(0, "gpu01", "load", Status::Critical, 42.0),
+            record(0, "db01", "load", Status::Ok, 1.0),
+            record(5, "gpu01", "load", Status::Critical, 40.0),
+            record(10, "gpu01", "load", Status::Ok, 3.2),
+            muted,
+            record(15, "gpu01", "load", Status::Critical, 12.5),
+            record(20, "gpu01", "load", Status::Ok, 2.0),
+        ];
+
+        let servers: Vec<Server> =
+            serde_yaml::from_str("[{name: gpu01, host: gpu01.example.com}]").unwrap();
+        let report = digest(&records, "24h", &servers);
+        assert_eq!(
+            report.notes[0].message,
+            "📋 last 24h: 2 incidents on 1 server over 5 runs, worst load5 42 on gpu01"
+        );
+        assert_eq!(report.servers[0].name, "db01");
+        assert_eq!(report.servers[0].results[0].message, "load: max load5 90");
+        assert!(report.servers[0].results[0].muted);
+        assert_eq!(report.servers[1].host, "gpu01.example.com");
+        assert_eq!(
+            report.servers[1].results[0].message,
+            "load: 2 incidents, max load5 42"
+        );
+        assert_eq!(report.servers[1].results[0].status, Status::Ok);
+
+        let report = digest(&records[1..2], "7d", &[]);
+        assert_eq!(
+            report.notes[0].message,
+            "📋 last 7d: no incident over 1 run"
+        );
+        assert_eq!(
+            digest(&[], "1d", &[]).notes[0].message,
+            "📋 last 1d: no results recorded"
+        );
+    }
+}
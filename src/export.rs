@@ -0,0 +1,614 @@
+use crate::notify::secret;
+use crate::otlp;
+use crate::report::{Report, ServerReport, Status};
+use crate::webhook;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::collections::HashMap;
+use std::io::{Read, Write};
+use std::net::{TcpStream, ToSocketAddrs, UdpSocket};
+use std::time::Duration;
+
+/// Measurement of the exported values, unless configured otherwise.
+const DEFAULT_MEASUREMENT: &str = "remotesysmonitor";
+/// Prefix of the Graphite and StatsD paths, and service name of OTLP, unless configured otherwise.
+const DEFAULT_PREFIX: &str = "remotesysmonitor";
+/// Timeout of the connections to Carbon.
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// A destination the values measured by the checks are written to after every run, e.g. to feed
+/// Grafana dashboards.
+///
+/// ```yaml
+/// exporters:
+///   - type: influxdb
+///     url: https://influx.example.com:8086
+///     org: ops
+///     bucket: monitoring
+///     token_env: INFLUX_TOKEN
+///   - type: line_protocol # print the values in stdout instead
+///   - type: graphite
+///     host: carbon.example.com
+///   - type: statsd
+///     host: localhost
+///   - type: otlp
+///     endpoint: http://localhost:4318
+///   - type: uptime_kuma
+///     push_urls:
+///       web01: https://kuma.example.com/api/push/Xb3kQ9
+///   - type: zabbix
+///     host: zabbix.example.com
+/// ```
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Exporter {
+    /// Write the values to an InfluxDB v2 bucket, through its HTTP write API.
+    Influxdb {
+        /// Base URL of the InfluxDB server, e.g. `http://localhost:8086`.
+        url: String,
+        /// Organization owning the bucket.
+        org: String,
+        /// Bucket the values are written to.
+        bucket: String,
+        /// API token allowed to write to the bucket.
+        token: Option<String>,
+        /// Environment variable holding the API token, to keep it out of the configuration.
+        token_env: Option<String>,
+        /// Measurement of the points, `remotesysmonitor` by default.
+        measurement: Option<String>,
+    },
+    /// Print the values in stdout in the InfluxDB line protocol, e.g. for Telegraf's `exec` input.
+    LineProtocol {
+        /// Measurement of the points, `remotesysmonitor` by default.
+        measurement: Option<String>,
+    },
+    /// Send the values to Carbon in the plaintext Graphite protocol, over TCP.
+    Graphite {
+        /// Hostname of the Carbon server.
+        host: String,
+        /// Port of the plaintext protocol, 2003 by default.
+        port: Option<u16>,
+        /// First component of the paths, `remotesysmonitor` by default.
+        prefix: Option<String>,
+    },
+    /// Send the values to StatsD as gauges, over UDP.
+    Statsd {
+        /// Hostname of the StatsD server.
+        host: String,
+        /// Port of the server, 8125 by default.
+        port: Option<u16>,
+        /// First component of the paths, `remotesysmonitor` by default.
+        prefix: Option<String>,
+    },
+    /// Send the trace of every run (a span per server and per check) to an OpenTelemetry
+    /// collector, and optionally the values as metrics, over OTLP/HTTP with JSON payloads.
+    Otlp {
+        /// Base URL of the collector, e.g. `http://localhost:4318`, to which `/v1/traces` and
+        /// `/v1/metrics` are appended.
+        endpoint: String,
+        /// Headers of the requests, e.g. for authentication.
+        headers: Option<HashMap<String, String>>,
+        /// Headers whose value is read from an environment variable, by name of the variable.
+        headers_env: Option<HashMap<String, String>>,
+        /// `service.name` of the resource, `remotesysmonitor` by default.
+        service_name: Option<String>,
+        /// Whether to also export the measured values and the statuses of the checks as metrics.
+        #[serde(default)]
+        metrics: bool,
+    },
+    /// Push the status of every server to an Uptime Kuma push monitor: `up` unless a check
+    /// reported a ❌, with the worst result as message and the duration of the checks as ping.
+    UptimeKuma {
+        /// Push URL of the monitor of every server, by server name, as shown by Uptime Kuma (its
+        /// query is ignored). Servers without a monitor are not pushed.
+        push_urls: HashMap<String, String>,
+    },
+    /// Send the values and statuses to Zabbix trapper items, as `zabbix_sender` does.
+    Zabbix {
+        /// Hostname of the Zabbix server or proxy.
+        host: String,
+        /// Port of the trapper, 10051 by default.
+        port: Option<u16>,
+    },
+}
+
+impl Exporter {
+    /// Returns the name of the kind of exporter, for logging.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Exporter::Influxdb { .. } => "influxdb",
+            Exporter::LineProtocol { .. } => "line_protocol",
+            Exporter::Graphite { .. } => "graphite",
+            Exporter::Statsd { .. } => "statsd",
+            Exporter::Otlp { .. } => "otlp",
+            Exporter::UptimeKuma { .. } => "uptime_kuma",
+            Exporter::Zabbix { .. } => "zabbix",
+        }
+    }
+
+    /// Exports the values measured by the checks of a report, recorded at `time`.
+    ///
+    /// Nothing is written when no result of the report measures a value.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the exporter is misconfigured (e.g. its token is missing) or if the
+    /// values could not be delivered.
+    pub fn export(
+        &self,
+        report: &Report,
+        time: DateTime<Utc>,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        match self {
+            Exporter::Influxdb {
+                url,
+                org,
+                bucket,
+                token,
+                token_env,
+                measurement,
+            } => {
+                let lines = line_protocol(report, time, measurement.as_deref());
+                if lines.is_empty() {
+                    return Ok(());
+                }
+                let token = secret(token.as_deref(), token_env.as_deref(), "token")?;
+                let url = reqwest::Url::parse_with_params(
+                    &format!("{}/api/v2/write", url.trim_end_matches('/')),
+                    &[("org", org), ("bucket", bucket), ("precision", &"s".into())],
+                )?;
+                let authorization = format!("Token {}", token);
+                webhook::post_text(url.as_str(), &[("Authorization", &authorization)], &lines)
+            }
+            Exporter::LineProtocol { measurement } => {
+                print!("{}", line_protocol(report, time, measurement.as_deref()));
+                Ok(())
+            }
+            Exporter::Graphite { host, port, prefix } => {
+                let prefix = prefix.as_deref().unwrap_or(DEFAULT_PREFIX);
+                let lines: String = paths(report, prefix)
+                    .map(|(path, value)| format!("{} {} {}\n", path, value, time.timestamp()))
+                    .collect();
+                if lines.is_empty() {
+                    return Ok(());
+                }
+                let address = (host.as_str(), port.unwrap_or(2003))
+                    .to_socket_addrs()?
+                    .next()
+                    .ok_or_else(|| format!("could not resolve {}", host))?;
+                let mut stream = TcpStream::connect_timeout(&address, CONNECT_TIMEOUT)?;
+                stream.set_write_timeout(Some(CONNECT_TIMEOUT))?;
+                stream.write_all(lines.as_bytes())?;
+                Ok(())
+            }
+            Exporter::Statsd { host, port, prefix } => {
+                let prefix = prefix.as_deref().unwrap_or(DEFAULT_PREFIX);
+                let socket = UdpSocket::bind(("0.0.0.0", 0))?;
+                socket.connect((host.as_str(), port.unwrap_or(8125)))?;
+                // One datagram per value, so that none exceeds the size of a packet
+                for (path, value) in paths(report, prefix) {
+                    socket.send(format!("{}:{}|g", path, value).as_bytes())?;
+                }
+                Ok(())
+            }
+            Exporter::Otlp {
+                endpoint,
+                headers,
+                headers_env,
+                service_name,
+                metrics,
+            } => {
+                let mut values: Vec<(String, String)> =
+                    headers.clone().into_iter().flatten().collect();
+                for (name, env) in headers_env.iter().flatten() {
+                    values.push((name.clone(), secret(None, Some(env), name)?));
+                }
+                let headers: Vec<(&str, &str)> = values
+                    .iter()
+                    .map(|(n, v)| (n.as_str(), v.as_str()))
+                    .collect();
+                let endpoint = endpoint.trim_end_matches('/');
+                let service_name = service_name.as_deref().unwrap_or(DEFAULT_PREFIX);
+                webhook::post_json_with_headers(
+                    &format!("{}/v1/traces", endpoint),
+                    &headers,
+                    &otlp::traces(report, time, service_name),
+                )?;
+                if *metrics {
+                    webhook::post_json_with_headers(
+                        &format!("{}/v1/metrics", endpoint),
+                        &headers,
+                        &otlp::metrics(report, time, service_name),
+                    )?;
+                }
+                Ok(())
+            }
+            Exporter::UptimeKuma { push_urls } => {
+                let client = reqwest::blocking::Client::builder()
+                    .timeout(CONNECT_TIMEOUT)
+                    .build()?;
+                let mut errors = vec![];
+                for server in &report.servers {
+                    let Some(url) = push_urls.get(&server.name) else {
+                        continue;
+                    };
+                    let url = url.split('?').next().unwrap_or_default();
+                    let response = client.get(url).query(&kuma_push(server)).send();
+                    match response.and_then(|r| r.error_for_status()?.text()) {
+                        // Uptime Kuma answers unknown tokens with a 200 and `"ok": false`
+                        Ok(body) if !body.contains("\"ok\":false") => {}
+                        Ok(_) => errors.push(format!("{}: unknown push token", server.name)),
+                        Err(e) => errors.push(format!("{}: {}", server.name, e)),
+                    }
+                }
+                match errors.is_empty() {
+                    true => Ok(()),
+                    false => Err(errors.join(", ").into()),
+                }
+            }
+            Exporter::Zabbix { host, port } => {
+                let data = zabbix_data(report, time);
+                if data.is_empty() {
+                    return Ok(());
+                }
+                let payload = json!({"request": "sender data", "data": data}).to_string();
+                let address = (host.as_str(), port.unwrap_or(10051))
+                    .to_socket_addrs()?
+                    .next()
+                    .ok_or_else(|| format!("could not resolve {}", host))?;
+                let mut stream = TcpStream::connect_timeout(&address, CONNECT_TIMEOUT)?;
+                stream.set_read_timeout(Some(CONNECT_TIMEOUT))?;
+                stream.set_write_timeout(Some(CONNECT_TIMEOUT))?;
+                let mut packet = b"ZBXD\x01".to_vec();
+                packet.extend((payload.len() as u64).to_le_bytes());
+                packet.extend(payload.as_bytes());
+                stream.write_all(&packet)?;
+
+                let mut answer = vec![];
+                stream.read_to_end(&mut answer)?;
+                let answer: serde_json::Value =
+                    serde_json::from_slice(answer.get(13..).unwrap_or_default())?;
+                let info = answer["info"].as_str().unwrap_or_default();
+                if answer["response"] != "success" || !info.contains("failed: 0") {
+                    return Err(
+                        format!("zabbix answered `{}`: {}", answer["response"], info).into(),
+                    );
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+/// Returns the items sent to Zabbix: `remotesysmonitor.value[<check>,<metric>]` for the values
+/// measured by the checks, and `remotesysmonitor.status[<check>]` for the status of every check
+/// (and of the `ssh` connection) from 0 (✅) to 3 (❌), on the Zabbix host named after the server.
+fn zabbix_data(report: &Report, time: DateTime<Utc>) -> Vec<serde_json::Value> {
+    let mut data = vec![];
+    for server in &report.servers {
+        for result in &server.results {
+            if let Some(metric) = &result.metric {
+                data.push(json!({
+                    "host": server.name,
+                    "key": format!(
+                        "remotesysmonitor.value[{},{}]",
+                        key_parameter(&result.check),
+                        key_parameter(&metric.name)
+                    ),
+                    "value": metric.value.to_string(),
+                    "clock": time.timestamp(),
+                }));
+            }
+        }
+        for step in &server.timings {
+            data.push(json!({
+                "host": server.name,
+                "key": format!("remotesysmonitor.status[{}]", key_parameter(&step.name)),
+                "value": (step.status as u8).to_string(),
+                "clock": time.timestamp(),
+            }));
+        }
+    }
+    data
+}
+
+/// Quotes a parameter of a Zabbix item key when it contains special characters.
+fn key_parameter(parameter: &str) -> String {
+    if parameter.contains([',', '[', ']', '"', ' ']) {
+        format!("\"{}\"", parameter.replace('"', "\\\""))
+    } else {
+        parameter.to_string()
+    }
+}
+
+/// Returns the query parameters of the push of a server to Uptime Kuma.
+fn kuma_push(server: &ServerReport) -> [(&'static str, String); 3] {
+    let status = server.status();
+    let message = server
+        .results
+        .iter()
+        .filter(|r| !r.muted && r.status == status && status > Status::Info)
+        .map(|r| r.headline())
+        .next()
+        .unwrap_or_else(|| "OK".to_string());
+    let ping = server
+        .timing
+        .as_ref()
+        .map_or(String::new(), |t| t.duration.as_millis().to_string());
+    let up = match status {
+        Status::Critical => "down",
+        _ => "up",
+    };
+    [("status", up.to_string()), ("msg", message), ("ping", ping)]
+}
+
+/// Returns the Graphite paths of the values measured by the checks of a report, as
+/// `<prefix>.<server>.<check>.<metric>`, with their values.
+///
+/// The characters of the components other than letters, digits, `-` and `_` are replaced by `_`,
+/// so that e.g. the path `/data` of a `number_of_subfolders` check becomes `data`.
+fn paths<'a>(report: &'a Report, prefix: &'a str) -> impl Iterator<Item = (String, f64)> + 'a {
+    report.servers.iter().flat_map(move |server| {
+        server.results.iter().filter_map(move |result| {
+            let metric = result.metric.as_ref()?;
+            let path = format!(
+                "{}.{}.{}.{}",
+                prefix,
+                sanitize(&server.name),
+                sanitize(&result.check),
+                sanitize(&metric.name)
+            );
+            Some((path, metric.value))
+        })
+    })
+}
+
+/// Makes a name usable as a component of a Graphite path.
+fn sanitize(name: &str) -> String {
+    let sanitized: String = name
+        .chars()
+        .map(|c| match c {
+            'a'..='z' | 'A'..='Z' | '0'..='9' | '-' | '_' => c,
+            _ => '_',
+        })
+        .collect();
+    sanitized.trim_matches('_').to_string()
+}
+
+/// Renders the values measured by the checks of a report in the InfluxDB line protocol, one point
+/// per value with a precision of a second, e.g.:
+///
+/// ```text
+/// remotesysmonitor,server=web01,check=load,metric=load5 value=3.2,status=0i 1714557600
+/// ```
+///
+/// The `status` field is the severity of the result, from 0 (✅) to 3 (❌). Results without a value
+/// (and the notes of the report) are skipped.
+pub fn line_protocol(report: &Report, time: DateTime<Utc>, measurement: Option<&str>) -> String {
+    let measurement = escape(measurement.unwrap_or(DEFAULT_MEASUREMENT), ", ");
+    let mut lines = String::new();
+    for server in &report.servers {
+        for result in &server.results {
+            let Some(metric) = &result.metric else {
+                continue;
+            };
+            lines.push_str(&format!(
+                "{},server={},check={},metric={} value={},status={}i {}\n",
+                measurement,
+                escape(&server.name, ", ="),
+                escape(&result.check, ", ="),
+                escape(&metric.name, ", ="),
+                metric.value,
+                result.status as u8,
+                time.timestamp()
+            ));
+        }
+    }
+    lines
+}
+
+/// Escapes the given special characters of a name or tag with a backslash, as required by the
+/// line protocol.
+fn escape(value: &str, special: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        if special.contains(c) {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::report::{CheckResult, ServerReport, Status};
+
+    fn report() -> Report {
+        let mut server = ServerReport::new("web01", "web01.example.com");
+        server.results.push(
+            CheckResult::new(Status::Warning, "load 30.00 (5min) @ web01")
+                .with_check("load")
+                .with_metric("load5", 30.0),
+        );
+        server.results.push(
+            CheckResult::new(Status::Ok, "2 folders @ `web01:/my data`")
+                .with_check("number_of_subfolders")
+                .with_metric("/my data", 2.0),
+        );
+        server
+            .results
+            .push(CheckResult::new(Status::Info, "`ls`").with_check("ls"));
+        Report {
+            notes: vec![],
+            servers: vec![server],
+        }
+    }
+
+    #[test]
+    fn test_line_protocol() {
+        let time = "2024-05-01T10:00:00Z".parse().unwrap();
+        assert_eq!(
+            line_protocol(&report(), time, None),
+            "remotesysmonitor,server=web01,check=load,metric=load5 value=30,status=2i 1714557600\n\
+             remotesysmonitor,server=web01,check=number_of_subfolders,metric=/my\\ data \
+             value=2,status=0i 1714557600\n"
+        );
+        assert!(line_protocol(&Report::default(), time, Some("sys mon")).is_empty());
+    }
+
+    #[test]
+    fn test_paths() {
+        let report = report();
+        let paths: Vec<_> = paths(&report, "rsm").collect();
+        assert_eq!(
+            paths,
+            vec![
+                ("rsm.web01.load.load5".to_string(), 30.0),
+                ("rsm.web01.number_of_subfolders.my_data".to_string(), 2.0),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_export_graphite_and_statsd() {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let exporter = Exporter::Graphite {
+            host: "127.0.0.1".to_string(),
+            port: Some(port),
+            prefix: None,
+        };
+        exporter
+            .export(&report(), "2024-05-01T10:00:00Z".parse().unwrap())
+            .unwrap();
+        let mut received = String::new();
+        std::io::Read::read_to_string(&mut listener.accept().unwrap().0, &mut received).unwrap();
+        assert!(received.starts_with("remotesysmonitor.web01.load.load5 30 1714557600\n"));
+
+        let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let exporter = Exporter::Statsd {
+            host: "127.0.0.1".to_string(),
+            port: Some(socket.local_addr().unwrap().port()),
+            prefix: Some("rsm".to_string()),
+        };
+        exporter.export(&report(), Utc::now()).unwrap();
+        let mut buffer = [0; 512];
+        let length = socket.recv(&mut buffer).unwrap();
+        assert_eq!(&buffer[..length], b"rsm.web01.load.load5:30|g");
+    }
+
+    #[test]
+    fn test_kuma_push() {
+        let mut report = report();
+        let server = &mut report.servers[0];
+        assert_eq!(
+            kuma_push(server),
+            [
+                ("status", "up".to_string()),
+                ("msg", "⚠️ load 30.00 (5min) @ web01".to_string()),
+                ("ping", String::new()),
+            ]
+        );
+        server.results[0].status = Status::Ok;
+        assert_eq!(kuma_push(server)[1].1, "OK");
+        server.results[1].status = Status::Critical;
+        assert_eq!(kuma_push(server)[0].1, "down");
+
+        let mut kuma = mockito::Server::new();
+        let mock = kuma
+            .mock("GET", "/api/push/abc")
+            .match_query(mockito::Matcher::UrlEncoded("status".into(), "down".into()))
+            .with_body(r#"{"ok":true}"#)
+            .create();
+        let exporter = Exporter::UptimeKuma {
+            push_urls: HashMap::from([
+                (
+                    "web01".to_string(),
+                    format!("{}/api/push/abc?status=up&msg=OK&ping=", kuma.url()),
+                ),
+                ("db01".to_string(), format!("{}/api/push/def", kuma.url())),
+            ]),
+        };
+        exporter.export(&report, Utc::now()).unwrap();
+        mock.assert();
+
+        let mock = kuma
+            .mock("GET", "/api/push/abc")
+            .match_query(mockito::Matcher::Any)
+            .with_body(r#"{"ok":false,"msg":"Monitor not found or not active."}"#)
+            .create();
+        let error = exporter.export(&report, Utc::now()).unwrap_err();
+        assert_eq!(error.to_string(), "web01: unknown push token");
+        mock.assert();
+    }
+
+    #[test]
+    fn test_export_zabbix() {
+        let mut report = report();
+        report.servers[0].timings.push(crate::report::Timing {
+            name: "load".to_string(),
+            start: Utc::now(),
+            duration: Duration::from_secs(1),
+            status: Status::Warning,
+        });
+        let data = zabbix_data(&report, "2024-05-01T10:00:00Z".parse().unwrap());
+        assert_eq!(
+            data[1],
+            json!({
+                "host": "web01",
+                "key": "remotesysmonitor.value[number_of_subfolders,\"/my data\"]",
+                "value": "2",
+                "clock": 1714557600,
+            })
+        );
+        assert_eq!(data[2]["key"], "remotesysmonitor.status[load]");
+        assert_eq!(data[2]["value"], "2");
+
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let trapper = std::thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut header = [0; 13];
+            stream.read_exact(&mut header).unwrap();
+            let length = u64::from_le_bytes(header[5..].try_into().unwrap());
+            let mut payload = vec![0; length as usize];
+            stream.read_exact(&mut payload).unwrap();
+            let answer = r#"{"response":"success","info":"processed: 2; failed: 1; total: 3"}"#;
+            let mut packet = b"ZBXD\x01".to_vec();
+            packet.extend((answer.len() as u64).to_le_bytes());
+            packet.extend(answer.as_bytes());
+            stream.write_all(&packet).unwrap();
+            String::from_utf8(payload).unwrap()
+        });
+        let exporter = Exporter::Zabbix {
+            host: "127.0.0.1".to_string(),
+            port: Some(port),
+        };
+        let error = exporter.export(&report, Utc::now()).unwrap_err();
+        assert!(error.to_string().contains("failed: 1"));
+        assert!(trapper.join().unwrap().starts_with(r#"{"data":[{"clock":"#));
+    }
+
+    #[test]
+    fn test_export_influxdb() {
+        let mut server = mockito::Server::new();
+        let mock = server
+            .mock("POST", "/api/v2/write")
+            .match_query("org=ops&bucket=monitoring&precision=s")
+            .match_header("authorization", "Token abc")
+            .match_body(mockito::Matcher::Regex("^servers,server=web01,".into()))
+            .with_status(204)
+            .create();
+        let exporter: Exporter = serde_yaml::from_str(&format!(
+            "{{type: influxdb, url: '{}/', org: ops, bucket: monitoring, token: abc, \
+             measurement: servers}}",
+            server.url()
+        ))
+        .unwrap();
+        exporter.export(&report(), Utc::now()).unwrap();
+        mock.assert();
+    }
+}
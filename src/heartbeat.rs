@@ -0,0 +1,85 @@
+use crate::notify::secret;
+use crate::webhook;
+use serde::{Deserialize, Serialize};
+
+/// A dead man's switch pinged around every run, e.g. a check of healthchecks.io (or of a
+/// compatible service), so that a monitor that stopped running is noticed.
+///
+/// The switch is pinged at `<url>/start` when a run starts, and at `<url>` once it is over, or at
+/// `<url>/fail` if a check reported a ❌ or the report could not be sent. The plain text report is
+/// sent along with the last ping.
+///
+/// ```yaml
+/// general:
+///   heartbeat:
+///     url_env: HEALTHCHECKS_URL # e.g. https://hc-ping.com/<uuid>
+/// ```
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Heartbeat {
+    /// Ping URL of the check.
+    pub url: Option<String>,
+    /// Environment variable holding the ping URL.
+    pub url_env: Option<String>,
+}
+
+impl Heartbeat {
+    /// Signals the start of a run.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the URL is missing or the ping could not be delivered.
+    pub fn start(&self) -> Result<(), Box<dyn std::error::Error>> {
+        webhook::post_text(&format!("{}/start", self.url()?), &[], "")
+    }
+
+    /// Signals the end of a run, as a failure if `failed`, with the report as body.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the URL is missing or the ping could not be delivered.
+    pub fn finish(&self, failed: bool, report: &str) -> Result<(), Box<dyn std::error::Error>> {
+        let url = match failed {
+            true => format!("{}/fail", self.url()?),
+            false => self.url()?,
+        };
+        webhook::post_text(&url, &[], report)
+    }
+
+    fn url(&self) -> Result<String, Box<dyn std::error::Error>> {
+        let url = secret(self.url.as_deref(), self.url_env.as_deref(), "url")?;
+        Ok(url.trim_end_matches('/').to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_heartbeat() {
+        let mut server = mockito::Server::new();
+        let heartbeat = Heartbeat {
+            url: Some(format!("{}/ping/abc/", server.url())),
+            url_env: None,
+        };
+
+        let start = server.mock("POST", "/ping/abc/start").create();
+        let success = server
+            .mock("POST", "/ping/abc")
+            .match_body("✅ load 1.00")
+            .create();
+        let failure = server.mock("POST", "/ping/abc/fail").create();
+        heartbeat.start().unwrap();
+        heartbeat.finish(false, "✅ load 1.00").unwrap();
+        heartbeat.finish(true, "❌ load 60.00").unwrap();
+        start.assert();
+        success.assert();
+        failure.assert();
+
+        let heartbeat = Heartbeat {
+            url: None,
+            url_env: None,
+        };
+        assert!(heartbeat.start().is_err());
+    }
+}
@@ -0,0 +1,236 @@
+use crate::report::{Report, Status};
+use chrono::{DateTime, Utc};
+use log::{error, warn};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::io::{BufRead, BufReader, Read, Seek, SeekFrom, Write};
+use std::path::Path;
+
+/// A result of a check, as recorded in the history file.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Record {
+    /// When the run producing the result started.
+    pub time: DateTime<Utc>,
+    /// Name of the server.
+    pub server: String,
+    /// Name of the check, `ssh` for connection failures.
+    pub check: String,
+    /// Status of the result.
+    pub status: Status,
+    /// Message of the result, without the status emoji.
+    pub message: String,
+    /// Name of the measured value, see `Metric`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub metric: Option<String>,
+    /// The measured value, e.g. the load average.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub value: Option<f64>,
+    /// Whether the result fell in a maintenance window.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub muted: bool,
+}
+
+/// Returns the records of the results of a report, the notes excepted.
+pub fn records(report: &Report, time: DateTime<Utc>) -> Vec<Record> {
+    report
+        .servers
+        .iter()
+        .flat_map(|server| {
+            server.results.iter().map(|result| Record {
+                time,
+                server: server.name.clone(),
+                check: result.check.clone(),
+                status: result.status,
+                message: result.message.clone(),
+                metric: result.metric.as_ref().map(|m| m.name.clone()),
+                value: result.metric.as_ref().map(|m| m.value),
+                muted: result.muted,
+            })
+        })
+        .collect()
+}
+
+/// Appends records to a history file, one JSON object per line, creating the file if needed.
+///
+/// # Errors
+///
+/// Returns an error if the file cannot be opened or written.
+pub fn append(path: &str, records: &[Record]) -> Result<(), Box<dyn std::error::Error>> {
+    let mut lines = String::new();
+    for record in records {
+        lines.push_str(&serde_json::to_string(record)?);
+        lines.push('\n');
+    }
+    let mut file = std::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .map_err(|e| {
+            error!("Could not open history file {}: {}", path, e);
+            Box::<dyn std::error::Error>::from(e)
+        })?;
+    // A single write keeps the records of a run together
+    file.write_all(lines.as_bytes()).map_err(|e| {
+        error!("Could not write history file {}: {}", path, e);
+        Box::<dyn std::error::Error>::from(e)
+    })?;
+    Ok(())
+}
+
+/// Size of the end of the history file read by `latest`.
+const TAIL: u64 = 1024 * 1024;
+
+/// The latest measured values of a history, by server, check and metric name.
+pub type Latest = HashMap<(String, String, String), f64>;
+
+/// Returns the latest value of every metric recorded in a history file.
+///
+/// Only the end of the file is read, so that it stays cheap on every run however large the history
+/// grows: metrics that were not recorded in its last megabyte (e.g. of a group checked once a day
+/// among frequent ones) are missing. A missing file results in no values.
+///
+/// # Errors
+///
+/// Returns an error if the file exists but cannot be read.
+pub fn latest(path: &str) -> Result<Latest, Box<dyn std::error::Error>> {
+    if !Path::new(path).exists() {
+        return Ok(Latest::new());
+    }
+    let mut file = std::fs::File::open(path)?;
+    let length = file.metadata()?.len();
+    let start = length.saturating_sub(TAIL);
+    file.seek(SeekFrom::Start(start))?;
+    let mut tail = vec![];
+    file.read_to_end(&mut tail)?;
+    let mut lines = tail.split(|b| *b == b'\n');
+    if start > 0 {
+        // The first line is most likely cut
+        lines.next();
+    }
+
+    let mut values = Latest::new();
+    for line in lines {
+        let Ok(record) = serde_json::from_slice::<Record>(line) else {
+            continue;
+        };
+        if let (Some(metric), Some(value)) = (record.metric, record.value) {
+            values.insert((record.server, record.check, metric), value);
+        }
+    }
+    Ok(values)
+}
+
+/// Sets the previous value of the metrics of a report from the latest values of the history, so
+/// that their trend is shown.
+pub fn set_previous(report: &mut Report, latest: &Latest) {
+    for server in &mut report.servers {
+        for result in &mut server.results {
+            if let Some(metric) = &result.metric {
+                let key = (
+                    server.name.clone(),
+                    result.check.clone(),
+                    metric.name.clone(),
+                );
+                result.previous = latest.get(&key).copied();
+            }
+        }
+    }
+}
+
+/// Loads the records of a history file, optionally only the ones recorded at or after `since`.
+///
+/// A missing file results in an empty history, and lines that cannot be parsed (e.g. truncated by
+/// a crash) are logged and skipped.
+///
+/// # Errors
+///
+/// Returns an error if the file exists but cannot be read.
+pub fn load(
+    path: &str,
+    since: Option<DateTime<Utc>>,
+) -> Result<Vec<Record>, Box<dyn std::error::Error>> {
+    if !Path::new(path).exists() {
+        return Ok(vec![]);
+    }
+    let file = std::fs::File::open(path).map_err(|e| {
+        error!("Could not read history file {}: {}", path, e);
+        Box::<dyn std::error::Error>::from(e)
+    })?;
+    let mut records = vec![];
+    for (number, line) in BufReader::new(file).lines().enumerate() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        match serde_json::from_str::<Record>(&line) {
+            Ok(record) if since.is_none_or(|since| record.time >= since) => records.push(record),
+            Ok(_) => {}
+            Err(e) => warn!("Skipping line {} of {}: {}", number + 1, path, e),
+        }
+    }
+    Ok(records)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::report::{CheckResult, ServerReport};
+
+    #[test]
+    fn test_append_and_load() {
+        let path = std::env::temp_dir().join("remotesysmonitor-test-history.jsonl");
+        std::fs::remove_file(&path).ok();
+        let path = path.to_str().unwrap();
+
+        let mut server = ServerReport::new("web01", "web01.example.com");
+        server.results.push(
+            CheckResult::new(Status::Warning, "load 30.00 (5min) @ web01")
+                .with_check("load")
+                .with_metric("load5", 30.0),
+        );
+        server
+            .results
+            .push(CheckResult::new(Status::Info, "`ls`").with_check("ls"));
+        let report = Report {
+            notes: vec![CheckResult::new(Status::Ok, "recovered: load @ web01")],
+            servers: vec![server],
+        };
+
+        let first: DateTime<Utc> = "2024-05-01T10:00:00Z".parse().unwrap();
+        let second: DateTime<Utc> = "2024-05-01T10:05:00Z".parse().unwrap();
+        append(path, &records(&report, first)).unwrap();
+        append(path, &records(&report, second)).unwrap();
+        std::fs::OpenOptions::new()
+            .append(true)
+            .open(path)
+            .unwrap()
+            .write_all(b"{\"truncated\n")
+            .unwrap();
+
+        let history = load(path, None).unwrap();
+        assert_eq!(history.len(), 4);
+        assert_eq!(history[0].check, "load");
+        assert_eq!(history[0].metric.as_deref(), Some("load5"));
+        assert_eq!(history[0].value, Some(30.0));
+        assert_eq!(history[1].value, None);
+        assert_eq!(load(path, Some(second)).unwrap().len(), 2);
+
+        let latest = latest(path).unwrap();
+        assert_eq!(latest.len(), 1);
+        let key = ("web01".to_string(), "load".to_string(), "load5".to_string());
+        assert_eq!(latest[&key], 30.0);
+
+        let mut report = report;
+        report.servers[0].results[0].metric.as_mut().unwrap().value = 12.0;
+        set_previous(&mut report, &latest);
+        assert_eq!(report.servers[0].results[0].previous, Some(30.0));
+        assert_eq!(report.servers[0].results[1].previous, None);
+
+        let line = std::fs::read_to_string(path).unwrap();
+        assert!(line.starts_with(
+            "{\"time\":\"2024-05-01T10:00:00Z\",\"server\":\"web01\",\"check\":\"load\",\
+             \"status\":\"warning\",\"message\":\"load 30.00 (5min) @ web01\",\
+             \"metric\":\"load5\",\"value\":30.0}\n"
+        ));
+    }
+}
@@ -0,0 +1,284 @@
+use crate::report::{Report, Status};
+use crate::state::{self, Change};
+use crate::webhook;
+use serde_json::{json, Value};
+
+/// Endpoint of the PagerDuty Events API v2.
+pub const PAGERDUTY_EVENTS_URL: &str = "https://events.pagerduty.com/v2/enqueue";
+/// Endpoint of the Opsgenie API, use `https://api.eu.opsgenie.com` for EU accounts.
+pub const OPSGENIE_API_URL: &str = "https://api.opsgenie.com";
+
+/// A failing check, opened as an incident on a paging service.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Incident {
+    /// Key identifying the check across runs, used to deduplicate and resolve the incident.
+    pub key: String,
+    /// Name of the server.
+    pub server: String,
+    /// Hostname or IP address of the server.
+    pub host: String,
+    /// Name of the check.
+    pub check: String,
+    /// One-line description of the failure.
+    pub summary: String,
+    /// Details of the failure, e.g. command outputs.
+    pub details: String,
+}
+
+/// Returns the incidents of a report: one per check with `Critical` results that are not muted.
+pub fn incidents(report: &Report) -> Vec<Incident> {
+    let mut incidents: Vec<Incident> = vec![];
+    for server in &report.servers {
+        for result in &server.results {
+            if result.muted || result.status != Status::Critical {
+                continue;
+            }
+            let key = incident_key(&server.name, &result.check);
+            let details = result.details.as_deref().unwrap_or("").trim_end();
+            match incidents.iter_mut().find(|i| i.key == key) {
+                // Checks with several results (e.g. one per URL) are merged into one incident
+                Some(incident) => {
+                    incident.summary = format!("{}; {}", incident.summary, result.message);
+                    if !details.is_empty() {
+                        incident.details = format!("{}\n{}", incident.details, details);
+                    }
+                }
+                None => incidents.push(Incident {
+                    key,
+                    server: server.name.clone(),
+                    host: server.host.clone(),
+                    check: result.check.clone(),
+                    summary: format!("{} @ {}: {}", result.check, server.name, result.message),
+                    details: details.to_string(),
+                }),
+            }
+        }
+    }
+    incidents
+}
+
+/// Returns the keys of the incidents to resolve, i.e. of the checks of the routed servers that
+/// recovered. Recoveries are only known when a `state_file` is configured.
+pub fn resolved(report: &Report, changes: &[Change]) -> Vec<String> {
+    changes
+        .iter()
+        .filter_map(|change| match change {
+            Change::Recovered(key) => Some(state::split_key(key)),
+            _ => None,
+        })
+        .filter(|(server, _)| report.servers.iter().any(|s| s.name == *server))
+        .map(|(server, check)| incident_key(server, check))
+        .collect()
+}
+
+fn incident_key(server: &str, check: &str) -> String {
+    format!("remotesysmonitor/{}/{}", server, check)
+}
+
+/// Triggers a PagerDuty event for every incident of the report and resolves the events of the
+/// checks that recovered.
+///
+/// PagerDuty deduplicates the events by their key, so the incidents of checks that keep failing
+/// are not opened again on every run.
+///
+/// # Errors
+///
+/// Returns an error as soon as one of the events could not be delivered.
+pub fn pagerduty(
+    url: &str,
+    routing_key: &str,
+    report: &Report,
+    changes: &[Change],
+) -> Result<(), Box<dyn std::error::Error>> {
+    for incident in incidents(report) {
+        webhook::post_json(url, &pagerduty_trigger(routing_key, &incident))?;
+    }
+    for key in resolved(report, changes) {
+        let event = json!({
+            "routing_key": routing_key,
+            "event_action": "resolve",
+            "dedup_key": key,
+        });
+        webhook::post_json(url, &event)?;
+    }
+    Ok(())
+}
+
+fn pagerduty_trigger(routing_key: &str, incident: &Incident) -> Value {
+    json!({
+        "routing_key": routing_key,
+        "event_action": "trigger",
+        "dedup_key": incident.key,
+        "payload": {
+            "summary": incident.summary,
+            "source": incident.host,
+            "severity": "critical",
+            "component": incident.check,
+            "custom_details": {"server": incident.server, "details": incident.details},
+        },
+    })
+}
+
+/// Creates an Opsgenie alert for every incident of the report and closes the alerts of the checks
+/// that recovered.
+///
+/// Opsgenie deduplicates the alerts by their alias, so the alerts of checks that keep failing are
+/// not opened again on every run.
+///
+/// # Errors
+///
+/// Returns an error as soon as one of the requests could not be delivered.
+pub fn opsgenie(
+    api_url: &str,
+    api_key: &str,
+    report: &Report,
+    changes: &[Change],
+) -> Result<(), Box<dyn std::error::Error>> {
+    let api_url = api_url.trim_end_matches('/');
+    let authorization = format!("GenieKey {}", api_key);
+    let headers = [("Authorization", authorization.as_str())];
+
+    for incident in incidents(report) {
+        let url = format!("{}/v2/alerts", api_url);
+        webhook::post_json_with_headers(&url, &headers, &opsgenie_alert(&incident))?;
+    }
+    for key in resolved(report, changes) {
+        let url = format!(
+            "{}/v2/alerts/{}/close?identifierType=alias",
+            api_url,
+            urlencode(&key)
+        );
+        let body = json!({"source": "remotesysmonitor", "note": "The check passes again"});
+        webhook::post_json_with_headers(&url, &headers, &body)?;
+    }
+    Ok(())
+}
+
+fn opsgenie_alert(incident: &Incident) -> Value {
+    json!({
+        // Opsgenie truncates longer messages
+        "message": incident.summary.chars().take(130).collect::<String>(),
+        "alias": incident.key,
+        "description": incident.details,
+        "entity": incident.server,
+        "source": "remotesysmonitor",
+        "priority": "P1",
+        "tags": [incident.check],
+    })
+}
+
+/// Percent-encodes the characters of an alias that are not allowed in a URL path segment.
+fn urlencode(text: &str) -> String {
+    text.bytes()
+        .map(|b| match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => {
+                (b as char).to_string()
+            }
+            _ => format!("%{:02X}", b),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::report::{CheckResult, ServerReport};
+
+    fn report() -> Report {
+        let mut server = ServerReport::new("web01", "web01.example.com");
+        server
+            .results
+            .push(CheckResult::new(Status::Critical, "https://a is down").with_check("ping"));
+        server
+            .results
+            .push(CheckResult::new(Status::Critical, "https://b is down").with_check("ping"));
+        server
+            .results
+            .push(CheckResult::new(Status::Warning, "load 30.00").with_check("load"));
+        let mut muted = CheckResult::new(Status::Critical, "85°C").with_check("temperature");
+        muted.muted = true;
+        server.results.push(muted);
+        Report {
+            notes: vec![],
+            servers: vec![server],
+        }
+    }
+
+    #[test]
+    fn test_incidents() {
+        let incidents = incidents(&report());
+        assert_eq!(incidents.len(), 1);
+        assert_eq!(incidents[0].key, "remotesysmonitor/web01/ping");
+        assert_eq!(
+            incidents[0].summary,
+            "ping @ web01: https://a is down; https://b is down"
+        );
+    }
+
+    #[test]
+    fn test_resolved() {
+        let changes = vec![
+            Change::Recovered("web01/load".to_string()),
+            Change::Recovered("db01/load".to_string()),
+            Change::Failed("web01/ping".to_string()),
+        ];
+        assert_eq!(
+            resolved(&report(), &changes),
+            vec!["remotesysmonitor/web01/load"]
+        );
+    }
+
+    #[test]
+    fn test_pagerduty() {
+        let mut server = mockito::Server::new();
+        let trigger = server
+            .mock("POST", "/v2/enqueue")
+            .match_body(mockito::Matcher::PartialJson(json!({
+                "event_action": "trigger",
+                "dedup_key": "remotesysmonitor/web01/ping",
+            })))
+            .with_status(202)
+            .create();
+        let resolve = server
+            .mock("POST", "/v2/enqueue")
+            .match_body(mockito::Matcher::PartialJson(json!({
+                "event_action": "resolve",
+                "dedup_key": "remotesysmonitor/web01/load",
+            })))
+            .with_status(202)
+            .create();
+
+        let url = format!("{}/v2/enqueue", server.url());
+        let changes = vec![Change::Recovered("web01/load".to_string())];
+        pagerduty(&url, "key", &report(), &changes).unwrap();
+        trigger.assert();
+        resolve.assert();
+    }
+
+    #[test]
+    fn test_opsgenie() {
+        let mut server = mockito::Server::new();
+        let create = server
+            .mock("POST", "/v2/alerts")
+            .match_header("authorization", "GenieKey key")
+            .match_body(mockito::Matcher::PartialJson(json!({
+                "alias": "remotesysmonitor/web01/ping",
+                "priority": "P1",
+            })))
+            .with_status(202)
+            .create();
+        let close = server
+            .mock("POST", "/v2/alerts/remotesysmonitor%2Fweb01%2Fload/close")
+            .match_query(mockito::Matcher::UrlEncoded(
+                "identifierType".to_string(),
+                "alias".to_string(),
+            ))
+            .with_status(202)
+            .create();
+
+        let changes = vec![Change::Recovered("web01/load".to_string())];
+        opsgenie(&server.url(), "key", &report(), &changes).unwrap();
+        create.assert();
+        close.assert();
+    }
+}
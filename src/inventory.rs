@@ -0,0 +1,744 @@
+use crate::ansible;
+use crate::config::Server;
+use crate::notify::secret;
+use chrono::Utc;
+use hmac_sha256::{Hash, HMAC};
+use log::{error, info, warn};
+use serde::{Deserialize, Serialize};
+use serde_json::Value as Json;
+use serde_yaml::{Mapping, Value};
+use std::collections::BTreeMap;
+use std::time::Duration;
+
+/// Timeout of the requests to the APIs of the providers.
+const TIMEOUT: Duration = Duration::from_secs(30);
+/// Version of the EC2 query API.
+const EC2_VERSION: &str = "2016-11-15";
+
+/// A source of servers enumerated at every run from the API of a cloud provider or from an Ansible
+/// inventory, rather than listed in `servers`, e.g. for autoscaled instances.
+///
+/// Every instance found becomes a server named after it, with the settings of `server`, which
+/// are those of an entry of `servers` without `name` and `host` (including `template`). A server
+/// of `servers` with the same name takes precedence. When a provider cannot be reached, the
+/// servers it returned last are kept.
+///
+/// ```yaml
+/// inventories:
+///   - provider: aws
+///     region: eu-west-1
+///     tags: {Role: web} # credentials from AWS_ACCESS_KEY_ID and AWS_SECRET_ACCESS_KEY
+///     address: private
+///     server:
+///       user: ec2-user
+///       template: web
+///   - provider: hetzner
+///     label_selector: env=prod
+///     token_env: HCLOUD_TOKEN
+///     server:
+///       checks:
+///         load: {interval: 5, critical: 8}
+///   - provider: digitalocean
+///     tag: web
+///     token_env: DIGITALOCEAN_TOKEN
+///   - provider: ansible
+///     path: /etc/ansible/hosts
+///     group: webservers
+/// ```
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Inventory {
+    /// Provider enumerating the servers, and how to query it.
+    #[serde(flatten)]
+    pub provider: Provider,
+    /// Address of the instances to connect to, `public` by default.
+    #[serde(default)]
+    pub address: Address,
+    /// Settings of every server found, as those of an entry of `servers` without `name` and
+    /// `host`.
+    #[serde(default)]
+    pub server: Mapping,
+}
+
+/// A cloud provider or an Ansible inventory, with the filter selecting the instances to monitor.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(tag = "provider", rename_all = "snake_case")]
+pub enum Provider {
+    /// Running AWS EC2 instances, with credentials from the `AWS_ACCESS_KEY_ID`,
+    /// `AWS_SECRET_ACCESS_KEY` and optionally `AWS_SESSION_TOKEN` environment variables. They are
+    /// named after their `Name` tag, or their ID without one.
+    Aws {
+        /// Region of the instances, e.g. `eu-west-1`.
+        region: String,
+        /// Tags the instances must have, by key.
+        #[serde(default)]
+        tags: BTreeMap<String, String>,
+        /// URL of the EC2 API, `https://ec2.<region>.amazonaws.com` by default.
+        endpoint: Option<String>,
+    },
+    /// Hetzner Cloud servers.
+    Hetzner {
+        /// Label selector of the servers, e.g. `env=prod,role in (web,api)`.
+        label_selector: Option<String>,
+        /// API token of the project.
+        token: Option<String>,
+        /// Environment variable holding the API token.
+        token_env: Option<String>,
+        /// URL of the API, `https://api.hetzner.cloud/v1` by default.
+        endpoint: Option<String>,
+    },
+    /// DigitalOcean droplets.
+    Digitalocean {
+        /// Tag of the droplets.
+        tag: Option<String>,
+        /// API token.
+        token: Option<String>,
+        /// Environment variable holding the API token.
+        token_env: Option<String>,
+        /// URL of the API, `https://api.digitalocean.com/v2` by default.
+        endpoint: Option<String>,
+    },
+    /// The hosts of an Ansible inventory (INI or YAML), read again at every run, with their
+    /// `ansible_host`, `ansible_user`, `ansible_port` and `ansible_ssh_private_key_file`
+    /// overriding the settings of `server`. `address` does not apply.
+    Ansible {
+        /// Path of the inventory.
+        path: String,
+        /// Group of the hosts, and of its children, every host if omitted.
+        group: Option<String>,
+    },
+}
+
+/// Address of the instances to connect to.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Address {
+    /// Public IPv4 address, for a monitor outside of the cloud.
+    #[default]
+    Public,
+    /// Private IPv4 address, for a monitor in the same network.
+    Private,
+}
+
+/// An instance found by an inventory.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Host {
+    /// Name of the instance, the name of its server.
+    pub name: String,
+    /// Public IPv4 address of the instance, if any.
+    pub public: Option<String>,
+    /// Private IPv4 address of the instance, if any.
+    pub private: Option<String>,
+    /// Settings of its server given by the source, overriding those of `server`, e.g. the user of
+    /// an Ansible host.
+    pub settings: Mapping,
+}
+
+impl Inventory {
+    /// Returns the name of the provider, for logging.
+    pub fn kind(&self) -> &'static str {
+        match self.provider {
+            Provider::Aws { .. } => "aws",
+            Provider::Hetzner { .. } => "hetzner",
+            Provider::Digitalocean { .. } => "digitalocean",
+            Provider::Ansible { .. } => "ansible",
+        }
+    }
+
+    /// Checks that the credentials of the provider are available without querying it, or that
+    /// the Ansible inventory can be read.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error naming the missing setting or environment variable, or why the inventory
+    /// cannot be read.
+    pub fn check_access(&self) -> Result<(), Box<dyn std::error::Error>> {
+        match &self.provider {
+            Provider::Aws { .. } => AwsCredentials::from_env().map(|_| ()),
+            Provider::Hetzner {
+                token, token_env, ..
+            }
+            | Provider::Digitalocean {
+                token, token_env, ..
+            } => secret(token.as_deref(), token_env.as_deref(), "token").map(|_| ()),
+            Provider::Ansible { path, group } => ansible::load(path, group.as_slice()).map(|_| ()),
+        }
+    }
+
+    /// Enumerates the instances of the provider matching the filter.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the credentials are missing, or if the API cannot be reached or
+    /// answers with an error.
+    pub fn hosts(&self) -> Result<Vec<Host>, Box<dyn std::error::Error>> {
+        let client = reqwest::blocking::Client::builder()
+            .timeout(TIMEOUT)
+            .build()?;
+        match &self.provider {
+            Provider::Aws {
+                region,
+                tags,
+                endpoint,
+            } => {
+                let credentials = AwsCredentials::from_env()?;
+                let endpoint = endpoint
+                    .clone()
+                    .unwrap_or_else(|| format!("https://ec2.{}.amazonaws.com", region));
+                aws_hosts(&client, &endpoint, region, tags, &credentials)
+            }
+            Provider::Hetzner {
+                label_selector,
+                token,
+                token_env,
+                endpoint,
+            } => {
+                let token = secret(token.as_deref(), token_env.as_deref(), "token")?;
+                let endpoint = endpoint
+                    .as_deref()
+                    .unwrap_or("https://api.hetzner.cloud/v1");
+                hetzner_hosts(&client, endpoint, label_selector.as_deref(), &token)
+            }
+            Provider::Digitalocean {
+                tag,
+                token,
+                token_env,
+                endpoint,
+            } => {
+                let token = secret(token.as_deref(), token_env.as_deref(), "token")?;
+                let endpoint = endpoint
+                    .as_deref()
+                    .unwrap_or("https://api.digitalocean.com/v2");
+                digitalocean_hosts(&client, endpoint, tag.as_deref(), &token)
+            }
+            Provider::Ansible { path, group } => Ok(ansible::load(path, group.as_slice())?
+                .into_iter()
+                .map(|host| {
+                    let settings = host.settings();
+                    let address = settings
+                        .get("host")
+                        .and_then(Value::as_str)
+                        .map(str::to_string);
+                    Host {
+                        name: host.name,
+                        public: address.clone(),
+                        private: address,
+                        settings,
+                    }
+                })
+                .collect()),
+        }
+    }
+
+    /// Returns the server monitoring an instance, or `None` if it lacks the configured address.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the settings of `server` are invalid.
+    pub fn server(&self, host: &Host) -> Result<Option<Server>, Box<dyn std::error::Error>> {
+        let address = match (&self.provider, self.address) {
+            (Provider::Ansible { .. }, _) | (_, Address::Public) => &host.public,
+            (_, Address::Private) => &host.private,
+        };
+        let Some(address) = address else {
+            return Ok(None);
+        };
+        let mut server = self.server.clone();
+        server.insert("name".into(), host.name.clone().into());
+        server.insert("host".into(), address.clone().into());
+        server.extend(host.settings.clone());
+        Ok(Some(serde_yaml::from_value(Value::Mapping(server))?))
+    }
+}
+
+/// The servers found by the inventories of a configuration, added to its `servers` at every run.
+#[derive(Debug, Default)]
+pub struct Discovery {
+    /// Instances last found by every inventory, by position.
+    hosts: Vec<Option<Vec<Host>>>,
+    /// Names of the servers added to the configuration.
+    added: Vec<String>,
+}
+
+impl Discovery {
+    /// Enumerates the instances of the `inventories` of a configuration again, replacing the
+    /// servers added to its `servers` by the previous call with the servers found. Returns whether
+    /// they changed.
+    ///
+    /// The errors are logged, the instances found previously by an inventory that fails being
+    /// kept.
+    pub fn refresh(&mut self, inventories: &[Inventory], servers: &mut Vec<Server>) -> bool {
+        if inventories.is_empty() {
+            return false;
+        }
+        self.hosts.resize(inventories.len(), None);
+        for (inventory, hosts) in inventories.iter().zip(self.hosts.iter_mut()) {
+            match inventory.hosts() {
+                Ok(found) => *hosts = Some(found),
+                Err(e) => error!(
+                    "Could not enumerate the servers of inventory `{}`, keeping the {} found previously: {}",
+                    inventory.kind(),
+                    hosts.as_ref().map_or(0, Vec::len),
+                    e
+                ),
+            }
+        }
+
+        servers.retain(|s| !self.added.contains(&s.name));
+        let mut added = vec![];
+        for (inventory, hosts) in inventories.iter().zip(&self.hosts) {
+            for host in hosts.iter().flatten() {
+                if servers.iter().any(|s| s.name == host.name) {
+                    continue;
+                }
+                match inventory.server(host) {
+                    Ok(Some(server)) => {
+                        added.push(server.name.clone());
+                        servers.push(server);
+                    }
+                    Ok(None) => warn!(
+                        "Server {} of inventory `{}` has no {:?} address, skipping it",
+                        host.name,
+                        inventory.kind(),
+                        inventory.address
+                    ),
+                    Err(e) => error!(
+                        "Invalid settings of the servers of inventory `{}`: {}",
+                        inventory.kind(),
+                        e
+                    ),
+                }
+            }
+        }
+        let changed = added != self.added;
+        if changed {
+            info!("Found {} server(s) in the inventories", added.len());
+        }
+        self.added = added;
+        changed
+    }
+}
+
+/// Credentials of the AWS API.
+struct AwsCredentials {
+    access_key_id: String,
+    secret_access_key: String,
+    session_token: Option<String>,
+}
+
+impl AwsCredentials {
+    fn from_env() -> Result<AwsCredentials, Box<dyn std::error::Error>> {
+        let var = |name: &str| {
+            std::env::var(name).map_err(|_| format!("environment variable {} is not set", name))
+        };
+        Ok(AwsCredentials {
+            access_key_id: var("AWS_ACCESS_KEY_ID")?,
+            secret_access_key: var("AWS_SECRET_ACCESS_KEY")?,
+            session_token: std::env::var("AWS_SESSION_TOKEN").ok(),
+        })
+    }
+}
+
+/// Enumerates the running EC2 instances with the given tags, following the pages of results.
+fn aws_hosts(
+    client: &reqwest::blocking::Client,
+    endpoint: &str,
+    region: &str,
+    tags: &BTreeMap<String, String>,
+    credentials: &AwsCredentials,
+) -> Result<Vec<Host>, Box<dyn std::error::Error>> {
+    let mut params = vec![
+        ("Action".to_string(), "DescribeInstances".to_string()),
+        ("Version".to_string(), EC2_VERSION.to_string()),
+        (
+            "Filter.1.Name".to_string(),
+            "instance-state-name".to_string(),
+        ),
+        ("Filter.1.Value.1".to_string(), "running".to_string()),
+    ];
+    for (i, (key, value)) in tags.iter().enumerate() {
+        params.push((format!("Filter.{}.Name", i + 2), format!("tag:{}", key)));
+        params.push((format!("Filter.{}.Value.1", i + 2), value.clone()));
+    }
+    let url = reqwest::Url::parse(endpoint)?;
+    let host = match url.port() {
+        Some(port) => format!("{}:{}", url.host_str().unwrap_or_default(), port),
+        None => url.host_str().unwrap_or_default().to_string(),
+    };
+
+    let mut hosts = vec![];
+    let mut next_token: Option<String> = None;
+    loop {
+        let mut params = params.clone();
+        if let Some(token) = next_token.take() {
+            params.push(("NextToken".to_string(), token));
+        }
+        params.sort();
+        let query = params
+            .iter()
+            .map(|(k, v)| format!("{}={}", aws_encode(k), aws_encode(v)))
+            .collect::<Vec<_>>()
+            .join("&");
+        let time = Utc::now().format("%Y%m%dT%H%M%SZ").to_string();
+        let mut request = client
+            .get(format!("{}/?{}", endpoint.trim_end_matches('/'), query))
+            .header("x-amz-date", &time)
+            .header(
+                "authorization",
+                aws_authorization(&host, &query, &time, region, credentials),
+            );
+        if let Some(token) = &credentials.session_token {
+            request = request.header("x-amz-security-token", token);
+        }
+        let response = request.send()?;
+        let status = response.status();
+        let body = response.text()?;
+        if !status.is_success() {
+            return Err(format!("EC2 answered `{}`: {}", status, body).into());
+        }
+        let (page, token) = parse_ec2_instances(&body)?;
+        hosts.extend(page);
+        match token {
+            Some(token) => next_token = Some(token),
+            None => return Ok(hosts),
+        }
+    }
+}
+
+/// Percent-encodes a value as required by the signature of the AWS requests, every byte but the
+/// unreserved characters being encoded.
+fn aws_encode(value: &str) -> String {
+    value
+        .bytes()
+        .map(|b| match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => {
+                (b as char).to_string()
+            }
+            _ => format!("%{:02X}", b),
+        })
+        .collect()
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// Derives the key signing the requests of a day (`YYYYMMDD`) to a service of AWS.
+fn aws_signing_key(secret_access_key: &str, date: &str, region: &str, service: &str) -> [u8; 32] {
+    let key = HMAC::mac(date, format!("AWS4{}", secret_access_key));
+    let key = HMAC::mac(region, key);
+    let key = HMAC::mac(service, key);
+    HMAC::mac("aws4_request", key)
+}
+
+/// Returns the `Authorization` header of a GET request to the EC2 API, signed with Signature
+/// Version 4 over its host, its `x-amz-date` (`time`) and its session token if any.
+fn aws_authorization(
+    host: &str,
+    query: &str,
+    time: &str,
+    region: &str,
+    credentials: &AwsCredentials,
+) -> String {
+    let date = &time[..8];
+    let mut headers = vec![("host", host), ("x-amz-date", time)];
+    if let Some(token) = &credentials.session_token {
+        headers.push(("x-amz-security-token", token));
+    }
+    let signed_headers = headers
+        .iter()
+        .map(|(name, _)| *name)
+        .collect::<Vec<_>>()
+        .join(";");
+    let canonical_headers: String = headers
+        .iter()
+        .map(|(name, value)| format!("{}:{}\n", name, value))
+        .collect();
+    let canonical_request = format!(
+        "GET\n/\n{}\n{}\n{}\n{}",
+        query,
+        canonical_headers,
+        signed_headers,
+        hex(&Hash::hash(b""))
+    );
+    let scope = format!("{}/{}/ec2/aws4_request", date, region);
+    let string_to_sign = format!(
+        "AWS4-HMAC-SHA256\n{}\n{}\n{}",
+        time,
+        scope,
+        hex(&Hash::hash(canonical_request.as_bytes()))
+    );
+    let key = aws_signing_key(&credentials.secret_access_key, date, region, "ec2");
+    format!(
+        "AWS4-HMAC-SHA256 Credential={}/{}, SignedHeaders={}, Signature={}",
+        credentials.access_key_id,
+        scope,
+        signed_headers,
+        hex(&HMAC::mac(string_to_sign, key))
+    )
+}
+
+/// Parses a page of the response of `DescribeInstances`, returning its instances and the token
+/// of the next page.
+fn parse_ec2_instances(
+    body: &str,
+) -> Result<(Vec<Host>, Option<String>), Box<dyn std::error::Error>> {
+    let document = roxmltree::Document::parse(body)?;
+    let child = |node: roxmltree::Node, name: &str| {
+        node.children()
+            .find(|n| n.has_tag_name(name))
+            .and_then(|n| n.text())
+            .map(str::to_string)
+    };
+    let mut hosts = vec![];
+    for set in document
+        .descendants()
+        .filter(|n| n.has_tag_name("instancesSet"))
+    {
+        for instance in set.children().filter(|n| n.has_tag_name("item")) {
+            let id = child(instance, "instanceId").unwrap_or_default();
+            let name = instance
+                .children()
+                .find(|n| n.has_tag_name("tagSet"))
+                .into_iter()
+                .flat_map(|tags| tags.children())
+                .find(|tag| child(*tag, "key").as_deref() == Some("Name"))
+                .and_then(|tag| child(tag, "value"));
+            hosts.push(Host {
+                name: name.unwrap_or(id),
+                public: child(instance, "ipAddress"),
+                private: child(instance, "privateIpAddress"),
+                settings: Mapping::new(),
+            });
+        }
+    }
+    let next_token = child(document.root_element(), "nextToken");
+    Ok((hosts, next_token))
+}
+
+/// Sends an authenticated GET request to a JSON API, returning the parsed response.
+fn get_json(
+    client: &reqwest::blocking::Client,
+    url: &str,
+    token: &str,
+) -> Result<Json, Box<dyn std::error::Error>> {
+    let response = client.get(url).bearer_auth(token).send()?;
+    let status = response.status();
+    let body = response.text()?;
+    if !status.is_success() {
+        return Err(format!("API answered `{}`: {}", status, body).into());
+    }
+    Ok(serde_json::from_str(&body)?)
+}
+
+/// Enumerates the Hetzner Cloud servers matching a label selector, following the pages of
+/// results.
+fn hetzner_hosts(
+    client: &reqwest::blocking::Client,
+    endpoint: &str,
+    label_selector: Option<&str>,
+    token: &str,
+) -> Result<Vec<Host>, Box<dyn std::error::Error>> {
+    let mut hosts = vec![];
+    let mut page = 1;
+    loop {
+        let mut url = reqwest::Url::parse(&format!("{}/servers", endpoint.trim_end_matches('/')))?;
+        url.query_pairs_mut()
+            .append_pair("per_page", "50")
+            .append_pair("page", &page.to_string());
+        if let Some(selector) = label_selector {
+            url.query_pairs_mut()
+                .append_pair("label_selector", selector);
+        }
+        let response = get_json(client, url.as_str(), token)?;
+        for server in response["servers"].as_array().into_iter().flatten() {
+            hosts.push(Host {
+                name: server["name"].as_str().unwrap_or_default().to_string(),
+                public: server["public_net"]["ipv4"]["ip"]
+                    .as_str()
+                    .map(str::to_string),
+                private: server["private_net"][0]["ip"].as_str().map(str::to_string),
+                settings: Mapping::new(),
+            });
+        }
+        match response["meta"]["pagination"]["next_page"].as_u64() {
+            Some(next) => page = next,
+            None => return Ok(hosts),
+        }
+    }
+}
+
+/// Enumerates the DigitalOcean droplets with a tag, following the pages of results.
+fn digitalocean_hosts(
+    client: &reqwest::blocking::Client,
+    endpoint: &str,
+    tag: Option<&str>,
+    token: &str,
+) -> Result<Vec<Host>, Box<dyn std::error::Error>> {
+    let mut url = reqwest::Url::parse(&format!("{}/droplets", endpoint.trim_end_matches('/')))?;
+    url.query_pairs_mut().append_pair("per_page", "200");
+    if let Some(tag) = tag {
+        url.query_pairs_mut().append_pair("tag_name", tag);
+    }
+    let mut hosts = vec![];
+    let mut url = url.to_string();
+    loop {
+        let response = get_json(client, &url, token)?;
+        for droplet in response["droplets"].as_array().into_iter().flatten() {
+            let address = |kind: &str| {
+                droplet["networks"]["v4"]
+                    .as_array()
+                    .into_iter()
+                    .flatten()
+                    .find(|network| network["type"] == kind)
+                    .and_then(|network| network["ip_address"].as_str())
+                    .map(str::to_string)
+            };
+            hosts.push(Host {
+                name: droplet["name"].as_str().unwrap_or_default().to_string(),
+                public: address("public"),
+                private: address("private"),
+                settings: Mapping::new(),
+            });
+        }
+        match response["links"]["pages"]["next"].as_str() {
+            Some(next) => url = next.to_string(),
+            None => return Ok(hosts),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+
+    #[test]
+    fn test_aws_signature() {
+        // Example of the documentation of Signature Version 4
+        let key = aws_signing_key(
+            "wJalrXUtnFEMI/K7MDENG+bPxRfiCYEXAMPLEKEY",
+            "20120215",
+            "us-east-1",
+            "iam",
+        );
+        assert_eq!(
+            hex(&key),
+            "f4780e2d9f65fa895f9c67b32ce1baf0b0d8a43505a000a1a9e090d414db404d"
+        );
+        assert_eq!(aws_encode("tag:Role=a b~"), "tag%3ARole%3Da%20b~");
+    }
+
+    #[test]
+    fn test_parse_ec2_instances() {
+        let body = r#"<DescribeInstancesResponse xmlns="http://ec2.amazonaws.com/doc/2016-11-15/">
+            <reservationSet><item><instancesSet>
+                <item>
+                    <instanceId>i-0abc</instanceId>
+                    <privateIpAddress>10.0.0.5</privateIpAddress>
+                    <ipAddress>203.0.113.5</ipAddress>
+                    <networkInterfaceSet><item><privateIpAddress>10.0.0.9</privateIpAddress></item></networkInterfaceSet>
+                    <tagSet><item><key>Role</key><value>web</value></item><item><key>Name</key><value>web01</value></item></tagSet>
+                </item>
+                <item><instanceId>i-0def</instanceId><privateIpAddress>10.0.0.6</privateIpAddress></item>
+            </instancesSet></item></reservationSet>
+            <nextToken>page2</nextToken>
+        </DescribeInstancesResponse>"#;
+        let (hosts, next_token) = parse_ec2_instances(body).unwrap();
+        assert_eq!(
+            hosts,
+            vec![
+                Host {
+                    name: "web01".to_string(),
+                    public: Some("203.0.113.5".to_string()),
+                    private: Some("10.0.0.5".to_string()),
+                    settings: Mapping::new(),
+                },
+                Host {
+                    name: "i-0def".to_string(),
+                    public: None,
+                    private: Some("10.0.0.6".to_string()),
+                    settings: Mapping::new(),
+                },
+            ]
+        );
+        assert_eq!(next_token.as_deref(), Some("page2"));
+    }
+
+    #[test]
+    fn test_discovery() {
+        let mut api = mockito::Server::new();
+        let first = api
+            .mock("GET", "/servers?per_page=50&page=1&label_selector=env%3Dprod")
+            .match_header("authorization", "Bearer secret")
+            .with_body(
+                r#"{"servers": [{"name": "web01", "public_net": {"ipv4": {"ip": "203.0.113.1"}}, "private_net": []},
+                                {"name": "static", "public_net": {"ipv4": {"ip": "203.0.113.2"}}, "private_net": []}],
+                    "meta": {"pagination": {"next_page": 2}}}"#,
+            )
+            .create();
+        let second = api
+            .mock("GET", "/servers?per_page=50&page=2&label_selector=env%3Dprod")
+            .with_body(
+                r#"{"servers": [{"name": "db01", "public_net": {"ipv4": null}, "private_net": [{"ip": "10.0.0.3"}]}],
+                    "meta": {"pagination": {"next_page": null}}}"#,
+            )
+            .create();
+        let mut config: Config = serde_yaml::from_str(&format!(
+            "servers: [{{name: static, host: localhost, local: true}}]\n\
+             inventories:\n\
+             - {{provider: hetzner, label_selector: env=prod, token: secret, endpoint: '{}',\n\
+                server: {{user: root, checks: {{load: {{interval: 5}}}}}}}}",
+            api.url()
+        ))
+        .unwrap();
+
+        let inventories = config.inventories.as_deref().unwrap();
+        let mut discovery = Discovery::default();
+        assert!(discovery.refresh(inventories, &mut config.servers));
+        first.assert();
+        second.assert();
+        let names: Vec<_> = config.servers.iter().map(|s| s.name.as_str()).collect();
+        // db01 has no public address, and the static server takes precedence
+        assert_eq!(names, ["static", "web01"]);
+        assert_eq!(config.servers[0].host.first(), "localhost");
+        assert_eq!(config.servers[1].host.first(), "203.0.113.1");
+        assert_eq!(config.servers[1].user.as_deref(), Some("root"));
+        assert!(config.servers[1]
+            .checks
+            .as_ref()
+            .unwrap()
+            .contains_key("load"));
+
+        // The servers found previously are kept when the API fails
+        api.reset();
+        api.mock("GET", mockito::Matcher::Any)
+            .with_status(500)
+            .create();
+        assert!(!discovery.refresh(inventories, &mut config.servers));
+        assert_eq!(config.servers.len(), 2);
+    }
+
+    #[test]
+    fn test_digitalocean() {
+        let mut api = mockito::Server::new();
+        let next = format!("{}/droplets?page=2", api.url());
+        api.mock("GET", "/droplets?per_page=200&tag_name=web")
+            .with_body(format!(
+                r#"{{"droplets": [{{"name": "web01", "networks": {{"v4": [
+                    {{"ip_address": "10.0.0.1", "type": "private"}},
+                    {{"ip_address": "203.0.113.1", "type": "public"}}]}}}}],
+                    "links": {{"pages": {{"next": "{}"}}}}}}"#,
+                next
+            ))
+            .create();
+        api.mock("GET", "/droplets?page=2")
+            .with_body(r#"{"droplets": [{"name": "web02", "networks": {"v4": []}}], "links": {}}"#)
+            .create();
+        let client = reqwest::blocking::Client::new();
+        let hosts = digitalocean_hosts(&client, &api.url(), Some("web"), "secret").unwrap();
+        assert_eq!(hosts.len(), 2);
+        assert_eq!(hosts[0].public.as_deref(), Some("203.0.113.1"));
+        assert_eq!(hosts[0].private.as_deref(), Some("10.0.0.1"));
+        assert_eq!(hosts[1].public, None);
+    }
+}
@@ -5,7 +5,7 @@
 //! ## Usage
 //!
 //! To utilize `RemoteSysMonitor`, follow these steps:
-//! 1. Prepare a `config.yaml` file according to your monitoring requirements, detailing the servers to be monitored along with the specific checks for each.
+//! 1. Prepare a `config.yaml` file according to your monitoring requirements, detailing the servers to be monitored along with the specific checks for each. `remotesysmonitor init` writes a commented example covering every check.
 //! 2. Set the `SLACK_HOOK_URL` environment variable to your Slack webhook URL to enable Slack notifications.
 //! 3. Launch the application, providing the path to your configuration file as the argument.
 //!
@@ -28,42 +28,194 @@
 //! servers:
 //!   - name: "Server 1"
 //!     host: "192.168.1.1"
+//!     port: 22
 //!     user: "user"
 //!     private_key: "/path/to/private/key"
 //!     checks:
-//!       ping: ["example.com", "google.com"]
-//!       load: 5
-//!       temperature: "/sys/class/thermal/thermal_zone0/temp"
-//!       custom_command: "custom_script.sh"
+//!       ping:
+//!         url: ["/", "/health"]
+//!       load:
+//!         interval: 5
+//!       temperature:
+//!         sensor: "/sys/class/thermal/thermal_zone0/temp"
+//!       custom_command:
+//!         command: "custom_script.sh"
 //! ```
 //!
+//! See `conf/conf.example.yaml`, also written by `remotesysmonitor init`, for every available check.
+//!
 //! ## Contributing to RemoteSysMonitor
 //!
 //! Contributions to `RemoteSysMonitor` are highly encouraged and appreciated. Whether it's through submitting pull requests with code enhancements, bug fixes, or feature additions, or by reporting issues and suggesting improvements, your input helps make `RemoteSysMonitor` better for everyone.
 //!
 //! Please feel free to submit pull requests or open issues on the project's GitHub repository for any bugs you encounter or enhancements you believe are worth adding.
 
+pub mod ansible;
 pub mod checks;
 pub mod config;
+pub mod dashboard;
+pub mod digest;
+pub mod export;
+pub mod heartbeat;
+pub mod history;
+pub mod incidents;
+pub mod inventory;
+pub mod maintenance;
+pub mod monitor;
+pub mod nagios;
+pub mod notify;
+pub mod otlp;
+pub mod platform;
+pub mod policy;
+pub mod pushover;
+pub mod reload;
+pub mod report;
+pub mod runner;
+pub mod schedule;
+pub mod shutdown;
 pub mod slack;
 pub mod ssh;
+pub mod ssh_config;
+pub mod state;
+pub mod systemd;
+pub mod teams;
+pub mod template;
 pub mod utils;
-use crate::config::Check;
-use clap::Parser;
-use log::info;
+pub mod validate;
+pub mod webhook;
+use crate::inventory::Discovery;
+use crate::notify::Notifier;
+use crate::report::{Markers, Status};
+use crate::schedule::Scheduler;
+use crate::slack::SlackConfig;
+use crate::ssh::SessionPool;
+use crate::ssh_config::SshConfig;
+use crate::state::State;
+use clap::{Parser, Subcommand};
+use log::{error, info, warn, LevelFilter};
+use sd_notify::NotifyState;
+
+use std::env;
+use std::time::{Duration, Instant};
 
-use std::{env, vec};
+/// Exit code when every check passed.
+const EXIT_OK: i32 = 0;
+/// Exit code when a check reported a ⚠️ (warning) but none a ❌.
+const EXIT_WARNING: i32 = 1;
+/// Exit code when a check reported a ❌ (critical).
+const EXIT_CRITICAL: i32 = 2;
+/// Exit code when the configuration is invalid, a server could not be connected to or the report
+/// could not be sent.
+const EXIT_ERROR: i32 = 3;
+
+/// Checks of the servers imported from `~/.ssh/config` without a template.
+const DEFAULT_CHECKS: &str = "load: {interval: 5, warning: 4, critical: 8}";
 
 #[derive(Parser)]
-#[command(author, version, about)]
+#[command(
+    author,
+    version,
+    about,
+    args_conflicts_with_subcommands = true,
+    subcommand_negates_reqs = true
+)]
 struct Args {
-    config: String,
+    /// Path to the configuration file
+    #[arg(required = true)]
+    config: Option<String>,
     #[clap(short, long)]
-    /// Post a check to Slack even if there is no ❌ in the checks
+    /// Send the report to every notifier even if there is no ❌ (critical) in the checks
     full: bool,
     #[clap(short, long)]
     /// Print the output of the checks in stdout
     print: bool,
+    #[clap(short, long)]
+    /// Only check the servers of this group, and only the checks of this group (can be repeated)
+    group: Vec<String>,
+    #[clap(short, long)]
+    /// Keep running, checking the servers every `interval` seconds of the general settings
+    /// (300 by default) over SSH sessions kept open between runs
+    daemon: bool,
+    #[clap(long, requires = "daemon")]
+    /// Write the PID of the daemon to this file, removed when it exits
+    pid_file: Option<String>,
+    #[clap(long, conflicts_with = "daemon")]
+    /// List the commands that would run on every server and the notifiers that would be used,
+    /// without connecting to the servers
+    dry_run: bool,
+    #[clap(long)]
+    /// Refuse to run the checks whose command breaks the `command_policy` of the configuration,
+    /// or all of them if it has none
+    read_only: bool,
+    #[clap(long, conflicts_with_all = ["daemon", "dry_run", "full"])]
+    /// Behave as a Nagios plugin: print a status line with performance data instead of notifying,
+    /// and exit with the status of the checks
+    nagios: bool,
+    #[clap(short, long, action = clap::ArgAction::Count, global = true)]
+    /// Log more details: -v for the progress of the runs, -vv for every connection and command
+    verbose: u8,
+    #[clap(short, long, conflicts_with = "verbose", global = true)]
+    /// Only log errors
+    quiet: bool,
+    #[command(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Check the configuration file without connecting to the servers
+    Validate {
+        /// Path to the configuration file
+        config: String,
+    },
+    /// Send a summary of the results recorded in the `history_file` to the notifiers, e.g. every
+    /// morning from cron
+    Digest {
+        /// Path to the configuration file
+        config: String,
+        /// Period to summarize, e.g. `24h`, `1d` or `1w`
+        #[clap(long, default_value = "24h")]
+        period: String,
+        /// Print the digest in stdout instead of sending it
+        #[clap(short, long)]
+        print: bool,
+    },
+    /// Convert the hosts of an Ansible inventory (INI or YAML) into servers, printed in stdout,
+    /// e.g. to be included in the configuration
+    ImportInventory {
+        /// Path to the Ansible inventory
+        inventory: String,
+        /// Only import the hosts of this group and of its children (can be repeated)
+        #[clap(short, long)]
+        group: Vec<String>,
+        /// Template of the imported servers (can be repeated)
+        #[clap(short, long)]
+        template: Vec<String>,
+    },
+    /// Convert the hosts of the `Host` blocks of `~/.ssh/config` into servers using it, printed in
+    /// stdout, e.g. to be included in the configuration
+    ImportSshConfig {
+        /// Only import the hosts matching this pattern, with `*` and `?` wildcards (can be
+        /// repeated)
+        #[clap(short, long)]
+        include: Vec<String>,
+        /// Skip the hosts matching this pattern (can be repeated)
+        #[clap(short, long)]
+        exclude: Vec<String>,
+        /// Template of the imported servers (can be repeated), instead of the default `load`
+        /// check
+        #[clap(short, long)]
+        template: Vec<String>,
+    },
+    /// Write a commented example configuration covering every check and notifier
+    Init {
+        /// Path of the configuration file to create
+        #[arg(default_value = "config.yaml")]
+        config: String,
+        /// Overwrite the file if it already exists
+        #[clap(long)]
+        force: bool,
+    },
 }
 
 /// Entry point of the monitoring application.
@@ -71,10 +223,15 @@ struct Args {
 /// This function performs the following steps:
 /// 1. Reads the configuration file path from the command line arguments.
 /// 2. Loads the configuration from the specified path.
-/// 3. Retrieves the Slack webhook URL from an environment variable.
+/// 3. Sets up the notifiers, or retrieves the Slack webhook URL from an environment variable if
+///    none are configured.
 /// 4. Iterates over each server defined in the configuration, creating SSH sessions and executing specified checks.
 /// 5. Collects the results of all checks into a payload.
-/// 6. Posts the payload to a Slack channel using the webhook URL.
+/// 6. Sends the results routed to every notifier whose minimum status is reached.
+/// 7. With `--daemon`, repeats steps 4 to 6 every `interval` seconds, reusing the SSH sessions.
+///
+/// With `--dry-run`, the commands that would run on every server and the notifiers that would be
+/// used are printed instead, without connecting to the servers.
 ///
 /// # Command Line Arguments
 ///
@@ -82,7 +239,8 @@ struct Args {
 ///
 /// # Environment Variables
 ///
-/// - `SLACK_HOOK_URL`: The webhook URL for posting messages to Slack. This must be set before running the application.
+/// - `SLACK_HOOK_URL`: The webhook URL for posting messages to Slack. This must be set before running the application,
+///   unless `notifiers` are configured.
 ///
 /// # Errors
 ///
@@ -95,9 +253,15 @@ struct Args {
 ///
 /// # Exit Codes
 ///
-/// The application exits with code 1 if:
-/// - The configuration file path is not provided.
-/// - The `SLACK_HOOK_URL` environment variable is not set.
+/// After a single run, the application exits with code:
+/// - 0 if every check passed (muted results are ignored),
+/// - 1 if a check reported a ⚠️ (warning) but none a ❌,
+/// - 2 if a check reported a ❌ (critical),
+/// - 3 if the configuration could not be loaded (or `validate` found problems), the
+///   `SLACK_HOOK_URL` environment variable is not set and no `notifiers` are configured, a server
+///   could not be connected to, or the report could not be sent to a notifier (in which case it
+///   is printed to stdout instead). In daemon mode, only configuration errors stop the
+///   application.
 ///
 /// # Examples
 ///
@@ -116,92 +280,511 @@ struct Args {
 /// The function aggregates all check results into a single message payload, which is then posted to Slack.
 /// It sorts checks for each server alphabetically by their names before execution, ensuring a consistent
 /// order in the Slack message. Each check's result is separated by new lines in the final Slack message.
-fn main() -> Result<(), Box<dyn std::error::Error>> {
-    env_logger::init();
-
+fn main() {
     let cli = Args::parse();
+    init_logger(cli.verbose, cli.quiet);
 
-    info!("Loading configuration from {}", cli.config.as_str());
-    let config = config::load_config(cli.config.as_str())?;
-
-    let slack_hook_url = match env::var("SLACK_HOOK_URL") {
-        Ok(url) => url,
-        Err(_) => {
-            eprintln!("SLACK_HOOK_URL environment variable not set");
-            std::process::exit(1);
+    let nagios = cli.nagios;
+    let code = match run(cli) {
+        Ok(code) => code,
+        Err(e) => {
+            error!("{}", e);
+            if nagios {
+                println!("UNKNOWN - {}", e);
+            }
+            EXIT_ERROR
         }
     };
+    std::process::exit(code);
+}
 
-    let mut payload: Vec<String> = vec![];
-
-    for server in config.servers {
-        // Add the server name to the payload
-        payload.push(format!("🖥️ {} (`{}`)", server.name, server.host));
-
-        let sess = match ssh::create_session(
-            server.host.as_str(),
-            server.port,
-            server.user.as_str(),
-            server.private_key.as_str(),
-        ) {
-            Ok(sess) => sess,
-            Err(e) => {
-                eprintln!("Failed to create SSH session for {}: {}", server.name, e);
-                let error_msg = format!("❌ could not start SSH session with {}", server.name);
-                payload.push(error_msg);
-                continue;
-            }
-        };
-
-        if let Some(checks) = server.checks {
-            let mut sorted_checks: Vec<(&String, &Check)> = checks.iter().collect();
-            sorted_checks.sort_by(|a, b| a.0.cmp(b.0));
-            for (_check_name, check_details) in sorted_checks {
-                let result = match check_details {
-                    Check::Ping { url } => {
-                        checks::ping(&("https://".to_owned() + server.host.as_str()), url)
-                    }
-                    Check::Temperature { sensor } => checks::temperature(&sess, sensor.as_str()),
-                    Check::Load { interval } => {
-                        checks::load(&sess, server.name.as_str(), *interval)
+/// Configures the logs of the application from the `-v` and `-q` flags, warnings and errors being
+/// logged by default. The `RUST_LOG` environment variable is used when neither flag is given,
+/// e.g. to enable the logs of the dependencies.
+fn init_logger(verbose: u8, quiet: bool) {
+    let level = match (quiet, verbose) {
+        (true, _) => LevelFilter::Error,
+        (false, 0) => LevelFilter::Warn,
+        (false, 1) => LevelFilter::Info,
+        (false, 2) => LevelFilter::Debug,
+        (false, _) => LevelFilter::Trace,
+    };
+    let mut builder = env_logger::Builder::new();
+    builder.filter_level(LevelFilter::Warn);
+    builder.filter_module(env!("CARGO_PKG_NAME"), level);
+    if !quiet && verbose == 0 {
+        builder.parse_env("RUST_LOG");
+    }
+    // Under systemd, log to the journal with the priority of every record
+    let logger = builder.build();
+    let filter = logger.filter();
+    let result = match systemd::JournalLogger::connect(logger) {
+        Ok(journal) => log::set_boxed_logger(Box::new(journal)),
+        Err(logger) => log::set_boxed_logger(Box::new(logger)),
+    };
+    if result.is_ok() {
+        log::set_max_level(filter);
+    }
+}
+
+/// Waits until the next scheduled run, returning early with a run requested through the dashboard,
+/// when a shutdown is requested, or for a scheduled run when the configuration is to be reloaded,
+/// while pinging the watchdog of systemd, if enabled, as often as it expects.
+fn wait(
+    triggers: &std::sync::mpsc::Receiver<monitor::Recheck>,
+    next: Instant,
+    watchdog: Option<Duration>,
+    watcher: Option<&reload::Watcher>,
+) -> Option<monitor::Recheck> {
+    loop {
+        let remaining = next.saturating_duration_since(Instant::now());
+        if watchdog.is_some() {
+            systemd::notify(&[NotifyState::Watchdog]);
+        }
+        // Wake up regularly to notice a shutdown
+        let timeout = watchdog
+            .map_or(remaining, |w| remaining.min(w))
+            .min(Duration::from_secs(1));
+        if shutdown::requested() || watcher.is_some_and(|w| w.pending()) {
+            return None;
+        }
+        match triggers.recv_timeout(timeout) {
+            Ok(recheck) => return Some(recheck),
+            Err(_) if timeout == remaining => return None,
+            Err(_) => {}
+        }
+    }
+}
+
+/// Closes the pooled SSH sessions of the servers that are no longer in the configuration, e.g.
+/// removed or renamed by a reload, or no longer found by an inventory.
+fn prune_sessions(pool: &mut SessionPool, servers: &[config::Server]) {
+    let names: Vec<&str> = servers.iter().map(|s| s.name.as_str()).collect();
+    let open = pool.len();
+    pool.retain(&names);
+    if pool.len() < open {
+        info!(
+            "Closed {} SSH session(s) to removed servers",
+            open - pool.len()
+        );
+    }
+}
+
+/// Takes the notifiers out of the configuration, or creates the Slack notifier of the
+/// `SLACK_HOOK_URL` environment variable if none are configured.
+///
+/// Returns `None` (after logging an error) if there are no notifiers and the variable is not set.
+fn notifiers(config: &mut config::Config) -> Option<Vec<Notifier>> {
+    match config.notifiers.take() {
+        Some(notifiers) if !notifiers.is_empty() => Some(notifiers),
+        _ => match (env::var("SLACK_HOOK_URL"), env::var("SLACK_CHANNEL")) {
+            (Ok(url), _) => Some(vec![Notifier::slack(
+                &url,
+                config.slack.take().unwrap_or_default(),
+            )]),
+            // Without a webhook, post as the bot of a Slack app
+            (Err(_), Ok(channel)) if env::var_os("SLACK_BOT_TOKEN").is_some() => {
+                Some(vec![Notifier::slack_api(
+                    &channel,
+                    config.slack.take().unwrap_or_default(),
+                )])
+            }
+            _ => {
+                error!("SLACK_HOOK_URL environment variable not set");
+                None
+            }
+        },
+    }
+}
+
+/// Returns the exit code of a run from its report: `EXIT_ERROR` if a server could not be connected
+/// to, and otherwise the code of the worst status.
+fn exit_code(report: &report::Report) -> i32 {
+    if report.unreachable() {
+        return EXIT_ERROR;
+    }
+    match report.status() {
+        Status::Critical => EXIT_CRITICAL,
+        Status::Warning => EXIT_WARNING,
+        Status::Ok | Status::Info => EXIT_OK,
+    }
+}
+
+/// Prints the `servers` imported from a source, as a configuration file to include.
+fn print_servers(
+    source: &str,
+    servers: Vec<serde_yaml::Mapping>,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let mut document = serde_yaml::Mapping::new();
+    document.insert("servers".into(), servers.into());
+    println!("# Imported from {}", source);
+    print!("{}", serde_yaml::to_string(&document)?);
+    Ok(())
+}
+
+/// Runs the application and returns its exit code, see `main`.
+fn run(cli: Args) -> Result<i32, Box<dyn std::error::Error>> {
+    match &cli.command {
+        Some(Command::Validate { config }) => {
+            let config = config::load_config(config)?;
+            let problems = validate::print_summary(&validate::validate(&config));
+            return Ok(if problems > 0 { EXIT_ERROR } else { EXIT_OK });
+        }
+        Some(Command::Digest {
+            config,
+            period,
+            print,
+        }) => {
+            let mut config = config::load_config(config)?;
+            utils::set_timestamps(config.general.as_ref().and_then(|g| g.timestamps.as_ref()));
+            let history_file = config.general.as_ref().and_then(|g| g.history_file.clone());
+            let Some(history_file) = history_file else {
+                error!("A digest requires a `history_file` in the general settings");
+                return Ok(EXIT_ERROR);
+            };
+            let since = chrono::Utc::now() - digest::parse_period(period)?;
+            let records = history::load(&history_file, Some(since))?;
+            let report = digest::digest(&records, period, &config.servers);
+            let (separator, markers) = config
+                .general
+                .as_ref()
+                .map_or((String::new(), Markers::default()), |g| {
+                    (g.separator.clone(), g.markers.clone())
+                });
+            if *print {
+                println!("{}", report.to_text_with(&separator, &markers));
+                return Ok(EXIT_OK);
+            }
+
+            let Some(notifiers) = notifiers(&mut config) else {
+                return Ok(EXIT_ERROR);
+            };
+            let mut code = EXIT_OK;
+            for notifier in notifiers.iter().filter(|n| n.sends_messages()) {
+                let routed = notifier.report_for(&report, &config.servers);
+                match notifier.send(&routed, &[], &separator, &markers) {
+                    Ok(()) => info!("Digest sent to {}", notifier.name),
+                    Err(e) => {
+                        error!("Could not send the digest to {}: {}", notifier.name, e);
+                        code = EXIT_ERROR;
                     }
-                    Check::NumberOfSubfolders { path, max_folders } => {
-                        checks::number_of_folders(&sess, server.name.as_str(), path, max_folders)
+                }
+            }
+            return Ok(code);
+        }
+        Some(Command::ImportInventory {
+            inventory,
+            group,
+            template,
+        }) => {
+            let servers: Vec<_> = ansible::load(inventory, group)?
+                .into_iter()
+                .map(|host| {
+                    let mut server = host.settings();
+                    if !template.is_empty() {
+                        server.insert("template".into(), template.clone().into());
                     }
-                    Check::CustomCommand { command } => checks::custom_command(&sess, command),
-                    Check::ListOldDirectories { loc, cutoff } => {
-                        checks::list_old_directories(&sess, loc, *cutoff)
+                    server
+                })
+                .collect();
+            print_servers(inventory, servers)?;
+            return Ok(EXIT_OK);
+        }
+        Some(Command::ImportSshConfig {
+            include,
+            exclude,
+            template,
+        }) => {
+            let servers: Vec<_> = SshConfig::load_default()
+                .hosts(include, exclude)
+                .into_iter()
+                .map(|host| {
+                    let mut server = serde_yaml::Mapping::new();
+                    server.insert("name".into(), host.clone().into());
+                    server.insert("host".into(), host.into());
+                    server.insert("use_ssh_config".into(), true.into());
+                    match template.is_empty() {
+                        true => {
+                            let checks: serde_yaml::Value =
+                                serde_yaml::from_str(DEFAULT_CHECKS).expect("valid checks");
+                            server.insert("checks".into(), checks);
+                        }
+                        false => {
+                            server.insert("template".into(), template.clone().into());
+                        }
                     }
-                    _ => return Err("Unknown check".into()),
-                };
-
-                payload.push(result);
+                    server
+                })
+                .collect();
+            if servers.is_empty() {
+                warn!("No host found in ~/.ssh/config");
             }
+            print_servers("~/.ssh/config", servers)?;
+            return Ok(EXIT_OK);
         }
+        Some(Command::Init { config, force }) => {
+            config::write_example(config, *force)?;
+            println!(
+                "Example configuration written to {}, edit it and check it with `remotesysmonitor validate {}`",
+                config, config
+            );
+            return Ok(EXIT_OK);
+        }
+        None => {}
+    }
+
+    let config_path = cli.config.as_deref().unwrap_or_default();
+    info!("Loading configuration from {}", config_path);
+    let mut config = config::load_config(config_path)?;
+    config.read_only = cli.read_only;
+    utils::set_timestamps(config.general.as_ref().and_then(|g| g.timestamps.as_ref()));
+    let mut discovery = Discovery::default();
+    discovery.refresh(
+        config.inventories.as_deref().unwrap_or_default(),
+        &mut config.servers,
+    );
 
-        // Add a separator between servers, if it has been defined
-        match config.general {
-            Some(ref general) => {
-                payload.push(general.separator.repeat(10));
+    let notifiers = match cli.nagios {
+        // Nagios notifies on its own
+        true => vec![],
+        false => match notifiers(&mut config) {
+            Some(notifiers) => notifiers,
+            None => return Ok(EXIT_ERROR),
+        },
+    };
+    for server in &config.servers {
+        for name in server.notify.iter().flatten() {
+            if !notifiers.iter().any(|n| &n.name == name) {
+                warn!("Unknown notifier `{}` for server {}", name, server.name);
             }
-            None => payload.push("".to_string()),
         }
     }
 
-    let flatten: Vec<String> = payload
-        .iter()
-        .flat_map(|p| p.split('\n').map(|s| s.to_string()))
-        .collect();
+    if cli.dry_run {
+        let ssh_config = config.ssh_config();
+        println!(
+            "{}",
+            monitor::dry_run(&config, &cli.group, &ssh_config, &notifiers)?
+        );
+        return Ok(EXIT_OK);
+    }
 
-    if cli.print {
-        println!("{}", flatten.join("\n"));
+    let general = config.general.as_ref();
+    let state_file = general.and_then(|g| g.state_file.as_deref());
+    let history_file = general.and_then(|g| g.history_file.as_deref());
+    let heartbeat = general.and_then(|g| g.heartbeat.as_ref());
+    let only_on_change = general.is_some_and(|g| g.only_on_change);
+    if only_on_change && state_file.is_none() {
+        warn!("`only_on_change` requires a `state_file`, notifying on every run");
     }
+    let mut state = match state_file {
+        Some(path) => State::load(path)?,
+        None => State::default(),
+    };
+    // Add a separator between servers, if it has been defined
+    let separator = general.map_or("", |g| g.separator.as_str());
+    let default_markers = Markers::default();
+    let markers = general.map_or(&default_markers, |g| &g.markers);
+    let interval = Duration::from_secs(general.and_then(|g| g.interval).unwrap_or(300));
 
-    if cli.full || flatten.iter().any(|s| s.contains('❌')) {
-        slack::post_to_slack(slack_hook_url.as_str(), flatten.join("\n").as_str());
-    } else {
-        println!("No ❌ found in checks, not posting to Slack. Use --full to post anyway and --help for more options.");
+    if !config.servers.iter().any(|s| s.in_groups(&cli.group)) {
+        warn!("No server belongs to the group(s) {}", cli.group.join(", "));
     }
 
-    Ok(())
+    // Runs triggered through the dashboard, the sender is kept alive for as long as the loop
+    let (trigger, triggers) =
+        std::sync::mpsc::sync_channel::<monitor::Recheck>(dashboard::MAX_QUEUED_RUNS);
+    let dashboard = match general.and_then(|g| g.dashboard.as_ref()) {
+        Some(settings) if cli.daemon => {
+            let dashboard = dashboard::Dashboard {
+                latest: dashboard::Latest::default(),
+                runs: trigger.clone(),
+                token: settings.token()?,
+                slack_signing_secret: settings.slack_signing_secret()?,
+                markers: markers.clone(),
+            };
+            let address = dashboard::serve(settings, dashboard.clone())?;
+            info!("Serving the dashboard on http://{}", address);
+            Some(dashboard.latest)
+        }
+        Some(_) => {
+            warn!("The dashboard is only served with --daemon");
+            None
+        }
+        None => None,
+    };
+
+    let mut ssh_config = config.ssh_config();
+    let mut watcher = match cli.daemon {
+        true => Some(reload::Watcher::new(config_path)?),
+        false => None,
+    };
+    let _pid_file = cli
+        .pid_file
+        .as_deref()
+        .map(systemd::PidFile::create)
+        .transpose()?;
+    let watchdog = systemd::watchdog().filter(|_| cli.daemon);
+    shutdown::register()?;
+    if cli.daemon {
+        systemd::notify(&[NotifyState::Ready]);
+    }
+
+    let mut pool = SessionPool::new();
+    let mut scheduler = Scheduler::default();
+    let mut recheck: Option<monitor::Recheck> = None;
+    let mut next = Instant::now();
+    loop {
+        // Runs on demand neither ping the heartbeat nor delay the next scheduled run
+        let heartbeat = heartbeat.filter(|_| recheck.is_none());
+        if recheck.is_none() {
+            next = Instant::now() + interval;
+        }
+        if watcher.as_mut().is_some_and(|w| w.changed()) {
+            match reload::load(config_path) {
+                Ok(reloaded) => {
+                    config.servers = reloaded.servers;
+                    config.groups = reloaded.groups;
+                    config.command_policy = reloaded.command_policy;
+                    config.inventories = reloaded.inventories;
+                    discovery = Discovery::default();
+                    discovery.refresh(
+                        config.inventories.as_deref().unwrap_or_default(),
+                        &mut config.servers,
+                    );
+                    info!(
+                        "Reloaded {} server(s) from {}",
+                        config.servers.len(),
+                        config_path
+                    );
+                    ssh_config = config.ssh_config();
+                    prune_sessions(&mut pool, &config.servers);
+                    // Run the checks that changed rather than reporting their previous results
+                    scheduler = Scheduler::default();
+                }
+                Err(e) => error!(
+                    "Could not reload {}, keeping the current configuration: {}",
+                    config_path, e
+                ),
+            }
+        }
+        if let Some(heartbeat) = heartbeat {
+            if let Err(e) = heartbeat.start() {
+                warn!("Could not ping the heartbeat: {}", e);
+            }
+        }
+        let mut run = monitor::run(
+            &config,
+            &cli.group,
+            recheck.as_ref(),
+            &ssh_config,
+            &mut pool,
+            &mut state,
+            &mut scheduler,
+        )?;
+        if let Some(path) = history_file {
+            match history::latest(path) {
+                Ok(latest) => history::set_previous(&mut run.report, &latest),
+                Err(e) => warn!("Could not read the history file {}: {}", path, e),
+            }
+            // The history is not worth losing the notifications for
+            if let Err(e) = history::append(path, &history::records(&run.report, run.time)) {
+                warn!("Could not append to the history file {}: {}", path, e);
+            }
+        }
+        if let Some(latest) = &dashboard {
+            if let Ok(mut latest) = latest.lock() {
+                match latest.as_mut() {
+                    Some(snapshot) => {
+                        snapshot.update(run.report.clone(), recheck.as_ref(), run.time)
+                    }
+                    None => {
+                        *latest = Some(dashboard::Snapshot {
+                            report: run.report.clone(),
+                            time: run.time,
+                        })
+                    }
+                }
+            }
+        }
+        for exporter in config.exporters.iter().flatten() {
+            if let Err(e) = exporter.export(&run.report, run.time) {
+                error!("Could not export the values to {}: {}", exporter.kind(), e);
+            }
+        }
+
+        if cli.print {
+            println!("{}", run.report.to_text_with(separator, markers));
+        }
+        if let Some(url) = recheck.as_ref().and_then(|r| r.response_url.as_deref()) {
+            let default = SlackConfig::default();
+            let slack = config.slack.as_ref().unwrap_or(&default);
+            let text = run.report.to_text_with(separator, markers);
+            if let Err(e) = slack::post_response(url, &run.report, slack, &text, markers) {
+                error!("Could not answer the Slack slash command: {}", e);
+            }
+        }
+
+        let (notified, failed) = monitor::notify(&config, &notifiers, &run, cli.full, &mut state);
+        // A state that cannot be saved only costs the changes of the next run
+        if let Some(path) = state_file {
+            if let Err(e) = state.save(path) {
+                error!("Could not save the state to {}: {}", path, e);
+            }
+        }
+        if let Some(heartbeat) = heartbeat {
+            let failing = failed || run.report.status() == Status::Critical;
+            if let Err(e) = heartbeat.finish(failing, &run.report.to_text_with(separator, markers))
+            {
+                warn!("Could not ping the heartbeat: {}", e);
+            }
+        }
+        if cli.nagios {
+            let code = exit_code(&run.report);
+            println!("{}", nagios::plugin_output(&run.report, code));
+            return Ok(code);
+        }
+        if failed {
+            // Do not lose the report, print it instead
+            if !cli.print {
+                println!("{}", run.report.to_text_with(separator, markers));
+            }
+            if !cli.daemon {
+                return Ok(EXIT_ERROR);
+            }
+        }
+        if !notified && !cli.daemon {
+            println!("No ❌ found in checks (or no change since the last run), not notifying. Use --full to notify anyway and --help for more options.");
+        }
+
+        if !cli.daemon {
+            return Ok(exit_code(&run.report));
+        }
+        info!(
+            "Checked {} server(s), next run in {:?}",
+            run.report.servers.len(),
+            next.saturating_duration_since(Instant::now())
+        );
+        systemd::notify(&[NotifyState::Status(&run.report.summary())]);
+        recheck = wait(&triggers, next, watchdog, watcher.as_ref());
+        if shutdown::requested() {
+            break;
+        }
+        // Runs on demand check the servers already found
+        if recheck.is_none()
+            && discovery.refresh(
+                config.inventories.as_deref().unwrap_or_default(),
+                &mut config.servers,
+            )
+        {
+            ssh_config = config.ssh_config();
+            prune_sessions(&mut pool, &config.servers);
+        }
+        if let Some(recheck) = &recheck {
+            info!("Running the {} on demand", recheck);
+        }
+    }
+    info!("Shutting down");
+    systemd::notify(&[NotifyState::Stopping]);
+    Ok(EXIT_OK)
 }
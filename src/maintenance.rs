@@ -0,0 +1,264 @@
+use chrono::{DateTime, Duration, Local, Utc};
+use cron::Schedule;
+use log::error;
+use serde::{Deserialize, Serialize};
+use std::str::FromStr;
+
+/// A period during which failures are still collected but not notified.
+///
+/// A window is either a one-off period defined by `start` and `end`, or a recurring period
+/// starting at every occurrence of the `cron` expression and lasting `duration` minutes.
+///
+/// ```yaml
+/// maintenance:
+///   - start: 2024-05-01T22:00:00+02:00
+///     end: 2024-05-02T02:00:00+02:00
+///   - cron: "0 3 * * Sun" # every Sunday at 03:00, local time
+///     duration: 60
+///     checks: [load]
+/// ```
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MaintenanceWindow {
+    /// Start of a one-off window.
+    pub start: Option<DateTime<Utc>>,
+    /// End of a one-off window.
+    pub end: Option<DateTime<Utc>>,
+    /// Cron expression, in local time, at which a recurring window starts. Both the standard
+    /// five-field format, numbering the days of the week from 0 for Sunday (7 being Sunday too),
+    /// and the six-field format with seconds of the `cron` crate, numbering them from 1 for
+    /// Sunday, are accepted. The names of the days (e.g. `Mon-Fri`) are the same in both.
+    pub cron: Option<String>,
+    /// Duration of a recurring window in minutes.
+    pub duration: Option<i64>,
+    /// Servers the window applies to, all servers if omitted. Only relevant for global windows.
+    pub servers: Option<Vec<String>>,
+    /// Checks the window applies to, all checks if omitted.
+    pub checks: Option<Vec<String>>,
+}
+
+impl MaintenanceWindow {
+    /// Returns whether the window is active at the given time.
+    ///
+    /// Windows with an invalid cron expression are logged and considered inactive.
+    pub fn is_active(&self, now: DateTime<Utc>) -> bool {
+        if let Some(expression) = &self.cron {
+            let schedule = match parse_cron(expression) {
+                Ok(schedule) => schedule,
+                Err(e) => {
+                    error!(
+                        "Invalid maintenance cron expression `{}`: {}",
+                        expression, e
+                    );
+                    return false;
+                }
+            };
+            let duration = Duration::minutes(self.duration.unwrap_or(0));
+            let now = now.with_timezone(&Local);
+            // The window is active if it started less than `duration` ago
+            return schedule
+                .after(&(now - duration))
+                .next()
+                .is_some_and(|start| start <= now);
+        }
+
+        match (self.start, self.end) {
+            (Some(start), Some(end)) => start <= now && now < end,
+            (Some(start), None) => start <= now,
+            (None, Some(end)) => now < end,
+            (None, None) => false,
+        }
+    }
+
+    /// Returns whether the window covers the given check of the given server.
+    pub fn applies_to(&self, server: &str, check: &str) -> bool {
+        let server_matches = self
+            .servers
+            .as_ref()
+            .is_none_or(|servers| servers.iter().any(|s| s == server));
+        let check_matches = self
+            .checks
+            .as_ref()
+            .is_none_or(|checks| checks.iter().any(|c| c == check));
+        server_matches && check_matches
+    }
+}
+
+/// Parses a cron expression, accepting the standard five-field format by assuming the first
+/// second of the minute and naming its numbered days of the week (see [`day_names`]).
+pub fn parse_cron(expression: &str) -> Result<Schedule, cron::error::Error> {
+    let fields: Vec<&str> = expression.split_whitespace().collect();
+    if let [minutes, hours, days, months, weekdays] = fields[..] {
+        Schedule::from_str(&format!(
+            "0 {} {} {} {} {}",
+            minutes,
+            hours,
+            days,
+            months,
+            day_names(weekdays)
+        ))
+    } else {
+        Schedule::from_str(expression)
+    }
+}
+
+/// Names the days of the week of a standard cron field, numbered from 0 (or 7) for Sunday,
+/// whereas the `cron` crate numbers them from 1 for Sunday, e.g. `1-5` becomes `MON-FRI`. A
+/// range ending on Sunday as 7 is split, Sunday coming first for the `cron` crate.
+fn day_names(field: &str) -> String {
+    const DAYS: [&str; 8] = ["SUN", "MON", "TUE", "WED", "THU", "FRI", "SAT", "SUN"];
+    let day = |d: &str| {
+        d.parse::<usize>()
+            .ok()
+            .and_then(|d| DAYS.get(d))
+            .map_or(d.to_string(), |name| name.to_string())
+    };
+    field
+        .split(',')
+        .map(|item| {
+            let (range, step) = match item.split_once('/') {
+                Some((range, step)) => (range, Some(step)),
+                None => (item, None),
+            };
+            let range = match range.split_once('-') {
+                Some((start, "7")) if step.is_none() && start != "7" => {
+                    format!("{}-SAT,SUN", day(start))
+                }
+                Some((start, end)) => format!("{}-{}", day(start), day(end)),
+                None => day(range),
+            };
+            match step {
+                Some(step) => format!("{}/{}", range, step),
+                None => range,
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+/// Returns whether a check of a server is muted by any of the given maintenance windows.
+pub fn is_muted(
+    windows: &[MaintenanceWindow],
+    server: &str,
+    check: &str,
+    now: DateTime<Utc>,
+) -> bool {
+    windows
+        .iter()
+        .any(|w| w.applies_to(server, check) && w.is_active(now))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn window() -> MaintenanceWindow {
+        MaintenanceWindow {
+            start: None,
+            end: None,
+            cron: None,
+            duration: None,
+            servers: None,
+            checks: None,
+        }
+    }
+
+    #[test]
+    fn test_one_off_window() {
+        let now = Utc::now();
+        let w = MaintenanceWindow {
+            start: Some(now - Duration::minutes(5)),
+            end: Some(now + Duration::minutes(5)),
+            ..window()
+        };
+        assert!(w.is_active(now));
+        assert!(!w.is_active(now + Duration::minutes(10)));
+        assert!(!w.is_active(now - Duration::minutes(10)));
+    }
+
+    #[test]
+    fn test_recurring_window() {
+        // Every minute, lasting one minute: always active
+        let w = MaintenanceWindow {
+            cron: Some("* * * * *".to_string()),
+            duration: Some(1),
+            ..window()
+        };
+        assert!(w.is_active(Utc::now()));
+
+        // Only on the 1st of February at midnight, for an hour
+        let w = MaintenanceWindow {
+            cron: Some("0 0 1 2 *".to_string()),
+            duration: Some(60),
+            ..window()
+        };
+        let start = chrono::NaiveDate::from_ymd_opt(2024, 2, 1)
+            .unwrap()
+            .and_hms_opt(0, 30, 0)
+            .unwrap()
+            .and_local_timezone(Local)
+            .unwrap()
+            .with_timezone(&Utc);
+        assert!(w.is_active(start));
+        assert!(!w.is_active(start + Duration::hours(2)));
+    }
+
+    #[test]
+    fn test_days_of_week() {
+        assert_eq!(day_names("1-5"), "MON-FRI");
+        assert_eq!(day_names("0,6"), "SUN,SAT");
+        assert_eq!(day_names("5-7"), "FRI-SAT,SUN");
+        assert_eq!(day_names("*/2"), "*/2");
+        assert_eq!(day_names("Sun"), "Sun");
+
+        // 2024-06-03 is a Monday
+        let at = |day: u32| {
+            chrono::NaiveDate::from_ymd_opt(2024, 6, day)
+                .unwrap()
+                .and_hms_opt(3, 30, 0)
+                .unwrap()
+                .and_local_timezone(Local)
+                .unwrap()
+                .with_timezone(&Utc)
+        };
+        let weekdays = MaintenanceWindow {
+            cron: Some("0 3 * * 1-5".to_string()),
+            duration: Some(60),
+            ..window()
+        };
+        assert!((3..=7).all(|day| weekdays.is_active(at(day))));
+        assert!(!weekdays.is_active(at(8)));
+        assert!(!weekdays.is_active(at(9)));
+        for cron in ["0 3 * * 0", "0 3 * * 7"] {
+            let sundays = MaintenanceWindow {
+                cron: Some(cron.to_string()),
+                duration: Some(60),
+                ..window()
+            };
+            assert!(sundays.is_active(at(9)));
+            assert!(!sundays.is_active(at(3)));
+        }
+    }
+
+    #[test]
+    fn test_invalid_cron_is_inactive() {
+        let w = MaintenanceWindow {
+            cron: Some("not a cron".to_string()),
+            duration: Some(60),
+            ..window()
+        };
+        assert!(!w.is_active(Utc::now()));
+    }
+
+    #[test]
+    fn test_applies_to() {
+        let w = MaintenanceWindow {
+            servers: Some(vec!["web01".to_string()]),
+            checks: Some(vec!["load".to_string()]),
+            ..window()
+        };
+        assert!(w.applies_to("web01", "load"));
+        assert!(!w.applies_to("web01", "ping"));
+        assert!(!w.applies_to("web02", "load"));
+        assert!(window().applies_to("web02", "ping"));
+    }
+}
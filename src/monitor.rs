@@ -0,0 +1,1743 @@
+use crate::checks;
+use crate::config::{Check, CheckConfig, Config, Sensors, Server};
+use crate::maintenance;
+use crate::notify::Notifier;
+use crate::platform::{Os, Platform};
+use crate::report::{CheckResult, Markers, Observation, Report, ServerReport, Status, Timing};
+use crate::runner::{CommandRunner, LocalRunner, Timeout};
+use crate::schedule::Scheduler;
+use crate::shutdown;
+use crate::ssh::{self, SessionPool};
+use crate::ssh_config::SshConfig;
+use crate::state::{self, Change, State};
+use crate::utils;
+use log::{debug, error, info, warn};
+use std::collections::{BTreeMap, HashSet};
+use std::time::{Duration, Instant};
+
+/// The result of running the checks once.
+#[derive(Debug, Default)]
+pub struct Run {
+    /// Results of the checks, with the recovered checks as notes.
+    pub report: Report,
+    /// Checks that started or stopped failing since the previous run, according to the state.
+    pub changes: Vec<Change>,
+    /// When the run started.
+    pub time: chrono::DateTime<chrono::Utc>,
+}
+
+/// A request to run the checks again out of schedule, e.g. after a fix, restricted to a server and
+/// optionally to one of its checks.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Recheck {
+    /// Name of the server to check, all servers if omitted.
+    pub server: Option<String>,
+    /// Name of the check to run, all checks if omitted.
+    pub check: Option<String>,
+    /// Slack `response_url` of the slash command requesting the run, to post its results to.
+    pub response_url: Option<String>,
+}
+
+impl Recheck {
+    /// Returns whether a check of a server is part of the recheck.
+    pub fn includes(&self, server: &str, check: Option<&str>) -> bool {
+        self.server.as_ref().is_none_or(|s| s == server)
+            && check.is_none_or(|check| self.check.as_ref().is_none_or(|c| c == check))
+    }
+}
+
+impl std::fmt::Display for Recheck {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match (&self.server, &self.check) {
+            (Some(server), Some(check)) => write!(f, "check {} of {}", check, server),
+            (Some(server), None) => write!(f, "checks of {}", server),
+            (None, Some(check)) => write!(f, "check {} of all servers", check),
+            (None, None) => write!(f, "checks of all servers"),
+        }
+    }
+}
+
+/// Runs the checks of the servers once and records their statuses in the state.
+///
+/// The sessions of the servers are taken from the pool, so that they are reused across runs in
+/// daemon mode, while the checks of `local` servers run on this host. When `groups` is not empty,
+/// only the servers and checks of these groups are run, and a `recheck` further restricts the run
+/// to a server and check.
+///
+/// The checks with a schedule of their own only run when due according to the `scheduler`, their
+/// last results being reported otherwise, unless they are part of a `recheck`.
+///
+/// Once a shutdown is requested (SIGINT or SIGTERM), the check in progress completes but the
+/// following ones are not run, the report being marked as interrupted with a note.
+///
+/// # Errors
+///
+/// Returns an error if a check of the configuration is not implemented.
+pub fn run(
+    config: &Config,
+    groups: &[String],
+    recheck: Option<&Recheck>,
+    ssh_config: &SshConfig,
+    pool: &mut SessionPool,
+    state: &mut State,
+    scheduler: &mut Scheduler,
+) -> Result<Run, Box<dyn std::error::Error>> {
+    let included = |server: &str, check: Option<&str>| {
+        recheck.is_none_or(|recheck| recheck.includes(server, check))
+    };
+    let general = config.general.as_ref();
+    let reminder = general
+        .and_then(|g| g.reminder_interval)
+        .map(chrono::Duration::minutes);
+    let now = chrono::Utc::now();
+    let mut changes = vec![];
+    let mut report = Report::default();
+    let (mut skipped_servers, mut skipped_checks) = (0, 0);
+
+    for server in config
+        .servers
+        .iter()
+        .filter(|s| s.in_groups(groups) && included(&s.name, None))
+    {
+        if shutdown::requested() {
+            skipped_servers += 1;
+            continue;
+        }
+        let mut server_report = ServerReport::new(&server.name, server.host.first());
+        let server_start = (chrono::Utc::now(), Instant::now());
+
+        // Failures during maintenance are reported but neither notified nor recorded in the state
+        let windows: Vec<_> = general
+            .and_then(|g| g.maintenance.as_ref())
+            .into_iter()
+            .flatten()
+            .chain(server.maintenance.iter().flatten())
+            .cloned()
+            .collect();
+        let muted = |check: &str| maintenance::is_muted(&windows, &server.name, check, now);
+
+        let runner: &dyn CommandRunner = if server.local {
+            &LocalRunner
+        } else {
+            let connection_start = (chrono::Utc::now(), Instant::now());
+            let session = pool.get(&server.name, &server.ssh_targets(general, ssh_config));
+            server_report.timings.push(Timing {
+                name: "ssh".to_string(),
+                start: connection_start.0,
+                duration: connection_start.1.elapsed(),
+                status: match session {
+                    Ok(_) => Status::Ok,
+                    Err(_) => Status::Critical,
+                },
+            });
+            match session {
+                Ok((sess, endpoint)) => {
+                    // Report the address the server was reached at, when it has several
+                    if let Some(host) = server.host.all().get(endpoint) {
+                        server_report.host = host.clone();
+                    }
+                    let key = format!("{}/ssh", server.name);
+                    changes.extend(state.update(&key, Status::Ok, now, reminder));
+                    sess
+                }
+                Err(e) => {
+                    error!("Failed to create SSH session for {}: {}", server.name, e);
+                    let message = match e.downcast_ref::<ssh::HostKeyError>() {
+                        Some(e) => e.to_string(),
+                        None => format!("could not start SSH session with {}", server.name),
+                    };
+                    let mut result = CheckResult::new(Status::Critical, message).with_check("ssh");
+                    if muted("ssh") {
+                        result.muted = true;
+                    } else {
+                        let key = format!("{}/ssh", server.name);
+                        changes.extend(state.update(&key, Status::Critical, now, reminder));
+                    }
+                    server_report.results.push(result);
+                    server_report.timing = Some(Timing {
+                        name: server.name.clone(),
+                        start: server_start.0,
+                        duration: server_start.1.elapsed(),
+                        status: server_report.status(),
+                    });
+                    report.servers.push(server_report);
+                    continue;
+                }
+            }
+        };
+
+        // Only detected when a check depends on it, sparing a command to the other servers
+        let depends_on_os = |(_, check): &(&String, &CheckConfig)| {
+            matches!(
+                check.check,
+                Check::Temperature { .. } | Check::Cpu { .. } | Check::Uptime { .. }
+            )
+        };
+        let detected = match server.os {
+            None if config.checks_for(server, groups).iter().any(depends_on_os) => {
+                match Os::detect(runner) {
+                    Ok(os) => Some(os),
+                    Err(e) => {
+                        warn!(
+                            "Could not detect the operating system of {}, assuming Linux: {}",
+                            server.name, e
+                        );
+                        None
+                    }
+                }
+            }
+            _ => None,
+        };
+        let platform = server.platform(detected);
+
+        for (check_name, check_config) in config.checks_for(server, groups) {
+            if !included(&server.name, Some(check_name)) {
+                continue;
+            }
+            let key = format!("{}/{}", server.name, check_name);
+            if recheck.is_none() {
+                if let Some(mut results) = scheduler.previous(&key, check_config, now) {
+                    debug!("Check {} on {} is not due", check_name, server.name);
+                    let muted = muted(check_name);
+                    results.iter_mut().for_each(|result| result.muted = muted);
+                    server_report.results.extend(results);
+                    continue;
+                }
+            }
+            if shutdown::requested() {
+                skipped_checks += 1;
+                continue;
+            }
+            debug!("Running check {} on {}", check_name, server.name);
+            let check_start = (chrono::Utc::now(), Instant::now());
+            let results = match config.refusal(&check_config.check) {
+                Some(reason) => {
+                    error!(
+                        "Refused to run check {} on {}: {}",
+                        check_name, server.name, reason
+                    );
+                    let message = format!("refused by the command policy: {}", reason);
+                    vec![CheckResult::new(Status::Critical, message)]
+                }
+                None => run_with_retries(server, platform, runner, check_name, check_config),
+            };
+
+            let mut results: Vec<CheckResult> = results
+                .into_iter()
+                .map(|r| r.with_check(check_name))
+                .collect();
+            if let Check::Uptime { on_reboot } = check_config.check {
+                detect_reboot(state, &key, &server.name, on_reboot, &mut results, now);
+            }
+            if let Check::OomKills { .. } = check_config.check {
+                forget_reported_kills(state, &key, &server.name, &mut results);
+            }
+            if let Check::Logins {
+                max_failed,
+                failed_warning,
+                ..
+            } = check_config.check
+            {
+                summarize_logins(
+                    state,
+                    &key,
+                    &server.name,
+                    failed_warning,
+                    max_failed,
+                    &mut results,
+                );
+            }
+            if let Check::AccountChanges { on_change, .. } = check_config.check {
+                detect_file_changes(state, &key, &server.name, on_change, &mut results);
+            }
+            let duration = check_start.1.elapsed();
+            debug!(
+                "Check {} on {} took {:.2?}",
+                check_name, server.name, duration
+            );
+            server_report.timings.push(Timing {
+                name: check_name.clone(),
+                start: check_start.0,
+                duration,
+                status: results.iter().map(|r| r.status).max().unwrap_or_default(),
+            });
+
+            // Scheduled checks keep their schedule during maintenance
+            scheduler.record(&key, check_config, now, &results);
+            if muted(check_name) {
+                for mut result in results {
+                    result.muted = true;
+                    server_report.results.push(result);
+                }
+                continue;
+            }
+
+            let status = results.iter().map(|r| r.status).max().unwrap_or_default();
+            changes.extend(state.update(&key, status, now, reminder));
+            server_report.results.extend(results);
+        }
+
+        let duration = server_start.1.elapsed();
+        debug!("Checked {} in {:.2?}", server.name, duration);
+        server_report.timing = Some(Timing {
+            name: server.name.clone(),
+            start: server_start.0,
+            duration,
+            status: server_report.status(),
+        });
+        report.servers.push(server_report);
+    }
+    if let Some(footer) = report.footer() {
+        debug!("{}", footer);
+    }
+
+    // Announce the checks that passed again at the top of the report
+    report.notes = changes
+        .iter()
+        .filter_map(|change| match change {
+            Change::Recovered(key) => {
+                let (server, check) = state::split_key(key);
+                let message = format!("recovered: {} @ {}", check, server);
+                Some(CheckResult::new(Status::Ok, message).with_check(key))
+            }
+            _ => None,
+        })
+        .collect();
+    if skipped_servers + skipped_checks > 0 {
+        let message = format!(
+            "run interrupted by a shutdown: {} server(s) not checked, {} other check(s) not run",
+            skipped_servers, skipped_checks
+        );
+        info!("{}", message);
+        report
+            .notes
+            .push(CheckResult::new(Status::Warning, message));
+    }
+    if general.is_some_and(|g| g.failures_first) {
+        report.sort_failures_first();
+    }
+
+    Ok(Run {
+        report,
+        changes,
+        time: now,
+    })
+}
+
+/// Describes what a run would do without connecting to the servers: the commands of every check
+/// (and the URLs of the `ping` checks) by server, followed by the servers routed to every notifier
+/// and the status from which it is used.
+///
+/// The commands are recorded by running the checks against a runner answering every command with
+/// an empty output, so commands depending on the output of a previous one (e.g. the `INFO` of the
+/// `redis` check, sent after a successful `PING`) are not listed.
+///
+/// # Errors
+///
+/// Returns an error if a check of the configuration is not implemented.
+pub fn dry_run(
+    config: &Config,
+    groups: &[String],
+    ssh_config: &SshConfig,
+    notifiers: &[Notifier],
+) -> Result<String, Box<dyn std::error::Error>> {
+    let general = config.general.as_ref();
+    let mut lines = vec![];
+    let servers: Vec<&Server> = config
+        .servers
+        .iter()
+        .filter(|s| s.in_groups(groups))
+        .collect();
+    for server in &servers {
+        if server.local {
+            lines.push(format!("{} (local):", server.name));
+        } else {
+            let target = server.ssh_target(general, ssh_config);
+            lines.push(format!(
+                "{} ({}@{}:{}):",
+                server.name,
+                target.user,
+                utils::url_host(&target.host),
+                target.port
+            ));
+        }
+        for (check_name, check_config) in config.checks_for(server, groups) {
+            if let Check::Ping { url } = &check_config.check {
+                for url in url {
+                    lines.push(format!(
+                        "   {}: GET https://{}{}",
+                        check_name,
+                        utils::url_host(server.host.first()),
+                        url
+                    ));
+                }
+                continue;
+            }
+            let runner = Recorder::default();
+            run_check(server, server.platform(None), &runner, &check_config.check);
+            let refusal = config.refusal(&check_config.check);
+            for command in runner.commands.into_inner() {
+                match &refusal {
+                    Some(reason) => lines.push(format!(
+                        "   {}: {} (refused: {})",
+                        check_name, command, reason
+                    )),
+                    None => lines.push(format!("   {}: {}", check_name, command)),
+                }
+            }
+        }
+    }
+
+    lines.push("Notifiers:".to_string());
+    for notifier in notifiers {
+        let routed: Vec<&str> = servers
+            .iter()
+            .filter(|s| s.notify.as_ref().is_none_or(|n| n.contains(&notifier.name)))
+            .map(|s| s.name.as_str())
+            .collect();
+        lines.push(format!(
+            "   {}: {} from {}, or on recoveries",
+            notifier.name,
+            match routed.is_empty() {
+                true => "no server".to_string(),
+                false => routed.join(", "),
+            },
+            notifier.min_status.emoji()
+        ));
+    }
+    Ok(lines.join("\n"))
+}
+
+/// Records the commands of the checks instead of running them, answering them with an empty
+/// output.
+#[derive(Default)]
+struct Recorder {
+    commands: std::cell::RefCell<Vec<String>>,
+}
+
+impl CommandRunner for Recorder {
+    fn run_until(
+        &self,
+        command: &str,
+        _deadline: Option<std::time::Instant>,
+    ) -> Result<String, Box<dyn std::error::Error>> {
+        self.commands.borrow_mut().push(command.to_string());
+        Ok(String::new())
+    }
+}
+
+/// Runs a check, running it again up to `retries` times while it reports a ❌, so that a single
+/// blip does not trigger an alert. The results of the last attempt are returned.
+fn run_with_retries(
+    server: &Server,
+    platform: Platform,
+    runner: &dyn CommandRunner,
+    check_name: &str,
+    check_config: &CheckConfig,
+) -> Vec<CheckResult> {
+    let delay = Duration::from_secs(check_config.retry_delay.unwrap_or(5));
+    let mut results = run_attempt(server, platform, runner, check_config);
+    for attempt in 1..=check_config.retries {
+        if !results.iter().any(|r| r.status == Status::Critical) {
+            break;
+        }
+        info!(
+            "Check {} of {} failed, retrying ({}/{})",
+            check_name, server.name, attempt, check_config.retries
+        );
+        std::thread::sleep(delay);
+        results = run_attempt(server, platform, runner, check_config);
+    }
+    attach_top_processes(server, platform, runner, &check_config.check, &mut results);
+    results
+}
+
+/// Reports a reboot when the boot time measured by an `uptime` check changed since the previous
+/// run, e.g. "web03 rebooted 14 minutes ago" with the `on_reboot` status. The boot time is only
+/// kept in the state, the observation being removed from the result.
+fn detect_reboot(
+    state: &mut State,
+    key: &str,
+    server_name: &str,
+    on_reboot: Status,
+    results: &mut [CheckResult],
+    now: chrono::DateTime<chrono::Utc>,
+) {
+    for result in results {
+        let Some(Observation::Boot(boot)) = result.observation.take() else {
+            continue;
+        };
+        if state.rebooted(key, boot) {
+            result.status = on_reboot;
+            result.message = format!(
+                "{} rebooted {} ago",
+                server_name,
+                utils::format_duration(now - boot)
+            );
+        }
+    }
+}
+
+/// Removes the OOM kills found by an `oom_kills` check that were reported by a previous run, as
+/// recorded in the state, the kernel log holding them for much longer than the interval between
+/// the runs. The time of the kills is only kept in the state, as for `detect_reboot`.
+fn forget_reported_kills(
+    state: &mut State,
+    key: &str,
+    server_name: &str,
+    results: &mut Vec<CheckResult>,
+) {
+    let check = results.first().map(|r| r.check.clone()).unwrap_or_default();
+    results.retain_mut(|result| match result.observation.take() {
+        Some(Observation::Kill(time)) => state.new_kill(key, time),
+        _ => true,
+    });
+    if results.is_empty() {
+        let message = format!("no OOM kills since the previous run @ {}", server_name);
+        results.push(CheckResult::new(Status::Ok, message).with_check(&check));
+    }
+}
+
+/// Summarizes the logins found by a `logins` check that are new since the previous run, as
+/// recorded in the state: the logins of unexpected users are kept, while those of the allowed
+/// users are summarized as "3 logins since the previous run (alice, bob)" and the failed attempts
+/// as "120 failed login attempts since the previous run", against the thresholds, with their
+/// main sources as details. The times are only kept in the state, as for `forget_reported_kills`,
+/// the newest ones being recorded once all the logins were compared to those of the previous runs,
+/// so that the logins sharing a second are all counted.
+fn summarize_logins(
+    state: &mut State,
+    key: &str,
+    server_name: &str,
+    failed_warning: Option<u32>,
+    max_failed: Option<u32>,
+    results: &mut Vec<CheckResult>,
+) {
+    let check = results.first().map(|r| r.check.clone()).unwrap_or_default();
+    let mut users: Vec<String> = vec![];
+    let mut logins = 0;
+    let mut sources: BTreeMap<String, u32> = BTreeMap::new();
+    let mut failed = 0;
+    let mut failed_readable = true;
+    let (since, failed_since) = (state.last_login(key, false), state.last_login(key, true));
+    let mut newest = (None, None);
+    results.retain_mut(|result| match result.observation.take() {
+        Some(Observation::Login(time)) => {
+            if since.is_some_and(|since| time <= since) {
+                return false;
+            }
+            newest.0 = newest.0.max(Some(time));
+            if result.status != Status::Ok {
+                return true;
+            }
+            logins += 1;
+            let user = result.message.split(' ').next().unwrap_or_default();
+            if !users.iter().any(|u| u == user) {
+                users.push(user.to_string());
+            }
+            false
+        }
+        Some(Observation::FailedLogin(time)) => {
+            if failed_since.is_none_or(|since| time > since) {
+                newest.1 = newest.1.max(Some(time));
+                failed += 1;
+                *sources.entry(result.message.clone()).or_default() += 1;
+            }
+            false
+        }
+        Some(Observation::FailedLoginsUnreadable) => {
+            failed_readable = false;
+            true
+        }
+        _ => true,
+    });
+    if let Some(time) = newest.0 {
+        state.record_login(key, time, false);
+    }
+    if let Some(time) = newest.1 {
+        state.record_login(key, time, true);
+    }
+    // Errors, e.g. `last` failing, are not summarized
+    if results.iter().any(|r| r.message.starts_with("Error: ")) {
+        return;
+    }
+
+    let message = match logins {
+        0 => format!("no login since the previous run @ {}", server_name),
+        _ => format!(
+            "{} login{} since the previous run ({}) @ {}",
+            logins,
+            if logins == 1 { "" } else { "s" },
+            users.join(", "),
+            server_name
+        ),
+    };
+    results.push(
+        CheckResult::new(Status::Ok, message)
+            .with_check(&check)
+            .with_metric("logins", f64::from(logins)),
+    );
+    if failed_readable {
+        let status = Status::from_thresholds(
+            f64::from(failed),
+            failed_warning.map(f64::from),
+            max_failed.map_or(f64::INFINITY, f64::from),
+        );
+        let message = format!(
+            "{} failed login attempt{} since the previous run @ {}",
+            failed,
+            if failed == 1 { "" } else { "s" },
+            server_name
+        );
+        let mut result = CheckResult::new(status, message)
+            .with_check(&check)
+            .with_metric("failed_logins", f64::from(failed));
+        if failed > 0 {
+            let mut sources: Vec<(String, u32)> = sources.into_iter().collect();
+            sources.sort_by_key(|(_, count)| std::cmp::Reverse(*count));
+            let top: Vec<String> = sources
+                .iter()
+                .take(5)
+                .map(|(source, count)| format!("{:>6}  {}", count, source))
+                .collect();
+            result = result.with_details(top.join("\n"));
+        }
+        results.push(result);
+    }
+}
+
+/// Replaces the files read by an `account_changes` check by their changes since the previous run,
+/// e.g. "/etc/passwd changed since the previous run" with the `on_change` status and the lines
+/// added and the number of lines removed as details, or by a single result when none changed.
+/// Only the hashes of their lines are kept in the state (see `state::line_hashes`).
+fn detect_file_changes(
+    state: &mut State,
+    key: &str,
+    server_name: &str,
+    on_change: Status,
+    results: &mut Vec<CheckResult>,
+) {
+    let check = results.first().map(|r| r.check.clone()).unwrap_or_default();
+    let mut found = vec![];
+    let mut unchanged = 0;
+    results.retain_mut(|result| {
+        match result.observation.take() {
+            Some(Observation::File) => {}
+            Some(Observation::UnreadableFile) => {
+                // The content of the file is kept until it can be read again
+                found.push(result.message.clone());
+                result.message = format!("{} is not readable @ {}", result.message, server_name);
+                return true;
+            }
+            _ => return true,
+        }
+        let path = std::mem::take(&mut result.message);
+        let content = result.details.take().unwrap_or_default();
+        let lines = state::line_hashes(&content);
+        let previous = state.replace_file(key, &path, lines.clone());
+        found.push(path.clone());
+        match previous {
+            Some(previous) if previous != lines => {
+                result.status = on_change;
+                result.message =
+                    format!("{} changed since the previous run @ {}", path, server_name);
+                result.details = Some(line_changes(&previous, &content));
+                true
+            }
+            _ => {
+                unchanged += 1;
+                false
+            }
+        }
+    });
+    // Nothing is forgotten when the files could not be listed at all
+    if !found.is_empty() {
+        for path in state.forget_files(key, &found) {
+            let message = format!("{} removed since the previous run @ {}", path, server_name);
+            results.push(CheckResult::new(on_change, message).with_check(&check));
+        }
+    }
+    if results.is_empty() {
+        let message = format!(
+            "{} files unchanged since the previous run @ {}",
+            unchanged, server_name
+        );
+        results.push(CheckResult::new(Status::Ok, message).with_check(&check));
+    }
+}
+
+/// Counts the lines removed from a file, given the hashes of its previous lines, and lists those
+/// added to it, prefixed with `+`, up to 20 of them.
+fn line_changes(previous: &[String], current: &str) -> String {
+    const MAX_LINES: usize = 20;
+    let previous_lines: HashSet<&str> = previous.iter().map(String::as_str).collect();
+    let current_lines: HashSet<String> = state::line_hashes(current).into_iter().collect();
+    let removed = previous
+        .iter()
+        .filter(|hash| !current_lines.contains(*hash))
+        .count();
+    let mut added: Vec<String> = current
+        .lines()
+        .filter(|line| !previous_lines.contains(state::line_hash(line).as_str()))
+        .map(|line| format!("+ {}", line))
+        .collect();
+    if removed == 0 && added.is_empty() {
+        return "lines reordered".to_string();
+    }
+    if added.len() > MAX_LINES {
+        let more = added.len() - MAX_LINES;
+        added.truncate(MAX_LINES);
+        added.push(format!("… {} more lines", more));
+    }
+    if removed > 0 {
+        let plural = if removed == 1 { "" } else { "s" };
+        added.insert(0, format!("- {} line{} removed", removed, plural));
+    }
+    added.join("\n")
+}
+
+/// Attaches the busiest processes of the server to the first result of a `load` or `cpu` check
+/// that crossed a threshold, sparing the first steps of the investigation. The results of the
+/// errors, which have no metric, are left alone.
+fn attach_top_processes(
+    server: &Server,
+    platform: Platform,
+    runner: &dyn CommandRunner,
+    check: &Check,
+    results: &mut [CheckResult],
+) {
+    if !matches!(check, Check::Load { .. } | Check::Cpu { .. }) {
+        return;
+    }
+    let Some(result) = results
+        .iter_mut()
+        .find(|r| r.status != Status::Ok && r.metric.is_some())
+    else {
+        return;
+    };
+    match checks::top_processes(runner, platform) {
+        Ok(processes) => result.details = Some(processes),
+        Err(e) => warn!("Could not list the processes of {}: {}", server.name, e),
+    }
+}
+
+/// Runs a check once, within its `timeout` if any. A check that times out is reported as a single
+/// ❌ result, the results of its commands that completed in time being discarded.
+fn run_attempt(
+    server: &Server,
+    platform: Platform,
+    runner: &dyn CommandRunner,
+    check_config: &CheckConfig,
+) -> Vec<CheckResult> {
+    let Some(timeout) = check_config.timeout else {
+        return run_check(server, platform, runner, &check_config.check);
+    };
+    let runner = Timeout::new(runner, Duration::from_secs(timeout));
+    let results = run_check(server, platform, &runner, &check_config.check);
+    if runner.timed_out() {
+        let message = format!("⏱ timed out after {}s", timeout);
+        return vec![CheckResult::new(Status::Critical, message)];
+    }
+    results
+}
+
+/// Runs a check on a server once. A kind of check that is not implemented yet, e.g.
+/// `list_age`, gives a single ❌ result, the other checks still running.
+fn run_check(
+    server: &Server,
+    platform: Platform,
+    runner: &dyn CommandRunner,
+    check: &Check,
+) -> Vec<CheckResult> {
+    match check {
+        Check::Ping { url } => checks::ping(
+            &format!("https://{}", utils::url_host(server.host.first())),
+            url,
+        ),
+        Check::Temperature {
+            sensor,
+            warning,
+            critical,
+        } => match sensor {
+            Sensors::One(sensor) => checks::temperature(
+                runner,
+                platform,
+                sensor.as_str(),
+                *warning,
+                critical.unwrap_or(30),
+            ),
+            Sensors::Several(sensors) => {
+                checks::temperatures(runner, platform, sensors, *warning, critical.unwrap_or(30))
+            }
+        },
+        Check::Ipmi { ipmitool, ignore } => {
+            checks::ipmi(runner, server.name.as_str(), ipmitool, ignore)
+        }
+        Check::Ups {
+            ups,
+            min_charge,
+            charge_warning,
+        } => checks::ups(
+            runner,
+            server.name.as_str(),
+            ups,
+            *charge_warning,
+            *min_charge,
+        ),
+        Check::LmSensors { chips } => checks::lm_sensors(runner, server.name.as_str(), chips),
+        Check::Load {
+            interval,
+            warning,
+            critical,
+        } => checks::load(
+            runner,
+            server.name.as_str(),
+            platform,
+            *interval,
+            *warning,
+            critical.unwrap_or(50.0),
+        ),
+        Check::Cpu {
+            max_usage,
+            warning,
+            max_iowait,
+            iowait_warning,
+        } => checks::cpu(
+            runner,
+            server.name.as_str(),
+            platform,
+            *warning,
+            *max_usage,
+            *iowait_warning,
+            *max_iowait,
+        ),
+        Check::Uptime { .. } => checks::uptime(runner, server.name.as_str(), platform),
+        Check::Entropy {
+            min_entropy,
+            entropy_warning,
+        } => checks::entropy(
+            runner,
+            server.name.as_str(),
+            platform,
+            *min_entropy,
+            *entropy_warning,
+        ),
+        Check::Kernel { on_outdated_kernel } => {
+            checks::kernel(runner, server.name.as_str(), platform, *on_outdated_kernel)
+        }
+        Check::ProcessStates {
+            max_zombies,
+            zombies_warning,
+            max_blocked,
+            blocked_warning,
+        } => checks::process_states(
+            runner,
+            server.name.as_str(),
+            platform,
+            *zombies_warning,
+            *max_zombies,
+            *blocked_warning,
+            *max_blocked,
+        ),
+        Check::Network {
+            interfaces,
+            max_mbps,
+            mbps_warning,
+        } => checks::network(
+            runner,
+            server.name.as_str(),
+            platform,
+            interfaces,
+            *mbps_warning,
+            *max_mbps,
+        ),
+        Check::Connections {
+            max_established,
+            established_warning,
+            max_time_wait,
+            time_wait_warning,
+            port,
+        } => checks::connections(
+            runner,
+            server.name.as_str(),
+            platform,
+            *port,
+            *established_warning,
+            *max_established,
+            *time_wait_warning,
+            *max_time_wait,
+        ),
+        Check::ListeningPorts {
+            allowed_ports,
+            ignore_loopback,
+        } => checks::listening_ports(
+            runner,
+            server.name.as_str(),
+            platform,
+            allowed_ports,
+            *ignore_loopback,
+        ),
+        Check::Mounts {
+            mounts,
+            stat_timeout,
+        } => checks::mounts(
+            runner,
+            server.name.as_str(),
+            platform,
+            mounts,
+            stat_timeout.unwrap_or(5),
+        ),
+        Check::Logins { allowed_users, .. } => {
+            checks::logins(runner, server.name.as_str(), platform, allowed_users)
+        }
+        Check::DockerDisk {
+            max_docker_usage,
+            docker_usage_warning,
+            dangling_warning,
+            docker,
+        } => checks::docker_disk(
+            runner,
+            server.name.as_str(),
+            docker.as_deref().unwrap_or("docker"),
+            max_docker_usage.0,
+            docker_usage_warning.map(|w| w.0),
+            *dangling_warning,
+        ),
+        Check::ContainerRestarts {
+            max_restarts,
+            restart_window,
+            docker,
+        } => checks::container_restarts(
+            runner,
+            server.name.as_str(),
+            docker.as_deref().unwrap_or("docker"),
+            *max_restarts,
+            restart_window.unwrap_or(60),
+        ),
+        Check::Compose {
+            compose_files,
+            docker,
+        } => checks::compose(
+            runner,
+            server.name.as_str(),
+            docker.as_deref().unwrap_or("docker"),
+            compose_files,
+        ),
+        Check::SecurityModule {
+            enforcement,
+            max_denials,
+            denials_warning,
+        } => checks::security_module(
+            runner,
+            server.name.as_str(),
+            platform,
+            *enforcement,
+            *denials_warning,
+            *max_denials,
+        ),
+        Check::AccountChanges { files, .. } => {
+            let files = files.clone().unwrap_or_else(|| {
+                checks::ACCOUNT_FILES
+                    .iter()
+                    .map(|file| file.to_string())
+                    .collect()
+            });
+            checks::account_files(runner, server.name.as_str(), platform, &files)
+        }
+        Check::OomKills { on_kill } => {
+            checks::oom_kills(runner, server.name.as_str(), platform, *on_kill)
+        }
+        Check::DirSize {
+            path,
+            max_size,
+            warning,
+        } => checks::dir_size(
+            runner,
+            server.name.as_str(),
+            platform,
+            path,
+            max_size.0,
+            warning.map(|w| w.0),
+        ),
+        Check::TopDirectories {
+            parent,
+            max_size,
+            warning,
+            top,
+        } => checks::top_directories(
+            runner,
+            server.name.as_str(),
+            platform,
+            parent,
+            top.unwrap_or(5),
+            max_size.0,
+            warning.map(|w| w.0),
+        ),
+        Check::CertificateFiles {
+            certificates,
+            min_days,
+            warning_days,
+        } => checks::certificate_files(
+            runner,
+            server.name.as_str(),
+            certificates,
+            warning_days.unwrap_or(30),
+            min_days.unwrap_or(7),
+        ),
+        Check::Certbot {
+            letsencrypt,
+            logs,
+            overdue_days,
+        } => checks::certbot(
+            runner,
+            server.name.as_str(),
+            letsencrypt,
+            logs.as_deref().unwrap_or("/var/log/letsencrypt"),
+            overdue_days.unwrap_or(20),
+        ),
+        Check::Permissions {
+            audit_paths,
+            owner,
+            max_listed,
+        } => checks::permissions(
+            runner,
+            server.name.as_str(),
+            platform,
+            audit_paths,
+            owner.as_deref(),
+            max_listed.unwrap_or(20),
+        ),
+        Check::Quota {
+            max_quota_usage,
+            warning,
+            quotas,
+            filesystem,
+            xfs,
+        } => checks::quota(
+            runner,
+            server.name.as_str(),
+            *quotas,
+            filesystem.as_deref(),
+            *xfs,
+            *warning,
+            *max_quota_usage,
+        ),
+        Check::NumberOfFiles {
+            path,
+            max_files,
+            warning,
+        } => checks::number_of_files(
+            runner,
+            server.name.as_str(),
+            platform,
+            path,
+            *max_files,
+            *warning,
+        ),
+        Check::NumberOfSubfolders {
+            path,
+            max_folders,
+            warning,
+        } => checks::number_of_folders(
+            runner,
+            server.name.as_str(),
+            platform,
+            path,
+            max_folders,
+            *warning,
+        ),
+        Check::CustomCommand {
+            command,
+            max_lines,
+            max_chars,
+        } => checks::custom_command(runner, command, *max_lines, *max_chars),
+        Check::ListOldDirectories { loc, cutoff } => {
+            checks::list_old_directories(runner, platform, loc, *cutoff)
+        }
+        Check::Kubernetes {
+            kubeconfig,
+            namespace,
+            context,
+        } => checks::kubernetes(
+            runner,
+            server.name.as_str(),
+            kubeconfig,
+            namespace.as_deref(),
+            context.as_deref(),
+        ),
+        Check::Database {
+            engine,
+            user,
+            database,
+            max_connections,
+            max_replication_lag,
+        } => checks::database(
+            runner,
+            server.name.as_str(),
+            engine,
+            user.as_deref(),
+            database.as_deref(),
+            *max_connections,
+            *max_replication_lag,
+        ),
+        Check::Redis {
+            redis_url,
+            max_memory_mb,
+            max_clients,
+        } => checks::redis(
+            runner,
+            server.name.as_str(),
+            redis_url,
+            *max_memory_mb,
+            *max_clients,
+        ),
+        Check::WebServer {
+            status_url,
+            max_connections,
+            connections_warning,
+            max_busy_workers,
+            busy_workers_warning,
+        } => checks::web_server(
+            runner,
+            server.name.as_str(),
+            status_url,
+            *connections_warning,
+            *max_connections,
+            *busy_workers_warning,
+            *max_busy_workers,
+        ),
+        Check::Fail2ban {
+            fail2ban_client,
+            jails,
+            max_banned,
+            banned_warning,
+        } => checks::fail2ban(
+            runner,
+            server.name.as_str(),
+            fail2ban_client,
+            jails.as_deref(),
+            *banned_warning,
+            *max_banned,
+        ),
+        Check::QueueDepth {
+            max_depth,
+            vhost,
+            queues,
+        } => checks::queue_depth(
+            runner,
+            server.name.as_str(),
+            *max_depth,
+            vhost.as_deref(),
+            queues.as_deref(),
+        ),
+        _ => vec![CheckResult::new(
+            Status::Critical,
+            format!("Error: the `{}` check is not implemented", check.kind()),
+        )],
+    }
+}
+
+/// Sends the report of a run to the notifiers that want it, or to all of them with `full`.
+///
+/// When `only_on_change` is set (and a `state_file` is configured), nothing is sent unless a check
+/// changed status since the previous run. Notifiers keeping a status message up to date are used
+/// at every run regardless.
+///
+/// The changes delivered by a notifier are marked as notified in the state (see
+/// `State::mark_notified`), so that a reminder that could not be sent is due again at the next run.
+///
+/// Returns whether the report was sent to at least one notifier, and whether sending it to any of
+/// them failed.
+pub fn notify(
+    config: &Config,
+    notifiers: &[Notifier],
+    run: &Run,
+    full: bool,
+    state: &mut State,
+) -> (bool, bool) {
+    let general = config.general.as_ref();
+    let separator = general.map_or("", |g| g.separator.as_str());
+    let default = Markers::default();
+    let markers = general.map_or(&default, |g| &g.markers);
+    let only_on_change = general.is_some_and(|g| g.only_on_change && g.state_file.is_some());
+
+    let mut notified = false;
+    let mut failed = false;
+    for notifier in notifiers {
+        let routed = notifier.report_for(&run.report, &config.servers);
+        let changes = notifier.changes_for(&run.changes, &config.servers);
+        let unchanged = only_on_change && changes.is_empty();
+        if !(full || notifier.keeps_status() || (!unchanged && notifier.wants(&routed))) {
+            continue;
+        }
+        notified = true;
+        match notifier.send(&routed, &changes, separator, markers) {
+            Ok(()) => {
+                info!("Report sent to {}", notifier.name);
+                state.mark_notified(&changes, run.time);
+            }
+            Err(e) => {
+                error!("Could not send the report to {}: {}", notifier.name, e);
+                failed = true;
+            }
+        }
+    }
+    (notified, failed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_run_unreachable() {
+        let config: Config = serde_yaml::from_str(
+            "servers:\n\
+             - {name: a, host: 127.0.0.1, port: 1, user: u, private_key: k, groups: [web]}\n\
+             - {name: b, host: 127.0.0.1, port: 1, user: u, private_key: k}",
+        )
+        .unwrap();
+        let mut pool = SessionPool::new();
+        let mut state = State::default();
+
+        let groups = vec!["web".to_string()];
+        let run = run(
+            &config,
+            &groups,
+            None,
+            &SshConfig::default(),
+            &mut pool,
+            &mut state,
+            &mut Scheduler::default(),
+        )
+        .unwrap();
+        assert_eq!(run.report.servers.len(), 1);
+        let results = &run.report.servers[0].results;
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].check, "ssh");
+        assert_eq!(results[0].status, Status::Critical);
+        assert_eq!(run.changes, vec![Change::Failed("a/ssh".to_string())]);
+        assert!(pool.is_empty());
+    }
+
+    #[test]
+    fn test_run_read_only() {
+        let mut config: Config = serde_yaml::from_str(
+            "command_policy: {allow: [echo]}\n\
+             servers:\n\
+             - {name: self, host: localhost, local: true, \
+             checks: {greet: {command: echo hello}, clean: {command: touch /tmp/never}}}",
+        )
+        .unwrap();
+        config.read_only = true;
+        let run = run(
+            &config,
+            &[],
+            None,
+            &SshConfig::default(),
+            &mut SessionPool::new(),
+            &mut State::default(),
+            &mut Scheduler::default(),
+        )
+        .unwrap();
+        let results = &run.report.servers[0].results;
+        assert_eq!(results[0].check, "clean");
+        assert_eq!(results[0].status, Status::Critical);
+        assert_eq!(
+            results[0].message,
+            "refused by the command policy: program `touch` is not allowed"
+        );
+        assert_eq!(results[1].details.as_deref(), Some("hello\n"));
+    }
+
+    #[test]
+    fn test_run_unimplemented_check() {
+        let config: Config = serde_yaml::from_str(
+            "servers:\n\
+             - {name: self, host: localhost, local: true, \
+             checks: {greet: {command: echo hello}, \
+             reports: {path: [/var/reports], maximum_age: 60}}}",
+        )
+        .unwrap();
+        let run = run(
+            &config,
+            &[],
+            None,
+            &SshConfig::default(),
+            &mut SessionPool::new(),
+            &mut State::default(),
+            &mut Scheduler::default(),
+        )
+        .unwrap();
+        let results = &run.report.servers[0].results;
+        assert_eq!(results[0].details.as_deref(), Some("hello\n"));
+        assert_eq!(results[1].check, "reports");
+        assert_eq!(results[1].status, Status::Critical);
+        assert_eq!(
+            results[1].message,
+            "Error: the `list_age` check is not implemented"
+        );
+    }
+
+    #[test]
+    fn test_run_local() {
+        let config: Config = serde_yaml::from_str(
+            "servers:\n\
+             - {name: self, host: localhost, local: true, \
+             checks: {hello: {command: echo hello}, bye: {command: echo bye}}}\n\
+             - {name: other, host: localhost, local: true, \
+             checks: {hello: {command: echo hello}}}",
+        )
+        .unwrap();
+        let mut pool = SessionPool::new();
+        let mut state = State::default();
+
+        let run = run(
+            &config,
+            &[],
+            None,
+            &SshConfig::default(),
+            &mut pool,
+            &mut state,
+            &mut Scheduler::default(),
+        )
+        .unwrap();
+        let results = &run.report.servers[0].results;
+        assert_eq!(results.len(), 2);
+        assert_eq!(results[1].check, "hello");
+        assert_eq!(results[1].details.as_deref(), Some("hello\n"));
+        assert!(pool.is_empty());
+
+        let recheck = Recheck {
+            server: Some("self".to_string()),
+            check: Some("hello".to_string()),
+            response_url: None,
+        };
+        let rechecked = super::run(
+            &config,
+            &[],
+            Some(&recheck),
+            &SshConfig::default(),
+            &mut pool,
+            &mut state,
+            &mut Scheduler::default(),
+        )
+        .unwrap();
+        assert_eq!(rechecked.report.servers.len(), 1);
+        assert_eq!(rechecked.report.servers[0].results.len(), 1);
+        assert_eq!(rechecked.report.servers[0].results[0].check, "hello");
+        assert_eq!(recheck.to_string(), "check hello of self");
+    }
+
+    #[test]
+    fn test_run_scheduled() {
+        let config: Config = serde_yaml::from_str(
+            "servers:\n\
+             - {name: self, host: localhost, local: true, \
+             checks: {hourly: {command: echo hourly, every: 1h}, always: {command: echo now}}}",
+        )
+        .unwrap();
+        let mut scheduler = Scheduler::default();
+        let mut runs = vec![];
+        for _ in 0..2 {
+            runs.push(
+                run(
+                    &config,
+                    &[],
+                    None,
+                    &SshConfig::default(),
+                    &mut SessionPool::new(),
+                    &mut State::default(),
+                    &mut scheduler,
+                )
+                .unwrap(),
+            );
+        }
+        // The hourly check is not run again, but its results are still reported
+        let server = &runs[1].report.servers[0];
+        assert_eq!(server.results.len(), 2);
+        assert!(server.results.iter().any(|r| r.check == "hourly"));
+        assert_eq!(server.timings.len(), 1);
+        assert_eq!(server.timings[0].name, "always");
+        assert_eq!(runs[0].report.servers[0].timings.len(), 2);
+
+        // During maintenance, the hourly check keeps its schedule, its results being muted
+        let config: Config = serde_yaml::from_str(
+            "servers:\n\
+             - {name: self, host: localhost, local: true, \
+             maintenance: [{start: 2000-01-01T00:00:00Z, end: 2100-01-01T00:00:00Z}], \
+             checks: {hourly: {command: echo hourly, every: 1h}}}",
+        )
+        .unwrap();
+        let mut scheduler = Scheduler::default();
+        let mut timings = vec![];
+        for _ in 0..2 {
+            let run = run(
+                &config,
+                &[],
+                None,
+                &SshConfig::default(),
+                &mut SessionPool::new(),
+                &mut State::default(),
+                &mut scheduler,
+            )
+            .unwrap();
+            let server = &run.report.servers[0];
+            assert!(server.results.iter().all(|r| r.muted));
+            timings.push(server.timings.len());
+        }
+        assert_eq!(timings, [1, 0]);
+    }
+
+    #[test]
+    fn test_run_with_retries() {
+        let flag = std::env::temp_dir().join("remotesysmonitor-test-retries");
+        std::fs::remove_file(&flag).ok();
+        // Fails on the first attempt only
+        let command = format!("test -f {0} || (touch {0}; exit 1)", flag.display());
+        let config: Config = serde_yaml::from_str(&format!(
+            "servers:\n\
+             - {{name: self, host: localhost, local: true, \
+             checks: {{flaky: {{command: '{}', retries: 1, retry_delay: 0}}}}}}",
+            command
+        ))
+        .unwrap();
+        let mut pool = SessionPool::new();
+        let mut state = State::default();
+
+        let run = run(
+            &config,
+            &[],
+            None,
+            &SshConfig::default(),
+            &mut pool,
+            &mut state,
+            &mut Scheduler::default(),
+        )
+        .unwrap();
+        let results = &run.report.servers[0].results;
+        assert_eq!(results[0].status, Status::Info);
+        assert!(run.changes.is_empty());
+    }
+
+    #[test]
+    fn test_detect_reboot() {
+        let mut state = State::default();
+        let now = chrono::Utc::now();
+        let uptime = |boot: chrono::DateTime<chrono::Utc>| {
+            vec![CheckResult::new(Status::Ok, "up 3 days @ web03")
+                .with_observation(Observation::Boot(boot))]
+        };
+
+        let boot = now - chrono::Duration::days(3);
+        let mut results = uptime(boot);
+        detect_reboot(
+            &mut state,
+            "web03/uptime",
+            "web03",
+            Status::Critical,
+            &mut results,
+            now,
+        );
+        assert_eq!(results[0].status, Status::Ok);
+        assert_eq!(results[0].observation, None);
+
+        let boot = now - chrono::Duration::minutes(14);
+        let mut results = uptime(boot);
+        detect_reboot(
+            &mut state,
+            "web03/uptime",
+            "web03",
+            Status::Critical,
+            &mut results,
+            now,
+        );
+        assert_eq!(results[0].status, Status::Critical);
+        assert_eq!(results[0].message, "web03 rebooted 14 minutes ago");
+    }
+
+    #[test]
+    fn test_forget_reported_kills() {
+        let mut state = State::default();
+        let now = chrono::Utc::now();
+        let kill = |minutes: i64| {
+            let time = now - chrono::Duration::minutes(minutes);
+            CheckResult::new(
+                Status::Critical,
+                format!("java killed {} minutes ago", minutes),
+            )
+            .with_check("oom")
+            .with_observation(Observation::Kill(time))
+        };
+
+        let mut results = vec![kill(90), kill(30)];
+        forget_reported_kills(&mut state, "web01/oom", "web01", &mut results);
+        assert_eq!(results.len(), 2);
+        assert_eq!(results[0].observation, None);
+
+        let mut results = vec![kill(90), kill(30)];
+        forget_reported_kills(&mut state, "web01/oom", "web01", &mut results);
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].status, Status::Ok);
+        assert_eq!(results[0].check, "oom");
+        assert_eq!(
+            results[0].message,
+            "no OOM kills since the previous run @ web01"
+        );
+
+        let mut results = vec![kill(90), kill(30), kill(1)];
+        forget_reported_kills(&mut state, "web01/oom", "web01", &mut results);
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].message, "java killed 1 minutes ago");
+    }
+
+    #[test]
+    fn test_summarize_logins() {
+        let mut state = State::default();
+        let now = chrono::Utc::now();
+        let login = |status: Status, user: &str, minutes: i64| {
+            CheckResult::new(status, format!("{} logged in from 192.0.2.5 @ web01", user))
+                .with_check("logins")
+                .with_observation(Observation::Login(now - chrono::Duration::minutes(minutes)))
+        };
+        let failed = |source: &str, minutes: i64| {
+            CheckResult::new(Status::Ok, source)
+                .with_check("logins")
+                .with_observation(Observation::FailedLogin(
+                    now - chrono::Duration::minutes(minutes),
+                ))
+        };
+        let run = |state: &mut State| {
+            let mut results = vec![
+                login(Status::Ok, "alice", 90),
+                login(Status::Critical, "mallory", 60),
+                login(Status::Ok, "bob", 30),
+                // Several failures in the same second are all counted
+                failed("203.0.113.7", 40),
+                failed("203.0.113.7", 40),
+                failed("198.51.100.3", 20),
+            ];
+            summarize_logins(
+                state,
+                "web01/logins",
+                "web01",
+                Some(2),
+                Some(10),
+                &mut results,
+            );
+            results
+        };
+
+        let results = run(&mut state);
+        let messages: Vec<(Status, &str)> = results
+            .iter()
+            .map(|r| (r.status, r.message.as_str()))
+            .collect();
+        assert_eq!(
+            messages,
+            [
+                (Status::Critical, "mallory logged in from 192.0.2.5 @ web01"),
+                (
+                    Status::Ok,
+                    "2 logins since the previous run (alice, bob) @ web01"
+                ),
+                (
+                    Status::Warning,
+                    "3 failed login attempts since the previous run @ web01"
+                ),
+            ]
+        );
+        assert_eq!(results[0].observation, None);
+        assert_eq!(
+            results[2].details.as_deref(),
+            Some("     2  203.0.113.7\n     1  198.51.100.3")
+        );
+
+        // The next run finds the same logins
+        let results = run(&mut state);
+        assert_eq!(results.len(), 2);
+        assert_eq!(
+            results[0].message,
+            "no login since the previous run @ web01"
+        );
+        assert_eq!(results[1].status, Status::Ok);
+    }
+
+    #[test]
+    fn test_detect_file_changes() {
+        let mut state = State::default();
+        let read = |passwd: &str| {
+            vec![
+                CheckResult::new(Status::Ok, "/etc/passwd")
+                    .with_check("accounts")
+                    .with_details(passwd.to_string())
+                    .with_observation(Observation::File),
+                CheckResult::new(Status::Ok, "/etc/group")
+                    .with_check("accounts")
+                    .with_details("root:x:0:".to_string())
+                    .with_observation(Observation::File),
+            ]
+        };
+
+        let mut results = read("root:x:0:0::/root:/bin/sh");
+        detect_file_changes(
+            &mut state,
+            "web01/accounts",
+            "web01",
+            Status::Critical,
+            &mut results,
+        );
+        assert_eq!(results.len(), 1);
+        assert_eq!(
+            results[0].message,
+            "2 files unchanged since the previous run @ web01"
+        );
+        assert_eq!(results[0].check, "accounts");
+
+        let mut results = read("root:x:0:0::/root:/bin/sh\nbob:x:1001:1001::/home/bob:/bin/sh");
+        detect_file_changes(
+            &mut state,
+            "web01/accounts",
+            "web01",
+            Status::Critical,
+            &mut results,
+        );
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].status, Status::Critical);
+        assert_eq!(
+            results[0].message,
+            "/etc/passwd changed since the previous run @ web01"
+        );
+        assert_eq!(
+            results[0].details.as_deref(),
+            Some("+ bob:x:1001:1001::/home/bob:/bin/sh")
+        );
+        assert_eq!(results[0].observation, None);
+
+        let mut results = read("root:x:0:0::/root:/bin/sh\nbob:x:1001:1001::/home/bob:/bin/sh");
+        results.remove(1);
+        detect_file_changes(
+            &mut state,
+            "web01/accounts",
+            "web01",
+            Status::Critical,
+            &mut results,
+        );
+        assert_eq!(results.len(), 1);
+        assert_eq!(
+            results[0].message,
+            "/etc/group removed since the previous run @ web01"
+        );
+
+        let mut results = vec![CheckResult::new(Status::Critical, "Error: timeout")];
+        detect_file_changes(
+            &mut state,
+            "web01/accounts",
+            "web01",
+            Status::Critical,
+            &mut results,
+        );
+        assert_eq!(results[0].message, "Error: timeout");
+        assert!(state.file_lines["web01/accounts"].contains_key("/etc/passwd"));
+    }
+
+    #[test]
+    fn test_line_changes() {
+        let hashes = state::line_hashes;
+        assert_eq!(
+            line_changes(&hashes("a\nb\nc"), "a\nc\nd"),
+            "- 1 line removed\n+ d"
+        );
+        assert_eq!(line_changes(&hashes("a\nb"), "b\na"), "lines reordered");
+        let many: Vec<String> = (0..25).map(|i| i.to_string()).collect();
+        let changes = line_changes(&[], &many.join("\n"));
+        assert!(changes.ends_with("+ 19\n… 5 more lines"));
+    }
+
+    #[test]
+    fn test_attach_top_processes() {
+        let server: Server = serde_yaml::from_str("{name: web01, host: web01}").unwrap();
+        let load: Check = serde_yaml::from_str("{interval: 5}").unwrap();
+        let runner = crate::runner::MockRunner::new().ok("ps aux --sort=-%cpu | head -n 6", "ps");
+        let platform = Platform::default();
+
+        let mut results = vec![
+            CheckResult::new(Status::Critical, "Error: Could not parse load average"),
+            CheckResult::new(Status::Warning, "load 30").with_metric("load5", 30.0),
+        ];
+        attach_top_processes(&server, platform, &runner, &load, &mut results);
+        assert_eq!(results[0].details, None);
+        assert_eq!(results[1].details.as_deref(), Some("ps"));
+
+        let mut results = vec![CheckResult::new(Status::Ok, "load 1").with_metric("load5", 1.0)];
+        attach_top_processes(&server, platform, &runner, &load, &mut results);
+        assert_eq!(results[0].details, None);
+
+        let command: Check = serde_yaml::from_str("{command: ls}").unwrap();
+        let mut results = vec![CheckResult::new(Status::Warning, "x").with_metric("x", 1.0)];
+        attach_top_processes(&server, platform, &runner, &command, &mut results);
+        assert_eq!(results[0].details, None);
+    }
+
+    #[test]
+    fn test_run_with_timeout() {
+        let config: Config = serde_yaml::from_str(
+            "servers:\n\
+             - {name: self, host: localhost, local: true, \
+             checks: {stuck: {command: sleep 10, timeout: 1}, quick: {command: echo hello}}}",
+        )
+        .unwrap();
+        let mut pool = SessionPool::new();
+        let mut state = State::default();
+
+        let start = std::time::Instant::now();
+        let run = run(
+            &config,
+            &[],
+            None,
+            &SshConfig::default(),
+            &mut pool,
+            &mut state,
+            &mut Scheduler::default(),
+        )
+        .unwrap();
+        assert!(start.elapsed() < Duration::from_secs(5));
+        let results = &run.report.servers[0].results;
+        assert_eq!(results.len(), 2);
+        assert_eq!(results[0].check, "quick");
+        assert_eq!(results[1].check, "stuck");
+        assert_eq!(results[1].status, Status::Critical);
+        assert_eq!(results[1].message, "⏱ timed out after 1s");
+    }
+
+    #[test]
+    fn test_dry_run() {
+        let config: Config = serde_yaml::from_str(
+            "servers:\n\
+             - {name: web, host: web01, port: 2222, user: u, private_key: k, notify: [ops], \
+             checks: {ping: {url: [/, /health]}, load: {interval: 5}}}\n\
+             - {name: self, host: localhost, local: true, \
+             checks: {disk: {command: df -h /}}}",
+        )
+        .unwrap();
+        let notifiers: Vec<Notifier> = serde_yaml::from_str(
+            "- {name: ops, type: slack, url: 'http://localhost', min_status: warning}\n\
+             - {name: mail, type: teams, url: 'http://localhost'}",
+        )
+        .unwrap();
+
+        let plan = dry_run(&config, &[], &SshConfig::default(), &notifiers).unwrap();
+        assert_eq!(
+            plan,
+            "web (u@web01:2222):\n\
+             \x20  load: uptime\n\
+             \x20  ping: GET https://web01/\n\
+             \x20  ping: GET https://web01/health\n\
+             self (local):\n\
+             \x20  disk: df -h /\n\
+             Notifiers:\n\
+             \x20  ops: web, self from ⚠️, or on recoveries\n\
+             \x20  mail: self from ❌, or on recoveries"
+        );
+    }
+}
@@ -0,0 +1,135 @@
+use crate::report::{Report, Status};
+
+/// Renders a report as the output of a Nagios plugin, for the given exit code (0 to 3, as the
+/// exit codes of a run).
+///
+/// The first line gives the state and a summary of the failing results, followed by the values
+/// measured by the checks as performance data, labelled `<server>/<check>/<metric>`. The failing
+/// results are then listed one per line, e.g.:
+///
+/// ```text
+/// CRITICAL - 1 critical, 1 warning: load 60.00 (5min) @ web01 | 'web01/load/load5'=60
+/// CRITICAL: load 60.00 (5min) @ web01
+/// WARNING: 120 folders @ `db01:/data`
+/// ```
+///
+/// Muted results are left out of the summary, as they are of the exit code.
+pub fn plugin_output(report: &Report, exit_code: i32) -> String {
+    let state = match exit_code {
+        0 => "OK",
+        1 => "WARNING",
+        2 => "CRITICAL",
+        _ => "UNKNOWN",
+    };
+
+    let mut failing = vec![];
+    let mut results = 0;
+    for server in &report.servers {
+        for result in server.results.iter().filter(|r| !r.muted) {
+            results += 1;
+            if result.status >= Status::Warning {
+                failing.push(result);
+            }
+        }
+    }
+    // Worst first, in the order of the report otherwise
+    failing.sort_by_key(|r| std::cmp::Reverse(r.status));
+
+    let summary = match failing.first() {
+        None => format!(
+            "{} checks passed on {} servers",
+            results,
+            report.servers.len()
+        ),
+        Some(worst) => {
+            let criticals = failing
+                .iter()
+                .filter(|r| r.status == Status::Critical)
+                .count();
+            let mut counts = vec![];
+            if criticals > 0 {
+                counts.push(format!("{} critical", criticals));
+            }
+            if failing.len() > criticals {
+                counts.push(format!("{} warning", failing.len() - criticals));
+            }
+            format!("{}: {}", counts.join(", "), worst.message)
+        }
+    };
+
+    let perfdata: Vec<String> = report
+        .servers
+        .iter()
+        .flat_map(|server| {
+            server.results.iter().filter_map(move |result| {
+                let metric = result.metric.as_ref()?;
+                let label = format!("{}/{}/{}", server.name, result.check, metric.name);
+                Some(format!("'{}'={}", label.replace('\'', "''"), metric.value))
+            })
+        })
+        .collect();
+
+    // The separator of the performance data is not allowed in the rest of the output
+    let mut output = format!("{} - {}", state, summary.replace('|', "/"));
+    if !perfdata.is_empty() {
+        output.push_str(" | ");
+        output.push_str(&perfdata.join(" "));
+    }
+    for result in &failing {
+        let status = match result.status {
+            Status::Critical => "CRITICAL",
+            _ => "WARNING",
+        };
+        output.push_str(&format!(
+            "\n{}: {}",
+            status,
+            result.message.replace('|', "/")
+        ));
+    }
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::report::{CheckResult, ServerReport};
+
+    #[test]
+    fn test_plugin_output() {
+        let mut web = ServerReport::new("web01", "web01.example.com");
+        web.results.push(
+            CheckResult::new(Status::Ok, "load 1.00 (5min) @ web01")
+                .with_check("load")
+                .with_metric("load5", 1.0),
+        );
+        let mut db = ServerReport::new("db01", "db01.example.com");
+        db.results.push(
+            CheckResult::new(Status::Warning, "120 folders @ `db01:/data`")
+                .with_check("number_of_subfolders")
+                .with_metric("/data", 120.0),
+        );
+        let mut report = Report {
+            notes: vec![],
+            servers: vec![web, db],
+        };
+        assert_eq!(
+            plugin_output(&report, 1),
+            "WARNING - 1 warning: 120 folders @ `db01:/data` \
+             | 'web01/load/load5'=1 'db01/number_of_subfolders//data'=120\n\
+             WARNING: 120 folders @ `db01:/data`"
+        );
+
+        report.servers[0].results[0].status = Status::Critical;
+        report.servers[0].results[0].message = "ps | grep: 2 processes".to_string();
+        assert!(plugin_output(&report, 2)
+            .starts_with("CRITICAL - 1 critical, 1 warning: ps / grep: 2 processes | 'web01"));
+
+        report.servers[1].results.clear();
+        report.servers[0].results[0].status = Status::Ok;
+        report.servers[0].results[0].metric = None;
+        assert_eq!(
+            plugin_output(&report, 0),
+            "OK - 1 checks passed on 2 servers"
+        );
+    }
+}
@@ -0,0 +1,645 @@
+use crate::config::Server;
+use crate::incidents;
+use crate::pushover::{self, PushoverConfig};
+use crate::report::{Markers, Report, Status};
+use crate::slack::{self, SlackConfig};
+use crate::state::{self, Change};
+use crate::teams;
+use crate::template::Templates;
+use crate::utils;
+use lettre::message::header::ContentType;
+use lettre::transport::smtp::authentication::Credentials;
+use lettre::{Message, SmtpTransport, Transport};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// A destination the reports are sent to.
+///
+/// Every notifier receives the servers routed to it (all servers unless a server restricts its
+/// `notify` list) and is only used when the worst result among them reaches `min_status`, or
+/// when a check recovered.
+///
+/// ```yaml
+/// notifiers:
+///   - name: ops
+///     type: slack
+///     url_env: OPS_SLACK_HOOK_URL
+///     min_status: warning
+///   - name: pager
+///     type: slack
+///     url_env: PAGER_SLACK_HOOK_URL
+///     mention: "<!channel>"
+///   - name: fleet
+///     type: slack_api
+///     token_env: SLACK_BOT_TOKEN
+///     channel: "#fleet"
+///     thread: true
+///   - name: teams
+///     type: teams
+///     url_env: TEAMS_WEBHOOK_URL
+///   - name: mail
+///     type: email
+///     smtp_host: smtp.example.com
+///     username: monitor@example.com
+///     password_env: SMTP_PASSWORD
+///     from: monitor@example.com
+///     to: [ops@example.com]
+///   - name: oncall
+///     type: pagerduty
+///     routing_key_env: PAGERDUTY_ROUTING_KEY
+/// ```
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Notifier {
+    /// Name of the notifier, used by the servers to route their results.
+    pub name: String,
+    /// Minimum status of the results for the notifier to be used, `critical` by default.
+    #[serde(default = "default_min_status")]
+    pub min_status: Status,
+    /// Templates of the plain text reports, used by emails, Pushover and Slack messages in the
+    /// `text` format.
+    pub templates: Option<Templates>,
+    /// Markers of the statuses and servers, overriding the `markers` of the `general` settings,
+    /// e.g. plain text markers for email.
+    pub markers: Option<Markers>,
+    /// Kind of the notifier and its settings.
+    #[serde(flatten)]
+    pub kind: NotifierKind,
+}
+
+fn default_min_status() -> Status {
+    Status::Critical
+}
+
+/// The supported kinds of notifiers, selected by the `type` field.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum NotifierKind {
+    /// Post the report to a Slack incoming webhook.
+    Slack {
+        /// Webhook URL of the channel.
+        url: Option<String>,
+        /// Environment variable holding the webhook URL, to keep it out of the configuration.
+        url_env: Option<String>,
+        /// Layout and mention of the messages.
+        #[serde(flatten)]
+        settings: SlackConfig,
+    },
+    /// Post the report with the Slack Web API, as the bot of a Slack app, e.g. to thread the
+    /// servers under a summary message.
+    #[serde(rename = "slack_api")]
+    SlackApi {
+        /// Bot token of the Slack app (`xoxb-...`), with the `chat:write` scope.
+        token: Option<String>,
+        /// Environment variable holding the bot token.
+        token_env: Option<String>,
+        /// Name or ID of the channel to post to, which the bot must be a member of.
+        channel: String,
+        /// Channels to post to instead of `channel` depending on the worst status of the report,
+        /// e.g. `{critical: "#oncall"}` to only wake people up for critical reports.
+        #[serde(default)]
+        channels: BTreeMap<Status, String>,
+        /// Post one summary message per run, with the results of every server in its thread.
+        #[serde(default)]
+        thread: bool,
+        /// Upload the full output of the commands truncated by their `max_lines` or `max_chars`
+        /// as files next to the report, which requires the `files:write` scope.
+        #[serde(default)]
+        upload_outputs: bool,
+        /// Keep a single pinned message with the current status of the servers up to date in
+        /// `channel` at every run, instead of posting reports.
+        #[serde(default)]
+        status_message: bool,
+        /// Base URL of the Web API, `https://slack.com/api` by default.
+        api_url: Option<String>,
+        /// Layout and mention of the messages.
+        #[serde(flatten)]
+        settings: SlackConfig,
+    },
+    /// Post the report to a Microsoft Teams incoming webhook as Adaptive Cards. Mentions are
+    /// not supported.
+    Teams {
+        /// Webhook URL of the channel.
+        url: Option<String>,
+        /// Environment variable holding the webhook URL.
+        url_env: Option<String>,
+    },
+    /// Send the report by email through an SMTP server.
+    Email(EmailConfig),
+    /// Trigger a PagerDuty event for every failing check, and resolve it once the check recovers
+    /// (requires a `state_file`).
+    Pagerduty {
+        /// Integration key of the PagerDuty service.
+        routing_key: Option<String>,
+        /// Environment variable holding the integration key.
+        routing_key_env: Option<String>,
+        /// Events API endpoint, the public PagerDuty endpoint by default.
+        url: Option<String>,
+    },
+    /// Create an Opsgenie alert for every failing check, and close it once the check recovers
+    /// (requires a `state_file`).
+    Opsgenie {
+        /// API key of the Opsgenie integration.
+        api_key: Option<String>,
+        /// Environment variable holding the API key.
+        api_key_env: Option<String>,
+        /// API endpoint, e.g. `https://api.eu.opsgenie.com` for EU accounts.
+        api_url: Option<String>,
+    },
+    /// Send the report as a Pushover message, with a priority depending on its worst status.
+    Pushover(PushoverConfig),
+}
+
+/// Settings of an email notifier.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct EmailConfig {
+    /// Hostname of the SMTP server.
+    pub smtp_host: String,
+    /// Port of the SMTP server, the default port of the `security` mode is used if omitted.
+    pub smtp_port: Option<u16>,
+    /// How the connection to the SMTP server is secured, `starttls` by default.
+    #[serde(default)]
+    pub security: SmtpSecurity,
+    /// User to authenticate as, no authentication is performed if omitted.
+    pub username: Option<String>,
+    /// Environment variable holding the password of `username`.
+    pub password_env: Option<String>,
+    /// Sender of the emails, e.g. `RemoteSysMonitor <monitor@example.com>`.
+    pub from: String,
+    /// Recipients of the emails.
+    pub to: Vec<String>,
+}
+
+/// Encryption of the connection to an SMTP server.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SmtpSecurity {
+    /// Upgrade a plain connection with STARTTLS, on port 587 by default.
+    #[default]
+    Starttls,
+    /// Implicit TLS, on port 465 by default.
+    Tls,
+    /// No encryption, e.g. for a relay on the local network, on port 25 by default.
+    None,
+}
+
+impl Notifier {
+    /// Creates the Slack notifier used when no `notifiers` are configured, posting to the
+    /// webhook given by the `SLACK_HOOK_URL` environment variable.
+    pub fn slack(url: &str, settings: SlackConfig) -> Notifier {
+        Notifier {
+            name: "slack".to_string(),
+            min_status: default_min_status(),
+            templates: None,
+            markers: None,
+            kind: NotifierKind::Slack {
+                url: Some(url.to_string()),
+                url_env: None,
+                settings,
+            },
+        }
+    }
+
+    /// Creates the Slack notifier used when no `notifiers` are configured and the bot token of a
+    /// Slack app is given by the `SLACK_BOT_TOKEN` environment variable, posting with the Web API
+    /// to `channel`.
+    pub fn slack_api(channel: &str, settings: SlackConfig) -> Notifier {
+        Notifier {
+            name: "slack".to_string(),
+            min_status: default_min_status(),
+            templates: None,
+            markers: None,
+            kind: NotifierKind::SlackApi {
+                token: None,
+                token_env: Some("SLACK_BOT_TOKEN".to_string()),
+                channel: channel.to_string(),
+                channels: BTreeMap::new(),
+                thread: false,
+                upload_outputs: false,
+                status_message: false,
+                api_url: None,
+                settings,
+            },
+        }
+    }
+
+    /// Returns whether the notifier delivers the reports as messages to be read, as opposed to
+    /// incident trackers (PagerDuty, Opsgenie) which only track the failing checks.
+    pub fn sends_messages(&self) -> bool {
+        !matches!(
+            self.kind,
+            NotifierKind::Pagerduty { .. } | NotifierKind::Opsgenie { .. }
+        )
+    }
+
+    /// Returns whether the notifier keeps a status up to date (see `slack::post_status`), and is
+    /// thus used at every run whatever the status of the report.
+    pub fn keeps_status(&self) -> bool {
+        matches!(
+            self.kind,
+            NotifierKind::SlackApi {
+                status_message: true,
+                ..
+            }
+        )
+    }
+
+    /// Returns whether a server is routed to this notifier: servers without a `notify` list are
+    /// routed to every notifier, the others only to the notifiers they name.
+    fn routes(&self, server: &str, servers: &[Server]) -> bool {
+        servers
+            .iter()
+            .find(|s| s.name == server)
+            .and_then(|s| s.notify.as_ref())
+            .is_none_or(|notify| notify.contains(&self.name))
+    }
+
+    /// Returns the part of the report routed to this notifier (see `routes`).
+    ///
+    /// The notes about a check (e.g. its recovery) are kept if its server is routed, the other
+    /// notes (e.g. that the run was interrupted) always are.
+    pub fn report_for(&self, report: &Report, servers: &[Server]) -> Report {
+        Report {
+            notes: report
+                .notes
+                .iter()
+                .filter(|n| {
+                    n.check.is_empty() || self.routes(state::split_key(&n.check).0, servers)
+                })
+                .cloned()
+                .collect(),
+            servers: report
+                .servers
+                .iter()
+                .filter(|s| self.routes(&s.name, servers))
+                .cloned()
+                .collect(),
+        }
+    }
+
+    /// Returns the changes of status of the checks of the servers routed to this notifier.
+    pub fn changes_for(&self, changes: &[Change], servers: &[Server]) -> Vec<Change> {
+        changes
+            .iter()
+            .filter(|change| {
+                let (Change::Failed(key) | Change::Recovered(key) | Change::StillFailing(key)) =
+                    change;
+                self.routes(state::split_key(key).0, servers)
+            })
+            .cloned()
+            .collect()
+    }
+
+    /// Returns whether a report (as returned by `report_for`) deserves a notification.
+    pub fn wants(&self, report: &Report) -> bool {
+        report.status() >= self.min_status || !report.notes.is_empty()
+    }
+
+    /// Sends a report.
+    ///
+    /// # Arguments
+    ///
+    /// * `report` - The report to send, usually returned by `report_for`.
+    /// * `changes` - The changes of status of the run, usually returned by `changes_for`, used to
+    ///   resolve incidents.
+    /// * `separator` - The separator between servers in plain text reports.
+    /// * `markers` - The markers of the statuses and servers, unless the notifier has its own.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the notifier is misconfigured (e.g. its URL is missing or one of its
+    /// templates is invalid) or if the report could not be delivered.
+    pub fn send(
+        &self,
+        report: &Report,
+        changes: &[Change],
+        separator: &str,
+        markers: &Markers,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let markers = self.markers.as_ref().unwrap_or(markers);
+        let text = match &self.templates {
+            Some(templates) => templates.render(report, separator, markers)?,
+            None => report.to_text_with(separator, markers),
+        };
+        match &self.kind {
+            NotifierKind::Slack {
+                url,
+                url_env,
+                settings,
+            } => {
+                let url = secret(url.as_deref(), url_env.as_deref(), "url")?;
+                slack::post_report(&url, report, settings, &text, markers)
+            }
+            NotifierKind::SlackApi {
+                token,
+                token_env,
+                channel,
+                channels,
+                thread,
+                upload_outputs,
+                status_message,
+                api_url,
+                settings,
+            } => {
+                let token = secret(token.as_deref(), token_env.as_deref(), "token")?;
+                let api_url = api_url.as_deref().unwrap_or(slack::SLACK_API_URL);
+                if *status_message {
+                    return slack::post_status(api_url, &token, channel, report, settings, markers);
+                }
+                let channel = slack::channel_for(channel, channels, report.status());
+                slack::post_report_api(
+                    api_url,
+                    &token,
+                    channel,
+                    report,
+                    settings,
+                    *thread,
+                    *upload_outputs,
+                    &text,
+                    markers,
+                )
+            }
+            NotifierKind::Teams { url, url_env } => {
+                let url = secret(url.as_deref(), url_env.as_deref(), "url")?;
+                teams::post_report(&url, report, markers)
+            }
+            NotifierKind::Email(config) => send_email(config, report, &text, markers),
+            NotifierKind::Pagerduty {
+                routing_key,
+                routing_key_env,
+                url,
+            } => {
+                let key = secret(
+                    routing_key.as_deref(),
+                    routing_key_env.as_deref(),
+                    "routing_key",
+                )?;
+                let url = url.as_deref().unwrap_or(incidents::PAGERDUTY_EVENTS_URL);
+                incidents::pagerduty(url, &key, report, changes)
+            }
+            NotifierKind::Opsgenie {
+                api_key,
+                api_key_env,
+                api_url,
+            } => {
+                let key = secret(api_key.as_deref(), api_key_env.as_deref(), "api_key")?;
+                let url = api_url.as_deref().unwrap_or(incidents::OPSGENIE_API_URL);
+                incidents::opsgenie(url, &key, report, changes)
+            }
+            NotifierKind::Pushover(config) => {
+                let token = secret(
+                    config.token.as_deref(),
+                    config.token_env.as_deref(),
+                    "token",
+                )?;
+                let user = secret(config.user.as_deref(), config.user_env.as_deref(), "user")?;
+                let url = pushover::PUSHOVER_API_URL;
+                pushover::post_report(url, &token, &user, config, report, &text, markers)
+            }
+        }
+    }
+}
+
+/// Returns a secret given either directly or through an environment variable.
+///
+/// # Errors
+///
+/// Returns an error if the environment variable is not set, or if neither is given.
+pub fn secret(
+    value: Option<&str>,
+    env: Option<&str>,
+    field: &str,
+) -> Result<String, Box<dyn std::error::Error>> {
+    match (value, env) {
+        (Some(value), _) => Ok(value.to_string()),
+        (None, Some(env)) => std::env::var(env)
+            .map_err(|_| format!("environment variable {} is not set", env).into()),
+        (None, None) => Err(format!("neither `{}` nor `{}_env` is set", field, field).into()),
+    }
+}
+
+/// Builds the email of a report, with its plain text rendering as body.
+fn email_message(
+    config: &EmailConfig,
+    report: &Report,
+    text: &str,
+    markers: &Markers,
+) -> Result<Message, Box<dyn std::error::Error>> {
+    let subject = format!(
+        "{} RemoteSysMonitor · {}",
+        markers.status(report.status()),
+        utils::make_pretty_timestamp()
+    );
+    let mut builder = Message::builder()
+        .from(config.from.parse()?)
+        .subject(subject)
+        .header(ContentType::TEXT_PLAIN);
+    for to in &config.to {
+        builder = builder.to(to.parse()?);
+    }
+    Ok(builder.body(text.to_string())?)
+}
+
+/// Sends a report by email.
+fn send_email(
+    config: &EmailConfig,
+    report: &Report,
+    text: &str,
+    markers: &Markers,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let message = email_message(config, report, text, markers)?;
+
+    let mut transport = match config.security {
+        SmtpSecurity::Starttls => SmtpTransport::starttls_relay(&config.smtp_host)?,
+        SmtpSecurity::Tls => SmtpTransport::relay(&config.smtp_host)?,
+        SmtpSecurity::None => SmtpTransport::builder_dangerous(&config.smtp_host),
+    };
+    if let Some(port) = config.smtp_port {
+        transport = transport.port(port);
+    }
+    if let Some(username) = &config.username {
+        let password = secret(None, config.password_env.as_deref(), "password")?;
+        transport = transport.credentials(Credentials::new(username.clone(), password));
+    }
+
+    transport.build().send(&message)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::report::{CheckResult, ServerReport};
+
+    fn server(name: &str, notify: Option<Vec<&str>>) -> Server {
+        let yaml = format!(
+            "{{name: {}, host: {}.example.com, port: 22, user: monitor, private_key: key}}",
+            name, name
+        );
+        let mut server: Server = serde_yaml::from_str(&yaml).unwrap();
+        server.notify = notify.map(|n| n.into_iter().map(String::from).collect());
+        server
+    }
+
+    #[test]
+    fn test_deserialize_notifiers() {
+        let yaml = r#"
+- name: ops
+  type: slack
+  url_env: OPS_URL
+  min_status: warning
+  format: text
+- name: teams
+  type: teams
+  url: https://example.webhook.office.com/webhookb2/abc
+- name: mail
+  type: email
+  smtp_host: smtp.example.com
+  from: monitor@example.com
+  to: [ops@example.com]
+- name: fleet
+  type: slack_api
+  token_env: SLACK_BOT_TOKEN
+  channel: C0123ABC
+  channels: {critical: C0456DEF}
+  thread: true
+  mention: none
+"#;
+        let notifiers: Vec<Notifier> = serde_yaml::from_str(yaml).unwrap();
+        assert_eq!(notifiers[0].min_status, Status::Warning);
+        match &notifiers[0].kind {
+            NotifierKind::Slack {
+                url_env, settings, ..
+            } => {
+                assert_eq!(url_env.as_deref(), Some("OPS_URL"));
+                assert_eq!(settings.format, slack::SlackFormat::Text);
+            }
+            _ => panic!("expected a Slack notifier"),
+        }
+        assert!(matches!(notifiers[1].kind, NotifierKind::Teams { .. }));
+        assert_eq!(notifiers[2].min_status, Status::Critical);
+        match &notifiers[2].kind {
+            NotifierKind::Email(config) => assert_eq!(config.security, SmtpSecurity::Starttls),
+            _ => panic!("expected an email notifier"),
+        }
+        match &notifiers[3].kind {
+            NotifierKind::SlackApi {
+                channel,
+                channels,
+                thread,
+                settings,
+                ..
+            } => {
+                assert_eq!(channel, "C0123ABC");
+                assert_eq!(channels[&Status::Critical], "C0456DEF");
+                assert!(thread);
+                assert_eq!(settings.mention_for(Status::Critical), None);
+            }
+            _ => panic!("expected a Slack API notifier"),
+        }
+    }
+
+    #[test]
+    fn test_routing() {
+        let servers = vec![server("web01", None), server("db01", Some(vec!["pager"]))];
+        let mut report = Report::default();
+        let mut web = ServerReport::new("web01", "web01.example.com");
+        web.results
+            .push(CheckResult::new(Status::Warning, "load 30.00"));
+        report.servers.push(web);
+        let mut db = ServerReport::new("db01", "db01.example.com");
+        db.results.push(CheckResult::new(Status::Critical, "down"));
+        report.servers.push(db);
+
+        let notifier = |name: &str, min_status| Notifier {
+            name: name.to_string(),
+            min_status,
+            ..Notifier::slack("https://example.com", SlackConfig::default())
+        };
+
+        let ops = notifier("ops", Status::Warning);
+        let routed = ops.report_for(&report, &servers);
+        assert_eq!(routed.servers.len(), 1);
+        assert_eq!(routed.servers[0].name, "web01");
+        assert!(ops.wants(&routed));
+
+        let pager = notifier("pager", Status::Critical);
+        assert_eq!(pager.report_for(&report, &servers).servers.len(), 2);
+
+        // Warnings alone do not reach a notifier for critical results
+        report.servers.pop();
+        assert!(!pager.wants(&pager.report_for(&report, &servers)));
+    }
+
+    #[test]
+    fn test_routing_notes_and_changes() {
+        let servers = vec![
+            server("web01", Some(vec!["ops"])),
+            server("db01", Some(vec!["pager"])),
+        ];
+        let mut report = Report::default();
+        report
+            .notes
+            .push(CheckResult::new(Status::Ok, "recovered: disk @ db01").with_check("db01/disk"));
+        report.notes.push(CheckResult::new(
+            Status::Warning,
+            "run interrupted by a shutdown",
+        ));
+        report.servers.push(ServerReport::new("web01", "web01"));
+        report.servers.push(ServerReport::new("db01", "db01"));
+        let changes = vec![Change::Recovered("db01/disk".to_string())];
+        let notifier = |name: &str| Notifier {
+            name: name.to_string(),
+            ..Notifier::slack("https://example.com", SlackConfig::default())
+        };
+
+        // The recovery of a server only reaches the notifiers it is routed to
+        let ops = notifier("ops");
+        let routed = ops.report_for(&report, &servers);
+        assert_eq!(routed.notes.len(), 1);
+        assert_eq!(routed.notes[0].message, "run interrupted by a shutdown");
+        assert!(ops.changes_for(&changes, &servers).is_empty());
+
+        let pager = notifier("pager");
+        let routed = pager.report_for(&report, &servers);
+        assert_eq!(routed.notes.len(), 2);
+        assert_eq!(routed.servers[0].name, "db01");
+        assert_eq!(pager.changes_for(&changes, &servers), changes);
+
+        report.notes.pop();
+        assert!(!ops.wants(&ops.report_for(&report, &servers)));
+        assert!(pager.wants(&pager.report_for(&report, &servers)));
+    }
+
+    #[test]
+    fn test_email_message() {
+        let config = EmailConfig {
+            smtp_host: "smtp.example.com".to_string(),
+            smtp_port: None,
+            security: SmtpSecurity::None,
+            username: None,
+            password_env: None,
+            from: "RemoteSysMonitor <monitor@example.com>".to_string(),
+            to: vec!["ops@example.com".to_string()],
+        };
+        let mut report = Report::default();
+        report.notes.push(CheckResult::new(Status::Ok, "recovered"));
+        let message =
+            email_message(&config, &report, &report.to_text("-"), &Markers::default()).unwrap();
+        let formatted = String::from_utf8(message.formatted()).unwrap();
+        assert!(formatted.contains("To: ops@example.com"));
+        assert!(formatted.contains("recovered"));
+
+        let config = EmailConfig {
+            from: "not an address".to_string(),
+            ..config
+        };
+        assert!(email_message(&config, &report, "", &Markers::default()).is_err());
+    }
+
+    #[test]
+    fn test_secret() {
+        assert_eq!(secret(Some("abc"), None, "url").unwrap(), "abc");
+        assert!(secret(None, Some("REMOTESYSMONITOR_UNSET_VARIABLE"), "url").is_err());
+        assert!(secret(None, None, "url").is_err());
+    }
+}
@@ -0,0 +1,288 @@
+use crate::report::{Report, ServerReport, Status, Timing};
+use chrono::{DateTime, Utc};
+use serde_json::{json, Value};
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hasher};
+
+/// Name of the instrumentation scope of the spans and metrics.
+const SCOPE: &str = "remotesysmonitor";
+
+/// Builds the OTLP/JSON payload of the trace of a run, to be posted to `<endpoint>/v1/traces`.
+///
+/// The trace has a root span for the run, a span per server (from its connection to its last
+/// check) and, below it, a span per check and for the SSH connection. Spans of checks (and
+/// servers) with a ❌ have an error status, with the message of the first critical result.
+///
+/// Servers without timings, e.g. of reports not produced by a run, are left out.
+pub fn traces(report: &Report, time: DateTime<Utc>, service_name: &str) -> Value {
+    let trace_id = random_id(16);
+    let root_id = random_id(8);
+    let end = report
+        .servers
+        .iter()
+        .filter_map(|s| s.timing.as_ref().map(Timing::end))
+        .max()
+        .unwrap_or(time)
+        .max(time);
+
+    let mut spans = vec![span(
+        &trace_id,
+        &root_id,
+        None,
+        "run",
+        time,
+        end,
+        report.status(),
+        vec![(
+            "servers",
+            json!({"intValue": report.servers.len().to_string()}),
+        )],
+        error_message(report.servers.iter(), None),
+    )];
+    for server in &report.servers {
+        let Some(timing) = &server.timing else {
+            continue;
+        };
+        let server_id = random_id(8);
+        spans.push(span(
+            &trace_id,
+            &server_id,
+            Some(&root_id),
+            &server.name,
+            timing.start,
+            timing.end(),
+            timing.status,
+            vec![
+                ("server.name", json!({"stringValue": server.name})),
+                ("server.address", json!({"stringValue": server.host})),
+            ],
+            error_message(std::iter::once(server), None),
+        ));
+        for step in &server.timings {
+            spans.push(span(
+                &trace_id,
+                &random_id(8),
+                Some(&server_id),
+                &step.name,
+                step.start,
+                step.end(),
+                step.status,
+                vec![
+                    ("server.name", json!({"stringValue": server.name})),
+                    ("check", json!({"stringValue": step.name})),
+                    ("status", json!({"stringValue": step.status})),
+                ],
+                error_message(std::iter::once(server), Some(&step.name)),
+            ));
+        }
+    }
+
+    json!({
+        "resourceSpans": [{
+            "resource": resource(service_name),
+            "scopeSpans": [{
+                "scope": scope(),
+                "spans": spans,
+            }],
+        }],
+    })
+}
+
+/// Builds the OTLP/JSON payload of the metrics of a run, to be posted to `<endpoint>/v1/metrics`.
+///
+/// Two gauges are recorded: `remotesysmonitor.value`, the values measured by the checks with their
+/// server, check and metric as attributes, and `remotesysmonitor.status`, the status of every
+/// check from 0 (✅) to 3 (❌).
+pub fn metrics(report: &Report, time: DateTime<Utc>, service_name: &str) -> Value {
+    let time = nanos(time);
+    let mut values = vec![];
+    let mut statuses = vec![];
+    for server in &report.servers {
+        for result in &server.results {
+            if let Some(metric) = &result.metric {
+                values.push(json!({
+                    "asDouble": metric.value,
+                    "timeUnixNano": time,
+                    "attributes": attributes(&[
+                        ("server.name", &server.name),
+                        ("check", &result.check),
+                        ("metric", &metric.name),
+                    ]),
+                }));
+            }
+        }
+        for step in server.timings.iter().filter(|t| t.name != "ssh") {
+            statuses.push(json!({
+                "asInt": (step.status as u8).to_string(),
+                "timeUnixNano": time,
+                "attributes": attributes(&[("server.name", &server.name), ("check", &step.name)]),
+            }));
+        }
+    }
+
+    json!({
+        "resourceMetrics": [{
+            "resource": resource(service_name),
+            "scopeMetrics": [{
+                "scope": scope(),
+                "metrics": [
+                    {
+                        "name": "remotesysmonitor.value",
+                        "description": "Value measured by a check",
+                        "gauge": {"dataPoints": values},
+                    },
+                    {
+                        "name": "remotesysmonitor.status",
+                        "description": "Status of a check, from 0 (ok) to 3 (critical)",
+                        "gauge": {"dataPoints": statuses},
+                    },
+                ],
+            }],
+        }],
+    })
+}
+
+/// Builds a span. Times are in nanoseconds since the epoch, as decimal strings as required by
+/// OTLP/JSON for 64 bits integers.
+#[allow(clippy::too_many_arguments)]
+fn span(
+    trace_id: &str,
+    span_id: &str,
+    parent_id: Option<&str>,
+    name: &str,
+    start: DateTime<Utc>,
+    end: DateTime<Utc>,
+    status: Status,
+    attributes: Vec<(&str, Value)>,
+    error: Option<String>,
+) -> Value {
+    let attributes: Vec<Value> = attributes
+        .into_iter()
+        .map(|(key, value)| json!({"key": key, "value": value}))
+        .collect();
+    let status = match (status, error) {
+        (Status::Critical, error) => json!({"code": 2, "message": error.unwrap_or_default()}),
+        _ => json!({"code": 1}),
+    };
+    json!({
+        "traceId": trace_id,
+        "spanId": span_id,
+        "parentSpanId": parent_id.unwrap_or_default(),
+        "name": name,
+        "kind": 1,
+        "startTimeUnixNano": nanos(start),
+        "endTimeUnixNano": nanos(end),
+        "attributes": attributes,
+        "status": status,
+    })
+}
+
+/// Returns the message of the first unmuted critical result of the servers, optionally only
+/// among the results of a check.
+fn error_message<'a>(
+    servers: impl Iterator<Item = &'a ServerReport>,
+    check: Option<&str>,
+) -> Option<String> {
+    servers
+        .flat_map(|s| &s.results)
+        .filter(|r| check.is_none_or(|check| r.check == check))
+        .find(|r| r.status == Status::Critical && !r.muted)
+        .map(|r| r.message.clone())
+}
+
+fn resource(service_name: &str) -> Value {
+    json!({"attributes": attributes(&[("service.name", service_name)])})
+}
+
+fn scope() -> Value {
+    json!({"name": SCOPE, "version": env!("CARGO_PKG_VERSION")})
+}
+
+fn attributes(pairs: &[(&str, &str)]) -> Vec<Value> {
+    pairs
+        .iter()
+        .map(|(key, value)| json!({"key": key, "value": {"stringValue": value}}))
+        .collect()
+}
+
+fn nanos(time: DateTime<Utc>) -> String {
+    time.timestamp_nanos_opt().unwrap_or_default().to_string()
+}
+
+/// Returns a random identifier of `bytes` bytes in hexadecimal, for traces (16 bytes) and spans
+/// (8 bytes), using the randomly seeded hasher of the standard library.
+fn random_id(bytes: usize) -> String {
+    let mut id = String::with_capacity(bytes * 2);
+    while id.len() < bytes * 2 {
+        let mut hasher = RandomState::new().build_hasher();
+        hasher.write_usize(id.len());
+        id.push_str(&format!("{:016x}", hasher.finish()));
+    }
+    id.truncate(bytes * 2);
+    id
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::report::CheckResult;
+    use std::time::Duration;
+
+    fn timing(name: &str, start: DateTime<Utc>, millis: u64, status: Status) -> Timing {
+        Timing {
+            name: name.to_string(),
+            start,
+            duration: Duration::from_millis(millis),
+            status,
+        }
+    }
+
+    #[test]
+    fn test_traces() {
+        let time: DateTime<Utc> = "2024-05-01T10:00:00Z".parse().unwrap();
+        let mut server = ServerReport::new("web01", "web01.example.com");
+        server.results.push(
+            CheckResult::new(Status::Critical, "load 60.00 (5min) @ web01")
+                .with_check("load")
+                .with_metric("load5", 60.0),
+        );
+        server.timing = Some(timing("web01", time, 1500, Status::Critical));
+        server.timings = vec![
+            timing("ssh", time, 500, Status::Ok),
+            timing(
+                "load",
+                time + Duration::from_millis(500),
+                1000,
+                Status::Critical,
+            ),
+        ];
+        let report = Report {
+            notes: vec![],
+            servers: vec![server, ServerReport::new("digest", "")],
+        };
+
+        let payload = traces(&report, time, "monitoring");
+        let spans = payload["resourceSpans"][0]["scopeSpans"][0]["spans"]
+            .as_array()
+            .unwrap();
+        assert_eq!(spans.len(), 4);
+        assert_eq!(spans[0]["name"], "run");
+        assert_eq!(spans[0]["endTimeUnixNano"], "1714557601500000000");
+        assert_eq!(spans[1]["parentSpanId"], spans[0]["spanId"]);
+        assert_eq!(spans[3]["parentSpanId"], spans[1]["spanId"]);
+        assert_eq!(spans[3]["name"], "load");
+        assert_eq!(spans[3]["startTimeUnixNano"], "1714557600500000000");
+        assert_eq!(spans[3]["status"]["message"], "load 60.00 (5min) @ web01");
+        assert_eq!(spans[2]["status"]["code"], 1);
+        assert_eq!(spans[0]["traceId"].as_str().unwrap().len(), 32);
+        assert_eq!(spans[0]["spanId"].as_str().unwrap().len(), 16);
+        assert_ne!(spans[1]["spanId"], spans[2]["spanId"]);
+
+        let payload = metrics(&report, time, "monitoring");
+        let metrics = &payload["resourceMetrics"][0]["scopeMetrics"][0]["metrics"];
+        assert_eq!(metrics[0]["gauge"]["dataPoints"][0]["asDouble"], 60.0);
+        let statuses = metrics[1]["gauge"]["dataPoints"].as_array().unwrap();
+        assert_eq!(statuses.len(), 1);
+        assert_eq!(statuses[0]["asInt"], "3");
+    }
+}
@@ -0,0 +1,145 @@
+use crate::runner::CommandRunner;
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use serde::{Deserialize, Serialize};
+
+/// Operating system of a server, selecting the commands run by the built-in checks. When a
+/// server does not set it, it is detected with `uname` if one of its checks depends on it.
+///
+/// ```yaml
+/// servers:
+///   - name: ad01
+///     host: ad01.example.com
+///     os: windows
+/// ```
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Os {
+    /// GNU/Linux, the commands being run by a POSIX shell.
+    #[default]
+    Linux,
+    /// macOS, whose temperature sensors cannot be read without root.
+    Macos,
+    /// FreeBSD, whose temperature sensors are read with `sysctl`.
+    Freebsd,
+    /// Windows Server with OpenSSH, the commands of the built-in checks being PowerShell scripts.
+    /// The `custom_command`s are run as they are by the default shell of the SSH server.
+    Windows,
+}
+
+/// Implementation of the shell utilities of a server, selecting the options of the commands run by
+/// the built-in checks.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ShellProfile {
+    /// The full-featured utilities of the operating system, e.g. GNU coreutils and findutils.
+    #[default]
+    Standard,
+    /// BusyBox, as on OpenWrt routers and minimal containers, whose `find` may lack `-maxdepth`
+    /// and whose `uptime` output varies: portable POSIX forms of the commands are used instead.
+    Busybox,
+}
+
+/// The operating system and shell profile of a server.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Platform {
+    /// Operating system of the server.
+    pub os: Os,
+    /// Implementation of the shell utilities of the server.
+    pub shell: ShellProfile,
+}
+
+impl From<Os> for Platform {
+    fn from(os: Os) -> Platform {
+        Platform {
+            os,
+            shell: ShellProfile::default(),
+        }
+    }
+}
+
+impl Os {
+    /// Returns the name of the operating system, as written in the messages.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Os::Linux => "Linux",
+            Os::Macos => "macOS",
+            Os::Freebsd => "FreeBSD",
+            Os::Windows => "Windows",
+        }
+    }
+
+    /// Detects the operating system of a server from the output of `uname -s`, Linux being
+    /// assumed for the systems that are not recognized.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if `uname` fails, e.g. on Windows.
+    pub fn detect(runner: &dyn CommandRunner) -> Result<Os, Box<dyn std::error::Error>> {
+        Ok(match runner.run("uname -s")?.trim() {
+            "Darwin" => Os::Macos,
+            "FreeBSD" => Os::Freebsd,
+            _ => Os::Linux,
+        })
+    }
+}
+
+/// Wraps a PowerShell script into a command running it, whether the default shell of the Windows
+/// OpenSSH server is `cmd.exe` or PowerShell. The script is passed encoded, so that it does not
+/// need to be quoted for either shell.
+pub fn powershell(script: &str) -> String {
+    let utf16: Vec<u8> = script.encode_utf16().flat_map(u16::to_le_bytes).collect();
+    format!(
+        "powershell -NoProfile -NonInteractive -EncodedCommand {}",
+        STANDARD.encode(utf16)
+    )
+}
+
+/// Quotes a value as a literal string of PowerShell.
+pub fn powershell_quote(value: &str) -> String {
+    format!("'{}'", value.replace('\'', "''"))
+}
+
+/// Quotes a value as a single word of a POSIX shell, so that a path or a name of the
+/// configuration interpolated into the command of a built-in check cannot run other commands.
+/// The values made of characters without a meaning for the shell are left as they are.
+pub fn shell_quote(value: &str) -> String {
+    let plain = |c: char| c.is_ascii_alphanumeric() || "_-./:@%+=,~".contains(c);
+    match !value.is_empty() && value.chars().all(plain) {
+        true => value.to_string(),
+        false => format!("'{}'", value.replace('\'', r"'\''")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_powershell() {
+        assert_eq!(
+            powershell("echo 1"),
+            "powershell -NoProfile -NonInteractive -EncodedCommand ZQBjAGgAbwAgADEA"
+        );
+        assert_eq!(powershell_quote("C:\\it's"), "'C:\\it''s'");
+    }
+
+    #[test]
+    fn test_shell_quote() {
+        assert_eq!(shell_quote("/var/log"), "/var/log");
+        assert_eq!(shell_quote(""), "''");
+        assert_eq!(shell_quote("/srv/my app"), "'/srv/my app'");
+        assert_eq!(shell_quote("/tmp/$(reboot)"), "'/tmp/$(reboot)'");
+        assert_eq!(shell_quote("x'; reboot; '"), r"'x'\''; reboot; '\'''");
+    }
+
+    #[test]
+    fn test_detect() {
+        use crate::runner::MockRunner;
+        let runner = MockRunner::new().ok("uname -s", "Darwin\n");
+        assert_eq!(Os::detect(&runner).unwrap(), Os::Macos);
+        let runner = MockRunner::new().ok("uname -s", "Linux\n");
+        assert_eq!(Os::detect(&runner).unwrap(), Os::Linux);
+        assert!(Os::detect(&MockRunner::new()).is_err());
+    }
+}
@@ -0,0 +1,117 @@
+use serde::{Deserialize, Serialize};
+
+/// Shell metacharacters refused by `deny_metacharacters`, as they chain, substitute or redirect
+/// commands.
+const METACHARACTERS: &[char] = &[';', '|', '&', '$', '`', '<', '>', '(', ')', '\n'];
+
+/// Shell metacharacters always refused with `allow`, as they run or overwrite something else than
+/// the programs of the command.
+const HIDDEN_EFFECTS: &[char] = &['$', '`', '<', '>'];
+
+/// Restrictions on the commands of the `custom_command` checks and on the commands replacing the
+/// program of a built-in check, guarding against a harmful command slipped into a shared
+/// configuration. They are enforced with `--read-only`, which refuses to run
+/// the commands breaking them, and reported by `remotesysmonitor validate`.
+///
+/// ```yaml
+/// command_policy:
+///   allow: [df, uptime, /opt/scripts/backup_report.sh]
+///   deny_metacharacters: true
+/// ```
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct CommandPolicy {
+    /// Programs the commands may run, by name (e.g. `df`, also allowing `/bin/df`) or by path
+    /// (e.g. `./backup.sh`, only allowing that path). Every command of a pipeline or a sequence
+    /// must be allowed, and command substitutions and redirections are refused. Any program is
+    /// allowed if omitted.
+    pub allow: Option<Vec<String>>,
+    /// Refuse the commands with shell metacharacters (`;`, `|`, `&`, `$`, backquotes,
+    /// redirections and subshells), so that a command runs a single program.
+    #[serde(default)]
+    pub deny_metacharacters: bool,
+}
+
+impl CommandPolicy {
+    /// Checks a command against the policy.
+    ///
+    /// # Errors
+    ///
+    /// Returns the reason why the command breaks the policy, e.g. ``program `rm` is not allowed``.
+    pub fn check(&self, command: &str) -> Result<(), String> {
+        let refused = match (self.deny_metacharacters, self.allow.is_some()) {
+            (true, _) => METACHARACTERS,
+            (false, true) => HIDDEN_EFFECTS,
+            (false, false) => &[],
+        };
+        if let Some(c) = command.chars().find(|c| refused.contains(c)) {
+            let c = if c == '\n' {
+                "line break".to_string()
+            } else {
+                format!("`{}`", c)
+            };
+            return Err(format!("shell metacharacter {} is not allowed", c));
+        }
+        let Some(allow) = &self.allow else {
+            return Ok(());
+        };
+        for segment in command.split(|c| [';', '|', '&', '\n'].contains(&c)) {
+            let Some(program) = segment.split_whitespace().next() else {
+                continue;
+            };
+            let allowed = allow.iter().any(|entry| {
+                entry == program
+                    || (!entry.contains('/') && program.rsplit('/').next() == Some(entry.as_str()))
+            });
+            if !allowed {
+                return Err(format!("program `{}` is not allowed", program));
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_check() {
+        let policy = CommandPolicy {
+            allow: Some(vec!["df".to_string(), "./backup.sh".to_string()]),
+            deny_metacharacters: false,
+        };
+        assert!(policy.check("df -h /").is_ok());
+        assert!(policy.check("/bin/df -h / | df").is_ok());
+        assert!(policy.check("./backup.sh --dry-run").is_ok());
+        assert_eq!(
+            policy.check("/opt/backup.sh").unwrap_err(),
+            "program `/opt/backup.sh` is not allowed"
+        );
+        assert_eq!(
+            policy.check("df -h / && rm -rf /").unwrap_err(),
+            "program `rm` is not allowed"
+        );
+        // Substitutions and redirections would hide other programs or effects
+        assert_eq!(
+            policy.check("df $(rm -rf /)").unwrap_err(),
+            "shell metacharacter `$` is not allowed"
+        );
+        assert!(policy.check("df `rm -rf /`").is_err());
+        assert_eq!(
+            policy.check("df > /etc/passwd").unwrap_err(),
+            "shell metacharacter `>` is not allowed"
+        );
+        assert!(policy.check("df < /dev/null").is_err());
+
+        let policy = CommandPolicy {
+            allow: None,
+            deny_metacharacters: true,
+        };
+        assert!(policy.check("rm -rf /tmp/cache").is_ok());
+        assert_eq!(
+            policy.check("df -h $(pwd)").unwrap_err(),
+            "shell metacharacter `$` is not allowed"
+        );
+        assert!(policy.check("uptime\nreboot").is_err());
+    }
+}
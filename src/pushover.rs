@@ -0,0 +1,269 @@
+use crate::report::{Markers, Report, Status};
+use crate::utils;
+use crate::webhook;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+
+/// Endpoint of the Pushover messages API.
+pub const PUSHOVER_API_URL: &str = "https://api.pushover.net/1/messages.json";
+/// Maximum number of characters of a Pushover message.
+const MAX_MESSAGE: usize = 1024;
+/// Maximum number of characters of a Pushover title.
+const MAX_TITLE: usize = 250;
+
+/// Settings of a Pushover notifier.
+///
+/// ```yaml
+/// notifiers:
+///   - name: phone
+///     type: pushover
+///     token_env: PUSHOVER_TOKEN
+///     user_env: PUSHOVER_USER
+///     priorities:
+///       warning: 0
+///     expire: 1800
+/// ```
+#[derive(Debug, Serialize, Deserialize)]
+pub struct PushoverConfig {
+    /// API token of the Pushover application.
+    pub token: Option<String>,
+    /// Environment variable holding the API token.
+    pub token_env: Option<String>,
+    /// User (or group) key the messages are sent to.
+    pub user: Option<String>,
+    /// Environment variable holding the user key.
+    pub user_env: Option<String>,
+    /// Priority of the messages, by worst status of the report.
+    #[serde(default)]
+    pub priorities: Priorities,
+    /// Interval in seconds at which emergency messages (priority 2) are repeated until
+    /// acknowledged, at least 30.
+    #[serde(default = "default_retry")]
+    pub retry: u32,
+    /// Time in seconds after which emergency messages stop being repeated, at most 10800.
+    #[serde(default = "default_expire")]
+    pub expire: u32,
+}
+
+fn default_retry() -> u32 {
+    60
+}
+
+fn default_expire() -> u32 {
+    3600
+}
+
+/// Mapping of the statuses to Pushover priorities, from -2 (silent) to 2 (emergency).
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Priorities {
+    /// Priority of reports where all the checks pass, -1 (quiet) by default.
+    #[serde(default = "default_ok_priority")]
+    pub ok: i8,
+    /// Priority of reports with informational results only, -1 (quiet) by default.
+    #[serde(default = "default_info_priority")]
+    pub info: i8,
+    /// Priority of reports with warnings, 0 (normal) by default.
+    #[serde(default)]
+    pub warning: i8,
+    /// Priority of reports with critical results, 2 (emergency) by default.
+    #[serde(default = "default_critical_priority")]
+    pub critical: i8,
+}
+
+fn default_ok_priority() -> i8 {
+    -1
+}
+
+fn default_info_priority() -> i8 {
+    -1
+}
+
+fn default_critical_priority() -> i8 {
+    2
+}
+
+impl Default for Priorities {
+    fn default() -> Self {
+        Priorities {
+            ok: default_ok_priority(),
+            info: default_info_priority(),
+            warning: 0,
+            critical: default_critical_priority(),
+        }
+    }
+}
+
+impl Priorities {
+    /// Returns the priority of a report whose worst result has the given status.
+    pub fn for_status(&self, status: Status) -> i8 {
+        let priority = match status {
+            Status::Ok => self.ok,
+            Status::Info => self.info,
+            Status::Warning => self.warning,
+            Status::Critical => self.critical,
+        };
+        priority.clamp(-2, 2)
+    }
+}
+
+/// Sends a report as a Pushover message.
+///
+/// The message holds the plain text report, truncated to the 1024 characters accepted by
+/// Pushover, and its priority depends on the worst status of the report.
+///
+/// # Arguments
+///
+/// * `url` - The messages API endpoint, usually `PUSHOVER_API_URL`.
+/// * `token` - The API token of the application.
+/// * `user` - The user key.
+/// * `config` - The settings of the notifier.
+/// * `report` - The report to send.
+/// * `text` - The plain text rendering of the report.
+/// * `markers` - The markers of the statuses, the worst one starting the title.
+///
+/// # Errors
+///
+/// Returns an error if the message could not be delivered.
+pub fn post_report(
+    url: &str,
+    token: &str,
+    user: &str,
+    config: &PushoverConfig,
+    report: &Report,
+    text: &str,
+    markers: &Markers,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let message = build_message(token, user, config, report, text, markers);
+    webhook::post_json(url, &message)
+}
+
+fn build_message(
+    token: &str,
+    user: &str,
+    config: &PushoverConfig,
+    report: &Report,
+    text: &str,
+    markers: &Markers,
+) -> Value {
+    let status = report.status();
+    let title = format!(
+        "{} RemoteSysMonitor · {}",
+        markers.status(status),
+        utils::make_pretty_timestamp()
+    );
+    let priority = config.priorities.for_status(status);
+    let mut message = json!({
+        "token": token,
+        "user": user,
+        "title": utils::truncate(&title, MAX_TITLE),
+        "message": utils::truncate(text, MAX_MESSAGE),
+        "priority": priority,
+    });
+    if priority == 2 {
+        message["retry"] = json!(config.retry.max(30));
+        message["expire"] = json!(config.expire.min(10800));
+    }
+    message
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::report::{CheckResult, ServerReport};
+
+    fn config() -> PushoverConfig {
+        serde_yaml::from_str("{token: abc, user: def}").unwrap()
+    }
+
+    fn report(status: Status) -> Report {
+        let mut server = ServerReport::new("web01", "web01.example.com");
+        server
+            .results
+            .push(CheckResult::new(status, "x".repeat(2000)));
+        Report {
+            notes: vec![],
+            servers: vec![server],
+        }
+    }
+
+    #[test]
+    fn test_build_message() {
+        let critical = report(Status::Critical);
+        let text = critical.to_text("-");
+        let message = build_message(
+            "abc",
+            "def",
+            &config(),
+            &critical,
+            &text,
+            &Markers::default(),
+        );
+        assert_eq!(message["priority"], 2);
+        assert_eq!(message["retry"], 60);
+        assert_eq!(message["expire"], 3600);
+        assert_eq!(message["message"].as_str().unwrap().chars().count(), 1024);
+
+        let message = build_message(
+            "abc",
+            "def",
+            &config(),
+            &report(Status::Warning),
+            "-",
+            &Markers::default(),
+        );
+        assert_eq!(message["priority"], 0);
+        assert!(message.get("retry").is_none());
+
+        let markers = Markers {
+            warning: "[WARN]".to_string(),
+            ..Markers::default()
+        };
+        let message = build_message(
+            "abc",
+            "def",
+            &config(),
+            &report(Status::Warning),
+            "-",
+            &markers,
+        );
+        assert!(message["title"]
+            .as_str()
+            .unwrap()
+            .starts_with("[WARN] RemoteSysMonitor"));
+    }
+
+    #[test]
+    fn test_priorities() {
+        let config: PushoverConfig =
+            serde_yaml::from_str("{priorities: {critical: 1, warning: 5}}").unwrap();
+        assert_eq!(config.priorities.for_status(Status::Critical), 1);
+        assert_eq!(config.priorities.for_status(Status::Warning), 2);
+        assert_eq!(config.priorities.for_status(Status::Ok), -1);
+    }
+
+    #[test]
+    fn test_post_report() {
+        let mut server = mockito::Server::new();
+        let mock = server
+            .mock("POST", "/1/messages.json")
+            .match_body(mockito::Matcher::PartialJson(
+                json!({"token": "abc", "user": "def", "priority": 2}),
+            ))
+            .with_status(200)
+            .with_body(r#"{"status":1}"#)
+            .create();
+
+        let url = format!("{}/1/messages.json", server.url());
+        post_report(
+            &url,
+            "abc",
+            "def",
+            &config(),
+            &report(Status::Critical),
+            "-",
+            &Markers::default(),
+        )
+        .unwrap();
+        mock.assert();
+    }
+}
@@ -0,0 +1,130 @@
+use crate::config::{self, Config};
+use crate::validate;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::SystemTime;
+
+/// Watches the configuration file in daemon mode, so that its servers and checks are reloaded
+/// when it is modified or when the process receives SIGHUP.
+///
+/// The modification time of the file is polled while waiting for the next run, while SIGHUP also
+/// covers the changes to the files it includes.
+pub struct Watcher {
+    path: PathBuf,
+    modified: Option<SystemTime>,
+    hangup: Arc<AtomicBool>,
+}
+
+impl Watcher {
+    /// Starts watching a configuration file.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the SIGHUP handler cannot be registered.
+    pub fn new(path: &str) -> Result<Watcher, Box<dyn std::error::Error>> {
+        let hangup = Arc::new(AtomicBool::new(false));
+        signal_hook::flag::register(signal_hook::consts::SIGHUP, Arc::clone(&hangup))?;
+        let path = PathBuf::from(path);
+        Ok(Watcher {
+            modified: modified(&path),
+            path,
+            hangup,
+        })
+    }
+
+    /// Returns whether the configuration is to be reloaded, SIGHUP having been received or the
+    /// file modified since the previous call.
+    pub fn changed(&mut self) -> bool {
+        let hangup = self.hangup.swap(false, Ordering::Relaxed);
+        let modified = modified(&self.path);
+        let changed = modified != self.modified;
+        self.modified = modified;
+        hangup || changed
+    }
+
+    /// Returns whether `changed` would report a change, without consuming it, for the daemon to
+    /// stop waiting for its next run.
+    pub fn pending(&self) -> bool {
+        self.hangup.load(Ordering::Relaxed) || modified(&self.path) != self.modified
+    }
+}
+
+fn modified(path: &PathBuf) -> Option<SystemTime> {
+    std::fs::metadata(path).and_then(|m| m.modified()).ok()
+}
+
+/// Loads the configuration file again, to replace the servers, inventories, groups and command
+/// policy of the running configuration. The other settings (general settings, notifiers and
+/// exporters) are only read on start.
+///
+/// # Errors
+///
+/// Returns an error, for the running configuration to be kept, if the file cannot be loaded or if
+/// `remotesysmonitor validate` would find problems in its servers, inventories or groups, listing
+/// them.
+pub fn load(path: &str) -> Result<Config, Box<dyn std::error::Error>> {
+    let new = config::load_config(path)?;
+    let problems: Vec<String> = validate::validate(&new)
+        .into_iter()
+        .flat_map(|section| {
+            let title = section.title;
+            section.problems.into_iter().filter_map(move |problem| {
+                // The other general settings are not reloaded
+                if title == "general" {
+                    (problem.starts_with("group `") || problem.starts_with("inventory `"))
+                        .then_some(problem)
+                } else {
+                    Some(format!("{}: {}", title, problem))
+                }
+            })
+        })
+        .collect();
+    if !problems.is_empty() {
+        return Err(format!(
+            "{} problem(s) found: {}",
+            problems.len(),
+            problems.join("; ")
+        )
+        .into());
+    }
+    Ok(new)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_reload() {
+        let path = std::env::temp_dir().join("remotesysmonitor-test-reload.yaml");
+        let path_str = path.to_str().unwrap();
+        std::fs::write(&path, "servers: []").unwrap();
+        let mut watcher = Watcher::new(path_str).unwrap();
+        assert!(!watcher.changed());
+        assert!(!watcher.pending());
+
+        std::fs::write(
+            &path,
+            "servers:\n- {name: self, host: localhost, local: true, checks: {load: {interval: 7}}}\n\
+             groups: {web: {checks: {load: {interval: 5, warning: 90}}}}",
+        )
+        .unwrap();
+        let error = load(path_str).unwrap_err().to_string();
+        assert!(error.starts_with("2 problem(s) found"), "{}", error);
+
+        std::fs::write(
+            &path,
+            "servers:\n- {name: self, host: localhost, local: true, checks: {}}\n\
+             - {name: other, host: localhost, local: true, checks: {}}",
+        )
+        .unwrap();
+        assert_eq!(load(path_str).unwrap().servers.len(), 2);
+        // The modification time may not have changed within the resolution of the filesystem
+        watcher.hangup.store(true, Ordering::Relaxed);
+        assert!(watcher.pending());
+        assert!(watcher.changed());
+        assert!(!watcher.pending());
+        std::fs::remove_file(&path).ok();
+    }
+}
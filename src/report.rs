@@ -0,0 +1,691 @@
+use crate::runner::CommandError;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+/// Severity of the outcome of a check.
+///
+/// Statuses are ordered by severity, so the overall status of a set of results is simply the
+/// maximum of their statuses.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Status {
+    /// The check passed.
+    #[default]
+    Ok,
+    /// The check produced output worth reading but does not pass a verdict, e.g. a custom command.
+    Info,
+    /// The warning threshold of the check was crossed.
+    Warning,
+    /// The critical threshold of the check was crossed, or the check could not be performed.
+    Critical,
+}
+
+impl Status {
+    /// Returns the emoji used to mark a result with this status in the reports.
+    pub fn emoji(&self) -> &'static str {
+        match self {
+            Status::Ok => "✅",
+            Status::Info => "ℹ️",
+            Status::Warning => "⚠️",
+            Status::Critical => "❌",
+        }
+    }
+
+    /// Classifies a measured value against a warning and a critical threshold.
+    ///
+    /// A threshold is crossed when the value is at or above it. The warning threshold is optional,
+    /// checks without one can only be `Ok` or `Critical`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// assert_eq!(Status::from_thresholds(25.0, Some(20.0), 50.0), Status::Warning);
+    /// ```
+    pub fn from_thresholds(value: f64, warning: Option<f64>, critical: f64) -> Status {
+        if value >= critical {
+            Status::Critical
+        } else if warning.is_some_and(|warning| value >= warning) {
+            Status::Warning
+        } else {
+            Status::Ok
+        }
+    }
+}
+
+/// Markers of the statuses and of the servers in the reports, emojis by default.
+///
+/// They can be replaced, e.g. by words for plain text emails or by the custom emojis of a Slack
+/// workspace:
+///
+/// ```yaml
+/// markers:
+///   ok: "[OK]"
+///   warning: "[WARN]"
+///   critical: "[CRIT]"
+///   server: "#"
+/// ```
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Markers {
+    /// Marker of the passing results, `✅` by default.
+    pub ok: String,
+    /// Marker of the informational results, `ℹ️` by default.
+    pub info: String,
+    /// Marker of the warnings, `⚠️` by default.
+    pub warning: String,
+    /// Marker of the critical results, `❌` by default.
+    pub critical: String,
+    /// Marker of the headers of the servers, `🖥️` by default.
+    pub server: String,
+}
+
+impl Default for Markers {
+    fn default() -> Self {
+        Markers {
+            ok: Status::Ok.emoji().to_string(),
+            info: Status::Info.emoji().to_string(),
+            warning: Status::Warning.emoji().to_string(),
+            critical: Status::Critical.emoji().to_string(),
+            server: "🖥️".to_string(),
+        }
+    }
+}
+
+impl Markers {
+    /// Returns the marker of a status.
+    pub fn status(&self, status: Status) -> &str {
+        match status {
+            Status::Ok => &self.ok,
+            Status::Info => &self.info,
+            Status::Warning => &self.warning,
+            Status::Critical => &self.critical,
+        }
+    }
+}
+
+/// The outcome of a check, as reported to the user.
+///
+/// A single check can produce several results, e.g. one per path or per URL it inspects.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CheckResult {
+    /// Severity of the result.
+    pub status: Status,
+    /// One-line description of the result, without the status emoji.
+    pub message: String,
+    /// Optional multi-line details, rendered as a code block below the message.
+    pub details: Option<String>,
+    /// Full output of the command when the `details` only hold a truncated version of it, see
+    /// `utils::truncate_output`.
+    pub output: Option<String>,
+    /// Name of the check that produced the result. For the notes of a report, the
+    /// `<server>/<check>` key of the check a note is about, if any.
+    pub check: String,
+    /// Whether the result falls in a maintenance window, in which case it is reported but does
+    /// not count towards the status of the report.
+    pub muted: bool,
+    /// Exit status of the remote command, when the result comes from a failed command.
+    pub exit_code: Option<i32>,
+    /// Standard error of the remote command, when the result comes from a failed command.
+    pub stderr: Option<String>,
+    /// Value measured by the check, e.g. the load average, recorded in the history.
+    pub metric: Option<Metric>,
+    /// Value of the metric at the previous run, taken from the history, shown as a trend.
+    pub previous: Option<f64>,
+    /// What the check found for the monitor to compare with the previous run, removed before the
+    /// result is reported.
+    pub observation: Option<Observation>,
+}
+
+/// A finding of a check that the monitor compares with the previous run, as recorded in the state,
+/// e.g. the boot time of a server to detect a reboot (see `monitor::run`).
+#[derive(Debug, Clone, PartialEq)]
+pub enum Observation {
+    /// Boot time of the server, found by an `uptime` check.
+    Boot(DateTime<Utc>),
+    /// Time of an OOM kill, found by an `oom_kills` check.
+    Kill(DateTime<Utc>),
+    /// Time of a login, found by a `logins` check.
+    Login(DateTime<Utc>),
+    /// Time of a failed login, found by a `logins` check, the message being its source.
+    FailedLogin(DateTime<Utc>),
+    /// The failed logins could not be read by a `logins` check.
+    FailedLoginsUnreadable,
+    /// A file read by an `account_changes` check, the message being its path and the details its
+    /// content.
+    File,
+    /// A file that an `account_changes` check could not read, the message being its path.
+    UnreadableFile,
+}
+
+/// A named numeric value measured by a check.
+///
+/// The name identifies the value among the results of the check, e.g. `load5` for the `load` check
+/// or the path of a `number_of_subfolders` check inspecting several paths.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Metric {
+    /// Name of the value, unique within the check.
+    pub name: String,
+    /// The measured value.
+    pub value: f64,
+}
+
+impl CheckResult {
+    /// Creates a new result without details.
+    pub fn new(status: Status, message: impl Into<String>) -> Self {
+        CheckResult {
+            status,
+            message: message.into(),
+            details: None,
+            output: None,
+            check: String::new(),
+            muted: false,
+            exit_code: None,
+            stderr: None,
+            metric: None,
+            previous: None,
+            observation: None,
+        }
+    }
+
+    /// Sets the name of the check that produced the result.
+    pub fn with_check(mut self, check: &str) -> Self {
+        self.check = check.to_string();
+        self
+    }
+
+    /// Attaches multi-line details (e.g. the output of a command) to the result.
+    pub fn with_details(mut self, details: impl Into<String>) -> Self {
+        self.details = Some(details.into());
+        self
+    }
+
+    /// Attaches the value measured by the check to the result.
+    pub fn with_metric(mut self, name: impl Into<String>, value: f64) -> Self {
+        self.metric = Some(Metric {
+            name: name.into(),
+            value,
+        });
+        self
+    }
+
+    /// Attaches what the check found for the monitor to compare with the previous run.
+    pub fn with_observation(mut self, observation: Observation) -> Self {
+        self.observation = Some(observation);
+        self
+    }
+
+    /// Records the exit status and standard error of a failed remote command, if `error` is a
+    /// `CommandError`.
+    pub fn with_error(mut self, error: &(dyn std::error::Error + 'static)) -> Self {
+        if let Some(error) = error.downcast_ref::<CommandError>() {
+            self.exit_code = Some(error.exit_status);
+            self.stderr = Some(error.stderr.clone());
+        }
+        self
+    }
+
+    /// Renders the result on a single line, without its details, followed by the trend of its
+    /// metric when the previous value is known, e.g. `✅ load 3.20 (5min) @ web01 ↓ from 7.90`.
+    pub fn headline(&self) -> String {
+        self.headline_with(&Markers::default())
+    }
+
+    /// Renders the result as `headline` does, with the given markers.
+    pub fn headline_with(&self, markers: &Markers) -> String {
+        let trend = match (&self.metric, self.previous) {
+            (Some(metric), Some(previous)) if metric.value > previous => {
+                format!(" ↑ from {}", format_value(previous))
+            }
+            (Some(metric), Some(previous)) if metric.value < previous => {
+                format!(" ↓ from {}", format_value(previous))
+            }
+            _ => String::new(),
+        };
+        let suffix = if self.muted { " (maintenance)" } else { "" };
+        format!(
+            "{} {}{}{}",
+            markers.status(self.status),
+            self.message,
+            trend,
+            suffix
+        )
+    }
+
+    /// Renders the result with its details as a code block below it, with the given markers.
+    pub fn to_text(&self, markers: &Markers) -> String {
+        let mut text = self.headline_with(markers);
+        if let Some(details) = &self.details {
+            text.push_str(&format!("\n```\n{}\n```", details.trim_end()));
+        }
+        text
+    }
+}
+
+impl fmt::Display for CheckResult {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.to_text(&Markers::default()))
+    }
+}
+
+/// Formats a measured value, without decimals for integers and with two decimals otherwise.
+pub fn format_value(value: f64) -> String {
+    if value.fract() == 0.0 {
+        format!("{}", value)
+    } else {
+        format!("{:.2}", value)
+    }
+}
+
+/// The results of all the checks performed on a server.
+#[derive(Debug, Clone)]
+pub struct ServerReport {
+    /// Human-readable name of the server.
+    pub name: String,
+    /// Hostname or IP address of the server.
+    pub host: String,
+    /// Results of the checks, in the order they were performed.
+    pub results: Vec<CheckResult>,
+    /// When the server started being checked, and how long it took, connection included.
+    pub timing: Option<Timing>,
+    /// How long the connection (named `ssh`) and every check took, in the order they were
+    /// performed.
+    pub timings: Vec<Timing>,
+}
+
+/// When a step of a run (checking a server, or running a check) started and how long it took.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Timing {
+    /// Name of the server or of the check.
+    pub name: String,
+    /// When the step started.
+    pub start: chrono::DateTime<chrono::Utc>,
+    /// How long the step took.
+    pub duration: std::time::Duration,
+    /// Worst status of the results of the step.
+    pub status: Status,
+}
+
+impl Timing {
+    /// Returns when the step ended.
+    pub fn end(&self) -> chrono::DateTime<chrono::Utc> {
+        self.start + self.duration
+    }
+}
+
+impl ServerReport {
+    /// Creates an empty report for a server.
+    pub fn new(name: &str, host: &str) -> Self {
+        ServerReport {
+            name: name.to_string(),
+            host: host.to_string(),
+            results: vec![],
+            timing: None,
+            timings: vec![],
+        }
+    }
+
+    /// Returns the worst status among the results that are not muted.
+    pub fn status(&self) -> Status {
+        self.results
+            .iter()
+            .filter(|r| !r.muted)
+            .map(|r| r.status)
+            .max()
+            .unwrap_or_default()
+    }
+
+    /// Returns the header line introducing the server in the reports.
+    pub fn header(&self) -> String {
+        self.header_with(&Markers::default())
+    }
+
+    /// Returns the header line of the server, with the given markers.
+    pub fn header_with(&self, markers: &Markers) -> String {
+        format!("{} {} (`{}`)", markers.server, self.name, self.host)
+    }
+}
+
+/// The outcome of a run over all the servers.
+#[derive(Debug, Clone, Default)]
+pub struct Report {
+    /// Results that are not tied to a single check run, e.g. recovery announcements, shown
+    /// before the servers.
+    pub notes: Vec<CheckResult>,
+    /// The report of every server, in the order of the configuration unless sorted with
+    /// `sort_failures_first`.
+    pub servers: Vec<ServerReport>,
+}
+
+impl Report {
+    /// Returns the worst status among all the results that are not muted.
+    pub fn status(&self) -> Status {
+        self.notes
+            .iter()
+            .filter(|r| !r.muted)
+            .map(|r| r.status)
+            .chain(self.servers.iter().map(|s| s.status()))
+            .max()
+            .unwrap_or_default()
+    }
+
+    /// Returns whether a server could not be connected to, i.e. has a failing `ssh` result that is
+    /// not muted.
+    pub fn unreachable(&self) -> bool {
+        self.servers
+            .iter()
+            .flat_map(|s| &s.results)
+            .any(|r| r.check == "ssh" && r.status == Status::Critical && !r.muted)
+    }
+
+    /// Returns a one-line summary of the report, naming the servers with failures (⚠️ or ❌) in
+    /// the order of the report, e.g.:
+    ///
+    /// ```text
+    /// 3/30 servers failing: web02, gpu01, nas (14 checks OK)
+    /// ```
+    ///
+    /// Only the first ten servers are named, the others are counted. When nothing fails, the
+    /// summary reads e.g. `All 30 servers passing (140 checks OK)`.
+    pub fn summary(&self) -> String {
+        let passed = self
+            .servers
+            .iter()
+            .flat_map(|s| &s.results)
+            .filter(|r| r.status == Status::Ok && !r.muted)
+            .count();
+        let failing: Vec<&str> = self
+            .servers
+            .iter()
+            .filter(|s| s.status() >= Status::Warning)
+            .map(|s| s.name.as_str())
+            .collect();
+        let plural = |count: usize| if count == 1 { "" } else { "s" };
+        let checks = format!("({} check{} OK)", passed, plural(passed));
+        if failing.is_empty() {
+            let servers = self.servers.len();
+            return format!(
+                "All {} server{} passing {}",
+                servers,
+                plural(servers),
+                checks
+            );
+        }
+        let mut names = failing
+            .iter()
+            .take(10)
+            .copied()
+            .collect::<Vec<_>>()
+            .join(", ");
+        if failing.len() > 10 {
+            names.push_str(&format!(" and {} more", failing.len() - 10));
+        }
+        format!(
+            "{}/{} server{} failing: {} {}",
+            failing.len(),
+            self.servers.len(),
+            plural(self.servers.len()),
+            names,
+            checks
+        )
+    }
+
+    /// Returns the footer of the report with the duration of the run and its slowest server and
+    /// check, e.g.:
+    ///
+    /// ```text
+    /// ⏱️ 30 servers checked in 64.2s, slowest: gpu01 in 12.5s (disk_usage in 10.1s)
+    /// ```
+    ///
+    /// Returns `None` for reports without timings, e.g. digests.
+    pub fn footer(&self) -> Option<String> {
+        let timings: Vec<&Timing> = self
+            .servers
+            .iter()
+            .filter_map(|s| s.timing.as_ref())
+            .collect();
+        let start = timings.iter().map(|t| t.start).min()?;
+        let end = timings.iter().map(|t| t.end()).max()?;
+        let seconds = |duration: std::time::Duration| format!("{:.1}s", duration.as_secs_f64());
+        let total = (end - start).to_std().unwrap_or_default();
+        let mut footer = format!(
+            "⏱️ {} server{} checked in {}",
+            timings.len(),
+            if timings.len() == 1 { "" } else { "s" },
+            seconds(total)
+        );
+        let slowest = self
+            .servers
+            .iter()
+            .filter_map(|s| s.timing.as_ref().map(|t| (s, t.duration)))
+            .max_by_key(|(_, duration)| *duration);
+        if let Some((server, duration)) = slowest.filter(|_| timings.len() > 1) {
+            footer.push_str(&format!(
+                ", slowest: {} in {}",
+                server.name,
+                seconds(duration)
+            ));
+        }
+        if let Some(check) = slowest.and_then(|(s, _)| s.timings.iter().max_by_key(|t| t.duration))
+        {
+            footer.push_str(&format!(" ({} in {})", check.name, seconds(check.duration)));
+        }
+        Some(footer)
+    }
+
+    /// Moves the servers with failures to the top of the report, the worst first. Servers with the
+    /// same status keep their order.
+    pub fn sort_failures_first(&mut self) {
+        self.servers
+            .sort_by_key(|server| std::cmp::Reverse(server.status()));
+    }
+
+    /// Renders the report as plain text, starting with its `summary`, then a header line per
+    /// server followed by one line per result, and the `separator` repeated ten times between
+    /// servers. The `footer` with the timings of the run ends it, if any.
+    pub fn to_text(&self, separator: &str) -> String {
+        self.to_text_with(separator, &Markers::default())
+    }
+
+    /// Renders the report as `to_text` does, with the given markers.
+    pub fn to_text_with(&self, separator: &str, markers: &Markers) -> String {
+        let mut lines = vec![self.summary()];
+        lines.extend(self.notes.iter().map(|r| r.to_text(markers)));
+        for server in &self.servers {
+            lines.push(server.header_with(markers));
+            lines.extend(server.results.iter().map(|r| r.to_text(markers)));
+            lines.push(separator.repeat(10));
+        }
+        lines.extend(self.footer());
+        lines.join("\n")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_from_thresholds() {
+        assert_eq!(Status::from_thresholds(10.0, Some(20.0), 50.0), Status::Ok);
+        assert_eq!(
+            Status::from_thresholds(20.0, Some(20.0), 50.0),
+            Status::Warning
+        );
+        assert_eq!(
+            Status::from_thresholds(50.0, Some(20.0), 50.0),
+            Status::Critical
+        );
+        assert_eq!(Status::from_thresholds(30.0, None, 50.0), Status::Ok);
+    }
+
+    #[test]
+    fn test_status_ordering() {
+        let worst = [Status::Ok, Status::Critical, Status::Warning]
+            .into_iter()
+            .max()
+            .unwrap();
+        assert_eq!(worst, Status::Critical);
+    }
+
+    #[test]
+    fn test_report_status_ignores_muted() {
+        let mut server = ServerReport::new("web01", "web01.example.com");
+        server
+            .results
+            .push(CheckResult::new(Status::Warning, "load 30.00"));
+        let mut muted = CheckResult::new(Status::Critical, "load 90.00");
+        muted.muted = true;
+        server.results.push(muted);
+        assert_eq!(server.status(), Status::Warning);
+
+        let report = Report {
+            notes: vec![],
+            servers: vec![server],
+        };
+        assert_eq!(report.status(), Status::Warning);
+        assert_eq!(
+            report.to_text("-"),
+            "1/1 server failing: web01 (0 checks OK)\n\
+             🖥️ web01 (`web01.example.com`)\n⚠️ load 30.00\n❌ load 90.00 (maintenance)\n----------"
+        );
+
+        let markers: Markers =
+            serde_yaml::from_str("{warning: '[WARN]', critical: '[CRIT]', server: '#'}").unwrap();
+        assert_eq!(markers.ok, "✅");
+        assert_eq!(
+            report.to_text_with("", &markers),
+            "1/1 server failing: web01 (0 checks OK)\n\
+             # web01 (`web01.example.com`)\n[WARN] load 30.00\n[CRIT] load 90.00 (maintenance)\n"
+        );
+    }
+
+    #[test]
+    fn test_sort_failures_first() {
+        let server = |name: &str, status| {
+            let mut server = ServerReport::new(name, name);
+            server.results.push(CheckResult::new(status, "load"));
+            server
+        };
+        let mut report = Report {
+            notes: vec![],
+            servers: vec![
+                server("a", Status::Ok),
+                server("b", Status::Warning),
+                server("c", Status::Ok),
+                server("d", Status::Critical),
+                server("e", Status::Warning),
+            ],
+        };
+        assert_eq!(
+            report.summary(),
+            "3/5 servers failing: b, d, e (2 checks OK)"
+        );
+        report.sort_failures_first();
+        let names: Vec<&str> = report.servers.iter().map(|s| s.name.as_str()).collect();
+        assert_eq!(names, ["d", "b", "e", "a", "c"]);
+    }
+
+    #[test]
+    fn test_footer() {
+        let start: chrono::DateTime<chrono::Utc> = "2024-05-01T10:00:00Z".parse().unwrap();
+        let timing = |name: &str, offset: u64, millis: u64| Timing {
+            name: name.to_string(),
+            start: start + std::time::Duration::from_millis(offset),
+            duration: std::time::Duration::from_millis(millis),
+            status: Status::Ok,
+        };
+        let mut web = ServerReport::new("web01", "web01.example.com");
+        web.timing = Some(timing("web01", 0, 2000));
+        let mut gpu = ServerReport::new("gpu01", "gpu01.example.com");
+        gpu.timing = Some(timing("gpu01", 2000, 12_500));
+        gpu.timings = vec![timing("ssh", 2000, 400), timing("disk_usage", 2400, 10_100)];
+        let mut report = Report {
+            notes: vec![],
+            servers: vec![web, gpu],
+        };
+        assert_eq!(
+            report.footer().unwrap(),
+            "⏱️ 2 servers checked in 14.5s, slowest: gpu01 in 12.5s (disk_usage in 10.1s)"
+        );
+        assert!(report.to_text("").ends_with(
+            "\n⏱️ 2 servers checked in 14.5s, slowest: gpu01 in 12.5s (disk_usage in 10.1s)"
+        ));
+
+        report.servers.remove(1);
+        assert_eq!(report.footer().unwrap(), "⏱️ 1 server checked in 2.0s");
+        report.servers[0].timing = None;
+        assert_eq!(report.footer(), None);
+    }
+
+    #[test]
+    fn test_unreachable() {
+        let mut server = ServerReport::new("web01", "web01.example.com");
+        server
+            .results
+            .push(CheckResult::new(Status::Critical, "load 90.00").with_check("load"));
+        let mut report = Report {
+            notes: vec![],
+            servers: vec![server],
+        };
+        assert!(!report.unreachable());
+
+        let ssh = CheckResult::new(Status::Critical, "could not start SSH session with web01");
+        report.servers[0].results.push(ssh.with_check("ssh"));
+        assert!(report.unreachable());
+        report.servers[0].results[1].muted = true;
+        assert!(!report.unreachable());
+    }
+
+    #[test]
+    fn test_with_error() {
+        let error: Box<dyn std::error::Error> = Box::new(CommandError {
+            command: "false".to_string(),
+            exit_status: 1,
+            stderr: "failed".to_string(),
+        });
+        let result =
+            CheckResult::new(Status::Critical, error.to_string()).with_error(error.as_ref());
+        assert_eq!(result.exit_code, Some(1));
+        assert_eq!(result.stderr.as_deref(), Some("failed"));
+
+        let error: Box<dyn std::error::Error> = "connection lost".into();
+        let result =
+            CheckResult::new(Status::Critical, error.to_string()).with_error(error.as_ref());
+        assert_eq!(result.exit_code, None);
+    }
+
+    #[test]
+    fn test_display() {
+        let result = CheckResult::new(Status::Critical, "Directories older than 2 days:")
+            .with_details("/data/a\n/data/b\n");
+        assert_eq!(
+            result.to_string(),
+            "❌ Directories older than 2 days:\n```\n/data/a\n/data/b\n```"
+        );
+        assert_eq!(
+            CheckResult::new(Status::Ok, "load 1.00").to_string(),
+            "✅ load 1.00"
+        );
+    }
+
+    #[test]
+    fn test_headline_trend() {
+        let mut result =
+            CheckResult::new(Status::Ok, "load 3.20 (5min) @ web01").with_metric("load5", 3.2);
+        assert_eq!(result.headline(), "✅ load 3.20 (5min) @ web01");
+        result.previous = Some(7.9);
+        assert_eq!(result.headline(), "✅ load 3.20 (5min) @ web01 ↓ from 7.90");
+        result.previous = Some(1.0);
+        result.muted = true;
+        assert_eq!(
+            result.headline(),
+            "✅ load 3.20 (5min) @ web01 ↑ from 1 (maintenance)"
+        );
+        result.previous = Some(3.2);
+        assert_eq!(
+            result.headline(),
+            "✅ load 3.20 (5min) @ web01 (maintenance)"
+        );
+    }
+}
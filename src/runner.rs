@@ -0,0 +1,341 @@
+use crate::utils;
+use log::debug;
+use std::cell::Cell;
+use std::io::Read;
+use std::process::{Command, Stdio};
+use std::time::{Duration, Instant};
+
+/// Runs the commands of the checks on a server.
+///
+/// Checks only depend on this trait, so that they work unchanged whether the commands run over an
+/// SSH `Session` or locally with `LocalRunner`.
+pub trait CommandRunner {
+    /// Runs a shell command and returns its standard output.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the command could not be executed, or a `CommandError` if it exited
+    /// with a non-zero status.
+    fn run(&self, command: &str) -> Result<String, Box<dyn std::error::Error>> {
+        self.run_until(command, None)
+    }
+
+    /// Runs a shell command like `run`, abandoning it once `deadline` is passed.
+    ///
+    /// # Errors
+    ///
+    /// Same as `run`, and returns a `TimedOut` error if the command did not complete before the
+    /// deadline.
+    fn run_until(
+        &self,
+        command: &str,
+        deadline: Option<Instant>,
+    ) -> Result<String, Box<dyn std::error::Error>>;
+}
+
+/// Runs the commands of a check with another runner until a timeout expires, after which the
+/// remaining commands fail immediately.
+pub struct Timeout<'a> {
+    runner: &'a dyn CommandRunner,
+    deadline: Instant,
+    timed_out: Cell<bool>,
+}
+
+impl<'a> Timeout<'a> {
+    /// Starts the timeout, expiring after `timeout`.
+    pub fn new(runner: &'a dyn CommandRunner, timeout: Duration) -> Timeout<'a> {
+        Timeout {
+            runner,
+            deadline: Instant::now() + timeout,
+            timed_out: Cell::new(false),
+        }
+    }
+
+    /// Returns whether a command was abandoned because the timeout expired.
+    pub fn timed_out(&self) -> bool {
+        self.timed_out.get()
+    }
+}
+
+impl CommandRunner for Timeout<'_> {
+    fn run_until(
+        &self,
+        command: &str,
+        deadline: Option<Instant>,
+    ) -> Result<String, Box<dyn std::error::Error>> {
+        let deadline = deadline.map_or(self.deadline, |d| d.min(self.deadline));
+        let result = match Instant::now() >= deadline {
+            true => Err(TimedOut::new(command).into()),
+            false => self.runner.run_until(command, Some(deadline)),
+        };
+        if let Err(e) = &result {
+            if e.is::<TimedOut>() {
+                self.timed_out.set(true);
+            }
+        }
+        result
+    }
+}
+
+/// Runs the commands on the monitoring host itself, for servers with `local: true`.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct LocalRunner;
+
+impl CommandRunner for LocalRunner {
+    fn run_until(
+        &self,
+        command: &str,
+        deadline: Option<Instant>,
+    ) -> Result<String, Box<dyn std::error::Error>> {
+        debug!("Running `{}` locally", command);
+        #[cfg(windows)]
+        let mut shell = Command::new("cmd");
+        #[cfg(windows)]
+        shell.arg("/C").arg(command);
+        #[cfg(not(windows))]
+        let mut shell = Command::new("sh");
+        #[cfg(not(windows))]
+        shell.arg("-c").arg(command);
+
+        let Some(deadline) = deadline else {
+            let output = shell.output()?;
+            return command_output(command, output.status, output.stdout, output.stderr);
+        };
+
+        let mut child = shell
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()?;
+        // The outputs are read by threads so that a chatty command does not fill the pipes and
+        // block while it is being waited for
+        let stdout = read_in_thread(child.stdout.take());
+        let stderr = read_in_thread(child.stderr.take());
+        let status = loop {
+            if let Some(status) = child.try_wait()? {
+                break status;
+            }
+            if Instant::now() >= deadline {
+                child.kill().ok();
+                child.wait().ok();
+                // The readers are not joined, processes started by the command may still hold the
+                // pipes open
+                return Err(TimedOut::new(command).into());
+            }
+            std::thread::sleep(Duration::from_millis(20));
+        };
+        let output = |reader: std::thread::JoinHandle<Vec<u8>>| reader.join().unwrap_or_default();
+        command_output(command, status, output(stdout), output(stderr))
+    }
+}
+
+/// Returns the standard output of a completed command, or a `CommandError` if it failed.
+fn command_output(
+    command: &str,
+    status: std::process::ExitStatus,
+    stdout: Vec<u8>,
+    stderr: Vec<u8>,
+) -> Result<String, Box<dyn std::error::Error>> {
+    if !status.success() {
+        return Err(CommandError {
+            command: command.to_string(),
+            // Commands killed by a signal have no exit status
+            exit_status: status.code().unwrap_or(-1),
+            stderr: String::from_utf8_lossy(&stderr).into_owned(),
+        }
+        .into());
+    }
+    Ok(String::from_utf8(stdout)?)
+}
+
+/// Reads a pipe until the end of file in a new thread.
+fn read_in_thread(pipe: Option<impl Read + Send + 'static>) -> std::thread::JoinHandle<Vec<u8>> {
+    std::thread::spawn(move || {
+        let mut output = vec![];
+        if let Some(mut pipe) = pipe {
+            pipe.read_to_end(&mut output).ok();
+        }
+        output
+    })
+}
+
+/// Answers the commands of the checks with canned outputs, to test the checks without a server.
+///
+/// ```ignore
+/// let runner = MockRunner::new().ok("uptime", " 10:00:00 up 1 day,  load average: 0.50, 0.40, 0.30");
+/// let results = checks::load(&runner, "web01", 5, None, 50.0);
+/// ```
+#[cfg(test)]
+#[derive(Debug, Default)]
+pub struct MockRunner {
+    responses: Vec<(String, Result<String, CommandError>)>,
+    commands: std::cell::RefCell<Vec<String>>,
+}
+
+#[cfg(test)]
+impl MockRunner {
+    /// Creates a runner without any response, failing every command.
+    pub fn new() -> MockRunner {
+        MockRunner::default()
+    }
+
+    /// Answers `command` with the standard output `stdout`.
+    pub fn ok(mut self, command: &str, stdout: &str) -> MockRunner {
+        self.responses
+            .push((command.to_string(), Ok(stdout.to_string())));
+        self
+    }
+
+    /// Makes `command` exit with `exit_status` and the standard error `stderr`.
+    pub fn fail(mut self, command: &str, exit_status: i32, stderr: &str) -> MockRunner {
+        let error = CommandError {
+            command: command.to_string(),
+            exit_status,
+            stderr: stderr.to_string(),
+        };
+        self.responses.push((command.to_string(), Err(error)));
+        self
+    }
+
+    /// Returns the commands that were run, in order.
+    pub fn commands(&self) -> Vec<String> {
+        self.commands.borrow().clone()
+    }
+}
+
+#[cfg(test)]
+impl CommandRunner for MockRunner {
+    fn run_until(
+        &self,
+        command: &str,
+        _deadline: Option<Instant>,
+    ) -> Result<String, Box<dyn std::error::Error>> {
+        self.commands.borrow_mut().push(command.to_string());
+        match self.responses.iter().find(|(c, _)| c == command) {
+            Some((_, Ok(stdout))) => Ok(stdout.clone()),
+            Some((_, Err(error))) => Err(error.clone().into()),
+            None => Err(format!("unexpected command `{}`", command).into()),
+        }
+    }
+}
+
+/// Maximum number of characters of the standard error included in the message of a
+/// `CommandError`.
+const MAX_STDERR_MESSAGE: usize = 200;
+
+/// A command that exited with a non-zero status.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CommandError {
+    /// The command that was executed.
+    pub command: String,
+    /// Exit status of the command.
+    pub exit_status: i32,
+    /// Standard error of the command.
+    pub stderr: String,
+}
+
+impl std::fmt::Display for CommandError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "Command '{}' exited with status {}",
+            self.command, self.exit_status
+        )?;
+        let stderr = self.stderr.split_whitespace().collect::<Vec<_>>().join(" ");
+        if !stderr.is_empty() {
+            write!(f, ": {}", utils::truncate(&stderr, MAX_STDERR_MESSAGE))?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for CommandError {}
+
+/// A command abandoned because it did not complete in time.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TimedOut {
+    /// The command that was executed.
+    pub command: String,
+}
+
+impl TimedOut {
+    /// Creates the error of a command that timed out.
+    pub fn new(command: &str) -> TimedOut {
+        TimedOut {
+            command: command.to_string(),
+        }
+    }
+}
+
+impl std::fmt::Display for TimedOut {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Command '{}' timed out", self.command)
+    }
+}
+
+impl std::error::Error for TimedOut {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_command_error() {
+        let error = CommandError {
+            command: "df -h".to_string(),
+            exit_status: 1,
+            stderr: format!("df: /mnt: No such file\n{}", "x".repeat(300)),
+        };
+        let message = error.to_string();
+        assert!(
+            message.starts_with("Command 'df -h' exited with status 1: df: /mnt: No such file x")
+        );
+        assert!(message.ends_with('…'));
+
+        let error = CommandError {
+            stderr: "\n".to_string(),
+            ..error
+        };
+        assert_eq!(error.to_string(), "Command 'df -h' exited with status 1");
+    }
+
+    #[test]
+    fn test_mock_runner() {
+        let runner = MockRunner::new().ok("true", "").fail("false", 1, "nope");
+        assert_eq!(runner.run("true").unwrap(), "");
+        let error = runner.run("false").unwrap_err();
+        assert_eq!(error.downcast_ref::<CommandError>().unwrap().stderr, "nope");
+        assert!(runner.run("ls").is_err());
+        assert_eq!(runner.commands(), ["true", "false", "ls"]);
+    }
+
+    #[test]
+    fn test_local_runner() {
+        assert_eq!(LocalRunner.run("echo hello").unwrap(), "hello\n");
+
+        let error = LocalRunner.run("echo oops >&2; exit 3").unwrap_err();
+        let error = error.downcast_ref::<CommandError>().unwrap();
+        assert_eq!(error.exit_status, 3);
+        assert_eq!(error.stderr, "oops\n");
+    }
+
+    #[test]
+    fn test_timeout() {
+        let runner = Timeout::new(&LocalRunner, Duration::from_millis(500));
+        assert_eq!(runner.run("echo hello").unwrap(), "hello\n");
+        let error = runner.run("echo oops >&2; exit 3").unwrap_err();
+        assert_eq!(
+            error.downcast_ref::<CommandError>().unwrap().stderr,
+            "oops\n"
+        );
+        assert!(!runner.timed_out());
+
+        let start = Instant::now();
+        let error = runner.run("sleep 5").unwrap_err();
+        assert!(start.elapsed() < Duration::from_secs(2));
+        assert_eq!(error.to_string(), "Command 'sleep 5' timed out");
+        assert!(runner.timed_out());
+        // The following commands of the check are not run anymore
+        assert!(runner.run("echo hello").unwrap_err().is::<TimedOut>());
+    }
+}
@@ -0,0 +1,109 @@
+use crate::config::CheckConfig;
+use crate::digest;
+use crate::maintenance;
+use crate::report::CheckResult;
+use chrono::{DateTime, Duration, Local, Utc};
+use log::error;
+use std::collections::HashMap;
+
+/// Margin allowed on the `every` of a check, so that a check scheduled every 10 minutes is not
+/// delayed by a whole interval when the runs are slightly less than 10 minutes apart.
+const TOLERANCE: Duration = Duration::seconds(5);
+
+/// Keeps track of the checks with a schedule of their own (`every` or `cron`) in daemon mode: when
+/// they last ran, and their results then, reported again by the runs they are not due in.
+///
+/// The checks without a schedule run at every run, and are not tracked.
+#[derive(Debug, Default)]
+pub struct Scheduler {
+    last: HashMap<String, (DateTime<Utc>, Vec<CheckResult>)>,
+}
+
+impl Scheduler {
+    /// Returns the results of the last run of a check, keyed `<server>/<check>`, if it is not
+    /// due at `now`, or `None` if it has to run.
+    ///
+    /// A check is due if it never ran, if `every` has elapsed since it last ran, or if its `cron`
+    /// expression occurred since. Checks with an invalid schedule are logged and always due.
+    pub fn previous(
+        &self,
+        key: &str,
+        config: &CheckConfig,
+        now: DateTime<Utc>,
+    ) -> Option<Vec<CheckResult>> {
+        let (last, results) = self.last.get(key)?;
+        if let Some(every) = &config.every {
+            match digest::parse_period(every) {
+                Ok(every) if now - *last + TOLERANCE < every => {}
+                Ok(_) => return None,
+                Err(e) => {
+                    error!("Invalid schedule of check {}: {}", key, e);
+                    return None;
+                }
+            }
+        }
+        if let Some(expression) = &config.cron {
+            match maintenance::parse_cron(expression) {
+                Ok(schedule) => {
+                    let last = last.with_timezone(&Local);
+                    let now = now.with_timezone(&Local);
+                    if schedule.after(&last).next().is_some_and(|next| next <= now) {
+                        return None;
+                    }
+                }
+                Err(e) => {
+                    error!("Invalid cron expression of check {}: {}", key, e);
+                    return None;
+                }
+            }
+        }
+        Some(results.clone())
+    }
+
+    /// Records the results of a check that ran at `now`, if it has a schedule.
+    pub fn record(
+        &mut self,
+        key: &str,
+        config: &CheckConfig,
+        now: DateTime<Utc>,
+        results: &[CheckResult],
+    ) {
+        if config.every.is_some() || config.cron.is_some() {
+            self.last.insert(key.to_string(), (now, results.to_vec()));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::report::Status;
+
+    #[test]
+    fn test_previous() {
+        let mut scheduler = Scheduler::default();
+        let every: CheckConfig = serde_yaml::from_str("{url: [/], every: 10m}").unwrap();
+        let cron: CheckConfig = serde_yaml::from_str("{url: [/], cron: '0 0 1 1 *'}").unwrap();
+        let always: CheckConfig = serde_yaml::from_str("{url: [/]}").unwrap();
+        let results = vec![CheckResult::new(Status::Ok, "pong")];
+        let start: DateTime<Utc> = "2024-05-01T12:00:00Z".parse().unwrap();
+
+        assert!(scheduler.previous("a/ping", &every, start).is_none());
+        scheduler.record("a/ping", &every, start, &results);
+        scheduler.record("a/yearly", &cron, start, &results);
+        scheduler.record("a/always", &always, start, &results);
+
+        let later = start + Duration::minutes(5);
+        assert_eq!(
+            scheduler.previous("a/ping", &every, later).unwrap()[0].message,
+            "pong"
+        );
+        assert!(scheduler.previous("a/always", &always, later).is_none());
+        let later = start + Duration::minutes(10) - Duration::seconds(1);
+        assert!(scheduler.previous("a/ping", &every, later).is_none());
+
+        assert!(scheduler.previous("a/yearly", &cron, later).is_some());
+        let later = start + Duration::days(365);
+        assert!(scheduler.previous("a/yearly", &cron, later).is_none());
+    }
+}
@@ -0,0 +1,34 @@
+use signal_hook::consts::{SIGINT, SIGTERM};
+use signal_hook::flag;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, OnceLock};
+
+/// Exit code of the process when a second SIGINT or SIGTERM cuts the graceful shutdown short.
+const EXIT_FORCED: i32 = 130;
+
+/// Set once SIGINT or SIGTERM is received.
+static REQUESTED: OnceLock<Arc<AtomicBool>> = OnceLock::new();
+
+/// Handles SIGINT and SIGTERM by requesting a graceful shutdown: the runs stop before their next
+/// check, and the daemon exits once the partial report is sent. A second signal terminates the
+/// process immediately, in case the shutdown gets stuck (e.g. on a command without timeout).
+///
+/// # Errors
+///
+/// Returns an error if the handlers cannot be registered.
+pub fn register() -> Result<(), std::io::Error> {
+    let requested = REQUESTED.get_or_init(Arc::default);
+    for signal in [SIGINT, SIGTERM] {
+        // The shutdown is armed by the first signal, so it must be registered first
+        flag::register_conditional_shutdown(signal, EXIT_FORCED, Arc::clone(requested))?;
+        flag::register(signal, Arc::clone(requested))?;
+    }
+    Ok(())
+}
+
+/// Returns whether a graceful shutdown was requested.
+pub fn requested() -> bool {
+    REQUESTED
+        .get()
+        .is_some_and(|requested| requested.load(Ordering::SeqCst))
+}
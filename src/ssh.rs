@@ -1,19 +1,28 @@
-use log::error;
+use crate::runner::{CommandError, CommandRunner, TimedOut};
+use crate::utils;
+use base64::engine::general_purpose::{STANDARD, STANDARD_NO_PAD};
+use base64::Engine;
+use log::{debug, error, warn};
+use serde::{Deserialize, Serialize};
+use std::io::Write;
 
 /// This module handles SSH connections and command execution.
 ///
 /// It provides functionality to create SSH sessions and run commands on a remote server
 /// using the `ssh2` crate for Rust.
-use ssh2::Session;
+use ssh2::{CheckResult, HashType, HostKeyType, KnownHostFileKind, Session};
+use std::collections::HashMap;
 use std::io::Read;
-use std::net::TcpStream;
+use std::net::{SocketAddr, TcpStream, ToSocketAddrs};
 use std::path::Path;
+use std::time::{Duration, Instant};
 
 /// Executes a given command on an SSH session and returns the command's output as a `String`.
 ///
 /// This function opens a new channel on the provided SSH session, executes the specified command,
 /// and reads the output of the command into a `String`. It ensures that the command executes
-/// successfully by checking the command's exit status. If the command execution fails or if reading
+/// successfully by checking the command's exit status, returning a `CommandError` with the
+/// standard error of the command otherwise. If the command execution fails or if reading
 /// the output encounters an error, the function logs the error and returns an `Err` containing
 /// the error information.
 ///
@@ -47,13 +56,54 @@ use std::path::Path;
 /// - It fails to open a new channel on the SSH session.
 /// - The command execution fails.
 /// - Reading the command output into a string fails.
-/// - The command exits with a non-zero status.
+/// - The command exits with a non-zero status, in which case the error is a `CommandError`.
 ///
 /// All errors are logged with an appropriate error message and then returned as `Box<dyn std::error::Error>`.
 pub fn run_ssh_command(
     sess: &Session,
     command: &str,
 ) -> Result<String, Box<dyn std::error::Error>> {
+    run_ssh_command_until(sess, command, None)
+}
+
+/// Executes a command like `run_ssh_command`, abandoning it with a `TimedOut` error once
+/// `deadline` is passed.
+///
+/// The blocking operations of the session time out when the deadline is reached (or after the
+/// keepalive interval if it is shorter, in which case the command may be abandoned up to one
+/// interval late). The channel of an abandoned command is closed but the command itself may keep
+/// running on the server.
+pub fn run_ssh_command_until(
+    sess: &Session,
+    command: &str,
+    deadline: Option<Instant>,
+) -> Result<String, Box<dyn std::error::Error>> {
+    let Some(deadline) = deadline else {
+        return exec(sess, command, None);
+    };
+    let keepalive_timeout = sess.timeout();
+    let remaining = deadline
+        .saturating_duration_since(Instant::now())
+        .as_millis();
+    let remaining = u32::try_from(remaining).unwrap_or(u32::MAX).max(1);
+    sess.set_timeout(match keepalive_timeout {
+        0 => remaining,
+        t => t.min(remaining),
+    });
+    let result = exec(sess, command, Some(deadline));
+    sess.set_timeout(keepalive_timeout);
+    match result {
+        Err(_) if Instant::now() >= deadline => Err(TimedOut::new(command).into()),
+        result => result,
+    }
+}
+
+fn exec(
+    sess: &Session,
+    command: &str,
+    deadline: Option<Instant>,
+) -> Result<String, Box<dyn std::error::Error>> {
+    debug!("Running `{}`", command);
     let mut channel = sess.channel_session()?;
     channel.exec(command).map_err(|e| {
         error!(
@@ -62,39 +112,204 @@ pub fn run_ssh_command(
         );
         Box::<dyn std::error::Error>::from(e)
     })?;
-    let mut s = String::new();
-    channel.read_to_string(&mut s).map_err(|e| {
+    let s = read_output(sess, &mut channel, deadline).map_err(|e| {
+        if deadline.is_some_and(|d| Instant::now() >= d) {
+            // Stops the command from writing to a channel nobody reads anymore
+            channel.close().ok();
+            return TimedOut::new(command).into();
+        }
         error!(
             "could not read output of command '{}' due to error: {}",
             command, e
         );
         Box::<dyn std::error::Error>::from(e)
     })?;
+    // The standard error is buffered by libssh2 while the standard output is read
+    let stderr = read_output(sess, &mut channel.stderr(), deadline).unwrap_or_else(|e| {
+        debug!("Could not read the standard error of `{}`: {}", command, e);
+        String::new()
+    });
 
     channel.wait_close().ok();
     let exit_status = channel.exit_status()?;
     if exit_status != 0 {
-        return Err(Box::<dyn std::error::Error>::from(format!(
-            "Command '{}' exited with status {}",
-            command, exit_status
-        )));
+        return Err(CommandError {
+            command: command.to_string(),
+            exit_status,
+            stderr,
+        }
+        .into());
     }
 
     Ok(s)
 }
 
+impl CommandRunner for Session {
+    fn run_until(
+        &self,
+        command: &str,
+        deadline: Option<Instant>,
+    ) -> Result<String, Box<dyn std::error::Error>> {
+        run_ssh_command_until(self, command, deadline)
+    }
+}
+
+/// Reads an output stream of a channel until the end of file.
+///
+/// With keepalives enabled, blocking operations time out after the keepalive interval, at which
+/// point a keepalive is sent before reading again, so that silent long-running commands do not get
+/// the session dropped. Past the `deadline`, a timeout is returned instead.
+fn read_output(
+    sess: &Session,
+    stream: &mut impl Read,
+    deadline: Option<Instant>,
+) -> std::io::Result<String> {
+    let mut output = vec![];
+    let mut buffer = [0; 8192];
+    loop {
+        match stream.read(&mut buffer) {
+            Ok(0) => break,
+            Ok(n) => output.extend_from_slice(&buffer[..n]),
+            Err(e) if e.kind() == std::io::ErrorKind::TimedOut => {
+                if deadline.is_some_and(|d| Instant::now() >= d) {
+                    return Err(e);
+                }
+                sess.keepalive_send()?;
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::Interrupted => {}
+            Err(e) => return Err(e),
+        }
+    }
+    String::from_utf8(output).map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))
+}
+
+/// The details needed to connect to a server, see `Server::ssh_target`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SshTarget {
+    /// Hostname or IP address of the SSH server, IPv6 addresses without brackets.
+    pub host: String,
+    /// Port on which the SSH server is listening.
+    pub port: u16,
+    /// Username for authentication with the SSH server.
+    pub user: String,
+    /// Path to the private key file used for authentication.
+    pub private_key: String,
+    /// Whether to authenticate with the private key or a password.
+    pub auth: AuthMethod,
+    /// Environment variable holding the password.
+    pub password_env: Option<String>,
+    /// File holding the password, read when `password_env` is not set.
+    pub password_file: Option<String>,
+    /// Jump hosts to connect through, as in the `ProxyJump` option of OpenSSH
+    /// (`[user@]host[:port][,...]`).
+    pub proxy_jump: Option<String>,
+    /// How the host key of the server is verified against `known_hosts`.
+    pub host_key_checking: HostKeyChecking,
+    /// Path to the known hosts file.
+    pub known_hosts: String,
+    /// Keepalive settings of the connection, none are sent if omitted.
+    pub keepalive: Option<Keepalive>,
+    /// Expected SHA256 fingerprint of the host key (`SHA256:...`, as printed by
+    /// `ssh-keygen -lf`), checked instead of `known_hosts` when set.
+    pub host_key_fingerprint: Option<String>,
+    /// Timeout of the connection to every address of the host.
+    pub connect_timeout: Duration,
+}
+
+/// How to authenticate with a server.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AuthMethod {
+    /// Authenticate with a private key.
+    #[default]
+    Key,
+    /// Authenticate with a password, for appliances not supporting keys.
+    Password,
+}
+
+/// Keepalive settings of the SSH connections, similar to the `ServerAliveInterval` and
+/// `ServerAliveCountMax` options of OpenSSH.
+///
+/// ```yaml
+/// general:
+///   keepalive:
+///     interval: 30
+///     max_missed: 4
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Keepalive {
+    /// Interval in seconds between keepalive messages, sent while waiting on a command.
+    pub interval: u32,
+    /// Number of unanswered probes after which the connection is considered dead, 3 by default.
+    #[serde(default = "default_max_missed")]
+    pub max_missed: u32,
+}
+
+fn default_max_missed() -> u32 {
+    3
+}
+
+/// Verification of the host keys of the servers, similar to the `StrictHostKeyChecking` option of
+/// OpenSSH.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum HostKeyChecking {
+    /// Only connect to servers whose key is in the known hosts file.
+    Strict,
+    /// Add the keys of unknown servers to the known hosts file, but refuse to connect to servers
+    /// whose key changed.
+    #[default]
+    AcceptNew,
+    /// Do not verify the host keys.
+    Off,
+}
+
+/// A host key that could not be verified, reported as a failure of the server rather than a
+/// connection error.
+#[derive(Debug, thiserror::Error)]
+pub enum HostKeyError {
+    /// The key differs from the one recorded in the known hosts file.
+    #[error("host key of {host} changed, now {fingerprint}: possible man-in-the-middle attack or re-provisioned server, update {known_hosts}")]
+    Changed {
+        host: String,
+        fingerprint: String,
+        known_hosts: String,
+    },
+    /// The key differs from the pinned fingerprint.
+    #[error("host key of {host} is {fingerprint}, expected {expected}")]
+    NotPinned {
+        host: String,
+        fingerprint: String,
+        expected: String,
+    },
+    /// The server is not in the known hosts file and `host_key_checking` is `strict`.
+    #[error("host key of {host} ({fingerprint}) is not in {known_hosts}")]
+    Unknown {
+        host: String,
+        fingerprint: String,
+        known_hosts: String,
+    },
+}
+
 /// Establishes an SSH session using a private key for authentication.
 ///
-/// This function attempts to connect to an SSH server at a specified host and port,
-/// then authenticates the session using a specified username and the private key
-/// located at `private_key_path`. It ensures that the session is authenticated before
-/// returning the session object.
+/// This function attempts to connect to the SSH server of the target, then authenticates the
+/// session using the username and private key of the target, or its password with
+/// `AuthMethod::Password`. It ensures that the session is
+/// authenticated before returning the session object.
+///
+/// The addresses the host resolves to are tried in turn, alternating between IPv6 and IPv4 as
+/// with Happy Eyeballs (RFC 8305), each with the `connect_timeout` of the target.
+///
+/// Before authenticating, the host key of the server is verified against the pinned fingerprint
+/// or the known hosts file of the target, according to its `host_key_checking`.
+///
+/// When the target has jump hosts, the connection is forwarded through them by the local OpenSSH
+/// client (`ssh -W`), which must be installed and able to authenticate with the jump hosts
+/// non-interactively.
 ///
 /// # Parameters
-/// - `host`: The hostname or IP address of the SSH server as a string slice.
-/// - `port`: The port number on which the SSH server is listening.
-/// - `username`: The username for authentication with the SSH server.
-/// - `private_key_path`: The filesystem path to the private key file used for authentication.
+/// - `target`: The host, port, username and private key to connect with.
 ///
 /// # Returns
 /// - `Ok(Session)`: An authenticated SSH `Session` object if the connection and authentication succeed.
@@ -104,51 +319,95 @@ pub fn run_ssh_command(
 ///
 /// # Examples
 /// ```no_run
-/// use ssh2::Session;
-/// use std::net::TcpStream;
-///
-/// let session = create_session("127.0.0.1", 22, "username", "/path/to/private/key").unwrap();
+/// let target = SshTarget {
+///     host: "127.0.0.1".to_string(),
+///     port: 22,
+///     user: "username".to_string(),
+///     private_key: "/path/to/private/key".to_string(),
+///     auth: AuthMethod::Key,
+///     password_env: None,
+///     password_file: None,
+///     proxy_jump: None,
+///     host_key_checking: HostKeyChecking::AcceptNew,
+///     known_hosts: "/home/username/.ssh/known_hosts".to_string(),
+///     keepalive: None,
+///     host_key_fingerprint: None,
+///     connect_timeout: Duration::from_secs(10),
+/// };
+/// let session = create_session(&target).unwrap();
 /// // Use `session` for executing commands, transferring files, etc.
 /// ```
 ///
 /// # Errors
 /// This function will return an error in the following cases:
-/// - TCP connection to the specified host and port fails.
+/// - TCP connection to the specified host and port fails, or the jump hosts cannot be reached.
 /// - Creation of the SSH session object fails.
 /// - The SSH handshake fails.
-/// - Authentication with the provided username and private key fails.
+/// - The host key cannot be verified, in which case the error is a `HostKeyError`.
+/// - Authentication with the provided username and private key (or password) fails.
 /// - The session is not authenticated after attempting the provided authentication method.
 ///
 /// All errors are logged with an appropriate message for debugging purposes.
 ///
 /// # Remarks
 /// The function requires an SSH server to be accessible at the specified host and port.
-/// The private key file specified by `private_key_path` must be in a format recognized
+/// The private key file specified by `private_key` must be in a format recognized
 /// by the server (e.g., RSA, DSA) and must not be encrypted with a passphrase.
-pub fn create_session(
-    host: &str,
-    port: u16,
-    username: &str,
-    private_key_path: &str,
-) -> Result<Session, Box<dyn std::error::Error>> {
-    let host_w_port = format!("{}:{}", host, port);
-    let tcp = TcpStream::connect(&host_w_port).map_err(|e| {
-        error!("Could not connect to {}", host_w_port);
-        Box::<dyn std::error::Error>::from(e)
-    })?;
+pub fn create_session(target: &SshTarget) -> Result<Session, Box<dyn std::error::Error>> {
+    let host_w_port = format!("{}:{}", utils::url_host(&target.host), target.port);
+    debug!("Connecting to {}@{}", target.user, host_w_port);
 
     let mut sess = Session::new().expect("Failed to create SSH session");
-    sess.set_tcp_stream(tcp);
+    match &target.proxy_jump {
+        Some(proxy_jump) => sess.set_tcp_stream(jump(target, proxy_jump)?),
+        None => {
+            let tcp = connect(&target.host, target.port, target.connect_timeout).map_err(|e| {
+                error!("Could not connect to {}: {}", host_w_port, e);
+                Box::<dyn std::error::Error>::from(e)
+            })?;
+            if let Some(keepalive) = target.keepalive {
+                if let Err(e) = set_tcp_keepalive(&tcp, keepalive) {
+                    warn!(
+                        "Could not enable TCP keepalives with {}: {}",
+                        host_w_port, e
+                    );
+                }
+            }
+            sess.set_tcp_stream(tcp);
+        }
+    }
     sess.handshake()?;
+    verify_host_key(&sess, target)?;
 
-    sess.userauth_pubkey_file(username, None, Path::new(private_key_path), None)
-        .map_err(|e| {
-            error!(
-                "could not authenticate with {} using {}: {}",
-                host, private_key_path, e
-            );
-            Box::<dyn std::error::Error>::from(e)
-        })?;
+    match target.auth {
+        AuthMethod::Key => sess
+            .userauth_pubkey_file(&target.user, None, Path::new(&target.private_key), None)
+            .map_err(|e| {
+                error!(
+                    "could not authenticate with {} using {}: {}",
+                    target.host, target.private_key, e
+                );
+                Box::<dyn std::error::Error>::from(e)
+            })?,
+        AuthMethod::Password => {
+            let password = password(
+                target.password_env.as_deref(),
+                target.password_file.as_deref(),
+            )
+            .map_err(|e| {
+                error!("no password to authenticate with {}: {}", target.host, e);
+                e
+            })?;
+            sess.userauth_password(&target.user, &password)
+                .map_err(|e| {
+                    error!(
+                        "could not authenticate with {} using a password: {}",
+                        target.host, e
+                    );
+                    Box::<dyn std::error::Error>::from(e)
+                })?
+        }
+    }
 
     if !sess.authenticated() {
         let err_msg = format!("Authentication failed: {}", host_w_port);
@@ -156,17 +415,603 @@ pub fn create_session(
         return Err(err_msg.into());
     }
 
+    if let Some(keepalive) = target.keepalive.filter(|k| k.interval > 0) {
+        sess.set_keepalive(true, keepalive.interval);
+        // Blocking reads return at every interval to send the keepalives, see `read_output`
+        sess.set_timeout(keepalive.interval.saturating_mul(1000));
+    }
+
     Ok(sess)
 }
 
+/// Returns the password to authenticate with, read from the environment variable or, if it is not
+/// set, from the file (without the trailing newline).
+///
+/// # Errors
+///
+/// Returns an error if the variable is not set and the file cannot be read, or if neither is given.
+pub fn password(
+    env: Option<&str>,
+    file: Option<&str>,
+) -> Result<String, Box<dyn std::error::Error>> {
+    let mut problems = vec![];
+    if let Some(env) = env {
+        match std::env::var(env) {
+            Ok(password) => return Ok(password),
+            Err(_) => problems.push(format!("environment variable {} is not set", env)),
+        }
+    }
+    if let Some(file) = file {
+        let path = utils::expand_tilde(file);
+        match std::fs::read_to_string(&path) {
+            Ok(password) => return Ok(password.trim_end_matches(['\r', '\n']).to_string()),
+            Err(e) => problems.push(format!("could not read {}: {}", path, e)),
+        }
+    }
+    if problems.is_empty() {
+        return Err("`auth: password` requires `password_env` or `password_file`".into());
+    }
+    Err(problems.join(" and ").into())
+}
+
+/// Verifies the host key of a session against the pinned fingerprint or the known hosts file of
+/// the target, adding unknown hosts to the file with `HostKeyChecking::AcceptNew`.
+fn verify_host_key(sess: &Session, target: &SshTarget) -> Result<(), Box<dyn std::error::Error>> {
+    if target.host_key_checking == HostKeyChecking::Off && target.host_key_fingerprint.is_none() {
+        return Ok(());
+    }
+    let (key, key_type) = sess.host_key().ok_or("the server sent no host key")?;
+    let fingerprint = format!(
+        "SHA256:{}",
+        STANDARD_NO_PAD.encode(sess.host_key_hash(HashType::Sha256).unwrap_or_default())
+    );
+    let host = known_hosts_name(&target.host, target.port);
+
+    if let Some(expected) = &target.host_key_fingerprint {
+        if expected.trim() != fingerprint {
+            return Err(HostKeyError::NotPinned {
+                host,
+                fingerprint,
+                expected: expected.trim().to_string(),
+            }
+            .into());
+        }
+        return Ok(());
+    }
+
+    let mut known_hosts = sess.known_hosts()?;
+    if let Ok(content) = std::fs::read_to_string(&target.known_hosts) {
+        // Lines libssh2 does not understand (e.g. @cert-authority markers) are skipped
+        for line in content.lines() {
+            known_hosts.read_str(line, KnownHostFileKind::OpenSSH).ok();
+        }
+    }
+    match known_hosts.check_port(&target.host, target.port, key) {
+        CheckResult::Match => Ok(()),
+        CheckResult::Mismatch => Err(HostKeyError::Changed {
+            host,
+            fingerprint,
+            known_hosts: target.known_hosts.clone(),
+        }
+        .into()),
+        _ if target.host_key_checking == HostKeyChecking::Strict => Err(HostKeyError::Unknown {
+            host,
+            fingerprint,
+            known_hosts: target.known_hosts.clone(),
+        }
+        .into()),
+        _ => {
+            warn!(
+                "Adding the host key of {} ({}) to {}",
+                host, fingerprint, target.known_hosts
+            );
+            let Some(line) = known_hosts_line(&host, key, key_type) else {
+                warn!("Unknown host key type of {}, not adding it", host);
+                return Ok(());
+            };
+            let path = Path::new(&target.known_hosts);
+            if let Some(parent) = path.parent() {
+                std::fs::create_dir_all(parent)?;
+            }
+            let mut file = std::fs::OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(path)?;
+            writeln!(file, "{}", line)?;
+            Ok(())
+        }
+    }
+}
+
+/// Returns the name of a host in the known hosts file: the host itself on port 22 and
+/// `[host]:port` otherwise.
+fn known_hosts_name(host: &str, port: u16) -> String {
+    match port {
+        22 => host.to_string(),
+        port => format!("[{}]:{}", host, port),
+    }
+}
+
+/// Returns the line of the known hosts file recording a host key.
+fn known_hosts_line(host: &str, key: &[u8], key_type: HostKeyType) -> Option<String> {
+    let name = match key_type {
+        HostKeyType::Rsa => "ssh-rsa",
+        HostKeyType::Dss => "ssh-dss",
+        HostKeyType::Ecdsa256 => "ecdsa-sha2-nistp256",
+        HostKeyType::Ecdsa384 => "ecdsa-sha2-nistp384",
+        HostKeyType::Ecdsa521 => "ecdsa-sha2-nistp521",
+        HostKeyType::Ed25519 => "ssh-ed25519",
+        HostKeyType::Unknown => return None,
+    };
+    Some(format!("{} {} {}", host, name, STANDARD.encode(key)))
+}
+
+/// Keeps the SSH sessions of the servers open across checks and runs.
+///
+/// Sessions are kept by server name and reused as long as they are alive and the connection
+/// details of the server did not change; otherwise a new session is created. This avoids a new
+/// handshake on every run in daemon mode, which matters for servers rate-limiting connections.
+#[derive(Default)]
+pub struct SessionPool {
+    sessions: HashMap<String, (SshTarget, Session)>,
+}
+
+impl SessionPool {
+    /// Creates an empty pool.
+    pub fn new() -> SessionPool {
+        SessionPool::default()
+    }
+
+    /// Returns the session of a server along with the position of the target it is connected
+    /// to, reusing its open session if it is still alive.
+    ///
+    /// A new session is created with the first of the `targets` (the addresses of the server)
+    /// accepting it, the next ones being tried when a connection fails.
+    ///
+    /// # Errors
+    ///
+    /// Returns the error of `create_session` when a new session is needed and no target accepts
+    /// it, listing the error of every target if there are several. A `HostKeyError` is returned
+    /// as soon as it occurs, without trying the next targets.
+    pub fn get(
+        &mut self,
+        name: &str,
+        targets: &[SshTarget],
+    ) -> Result<(&Session, usize), Box<dyn std::error::Error>> {
+        let reusable = self
+            .sessions
+            .get(name)
+            .filter(|(t, sess)| targets.contains(t) && is_alive(sess))
+            .and_then(|(t, _)| targets.iter().position(|target| target == t));
+        if let Some(position) = reusable {
+            return Ok((&self.sessions[name].1, position));
+        }
+        if self.sessions.remove(name).is_some() {
+            debug!("Reconnecting to {}", name);
+        }
+
+        let mut errors = vec![];
+        for (position, target) in targets.iter().enumerate() {
+            match create_session(target) {
+                Ok(sess) => {
+                    if position > 0 {
+                        warn!(
+                            "Connected to {} at {}, {} unreachable",
+                            name,
+                            target.host,
+                            targets[..position]
+                                .iter()
+                                .map(|t| t.host.as_str())
+                                .collect::<Vec<_>>()
+                                .join(", ")
+                        );
+                    }
+                    self.sessions
+                        .insert(name.to_string(), (target.clone(), sess));
+                    return Ok((&self.sessions[name].1, position));
+                }
+                Err(e) if e.is::<HostKeyError>() || targets.len() == 1 => return Err(e),
+                Err(e) => errors.push(format!("{}: {}", target.host, e)),
+            }
+        }
+        Err(match errors.is_empty() {
+            true => "no address to connect to".into(),
+            false => format!("every address is unreachable ({})", errors.join("; ")).into(),
+        })
+    }
+
+    /// Closes the sessions of the servers not in `names`, called by the daemon once the
+    /// configuration is reloaded or the inventories refreshed, for the sessions to the servers
+    /// removed or renamed not to stay open for the life of the process.
+    pub fn retain(&mut self, names: &[&str]) {
+        self.sessions
+            .retain(|name, _| names.contains(&name.as_str()));
+    }
+
+    /// Returns the number of open sessions.
+    pub fn len(&self) -> usize {
+        self.sessions.len()
+    }
+
+    /// Returns whether no session is open.
+    pub fn is_empty(&self) -> bool {
+        self.sessions.is_empty()
+    }
+}
+
+/// Connects to the first reachable address of a host, trying its addresses in the order of
+/// `interleave`, each for at most `timeout`.
+fn connect(host: &str, port: u16, timeout: Duration) -> std::io::Result<TcpStream> {
+    let addresses = interleave((host, port).to_socket_addrs()?.collect());
+    let mut last_error = None;
+    for address in addresses {
+        match TcpStream::connect_timeout(&address, timeout) {
+            Ok(tcp) => return Ok(tcp),
+            Err(e) => {
+                debug!("Could not connect to {}: {}", address, e);
+                last_error = Some(e);
+            }
+        }
+    }
+    Err(last_error.unwrap_or_else(|| {
+        std::io::Error::new(
+            std::io::ErrorKind::NotFound,
+            format!("{} does not resolve to any address", host),
+        )
+    }))
+}
+
+/// Orders the addresses of a host by alternating their families, starting with the family of the
+/// first one, so that a broken IPv6 (or IPv4) network delays the connection by a single timeout.
+fn interleave(addresses: Vec<SocketAddr>) -> Vec<SocketAddr> {
+    let first_v6 = addresses.first().is_some_and(SocketAddr::is_ipv6);
+    let (preferred, other): (Vec<_>, Vec<_>) =
+        addresses.into_iter().partition(|a| a.is_ipv6() == first_v6);
+    let mut other = other.into_iter();
+    let mut ordered = vec![];
+    for address in preferred {
+        ordered.push(address);
+        ordered.extend(other.next());
+    }
+    ordered.extend(other);
+    ordered
+}
+
+/// Returns whether a session still works, by opening (and closing) a channel.
+fn is_alive(sess: &Session) -> bool {
+    match sess.channel_session() {
+        Ok(mut channel) => {
+            channel.close().ok();
+            true
+        }
+        Err(e) => {
+            debug!("Session is not alive anymore: {}", e);
+            false
+        }
+    }
+}
+
+/// Enables the TCP keepalives of a connection, dropped by the system after `max_missed`
+/// unanswered probes.
+fn set_tcp_keepalive(tcp: &TcpStream, keepalive: Keepalive) -> std::io::Result<()> {
+    let interval = std::time::Duration::from_secs(u64::from(keepalive.interval.max(1)));
+    let params = socket2::TcpKeepalive::new().with_time(interval);
+    #[cfg(any(
+        target_os = "linux",
+        target_os = "macos",
+        target_os = "freebsd",
+        target_os = "netbsd",
+        target_os = "windows"
+    ))]
+    let params = params
+        .with_interval(interval)
+        .with_retries(keepalive.max_missed);
+    socket2::SockRef::from(tcp).set_tcp_keepalive(&params)
+}
+
+/// Opens a connection to a host through jump hosts, forwarded by `ssh -W` over a socket pair.
+#[cfg(unix)]
+fn jump(
+    target: &SshTarget,
+    proxy_jump: &str,
+) -> Result<std::os::unix::net::UnixStream, Box<dyn std::error::Error>> {
+    use std::os::fd::OwnedFd;
+    use std::os::unix::net::UnixStream;
+    use std::process::{Command, Stdio};
+
+    let (stream, forwarded) = UnixStream::pair()?;
+    let output = forwarded.try_clone()?;
+    let mut child = Command::new("ssh")
+        .args(jump_args(target, proxy_jump))
+        .stdin(Stdio::from(OwnedFd::from(forwarded)))
+        .stdout(Stdio::from(OwnedFd::from(output)))
+        .stderr(Stdio::inherit())
+        .spawn()
+        .map_err(|e| {
+            error!("Could not start ssh to jump through {}: {}", proxy_jump, e);
+            Box::<dyn std::error::Error>::from(e)
+        })?;
+    // The client exits when the session is dropped and the socket closed
+    std::thread::spawn(move || child.wait());
+    Ok(stream)
+}
+
+#[cfg(not(unix))]
+fn jump(_target: &SshTarget, proxy_jump: &str) -> Result<TcpStream, Box<dyn std::error::Error>> {
+    Err(format!(
+        "Cannot jump through {}: ProxyJump is only supported on Unix",
+        proxy_jump
+    )
+    .into())
+}
+
+/// Returns the arguments of `ssh` forwarding a connection to the target through the jump hosts,
+/// the last of which is the destination of the client and the others passed to `-J`.
+#[cfg_attr(not(unix), allow(dead_code))]
+fn jump_args(target: &SshTarget, proxy_jump: &str) -> Vec<String> {
+    let host = target.host.trim_start_matches('[').trim_end_matches(']');
+    let mut args = vec![
+        "-o".to_string(),
+        "BatchMode=yes".to_string(),
+        "-W".to_string(),
+        format!("[{}]:{}", host, target.port),
+    ];
+    if let Some(keepalive) = target.keepalive.filter(|k| k.interval > 0) {
+        args.extend([
+            "-o".to_string(),
+            format!("ServerAliveInterval={}", keepalive.interval),
+            "-o".to_string(),
+            format!("ServerAliveCountMax={}", keepalive.max_missed),
+        ]);
+    }
+    let (first, last) = match proxy_jump.rsplit_once(',') {
+        Some((first, last)) => (Some(first), last),
+        None => (None, proxy_jump),
+    };
+    if let Some(first) = first {
+        args.extend(["-J".to_string(), first.to_string()]);
+    }
+
+    let last = last.trim_start_matches("ssh://");
+    let (user, destination) = match last.split_once('@') {
+        Some((user, destination)) => (Some(user), destination),
+        None => (None, last),
+    };
+    let (destination, jump_port) = match destination.rsplit_once(':') {
+        Some((d, p)) if !d.contains(':') && p.parse::<u16>().is_ok() => (d, Some(p)),
+        _ => (destination, None),
+    };
+    if let Some(user) = user {
+        args.extend(["-l".to_string(), user.to_string()]);
+    }
+    if let Some(jump_port) = jump_port {
+        args.extend(["-p".to_string(), jump_port.to_string()]);
+    }
+    args.extend(["--".to_string(), destination.to_string()]);
+    args
+}
+
+/// Returns the target of the SSH server of the integration tests, started by `tests/sshd/run.sh`.
+///
+/// The port and private key are taken from the `REMOTESYSMONITOR_TEST_SSH_PORT` and
+/// `REMOTESYSMONITOR_TEST_SSH_KEY` environment variables, and unknown host keys are accepted into
+/// a known hosts file in the temporary directory.
+#[cfg(test)]
+pub fn test_server() -> SshTarget {
+    let port = std::env::var("REMOTESYSMONITOR_TEST_SSH_PORT")
+        .ok()
+        .and_then(|p| p.parse().ok())
+        .unwrap_or(2222);
+    let private_key = std::env::var("REMOTESYSMONITOR_TEST_SSH_KEY")
+        .unwrap_or_else(|_| concat!(env!("CARGO_MANIFEST_DIR"), "/target/sshd/id_ed25519").into());
+    let known_hosts = std::env::temp_dir().join("remotesysmonitor-test-known-hosts");
+    SshTarget {
+        host: "127.0.0.1".to_string(),
+        port,
+        user: "monitor".to_string(),
+        private_key,
+        auth: AuthMethod::Key,
+        password_env: None,
+        password_file: None,
+        proxy_jump: None,
+        host_key_checking: HostKeyChecking::AcceptNew,
+        known_hosts: known_hosts.to_string_lossy().into_owned(),
+        keepalive: None,
+        host_key_fingerprint: None,
+        connect_timeout: Duration::from_secs(10),
+    }
+}
+
 #[cfg(test)]
 mod test {
+    use super::*;
+
+    fn target(host: &str, port: u16) -> SshTarget {
+        SshTarget {
+            host: host.to_string(),
+            port,
+            user: "monitor".to_string(),
+            private_key: "/keys/monitor".to_string(),
+            auth: AuthMethod::Key,
+            password_env: None,
+            password_file: None,
+            proxy_jump: None,
+            host_key_checking: HostKeyChecking::AcceptNew,
+            known_hosts: "/keys/known_hosts".to_string(),
+            keepalive: None,
+            host_key_fingerprint: None,
+            connect_timeout: Duration::from_secs(10),
+        }
+    }
 
     #[test]
-    #[ignore] // Heavily relies on external resources
-    fn test_create_session() {}
+    fn test_connect() {
+        let v4: SocketAddr = "192.0.2.1:22".parse().unwrap();
+        let v4b: SocketAddr = "192.0.2.2:22".parse().unwrap();
+        let v6: SocketAddr = "[2001:db8::1]:22".parse().unwrap();
+        let v6b: SocketAddr = "[2001:db8::2]:22".parse().unwrap();
+        assert_eq!(interleave(vec![v6, v6b, v4, v4b]), [v6, v4, v6b, v4b]);
+        assert_eq!(interleave(vec![v4, v4b, v6]), [v4, v6, v4b]);
+
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        // `localhost` may resolve to ::1 first, on which nothing listens
+        assert!(connect("localhost", port, Duration::from_secs(1)).is_ok());
+        assert!(connect("does-not-exist.invalid", port, Duration::from_secs(1)).is_err());
+    }
+
+    #[test]
+    fn test_password() {
+        std::env::set_var("REMOTESYSMONITOR_TEST_SSH_PASSWORD", "secret");
+        let file = std::env::temp_dir().join("remotesysmonitor-test-ssh-password");
+        std::fs::write(&file, "from file\n").unwrap();
+        let file = file.to_str();
+
+        let env = Some("REMOTESYSMONITOR_TEST_SSH_PASSWORD");
+        assert_eq!(password(env, file).unwrap(), "secret");
+        assert_eq!(
+            password(Some("REMOTESYSMONITOR_UNSET"), file).unwrap(),
+            "from file"
+        );
+
+        let error = password(Some("REMOTESYSMONITOR_UNSET"), Some("/does/not/exist"));
+        assert!(error.unwrap_err().to_string().starts_with(
+            "environment variable REMOTESYSMONITOR_UNSET is not set and could not read /does/not/exist"
+        ));
+        assert!(password(None, None).is_err());
+    }
+
+    #[test]
+    fn test_known_hosts_line() {
+        assert_eq!(known_hosts_name("web01", 22), "web01");
+        assert_eq!(known_hosts_name("web01", 2222), "[web01]:2222");
+        assert_eq!(known_hosts_name("2001:db8::1", 2222), "[2001:db8::1]:2222");
+        assert_eq!(
+            known_hosts_line("web01", b"key", HostKeyType::Ed25519).unwrap(),
+            "web01 ssh-ed25519 a2V5"
+        );
+        assert_eq!(
+            known_hosts_line("web01", b"key", HostKeyType::Unknown),
+            None
+        );
+    }
+
+    #[test]
+    fn test_jump_args() {
+        let mut target = target("10.0.0.1", 22);
+        assert_eq!(
+            jump_args(&target, "bastion").join(" "),
+            "-o BatchMode=yes -W [10.0.0.1]:22 -- bastion"
+        );
+
+        target.keepalive = Some(serde_yaml::from_str("{interval: 30}").unwrap());
+        assert_eq!(
+            jump_args(&target, "bastion").join(" "),
+            "-o BatchMode=yes -W [10.0.0.1]:22 -o ServerAliveInterval=30 \
+             -o ServerAliveCountMax=3 -- bastion"
+        );
+
+        assert_eq!(
+            jump_args(&self::target("db", 2222), "first,admin@bastion:2200").join(" "),
+            "-o BatchMode=yes -W [db]:2222 -J first -l admin -p 2200 -- bastion"
+        );
+    }
+
+    #[test]
+    fn test_session_pool() {
+        let mut pool = SessionPool::new();
+        // Nothing listens on port 1, the error of the connection is returned
+        assert!(pool.get("local", &[target("127.0.0.1", 1)]).is_err());
+        assert!(pool.is_empty());
+        // Every address is tried before giving up
+        let targets = [target("127.0.0.1", 1), target("127.0.0.1", 2)];
+        let error = pool.get("local", &targets).err().unwrap().to_string();
+        assert!(
+            error.starts_with("every address is unreachable (127.0.0.1: "),
+            "{}",
+            error
+        );
+        assert!(pool.is_empty());
+        pool.retain(&[]);
+        assert_eq!(pool.len(), 0);
+    }
+
+    #[test]
+    #[ignore] // Requires the SSH server of tests/sshd/run.sh
+    fn test_create_session() {
+        let mut target = test_server();
+        std::fs::remove_file(&target.known_hosts).ok();
+
+        // The key of the server is accepted on first use, then required
+        create_session(&target).unwrap();
+        let known_hosts = std::fs::read_to_string(&target.known_hosts).unwrap();
+        assert!(known_hosts.starts_with(&format!("[127.0.0.1]:{} ", target.port)));
+        target.host_key_checking = HostKeyChecking::Strict;
+        create_session(&target).unwrap();
+
+        target.host_key_fingerprint =
+            Some("SHA256:AAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAA".to_string());
+        let error = create_session(&target).err().unwrap();
+        assert!(matches!(
+            error.downcast_ref::<HostKeyError>(),
+            Some(HostKeyError::NotPinned { .. })
+        ));
+        target.host_key_fingerprint = None;
+
+        std::env::set_var("REMOTESYSMONITOR_TEST_SSH_SERVER_PASSWORD", "monitor");
+        target.auth = AuthMethod::Password;
+        target.password_env = Some("REMOTESYSMONITOR_TEST_SSH_SERVER_PASSWORD".to_string());
+        create_session(&target).unwrap();
+
+        target.keepalive = Some(Keepalive {
+            interval: 1,
+            max_missed: 3,
+        });
+        let sess = create_session(&target).unwrap();
+        // Waiting longer than the interval sends keepalives instead of timing out
+        assert_eq!(
+            run_ssh_command(&sess, "sleep 3 && echo done").unwrap(),
+            "done\n"
+        );
+
+        target.password_env = Some("REMOTESYSMONITOR_UNSET".to_string());
+        assert!(create_session(&target).is_err());
+    }
 
     #[test]
-    #[ignore] // Heavily relies on external resources
-    fn test_run_ssh_command() {}
+    #[ignore] // Requires the SSH server of tests/sshd/run.sh
+    fn test_run_ssh_command() {
+        let sess = create_session(&test_server()).unwrap();
+        assert_eq!(run_ssh_command(&sess, "echo hello").unwrap(), "hello\n");
+        assert_eq!(sess.run("whoami").unwrap(), "monitor\n");
+
+        let error = run_ssh_command(&sess, "echo oops >&2; exit 3").unwrap_err();
+        let error = error.downcast_ref::<CommandError>().unwrap();
+        assert_eq!(error.exit_status, 3);
+        assert_eq!(error.stderr, "oops\n");
+
+        let deadline = Instant::now() + std::time::Duration::from_secs(1);
+        let error = run_ssh_command_until(&sess, "sleep 10", Some(deadline)).unwrap_err();
+        assert!(error.is::<TimedOut>());
+        assert!(Instant::now() < deadline + std::time::Duration::from_secs(2));
+        // The session is still usable after a command was abandoned
+        assert_eq!(run_ssh_command(&sess, "echo hello").unwrap(), "hello\n");
+
+        let mut pool = SessionPool::new();
+        pool.get("test", &[test_server()]).unwrap();
+        pool.get("test", &[test_server()]).unwrap();
+        assert_eq!(pool.len(), 1);
+
+        // The next address is used when the first is unreachable, and kept
+        let mut unreachable = test_server();
+        unreachable.port = 1;
+        let targets = [unreachable, test_server()];
+        assert_eq!(pool.get("failover", &targets).unwrap().1, 1);
+        assert_eq!(pool.get("failover", &targets).unwrap().1, 1);
+
+        // The session of a server removed from the configuration is closed
+        assert_eq!(pool.len(), 2);
+        pool.retain(&["test"]);
+        assert_eq!(pool.len(), 1);
+    }
 }
@@ -0,0 +1,363 @@
+use crate::utils;
+use log::warn;
+use std::path::{Path, PathBuf};
+
+/// Maximum depth of nested `Include` directives.
+const MAX_INCLUDE_DEPTH: usize = 16;
+
+/// The settings of the OpenSSH client configuration (`~/.ssh/config`) used to connect to a host.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct HostConfig {
+    /// Real hostname to connect to (`HostName`).
+    pub hostname: Option<String>,
+    /// User to log in as (`User`).
+    pub user: Option<String>,
+    /// Port to connect to (`Port`).
+    pub port: Option<u16>,
+    /// First private key to authenticate with (`IdentityFile`).
+    pub identity_file: Option<String>,
+    /// Jump hosts to connect through (`ProxyJump`).
+    pub proxy_jump: Option<String>,
+}
+
+/// A `Host` block of an OpenSSH client configuration.
+#[derive(Debug, Clone, PartialEq)]
+struct Block {
+    /// Host patterns of the block, `None` for `Match` blocks which are not supported.
+    patterns: Option<Vec<String>>,
+    /// Options of the block, with lowercase keywords, in order.
+    options: Vec<(String, String)>,
+}
+
+/// A parsed OpenSSH client configuration.
+///
+/// Only the `Host` blocks (with `*`, `?` and `!` patterns) and `Include` directives are
+/// supported; `Match` blocks are ignored. As with OpenSSH, the first value obtained for an
+/// option wins.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SshConfig {
+    blocks: Vec<Block>,
+}
+
+impl SshConfig {
+    /// Loads the configuration of the current user, `~/.ssh/config`.
+    ///
+    /// A missing file results in an empty configuration, and unreadable included files are logged
+    /// and skipped.
+    pub fn load_default() -> SshConfig {
+        let path = utils::expand_tilde("~/.ssh/config");
+        match std::fs::read_to_string(&path) {
+            Ok(content) => SshConfig::parse(&content),
+            Err(e) => {
+                if Path::new(&path).exists() {
+                    warn!("Could not read {}: {}", path, e);
+                }
+                SshConfig::default()
+            }
+        }
+    }
+
+    /// Parses the content of a configuration file.
+    pub fn parse(content: &str) -> SshConfig {
+        let mut config = SshConfig {
+            blocks: vec![Block {
+                patterns: Some(vec!["*".to_string()]),
+                options: vec![],
+            }],
+        };
+        config.parse_into(content, 0);
+        config
+    }
+
+    fn parse_into(&mut self, content: &str, depth: usize) {
+        for line in content.lines() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let (keyword, value) = match line.split_once(|c: char| c.is_whitespace() || c == '=') {
+                Some((keyword, value)) => (
+                    keyword.to_lowercase(),
+                    value
+                        .trim_start_matches(|c: char| c.is_whitespace() || c == '=')
+                        .trim(),
+                ),
+                None => continue,
+            };
+
+            match keyword.as_str() {
+                "host" => self.blocks.push(Block {
+                    patterns: Some(value.split_whitespace().map(unquote).collect()),
+                    options: vec![],
+                }),
+                "match" => self.blocks.push(Block {
+                    patterns: None,
+                    options: vec![],
+                }),
+                "include" if depth < MAX_INCLUDE_DEPTH => {
+                    for path in value
+                        .split_whitespace()
+                        .flat_map(|p| include_paths(&unquote(p)))
+                    {
+                        match std::fs::read_to_string(&path) {
+                            Ok(content) => self.parse_into(&content, depth + 1),
+                            Err(e) => warn!("Could not read {}: {}", path.display(), e),
+                        }
+                    }
+                }
+                "include" => warn!("Too many nested includes in the SSH configuration"),
+                _ => {
+                    if let Some(block) = self.blocks.last_mut() {
+                        block.options.push((keyword, unquote(value)));
+                    }
+                }
+            }
+        }
+    }
+
+    /// Returns the hosts named by the `Host` blocks, in order, matching one of the `include`
+    /// patterns (any host if empty) and none of the `exclude` ones. Patterns with wildcards or
+    /// negations are not hosts, and are skipped.
+    pub fn hosts(&self, include: &[String], exclude: &[String]) -> Vec<String> {
+        let mut hosts: Vec<String> = vec![];
+        for pattern in self
+            .blocks
+            .iter()
+            .filter_map(|b| b.patterns.as_ref())
+            .flatten()
+        {
+            if pattern.contains(['*', '?', '!']) || hosts.contains(pattern) {
+                continue;
+            }
+            let included = include.is_empty() || include.iter().any(|p| wildcard_match(p, pattern));
+            if included && !exclude.iter().any(|p| wildcard_match(p, pattern)) {
+                hosts.push(pattern.clone());
+            }
+        }
+        hosts
+    }
+
+    /// Returns the settings applying to a host, e.g. an alias defined by a `Host` block.
+    pub fn lookup(&self, host: &str) -> HostConfig {
+        let mut values: Vec<(&str, &str)> = vec![];
+        for block in &self.blocks {
+            let Some(patterns) = &block.patterns else {
+                continue;
+            };
+            if !matches_patterns(patterns, host) {
+                continue;
+            }
+            for (keyword, value) in &block.options {
+                if !values.iter().any(|(k, _)| k == keyword) {
+                    values.push((keyword, value));
+                }
+            }
+        }
+        let get = |keyword: &str| {
+            values
+                .iter()
+                .find(|(k, _)| *k == keyword)
+                .map(|(_, v)| v.to_string())
+        };
+
+        let user = get("user");
+        let tokens = |value: String| expand_tokens(&value, host, user.as_deref());
+        HostConfig {
+            hostname: get("hostname").map(tokens),
+            port: get("port").and_then(|p| p.parse().ok()),
+            identity_file: get("identityfile").map(|f| utils::expand_tilde(&tokens(f))),
+            proxy_jump: get("proxyjump").filter(|p| p != "none"),
+            user,
+        }
+    }
+}
+
+/// Removes the double quotes around a value.
+fn unquote(value: &str) -> String {
+    value
+        .strip_prefix('"')
+        .and_then(|v| v.strip_suffix('"'))
+        .unwrap_or(value)
+        .to_string()
+}
+
+/// Returns the files matched by an `Include` path, relative to `~/.ssh` unless absolute. Only the
+/// last component of the path may contain wildcards.
+fn include_paths(path: &str) -> Vec<PathBuf> {
+    let path = utils::expand_tilde(path);
+    let path = if Path::new(&path).is_absolute() {
+        PathBuf::from(path)
+    } else {
+        PathBuf::from(utils::expand_tilde("~/.ssh")).join(path)
+    };
+    let Some(name) = path.file_name().and_then(|n| n.to_str()) else {
+        return vec![];
+    };
+    if !name.contains(['*', '?']) {
+        return vec![path];
+    }
+
+    let dir = path.parent().unwrap_or(Path::new("."));
+    let mut paths: Vec<PathBuf> = std::fs::read_dir(dir)
+        .into_iter()
+        .flatten()
+        .flatten()
+        .map(|entry| entry.path())
+        .filter(|p| {
+            p.file_name()
+                .and_then(|n| n.to_str())
+                .is_some_and(|n| wildcard_match(name, n))
+        })
+        .collect();
+    paths.sort();
+    paths
+}
+
+/// Returns whether a host matches a list of patterns: at least one pattern must match and no
+/// negated (`!`) pattern may match.
+fn matches_patterns(patterns: &[String], host: &str) -> bool {
+    let mut matched = false;
+    for pattern in patterns {
+        match pattern.strip_prefix('!') {
+            Some(negated) if wildcard_match(negated, host) => return false,
+            Some(_) => {}
+            None => matched |= wildcard_match(pattern, host),
+        }
+    }
+    matched
+}
+
+/// Matches a text against a pattern where `*` matches any sequence and `?` any single character.
+fn wildcard_match(pattern: &str, text: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let text: Vec<char> = text.chars().collect();
+    let (mut p, mut t) = (0, 0);
+    let mut backtrack: Option<(usize, usize)> = None;
+    while t < text.len() {
+        if p < pattern.len() && (pattern[p] == '?' || pattern[p] == text[t]) {
+            p += 1;
+            t += 1;
+        } else if p < pattern.len() && pattern[p] == '*' {
+            backtrack = Some((p, t));
+            p += 1;
+        } else if let Some((bp, bt)) = backtrack {
+            p = bp + 1;
+            t = bt + 1;
+            backtrack = Some((bp, bt + 1));
+        } else {
+            return false;
+        }
+    }
+    pattern[p..].iter().all(|c| *c == '*')
+}
+
+/// Expands the `%h` (host), `%r` (remote user), `%u` (local user), `%d` (home directory) and `%%`
+/// tokens of a value.
+fn expand_tokens(value: &str, host: &str, user: Option<&str>) -> String {
+    let mut expanded = String::new();
+    let mut chars = value.chars();
+    while let Some(c) = chars.next() {
+        if c != '%' {
+            expanded.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('h') => expanded.push_str(host),
+            Some('r') => expanded.push_str(&user.map_or_else(utils::current_user, String::from)),
+            Some('u') => expanded.push_str(&utils::current_user()),
+            Some('d') => expanded.push_str(&utils::expand_tilde("~")),
+            Some('%') => expanded.push('%'),
+            Some(other) => {
+                expanded.push('%');
+                expanded.push(other);
+            }
+            None => expanded.push('%'),
+        }
+    }
+    expanded
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CONFIG: &str = r#"
+# Comment
+Host web01 web01.example.com
+    HostName 10.0.0.1
+    User deploy
+    Port 2222
+
+Host *.internal !bastion.internal
+    ProxyJump bastion.internal
+    IdentityFile ~/.ssh/internal_%h
+
+Match host foo
+    User ignored
+
+Host *
+    User fallback
+    IdentityFile="~/.ssh/id_ed25519"
+"#;
+
+    #[test]
+    fn test_lookup() {
+        let config = SshConfig::parse(CONFIG);
+
+        let web = config.lookup("web01");
+        assert_eq!(web.hostname.as_deref(), Some("10.0.0.1"));
+        assert_eq!(web.user.as_deref(), Some("deploy"));
+        assert_eq!(web.port, Some(2222));
+        assert!(web.identity_file.unwrap().ends_with("/.ssh/id_ed25519"));
+        assert_eq!(web.proxy_jump, None);
+
+        let db = config.lookup("db.internal");
+        assert_eq!(db.hostname, None);
+        assert_eq!(db.user.as_deref(), Some("fallback"));
+        assert_eq!(db.proxy_jump.as_deref(), Some("bastion.internal"));
+        assert!(db
+            .identity_file
+            .unwrap()
+            .ends_with("/.ssh/internal_db.internal"));
+
+        let bastion = config.lookup("bastion.internal");
+        assert_eq!(bastion.proxy_jump, None);
+    }
+
+    #[test]
+    fn test_hosts() {
+        let config = SshConfig::parse(CONFIG);
+        assert_eq!(config.hosts(&[], &[]), ["web01", "web01.example.com"]);
+        let include = ["*.com".to_string(), "db*".to_string()];
+        assert_eq!(config.hosts(&include, &[]), ["web01.example.com"]);
+        assert_eq!(config.hosts(&[], &["web01.*".to_string()]), ["web01"]);
+    }
+
+    #[test]
+    fn test_first_value_wins() {
+        let config = SshConfig::parse("User first\nHost a\n  User second\n");
+        assert_eq!(config.lookup("a").user.as_deref(), Some("first"));
+    }
+
+    #[test]
+    fn test_wildcard_match() {
+        assert!(wildcard_match("*", "anything"));
+        assert!(wildcard_match("web??", "web01"));
+        assert!(wildcard_match("*.example.com", "a.b.example.com"));
+        assert!(!wildcard_match("*.example.com", "example.com"));
+        assert!(!wildcard_match("web?", "web01"));
+    }
+
+    #[test]
+    fn test_include() {
+        let dir = std::env::temp_dir().join("remotesysmonitor-test-ssh-config");
+        std::fs::remove_dir_all(&dir).ok();
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("a.conf"), "Host a\n  Port 2201\n").unwrap();
+        std::fs::write(dir.join("b.conf"), "Host b\n  Port 2202\n").unwrap();
+
+        let config = SshConfig::parse(&format!("Include {}/*.conf\n", dir.display()));
+        assert_eq!(config.lookup("a").port, Some(2201));
+        assert_eq!(config.lookup("b").port, Some(2202));
+    }
+}
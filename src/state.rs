@@ -0,0 +1,416 @@
+use crate::report::Status;
+use chrono::{DateTime, Duration, Utc};
+use hmac_sha256::Hash;
+use log::error;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::io::Write;
+use std::path::Path;
+
+/// Last known status of a check, as persisted in the state file.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CheckState {
+    /// Status of the check during the last run.
+    pub status: Status,
+    /// When the check entered its current status.
+    pub since: DateTime<Utc>,
+    /// When a notification about the check was last sent, if ever.
+    pub notified: Option<DateTime<Utc>>,
+}
+
+/// A change in the status of a check that deserves a notification.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Change {
+    /// The check started failing.
+    Failed(String),
+    /// The check was failing and passes again.
+    Recovered(String),
+    /// The check is still failing and a reminder is due.
+    StillFailing(String),
+}
+
+/// Persistent record of the last status of every check, keyed by `server/check`.
+///
+/// The state is stored as a JSON file between runs so that notifications can be sent only when
+/// a check starts or stops failing instead of on every run.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct State {
+    pub checks: BTreeMap<String, CheckState>,
+    /// Boot time of the servers at the last run of their `uptime` checks, keyed by `server/check`.
+    #[serde(default)]
+    pub boots: BTreeMap<String, DateTime<Utc>>,
+    /// Time of the last OOM kill reported by the `oom_kills` checks, keyed by `server/check`.
+    #[serde(default)]
+    pub kills: BTreeMap<String, DateTime<Utc>>,
+    /// Time of the last login, and of the last failed login, reported by the `logins` checks, keyed
+    /// by `server/check`.
+    #[serde(default)]
+    pub logins: BTreeMap<String, DateTime<Utc>>,
+    #[serde(default)]
+    pub failed_logins: BTreeMap<String, DateTime<Utc>>,
+    /// Hashes of the lines of the files compared by the `account_changes` checks at their last
+    /// run (see `line_hashes`), keyed by `server/check` and by path. Their content is not kept, so
+    /// that the state file does not disclose e.g. the sudoers rules.
+    #[serde(default)]
+    pub file_lines: BTreeMap<String, BTreeMap<String, Vec<String>>>,
+}
+
+impl State {
+    /// Loads the state from a JSON file, returning an empty state if the file does not exist yet.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file exists but cannot be read or parsed.
+    pub fn load(path: &str) -> Result<State, Box<dyn std::error::Error>> {
+        if !Path::new(path).exists() {
+            return Ok(State::default());
+        }
+        let content = std::fs::read_to_string(path).map_err(|e| {
+            error!("Could not read state file {}: {}", path, e);
+            Box::<dyn std::error::Error>::from(e)
+        })?;
+        let state = serde_json::from_str(&content).map_err(|e| {
+            error!("Could not parse state file {}: {}", path, e);
+            Box::<dyn std::error::Error>::from(e)
+        })?;
+        Ok(state)
+    }
+
+    /// Writes the state to a JSON file, replacing its previous content. On Unix, the file is only
+    /// readable by its owner, as it records e.g. the logins and the accounts of the servers.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the state cannot be serialized or the file cannot be written.
+    pub fn save(&self, path: &str) -> Result<(), Box<dyn std::error::Error>> {
+        let content = serde_json::to_string_pretty(self)?;
+        let mut options = std::fs::OpenOptions::new();
+        options.write(true).create(true).truncate(true);
+        #[cfg(unix)]
+        std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+        let write = |mut file: std::fs::File| {
+            // The mode only applies to new files, restrict the existing ones as well
+            #[cfg(unix)]
+            file.set_permissions(std::os::unix::fs::PermissionsExt::from_mode(0o600))?;
+            file.write_all(content.as_bytes())
+        };
+        options.open(path).and_then(write).map_err(|e| {
+            error!("Could not write state file {}: {}", path, e);
+            Box::<dyn std::error::Error>::from(e)
+        })?;
+        Ok(())
+    }
+
+    /// Records the current status of a check and returns the change it represents, if any.
+    ///
+    /// A check is considered failing when its status is `Critical` only, a warning being neither
+    /// announced nor reminded. A check that was never seen before and is failing is reported as
+    /// `Failed`. When `reminder` is given, a check that has been failing for longer than `reminder`
+    /// since the last notification about it (see `mark_notified`) is reported as `StillFailing`.
+    ///
+    /// # Arguments
+    ///
+    /// * `key` - Identifier of the check, e.g. `server/check`.
+    /// * `status` - Status of the check in the current run.
+    /// * `now` - Time of the current run.
+    /// * `reminder` - Optional interval after which still failing checks are notified again.
+    pub fn update(
+        &mut self,
+        key: &str,
+        status: Status,
+        now: DateTime<Utc>,
+        reminder: Option<Duration>,
+    ) -> Option<Change> {
+        let failing = status == Status::Critical;
+        let previous = self.checks.get(key).cloned();
+        let was_failing = previous
+            .as_ref()
+            .is_some_and(|p| p.status == Status::Critical);
+
+        let change = match (was_failing, failing) {
+            (false, true) => Some(Change::Failed(key.to_string())),
+            (true, false) => Some(Change::Recovered(key.to_string())),
+            (true, true) => {
+                let last = previous
+                    .as_ref()
+                    .and_then(|p| p.notified)
+                    .or(previous.as_ref().map(|p| p.since));
+                match (reminder, last) {
+                    (Some(reminder), Some(last)) if now - last >= reminder => {
+                        Some(Change::StillFailing(key.to_string()))
+                    }
+                    _ => None,
+                }
+            }
+            (false, false) => None,
+        };
+
+        let since = match &previous {
+            Some(p) if p.status == status => p.since,
+            _ => now,
+        };
+        // The notifications of a previous status do not count towards the reminders
+        let notified = match &previous {
+            Some(p) if p.status == status => p.notified,
+            _ => None,
+        };
+        self.checks.insert(
+            key.to_string(),
+            CheckState {
+                status,
+                since,
+                notified,
+            },
+        );
+
+        change
+    }
+
+    /// Records that the changes were notified at `now`, once a notifier delivered them, so that
+    /// the reminders about a check are counted from the last notification actually sent.
+    pub fn mark_notified(&mut self, changes: &[Change], now: DateTime<Utc>) {
+        for change in changes {
+            let (Change::Failed(key) | Change::Recovered(key) | Change::StillFailing(key)) = change;
+            if let Some(check) = self.checks.get_mut(key) {
+                check.notified = Some(now);
+            }
+        }
+    }
+
+    /// Records the boot time of a server measured by an `uptime` check, and returns whether it
+    /// changed since the previous run, i.e. whether the server rebooted in between. A boot time
+    /// moving by less than a minute is not a reboot, as Linux derives it from the clock.
+    pub fn rebooted(&mut self, key: &str, boot: DateTime<Utc>) -> bool {
+        self.boots
+            .insert(key.to_string(), boot)
+            .is_some_and(|previous| (boot - previous).num_seconds().abs() >= 60)
+    }
+
+    /// Records an OOM kill found by an `oom_kills` check and returns whether it is new, i.e. more
+    /// recent than the kills reported by the previous runs. The kills are expected in
+    /// chronological order.
+    pub fn new_kill(&mut self, key: &str, time: DateTime<Utc>) -> bool {
+        if self.kills.get(key).is_some_and(|last| time <= *last) {
+            return false;
+        }
+        self.kills.insert(key.to_string(), time);
+        true
+    }
+
+    /// Returns the time of the last login found by a `logins` check at the previous runs, or of
+    /// the last failed login. The logins found by a run are new if they are strictly more recent,
+    /// whether or not they share a second, which `last` and `lastb` do not tell apart.
+    pub fn last_login(&self, key: &str, failed: bool) -> Option<DateTime<Utc>> {
+        let logins = if failed {
+            &self.failed_logins
+        } else {
+            &self.logins
+        };
+        logins.get(key).copied()
+    }
+
+    /// Records the time of the last login found by a run of a `logins` check, or of the last
+    /// failed login, once all of them were compared to `last_login`.
+    pub fn record_login(&mut self, key: &str, time: DateTime<Utc>, failed: bool) {
+        let logins = if failed {
+            &mut self.failed_logins
+        } else {
+            &mut self.logins
+        };
+        let last = logins.entry(key.to_string()).or_insert(time);
+        *last = (*last).max(time);
+    }
+
+    /// Records the hashes of the lines of a file read by an `account_changes` check (see
+    /// `line_hashes`), and returns those of the previous run, if it was read then.
+    pub fn replace_file(
+        &mut self,
+        key: &str,
+        path: &str,
+        lines: Vec<String>,
+    ) -> Option<Vec<String>> {
+        self.file_lines
+            .entry(key.to_string())
+            .or_default()
+            .insert(path.to_string(), lines)
+    }
+
+    /// Forgets the files recorded for an `account_changes` check that are not among the files
+    /// found by its latest run, and returns their paths.
+    pub fn forget_files(&mut self, key: &str, found: &[String]) -> Vec<String> {
+        let Some(files) = self.file_lines.get_mut(key) else {
+            return vec![];
+        };
+        let removed: Vec<String> = files
+            .keys()
+            .filter(|path| !found.contains(path))
+            .cloned()
+            .collect();
+        files.retain(|path, _| found.contains(path));
+        removed
+    }
+}
+
+/// Returns the SHA-256 hash of a line of a file, in hexadecimal.
+pub fn line_hash(line: &str) -> String {
+    Hash::hash(line.as_bytes())
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
+
+/// Returns the hashes of the lines of a file, in order.
+pub fn line_hashes(content: &str) -> Vec<String> {
+    content.lines().map(line_hash).collect()
+}
+
+/// Splits a `server/check` key into the server and check names.
+pub fn split_key(key: &str) -> (&str, &str) {
+    key.rsplit_once('/').unwrap_or((key, ""))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_update_transitions() {
+        let mut state = State::default();
+        let now = Utc::now();
+
+        assert_eq!(state.update("srv/load", Status::Ok, now, None), None);
+        assert_eq!(
+            state.update("srv/load", Status::Critical, now, None),
+            Some(Change::Failed("srv/load".to_string()))
+        );
+        assert_eq!(state.update("srv/load", Status::Critical, now, None), None);
+        assert_eq!(
+            state.update("srv/load", Status::Warning, now, None),
+            Some(Change::Recovered("srv/load".to_string()))
+        );
+        // A check failing on its first run is a change as well
+        assert_eq!(
+            state.update("srv/ping", Status::Critical, now, None),
+            Some(Change::Failed("srv/ping".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_update_reminder() {
+        let mut state = State::default();
+        let start = Utc::now();
+        let reminder = Some(Duration::minutes(60));
+
+        state.update("srv/load", Status::Critical, start, reminder);
+        let later = start + Duration::minutes(30);
+        assert_eq!(
+            state.update("srv/load", Status::Critical, later, reminder),
+            None
+        );
+        let later = start + Duration::minutes(61);
+        assert_eq!(
+            state.update("srv/load", Status::Critical, later, reminder),
+            Some(Change::StillFailing("srv/load".to_string()))
+        );
+        // The reminder is due until it is delivered
+        let later = start + Duration::minutes(62);
+        let change = state.update("srv/load", Status::Critical, later, reminder);
+        assert_eq!(change, Some(Change::StillFailing("srv/load".to_string())));
+        state.mark_notified(&[change.unwrap()], later);
+        // The reminder interval restarts from the last notification
+        let later = start + Duration::minutes(90);
+        assert_eq!(
+            state.update("srv/load", Status::Critical, later, reminder),
+            None
+        );
+        assert_eq!(state.checks["srv/load"].since, start);
+    }
+
+    #[test]
+    fn test_rebooted() {
+        let mut state = State::default();
+        let boot = Utc::now() - Duration::days(3);
+        assert!(!state.rebooted("srv/uptime", boot));
+        assert!(!state.rebooted("srv/uptime", boot + Duration::seconds(1)));
+        assert!(state.rebooted("srv/uptime", Utc::now()));
+        assert!(!state.rebooted("srv/uptime", Utc::now()));
+    }
+
+    #[test]
+    fn test_new_kill() {
+        let mut state = State::default();
+        let first = Utc::now() - Duration::hours(2);
+        let second = Utc::now() - Duration::minutes(5);
+        assert!(state.new_kill("srv/oom", first));
+        assert!(state.new_kill("srv/oom", second));
+        // The next run finds the same kills in the kernel log
+        assert!(!state.new_kill("srv/oom", first));
+        assert!(!state.new_kill("srv/oom", second));
+        assert!(state.new_kill("srv/oom", Utc::now()));
+    }
+
+    #[test]
+    fn test_record_login() {
+        let mut state = State::default();
+        let time = Utc::now() - Duration::hours(1);
+        assert_eq!(state.last_login("srv/logins", false), None);
+        state.record_login("srv/logins", time, false);
+        state.record_login("srv/logins", time - Duration::minutes(5), false);
+        assert_eq!(state.last_login("srv/logins", false), Some(time));
+        // The failed logins are recorded apart
+        assert_eq!(state.last_login("srv/logins", true), None);
+    }
+
+    #[test]
+    fn test_replace_file() {
+        let mut state = State::default();
+        let passwd = line_hashes("root");
+        assert_eq!(
+            state.replace_file("srv/accounts", "/etc/passwd", passwd.clone()),
+            None
+        );
+        assert_eq!(
+            state.replace_file("srv/accounts", "/etc/passwd", line_hashes("root\nbob")),
+            Some(passwd)
+        );
+        state.replace_file("srv/accounts", "/etc/group", line_hashes("root"));
+        let found = ["/etc/passwd".to_string()];
+        assert_eq!(state.forget_files("srv/accounts", &found), ["/etc/group"]);
+        assert!(state.forget_files("srv/accounts", &found).is_empty());
+    }
+
+    #[test]
+    fn test_split_key() {
+        assert_eq!(split_key("web01/load"), ("web01", "load"));
+        assert_eq!(split_key("web01"), ("web01", ""));
+    }
+
+    #[test]
+    fn test_line_hashes() {
+        let hashes = line_hashes("root:x:0:0::/root:/bin/sh\nbob");
+        assert_eq!(hashes.len(), 2);
+        assert_eq!(hashes[1], line_hash("bob"));
+        assert_eq!(hashes[1].len(), 64);
+        assert!(!hashes[0].contains("root"));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_save_permissions() {
+        use std::os::unix::fs::PermissionsExt;
+        let path = std::env::temp_dir().join(format!("state-{}.json", std::process::id()));
+        let path = path.to_str().unwrap();
+        std::fs::write(path, "{}").unwrap();
+        std::fs::set_permissions(path, std::fs::Permissions::from_mode(0o644)).unwrap();
+        State::default().save(path).unwrap();
+        let mode = std::fs::metadata(path).unwrap().permissions().mode();
+        std::fs::remove_file(path).unwrap();
+        assert_eq!(mode & 0o777, 0o600);
+    }
+
+    #[test]
+    fn test_load_missing_file() {
+        let state = State::load("/does/not/exist/state.json").unwrap();
+        assert!(state.checks.is_empty());
+    }
+}
@@ -0,0 +1,157 @@
+use log::{debug, Level, Log, Metadata, Record};
+use sd_notify::NotifyState;
+use std::io::Write;
+use std::os::unix::net::UnixDatagram;
+use std::path::PathBuf;
+use std::time::Duration;
+
+/// Socket of the native protocol of journald.
+const JOURNAL_SOCKET: &str = "/run/systemd/journal/socket";
+
+/// Notifies systemd of the state of the service (e.g. `Ready` once started, or its `Status`), when
+/// running as a `Type=notify` unit. Does nothing otherwise.
+pub fn notify(states: &[NotifyState]) {
+    if let Err(e) = sd_notify::notify(false, states) {
+        debug!("Could not notify systemd: {}", e);
+    }
+}
+
+/// Returns how often the watchdog of systemd is to be pinged, half of the `WatchdogSec` of the
+/// unit, or `None` if the watchdog is not enabled.
+pub fn watchdog() -> Option<Duration> {
+    let mut usec = 0;
+    sd_notify::watchdog_enabled(false, &mut usec).then(|| Duration::from_micros(usec / 2))
+}
+
+/// A file holding the PID of the process, removed when dropped.
+pub struct PidFile(PathBuf);
+
+impl PidFile {
+    /// Writes the PID of the process to a file, replacing it if it exists.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file cannot be written.
+    pub fn create(path: &str) -> Result<PidFile, Box<dyn std::error::Error>> {
+        std::fs::write(path, format!("{}\n", std::process::id()))
+            .map_err(|e| format!("Could not write the PID file {}: {}", path, e))?;
+        Ok(PidFile(PathBuf::from(path)))
+    }
+}
+
+impl Drop for PidFile {
+    fn drop(&mut self) {
+        std::fs::remove_file(&self.0).ok();
+    }
+}
+
+/// A logger sending the records to journald over its native protocol, with their priority and
+/// source as fields (`PRIORITY`, `TARGET`, `CODE_FILE` and `CODE_LINE`), the records being
+/// filtered by the wrapped logger and written by it when journald cannot be reached.
+pub struct JournalLogger {
+    fallback: env_logger::Logger,
+    socket: UnixDatagram,
+}
+
+impl JournalLogger {
+    /// Connects to journald when the standard error of the process is sent to the journal, as
+    /// systemd sets `JOURNAL_STREAM` for services, giving the wrapped logger back otherwise.
+    pub fn connect(fallback: env_logger::Logger) -> Result<JournalLogger, env_logger::Logger> {
+        if std::env::var_os("JOURNAL_STREAM").is_none() {
+            return Err(fallback);
+        }
+        let Ok(socket) = UnixDatagram::unbound() else {
+            return Err(fallback);
+        };
+        match socket.connect(JOURNAL_SOCKET) {
+            Ok(()) => Ok(JournalLogger { fallback, socket }),
+            Err(_) => Err(fallback),
+        }
+    }
+}
+
+impl Log for JournalLogger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        self.fallback.enabled(metadata)
+    }
+
+    fn log(&self, record: &Record) {
+        if !self.fallback.matches(record) {
+            return;
+        }
+        if self.socket.send(&journal_entry(record)).is_err() {
+            self.fallback.log(record);
+        }
+    }
+
+    fn flush(&self) {}
+}
+
+/// Serializes a record in the native protocol of journald, the values spanning several lines
+/// being prefixed with their length.
+fn journal_entry(record: &Record) -> Vec<u8> {
+    let priority = match record.level() {
+        Level::Error => 3,
+        Level::Warn => 4,
+        Level::Info => 6,
+        Level::Debug | Level::Trace => 7,
+    };
+    let mut fields = vec![
+        ("PRIORITY", priority.to_string()),
+        ("SYSLOG_IDENTIFIER", env!("CARGO_PKG_NAME").to_string()),
+        ("TARGET", record.target().to_string()),
+        ("MESSAGE", record.args().to_string()),
+    ];
+    if let (Some(file), Some(line)) = (record.file(), record.line()) {
+        fields.push(("CODE_FILE", file.to_string()));
+        fields.push(("CODE_LINE", line.to_string()));
+    }
+
+    let mut entry = vec![];
+    for (name, value) in fields {
+        entry.extend_from_slice(name.as_bytes());
+        if value.contains('\n') {
+            entry.push(b'\n');
+            entry.write_all(&(value.len() as u64).to_le_bytes()).ok();
+        } else {
+            entry.push(b'=');
+        }
+        entry.extend_from_slice(value.as_bytes());
+        entry.push(b'\n');
+    }
+    entry
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_journal_entry() {
+        let entry = journal_entry(
+            &Record::builder()
+                .level(Level::Warn)
+                .target("remotesysmonitor::ssh")
+                .args(format_args!("Reconnecting\nto web01"))
+                .build(),
+        );
+        let mut expected = b"PRIORITY=4\nSYSLOG_IDENTIFIER=remotesysmonitor\n\
+                             TARGET=remotesysmonitor::ssh\nMESSAGE\n"
+            .to_vec();
+        expected.extend_from_slice(&21u64.to_le_bytes());
+        expected.extend_from_slice(b"Reconnecting\nto web01\n");
+        assert_eq!(entry, expected);
+    }
+
+    #[test]
+    fn test_pid_file() {
+        let path = std::env::temp_dir().join("remotesysmonitor-test.pid");
+        let pid_file = PidFile::create(path.to_str().unwrap()).unwrap();
+        assert_eq!(
+            std::fs::read_to_string(&path).unwrap(),
+            format!("{}\n", std::process::id())
+        );
+        drop(pid_file);
+        assert!(!path.exists());
+    }
+}
@@ -0,0 +1,194 @@
+use crate::report::{CheckResult, Markers, Report, ServerReport, Status};
+use crate::utils;
+use crate::webhook;
+use serde_json::{json, Value};
+
+/// Maximum size of a serialized card, below the 28KB accepted by Teams webhooks.
+const MAX_CARD_SIZE: usize = 24_000;
+/// Maximum number of characters of the details of a result.
+const MAX_DETAILS: usize = 2000;
+
+/// Posts a report to a Microsoft Teams incoming webhook (or a Workflows webhook) as Adaptive
+/// Cards.
+///
+/// # Arguments
+///
+/// * `url` - The webhook URL of the Teams channel.
+/// * `report` - The report of the run.
+/// * `markers` - The markers of the statuses and servers.
+///
+/// # Errors
+///
+/// Returns an error as soon as one of the cards could not be delivered.
+pub fn post_report(
+    url: &str,
+    report: &Report,
+    markers: &Markers,
+) -> Result<(), Box<dyn std::error::Error>> {
+    for card in build_cards(report, markers) {
+        webhook::post_json(url, &card)?;
+    }
+    Ok(())
+}
+
+/// Builds the Adaptive Card messages of a report.
+///
+/// The first card starts with a title holding the timestamp, followed by the summary line and the
+/// notes of the report.
+/// Every server gets a container styled by its status (red for critical, yellow for warning)
+/// listing its results, with their details in a monospace block. The timings of the run end the
+/// last card.
+///
+/// Teams rejects messages larger than 28KB, so the servers are spread over as many cards as
+/// needed to stay below this limit.
+pub fn build_cards(report: &Report, markers: &Markers) -> Vec<Value> {
+    let timestamp = utils::make_pretty_timestamp();
+    let title = |text: String| {
+        json!({
+            "type": "TextBlock",
+            "text": text,
+            "size": "Large",
+            "weight": "Bolder",
+            "wrap": true,
+        })
+    };
+
+    let mut body = vec![
+        title(format!("RemoteSysMonitor · {}", timestamp)),
+        json!({"type": "TextBlock", "text": report.summary(), "weight": "Bolder", "wrap": true}),
+    ];
+    body.extend(report.notes.iter().map(|note| text_block(note, markers)));
+
+    let mut cards = vec![];
+    for server in &report.servers {
+        let container = server_container(server, markers);
+        let size = json!(body).to_string().len() + container.to_string().len();
+        if size > MAX_CARD_SIZE && body.iter().any(|b| b["type"] == "Container") {
+            cards.push(card(std::mem::take(&mut body)));
+            body.push(title(format!(
+                "RemoteSysMonitor · {} (continued)",
+                timestamp
+            )));
+        }
+        body.push(container);
+    }
+    if let Some(footer) = report.footer() {
+        body.push(json!({"type": "TextBlock", "text": footer, "isSubtle": true, "wrap": true}));
+    }
+    cards.push(card(body));
+    cards
+}
+
+/// Wraps the body of an Adaptive Card in the message format expected by Teams webhooks.
+fn card(body: Vec<Value>) -> Value {
+    json!({
+        "type": "message",
+        "attachments": [{
+            "contentType": "application/vnd.microsoft.card.adaptive",
+            "content": {
+                "$schema": "http://adaptivecards.io/schemas/adaptive-card.json",
+                "type": "AdaptiveCard",
+                "version": "1.4",
+                "msteams": {"width": "Full"},
+                "body": body,
+            },
+        }],
+    })
+}
+
+/// Builds the container of a server.
+fn server_container(server: &ServerReport, markers: &Markers) -> Value {
+    let style = match server.status() {
+        Status::Critical => "attention",
+        Status::Warning => "warning",
+        _ => "default",
+    };
+    let mut items = vec![json!({
+        "type": "TextBlock",
+        "text": server.header_with(markers),
+        "weight": "Bolder",
+        "wrap": true,
+    })];
+    for result in &server.results {
+        items.push(text_block(result, markers));
+        if let Some(details) = &result.details {
+            items.push(json!({
+                "type": "TextBlock",
+                "text": utils::truncate(details.trim_end(), MAX_DETAILS),
+                "fontType": "Monospace",
+                "isSubtle": true,
+                "wrap": true,
+            }));
+        }
+    }
+    json!({
+        "type": "Container",
+        "style": style,
+        "separator": true,
+        "items": items,
+    })
+}
+
+/// Builds the text block of a result, without its details.
+fn text_block(result: &CheckResult, markers: &Markers) -> Value {
+    json!({"type": "TextBlock", "text": result.headline_with(markers), "wrap": true})
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_build_cards() {
+        let mut ok = ServerReport::new("web01", "web01.example.com");
+        ok.results.push(CheckResult::new(Status::Ok, "load 1.00"));
+        let mut failing = ServerReport::new("db01", "db01.example.com");
+        failing.results.push(
+            CheckResult::new(Status::Critical, "Directories older than 2 days:")
+                .with_details("/data/a\n"),
+        );
+        let report = Report {
+            notes: vec![CheckResult::new(Status::Ok, "recovered: load @ web02")],
+            servers: vec![ok, failing],
+        };
+
+        let cards = build_cards(&report, &Markers::default());
+        assert_eq!(cards.len(), 1);
+        let content = &cards[0]["attachments"][0]["content"];
+        assert_eq!(content["type"], "AdaptiveCard");
+
+        let body = content["body"].as_array().unwrap();
+        assert_eq!(body.len(), 5);
+        assert_eq!(body[1]["text"], "1/2 servers failing: db01 (1 check OK)");
+        assert_eq!(body[2]["text"], "✅ recovered: load @ web02");
+        assert_eq!(body[3]["style"], "default");
+        assert_eq!(body[4]["style"], "attention");
+        assert_eq!(body[4]["items"][2]["text"], "/data/a");
+        assert_eq!(body[4]["items"][2]["fontType"], "Monospace");
+    }
+
+    #[test]
+    fn test_build_cards_splits() {
+        let servers = (0..30)
+            .map(|i| {
+                let mut server = ServerReport::new(&format!("srv{}", i), "example.com");
+                server.results.push(
+                    CheckResult::new(Status::Info, "`cmd`").with_details("x".repeat(MAX_DETAILS)),
+                );
+                server
+            })
+            .collect();
+        let report = Report {
+            notes: vec![],
+            servers,
+        };
+
+        let cards = build_cards(&report, &Markers::default());
+        assert!(cards.len() > 1);
+        assert!(cards.iter().all(|c| c.to_string().len() < 28_000));
+        let body = cards[1]["attachments"][0]["content"]["body"]
+            .as_array()
+            .unwrap();
+        assert!(body[0]["text"].as_str().unwrap().ends_with("(continued)"));
+    }
+}
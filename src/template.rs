@@ -0,0 +1,236 @@
+use crate::report::{CheckResult, Markers, Report, ServerReport};
+use crate::utils;
+use minijinja::{context, Environment, Value};
+use serde::{Deserialize, Serialize};
+
+/// Templates overriding the plain text rendering of the reports of a notifier, in the
+/// [Jinja2 syntax](https://docs.rs/minijinja/latest/minijinja/syntax/index.html) of minijinja.
+///
+/// Every template is optional, the default rendering being used for the parts left out:
+///
+/// ```yaml
+/// templates:
+///   result: "{{ emoji }} [{{ server | upper }}] {{ message }}"
+///   server: "== {{ name }} ({{ host }}) =="
+///   report: |
+///     Run of {{ timestamp }}: {{ status }}
+///     {% for server in servers %}{{ server.header }}
+///     {{ server.results | join("\n") }}
+///     {% endfor %}
+/// ```
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Templates {
+    /// Line of every result, given `status` (e.g. `critical`), `emoji`, `message`, `check`,
+    /// `metric` (with `name` and `value`, if any), `previous`, `muted`, `details`, the `server`
+    /// and `host` it belongs to, and `line`, its default rendering.
+    pub result: Option<String>,
+    /// Header of every server, given its `name`, `host`, `status`, `emoji` and `header`, its
+    /// default rendering.
+    pub server: Option<String>,
+    /// Whole report, given its `summary` line, the rendered `notes`, the `servers` (each with its
+    /// `name`, `host`, `status`, `emoji`, rendered `header` and `results`), the overall `status`
+    /// and `emoji`, the `separator`, the `footer` with the timings of the run (if any) and its
+    /// `timestamp`.
+    pub report: Option<String>,
+}
+
+impl Templates {
+    /// Renders a report as plain text. Without a `report` template, it is laid out as
+    /// `Report::to_text` does, with the `result` and `server` templates applied. The `emoji` of
+    /// the statuses and the default renderings are those of the `markers`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if a template is invalid, or fails to render (e.g. a filter applied to a
+    /// value of the wrong type).
+    pub fn render(
+        &self,
+        report: &Report,
+        separator: &str,
+        markers: &Markers,
+    ) -> Result<String, minijinja::Error> {
+        let env = Environment::new();
+        let notes = report
+            .notes
+            .iter()
+            .map(|note| self.render_result(&env, note, None, markers))
+            .collect::<Result<Vec<_>, _>>()?;
+        let mut servers = vec![];
+        for server in &report.servers {
+            let header = match &self.server {
+                Some(template) => env.render_str(template, server_context(server, markers))?,
+                None => server.header_with(markers),
+            };
+            let results = server
+                .results
+                .iter()
+                .map(|result| self.render_result(&env, result, Some(server), markers))
+                .collect::<Result<Vec<_>, _>>()?;
+            servers.push((server, header, results));
+        }
+
+        let Some(template) = &self.report else {
+            let mut lines = vec![report.summary()];
+            lines.extend(notes);
+            for (_, header, results) in servers {
+                lines.push(header);
+                lines.extend(results);
+                lines.push(separator.repeat(10));
+            }
+            lines.extend(report.footer());
+            return Ok(lines.join("\n"));
+        };
+        let servers: Vec<Value> = servers
+            .into_iter()
+            .map(|(server, header, results)| {
+                context! {
+                    name => server.name,
+                    host => server.host,
+                    status => server.status(),
+                    emoji => markers.status(server.status()),
+                    header => header,
+                    results => results,
+                }
+            })
+            .collect();
+        env.render_str(
+            template,
+            context! {
+                summary => report.summary(),
+                notes => notes,
+                servers => servers,
+                status => report.status(),
+                emoji => markers.status(report.status()),
+                separator => separator,
+                footer => report.footer(),
+                timestamp => utils::make_pretty_timestamp(),
+            },
+        )
+    }
+
+    /// Checks that the templates are valid, without rendering them.
+    ///
+    /// # Errors
+    ///
+    /// Returns the syntax error of the first invalid template.
+    pub fn check(&self) -> Result<(), minijinja::Error> {
+        let mut env = Environment::new();
+        for (name, template) in [
+            ("result", &self.result),
+            ("server", &self.server),
+            ("report", &self.report),
+        ] {
+            if let Some(template) = template {
+                env.add_template(name, template)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn render_result(
+        &self,
+        env: &Environment,
+        result: &CheckResult,
+        server: Option<&ServerReport>,
+        markers: &Markers,
+    ) -> Result<String, minijinja::Error> {
+        let Some(template) = &self.result else {
+            return Ok(result.to_text(markers));
+        };
+        env.render_str(
+            template,
+            context! {
+                status => result.status,
+                emoji => markers.status(result.status),
+                message => result.message,
+                check => result.check,
+                metric => result.metric,
+                previous => result.previous,
+                muted => result.muted,
+                details => result.details,
+                server => server.map(|s| s.name.as_str()),
+                host => server.map(|s| s.host.as_str()),
+                line => result.to_text(markers),
+            },
+        )
+    }
+}
+
+fn server_context(server: &ServerReport, markers: &Markers) -> Value {
+    context! {
+        name => server.name,
+        host => server.host,
+        status => server.status(),
+        emoji => markers.status(server.status()),
+        header => server.header_with(markers),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::report::Status;
+
+    fn report() -> Report {
+        let mut web = ServerReport::new("web01", "web01.example.com");
+        web.results.push(
+            CheckResult::new(Status::Critical, "load 60.00 (5min) @ web01")
+                .with_check("load")
+                .with_metric("load5", 60.0),
+        );
+        Report {
+            notes: vec![CheckResult::new(Status::Ok, "disk recovered")],
+            servers: vec![web],
+        }
+    }
+
+    #[test]
+    fn test_render() {
+        let report = report();
+        assert_eq!(
+            Templates::default()
+                .render(&report, "-", &Markers::default())
+                .unwrap(),
+            report.to_text("-")
+        );
+
+        let templates = Templates {
+            result: Some(
+                "[{{ status | upper }}] {{ host or '-' }} {{ check }}={{ metric.value }}"
+                    .to_string(),
+            ),
+            server: Some("== {{ name }} ==".to_string()),
+            report: None,
+        };
+        assert_eq!(
+            templates.render(&report, "", &Markers::default()).unwrap(),
+            "1/1 server failing: web01 (0 checks OK)\n[OK] - =\n== web01 ==\n[CRITICAL] web01.example.com load=60.0\n"
+        );
+
+        let templates = Templates {
+            report: Some(
+                "{{ emoji }} {% for s in servers %}{{ s.name }}: {{ s.results | join(', ') }}\
+                 {% endfor %}"
+                    .to_string(),
+            ),
+            ..Templates::default()
+        };
+        assert_eq!(
+            templates.render(&report, "", &Markers::default()).unwrap(),
+            "❌ web01: ❌ load 60.00 (5min) @ web01"
+        );
+    }
+
+    #[test]
+    fn test_check() {
+        assert!(Templates::default().check().is_ok());
+        let templates = Templates {
+            result: Some("{{ message".to_string()),
+            ..Templates::default()
+        };
+        assert!(templates.check().is_err());
+        assert!(templates
+            .render(&report(), "", &Markers::default())
+            .is_err());
+    }
+}
@@ -1,14 +1,92 @@
-use chrono::Local;
-/// Generates a formatted timestamp string based on the current local time.
+use chrono::format::{Item, StrftimeItems};
+use chrono::{DateTime, Local, Utc};
+use chrono_tz::Tz;
+use serde::{Deserialize, Serialize};
+use std::sync::RwLock;
+
+/// Format of the timestamps when none is configured, e.g. "02/Feb/23 15:04 PST".
+const DEFAULT_TIMESTAMP_FORMAT: &str = "%d/%b/%y %H:%M %Z";
+
+/// Timestamp settings set at startup with `set_timestamps`, the local time in the default format
+/// until then.
+static TIMESTAMPS: RwLock<Option<Timestamps>> = RwLock::new(None);
+
+/// Timezone and format of the timestamps of the reports, e.g. for a container running in UTC
+/// notifying a team living in another timezone:
+///
+/// ```yaml
+/// timestamps:
+///   timezone: Europe/Paris
+///   format: "%Y-%m-%d %H:%M %Z"
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Timestamps {
+    /// Name of the timezone in the IANA database (e.g. `Europe/Paris` or `UTC`), the local
+    /// timezone of the machine if omitted.
+    pub timezone: Option<String>,
+    /// Format in the [strftime syntax](https://docs.rs/chrono/latest/chrono/format/strftime/index.html)
+    /// of chrono, `%d/%b/%y %H:%M %Z` if omitted.
+    pub format: Option<String>,
+}
+
+impl Timestamps {
+    /// Checks that the timezone exists and that the format is valid.
+    ///
+    /// # Errors
+    ///
+    /// Returns a description of the first problem found.
+    pub fn check(&self) -> Result<(), String> {
+        if let Some(timezone) = &self.timezone {
+            timezone
+                .parse::<Tz>()
+                .map_err(|_| format!("unknown timezone `{}`", timezone))?;
+        }
+        if let Some(format) = &self.format {
+            if StrftimeItems::new(format).any(|item| item == Item::Error) {
+                return Err(format!("invalid timestamp format `{}`", format));
+            }
+        }
+        Ok(())
+    }
+
+    /// Formats a time, falling back to the local timezone and the default format for the
+    /// settings that are invalid, since formatting with an invalid format panics.
+    pub fn format(&self, time: DateTime<Utc>) -> String {
+        let format = self
+            .format
+            .as_deref()
+            .filter(|f| !StrftimeItems::new(f).any(|item| item == Item::Error))
+            .unwrap_or(DEFAULT_TIMESTAMP_FORMAT);
+        match self
+            .timezone
+            .as_deref()
+            .and_then(|tz| tz.parse::<Tz>().ok())
+        {
+            Some(tz) => time.with_timezone(&tz).format(format).to_string(),
+            None => time.with_timezone(&Local).format(format).to_string(),
+        }
+    }
+}
+
+/// Sets the timezone and format of the timestamps returned by `make_pretty_timestamp`, for the
+/// rest of the process (or until set again, e.g. when the configuration is reloaded).
+pub fn set_timestamps(timestamps: Option<&Timestamps>) {
+    if let Ok(mut current) = TIMESTAMPS.write() {
+        *current = timestamps.cloned();
+    }
+}
+
+/// Generates a formatted timestamp string based on the current time.
 ///
-/// This function retrieves the current local time and formats it into a more readable string format,
-/// typically used for logging or displaying timestamps in a user-friendly manner. The format used is
+/// This function retrieves the current time and formats it into a more readable string format,
+/// typically used for logging or displaying timestamps in a user-friendly manner. The timezone
+/// and format are those set with `set_timestamps`, by default the local time as
 /// day/month(abbreviated)/year hour:minute timezone.
 ///
 /// # Returns
 ///
-/// Returns a `String` representing the current local time formatted as "dd/Mon/yy HH:MM TZ". For example,
-/// "02/Feb/23 15:04 PST".
+/// Returns a `String` representing the current time formatted as "dd/Mon/yy HH:MM TZ" by default.
+/// For example, "02/Feb/23 15:04 PST".
 ///
 /// # Examples
 ///
@@ -16,16 +94,165 @@ use chrono::Local;
 /// let timestamp = make_pretty_timestamp();
 /// println!("Current time: {}", timestamp);
 /// ```
+pub fn make_pretty_timestamp() -> String {
+    let now = Utc::now();
+    match TIMESTAMPS.read().ok().as_deref() {
+        Some(Some(timestamps)) => timestamps.format(now),
+        _ => Timestamps::default().format(now),
+    }
+}
+
+/// Returns the name of the user running the application, from the `USER` (or `LOGNAME`, or
+/// `USERNAME` on Windows) environment variable.
+pub fn current_user() -> String {
+    ["USER", "LOGNAME", "USERNAME"]
+        .iter()
+        .find_map(|var| std::env::var(var).ok().filter(|user| !user.is_empty()))
+        .unwrap_or_else(|| "root".to_string())
+}
+
+/// Expands a leading `~` in a path to the home directory of the current user.
 ///
-/// # Note
+/// Paths starting with `~user` are returned unchanged, as are all paths when the home directory
+/// is unknown.
 ///
-/// This function relies on the `chrono` crate's `Local::now` to get the current time and its `format` method
-/// to convert the time into a string. Ensure the `chrono` crate is included in your project's dependencies
-/// to use this function.
-pub fn make_pretty_timestamp() -> String {
-    let now = Local::now();
-    let formatted = now.format("%d/%b/%y %H:%M %Z").to_string();
-    formatted
+/// # Examples
+///
+/// ```
+/// // With HOME=/home/monitor
+/// assert_eq!(expand_tilde("~/.ssh/id_rsa"), "/home/monitor/.ssh/id_rsa");
+/// ```
+pub fn expand_tilde(path: &str) -> String {
+    let home = std::env::var("HOME").or_else(|_| std::env::var("USERPROFILE"));
+    match (path.strip_prefix('~'), home) {
+        (Some(rest), Ok(home)) if rest.is_empty() || rest.starts_with('/') => {
+            format!("{}{}", home.trim_end_matches('/'), rest)
+        }
+        _ => path.to_string(),
+    }
+}
+
+/// Returns a host as written in a URL or next to a port, an IPv6 address being enclosed in
+/// brackets, e.g. `[2001:db8::1]`.
+pub fn url_host(host: &str) -> String {
+    match host.contains(':') && !host.starts_with('[') {
+        true => format!("[{}]", host),
+        false => host.to_string(),
+    }
+}
+
+/// Truncates the output of a command to at most `max_lines` lines and `max_chars` characters,
+/// cutting it on a line boundary and counting the lines left out, e.g.:
+///
+/// ```text
+/// line 1
+/// line 2
+/// … 48 more lines
+/// ```
+///
+/// Returns `None` if the output fits within the limits.
+pub fn truncate_output(
+    output: &str,
+    max_lines: Option<usize>,
+    max_chars: Option<usize>,
+) -> Option<String> {
+    let lines: Vec<&str> = output.trim_end().lines().collect();
+    let mut kept: Vec<String> = vec![];
+    let mut chars = 0;
+    let mut cut = false;
+    for line in &lines {
+        if max_lines.is_some_and(|max| kept.len() >= max) {
+            break;
+        }
+        let len = line.chars().count();
+        if let Some(max) = max_chars.filter(|max| chars + len > *max) {
+            // A first line longer than the limit is cut rather than left out
+            if kept.is_empty() && max > 0 {
+                kept.push(truncate(line, max));
+                cut = true;
+            }
+            break;
+        }
+        chars += len + 1;
+        kept.push(line.to_string());
+    }
+    let more = lines.len() - kept.len();
+    if more == 0 && !cut {
+        return None;
+    }
+    if more > 0 {
+        kept.push(format!(
+            "… {} more line{}",
+            more,
+            if more == 1 { "" } else { "s" }
+        ));
+    }
+    Some(kept.join("\n"))
+}
+
+/// Truncates a text to at most `max` characters, marking the cut with an ellipsis.
+pub fn truncate(text: &str, max: usize) -> String {
+    if text.chars().count() <= max {
+        return text.to_string();
+    }
+    let mut truncated: String = text.chars().take(max - 1).collect();
+    truncated.push('…');
+    truncated
+}
+
+/// Binary units of the sizes, as printed by `format_size` and read by `parse_size`.
+const SIZE_UNITS: [&str; 5] = ["B", "KiB", "MiB", "GiB", "TiB"];
+
+/// Parses a size in bytes, either a plain number of bytes or a number followed by a unit, e.g.
+/// `500M`, `1.5 GiB` or `10GB`. The units are powers of 1024 whichever way they are written, as
+/// for `du` and `df`.
+///
+/// # Errors
+///
+/// Returns an error if the number or the unit is invalid.
+pub fn parse_size(size: &str) -> Result<u64, String> {
+    let size = size.trim();
+    let split = size
+        .find(|c: char| !c.is_ascii_digit() && c != '.')
+        .unwrap_or(size.len());
+    let (number, unit) = size.split_at(split);
+    let number: f64 = number
+        .parse()
+        .map_err(|_| format!("invalid size `{}`", size))?;
+    let unit = unit.trim().to_lowercase();
+    let exponent = match unit.trim_end_matches("ib").trim_end_matches('b') {
+        "" => 0,
+        "k" => 1,
+        "m" => 2,
+        "g" => 3,
+        "t" => 4,
+        _ => return Err(format!("unknown unit `{}` in size `{}`", unit, size)),
+    };
+    Ok((number * 1024f64.powi(exponent)).round() as u64)
+}
+
+/// Formats a size in bytes in its largest binary unit, e.g. "4.20 GiB".
+pub fn format_size(bytes: u64) -> String {
+    let mut size = bytes as f64;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < SIZE_UNITS.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+    match unit {
+        0 => format!("{} B", bytes),
+        _ => format!("{:.2} {}", size, SIZE_UNITS[unit]),
+    }
+}
+
+/// Formats a duration in its largest whole unit, e.g. "14 minutes" or "3 days".
+pub fn format_duration(duration: chrono::Duration) -> String {
+    let (count, unit) = match duration.num_seconds() {
+        s if s >= 86_400 => (s / 86_400, "day"),
+        s if s >= 3_600 => (s / 3_600, "hour"),
+        s => (s.max(0) / 60, "minute"),
+    };
+    format!("{} {}{}", count, unit, if count == 1 { "" } else { "s" })
 }
 
 #[cfg(test)]
@@ -38,6 +265,95 @@ mod tests {
         assert!(!timestamp.is_empty(), "Timestamp should not be empty");
     }
 
+    #[test]
+    fn test_timestamps() {
+        let time: DateTime<Utc> = "2024-01-15T09:30:00Z".parse().unwrap();
+        let timestamps = Timestamps {
+            timezone: Some("Europe/Paris".to_string()),
+            format: Some("%Y-%m-%d %H:%M %Z".to_string()),
+        };
+        assert!(timestamps.check().is_ok());
+        assert_eq!(timestamps.format(time), "2024-01-15 10:30 CET");
+
+        let invalid = Timestamps {
+            timezone: Some("Mars/Olympus".to_string()),
+            format: Some("%Y-%".to_string()),
+        };
+        assert_eq!(
+            invalid.check().unwrap_err(),
+            "unknown timezone `Mars/Olympus`"
+        );
+        let invalid = Timestamps {
+            timezone: Some("UTC".to_string()),
+            ..invalid
+        };
+        assert_eq!(
+            invalid.check().unwrap_err(),
+            "invalid timestamp format `%Y-%`"
+        );
+        assert_eq!(invalid.format(time), "15/Jan/24 09:30 UTC");
+    }
+
+    #[test]
+    fn test_truncate_output() {
+        let output: String = (1..=50).map(|i| format!("line {}\n", i)).collect();
+        assert_eq!(truncate_output(&output, None, None), None);
+        assert_eq!(truncate_output(&output, Some(50), Some(1000)), None);
+        assert_eq!(
+            truncate_output(&output, Some(2), None).unwrap(),
+            "line 1\nline 2\n… 48 more lines"
+        );
+        // "line 1" and "line 2" with their line breaks take 14 characters
+        assert_eq!(
+            truncate_output(&output, Some(10), Some(16)).unwrap(),
+            "line 1\nline 2\n… 48 more lines"
+        );
+        assert_eq!(
+            truncate_output("abcdef\nghi", None, Some(4)).unwrap(),
+            "abc…\n… 1 more line"
+        );
+    }
+
+    #[test]
+    fn test_expand_tilde() {
+        let home = std::env::var("HOME").unwrap();
+        assert_eq!(
+            expand_tilde("~/.ssh/id_rsa"),
+            format!("{}/.ssh/id_rsa", home.trim_end_matches('/'))
+        );
+        assert_eq!(expand_tilde("/etc/key"), "/etc/key");
+        assert_eq!(expand_tilde("~other/key"), "~other/key");
+    }
+
+    #[test]
+    fn test_size() {
+        assert_eq!(parse_size("1024"), Ok(1024));
+        assert_eq!(parse_size("500M"), Ok(500 * 1024 * 1024));
+        assert_eq!(parse_size("1.5 GiB"), Ok(3 * 512 * 1024 * 1024));
+        assert_eq!(parse_size("10GB"), Ok(10 * 1024 * 1024 * 1024));
+        assert_eq!(parse_size("2kb"), Ok(2048));
+        assert!(parse_size("10 parsecs").is_err());
+        assert!(parse_size("G").is_err());
+
+        assert_eq!(format_size(512), "512 B");
+        assert_eq!(format_size(1536), "1.50 KiB");
+        assert_eq!(format_size(4_509_715_660), "4.20 GiB");
+    }
+
+    #[test]
+    fn test_format_duration() {
+        assert_eq!(format_duration(chrono::Duration::seconds(30)), "0 minutes");
+        assert_eq!(format_duration(chrono::Duration::minutes(14)), "14 minutes");
+        assert_eq!(format_duration(chrono::Duration::minutes(61)), "1 hour");
+        assert_eq!(format_duration(chrono::Duration::days(12)), "12 days");
+    }
+
+    #[test]
+    fn test_truncate() {
+        assert_eq!(truncate("abc", 3), "abc");
+        assert_eq!(truncate("abcdef", 4), "abc…");
+    }
+
     #[test]
     fn test_make_pretty_timestamp_format() {
         let timestamp = make_pretty_timestamp();
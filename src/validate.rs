@@ -0,0 +1,941 @@
+use crate::config::{Check, CheckConfig, Config, Sensors, Server};
+use crate::digest;
+use crate::export::Exporter;
+use crate::inventory::{Host, Inventory};
+use crate::maintenance::{self, MaintenanceWindow};
+use crate::notify::{self, Notifier, NotifierKind};
+use crate::platform::{Os, ShellProfile};
+use crate::slack::SlackFormat;
+use crate::ssh::{self, AuthMethod, HostKeyChecking, SshTarget};
+use crate::ssh_config::SshConfig;
+use crate::utils::Timestamps;
+use serde_yaml::Mapping;
+use std::collections::{HashMap, HashSet};
+use std::fs::File;
+use std::net::ToSocketAddrs;
+
+/// The problems found in a part of the configuration.
+#[derive(Debug)]
+pub struct Section {
+    /// Human-readable title of the part, e.g. the name and address of a server.
+    pub title: String,
+    /// Short summary shown when no problem is found, e.g. the number of checks.
+    pub summary: String,
+    /// Description of every problem found.
+    pub problems: Vec<String>,
+}
+
+/// Validates a configuration without connecting to the servers.
+///
+/// Besides the parsing done when loading the configuration, this verifies that:
+/// - the private keys exist and are readable, and the hostnames resolve;
+/// - the parameters of the checks are sane (e.g. the load interval is 1, 5 or 15);
+/// - the maintenance windows are well-formed;
+/// - the notifiers referenced by the servers exist and their secrets are available.
+pub fn validate(config: &Config) -> Vec<Section> {
+    let mut sections = vec![];
+
+    let mut problems = vec![];
+    let notifier_names: Vec<&str> = match &config.notifiers {
+        Some(notifiers) if !notifiers.is_empty() => {
+            let mut seen = HashSet::new();
+            for notifier in notifiers {
+                if !seen.insert(notifier.name.as_str()) {
+                    problems.push(format!("duplicate notifier `{}`", notifier.name));
+                }
+                problems.extend(notifier_problems(notifier));
+            }
+            notifiers.iter().map(|n| n.name.as_str()).collect()
+        }
+        _ => {
+            if std::env::var("SLACK_HOOK_URL").is_err() {
+                problems.push(
+                    "SLACK_HOOK_URL is not set and no `notifiers` are configured".to_string(),
+                );
+            }
+            vec!["slack"]
+        }
+    };
+    if let Some(general) = &config.general {
+        if general.only_on_change && general.state_file.is_none() {
+            problems.push("`only_on_change` requires a `state_file`".to_string());
+        }
+        if general.reminder_interval.is_some_and(|r| r <= 0) {
+            problems.push("`reminder_interval` must be positive".to_string());
+        }
+        problems.extend(maintenance_problems(general.maintenance.as_deref()));
+        if let Some(Err(e)) = general.timestamps.as_ref().map(Timestamps::check) {
+            problems.push(format!("timestamps: {}", e));
+        }
+        if general.dashboard.is_some() && !cfg!(feature = "dashboard") {
+            problems
+                .push("the dashboard requires building with `--features dashboard`".to_string());
+        }
+        if let Some(heartbeat) = &general.heartbeat {
+            let url = notify::secret(
+                heartbeat.url.as_deref(),
+                heartbeat.url_env.as_deref(),
+                "url",
+            );
+            if let Err(e) = url {
+                problems.push(format!("heartbeat: {}", e));
+            }
+        }
+    }
+    for exporter in config.exporters.iter().flatten() {
+        let secrets: Vec<(Option<&str>, Option<&str>, &str)> = match exporter {
+            Exporter::Influxdb {
+                token, token_env, ..
+            } => vec![(token.as_deref(), token_env.as_deref(), "token")],
+            Exporter::Otlp { headers_env, .. } => headers_env
+                .iter()
+                .flatten()
+                .map(|(name, env)| (None, Some(env.as_str()), name.as_str()))
+                .collect(),
+            _ => vec![],
+        };
+        problems.extend(
+            secrets
+                .into_iter()
+                .filter_map(|(value, env, field)| notify::secret(value, env, field).err())
+                .map(|e| format!("exporter `{}`: {}", exporter.kind(), e)),
+        );
+        if let Exporter::UptimeKuma { push_urls } = exporter {
+            let mut names: Vec<_> = push_urls.keys().collect();
+            names.sort();
+            for name in names {
+                if !config.servers.iter().any(|s| &s.name == name) {
+                    problems.push(format!("exporter `uptime_kuma`: unknown server `{}`", name));
+                }
+            }
+        }
+    }
+    let mut groups: Vec<_> = config.groups.iter().flatten().collect();
+    groups.sort_by(|a, b| a.0.cmp(b.0));
+    for (name, group) in &groups {
+        problems.extend(
+            checks_problems(group.checks.as_ref())
+                .into_iter()
+                .chain(policy_problems(group.checks.as_ref(), config))
+                .chain(state_problems(group.checks.as_ref(), config))
+                .map(|p| format!("group `{}`: {}", name, p)),
+        );
+    }
+    let group_names: Vec<&str> = groups.iter().map(|(name, _)| name.as_str()).collect();
+    let ssh_config = config.ssh_config();
+    for inventory in config.inventories.iter().flatten() {
+        problems.extend(
+            inventory_problems(
+                inventory,
+                config,
+                &ssh_config,
+                &notifier_names,
+                &group_names,
+            )
+            .into_iter()
+            .map(|p| format!("inventory `{}`: {}", inventory.kind(), p)),
+        );
+    }
+    sections.push(Section {
+        title: "general".to_string(),
+        summary: format!("{} notifier(s)", notifier_names.len()),
+        problems,
+    });
+
+    let mut seen = HashSet::new();
+    for server in &config.servers {
+        let target = server.ssh_target(config.general.as_ref(), &ssh_config);
+        let mut problems = server_problems(server, &target, &notifier_names, &group_names);
+        // The other endpoints share the settings of the first one, checked above
+        if !server.local {
+            let targets = server.ssh_targets(config.general.as_ref(), &ssh_config);
+            problems.extend(targets.iter().skip(1).filter_map(resolution_problem));
+        }
+        problems.extend(policy_problems(server.checks.as_ref(), config));
+        problems.extend(state_problems(server.checks.as_ref(), config));
+        if server.os == Some(Os::Windows) && server.shell_profile == ShellProfile::Busybox {
+            problems.push("`shell_profile: busybox` does not apply to Windows".to_string());
+        }
+        for (name, check) in config.checks_for(server, &[]) {
+            let Some(os) = server.os else { break };
+            let supported = match check.check {
+                Check::Temperature { .. } => !matches!(os, Os::Windows | Os::Macos),
+                Check::OomKills { .. }
+                | Check::Network { .. }
+                | Check::Mounts { .. }
+                | Check::Quota { .. }
+                | Check::LmSensors { .. }
+                | Check::Logins { .. }
+                | Check::SecurityModule { .. }
+                | Check::Kernel { .. }
+                | Check::Entropy { .. } => os == Os::Linux,
+                Check::ProcessStates { .. }
+                | Check::TopDirectories { .. }
+                | Check::Ipmi { .. }
+                | Check::Ups { .. }
+                | Check::CertificateFiles { .. }
+                | Check::Certbot { .. }
+                | Check::WebServer { .. }
+                | Check::AccountChanges { .. }
+                | Check::Permissions { .. }
+                | Check::DockerDisk { .. }
+                | Check::ContainerRestarts { .. }
+                | Check::Compose { .. } => os != Os::Windows,
+                Check::Fail2ban { .. } => os == Os::Linux,
+                _ => true,
+            };
+            if !supported {
+                problems.push(format!(
+                    "check `{}`: `{}` is not supported on {}",
+                    name,
+                    check.check.kind(),
+                    os.name()
+                ));
+            }
+        }
+        if !seen.insert(server.name.as_str()) {
+            problems.insert(0, format!("duplicate server name `{}`", server.name));
+        }
+        let title = match server.local {
+            true => format!("{} (local)", server.name),
+            false => format!(
+                "{} ({}@{}:{})",
+                server.name, target.user, target.host, target.port
+            ),
+        };
+        sections.push(Section {
+            title,
+            summary: format!("{} check(s)", config.checks_for(server, &[]).len()),
+            problems,
+        });
+    }
+
+    sections
+}
+
+/// Prints the sections returned by `validate` and returns the total number of problems.
+pub fn print_summary(sections: &[Section]) -> usize {
+    let mut count = 0;
+    for section in sections {
+        if section.problems.is_empty() {
+            println!("✅ {}: {}", section.title, section.summary);
+        } else {
+            println!("❌ {}:", section.title);
+            for problem in &section.problems {
+                println!("   - {}", problem);
+            }
+        }
+        count += section.problems.len();
+    }
+    match count {
+        0 => println!("Configuration is valid"),
+        n => println!("{} problem(s) found", n),
+    }
+    count
+}
+
+fn notifier_problems(notifier: &Notifier) -> Vec<String> {
+    let secrets: Vec<(Option<&str>, Option<&str>, &str)> = match &notifier.kind {
+        NotifierKind::Slack { url, url_env, .. } | NotifierKind::Teams { url, url_env } => {
+            vec![(url.as_deref(), url_env.as_deref(), "url")]
+        }
+        NotifierKind::SlackApi {
+            token, token_env, ..
+        } => vec![(token.as_deref(), token_env.as_deref(), "token")],
+        NotifierKind::Email(config) => {
+            let mut secrets = vec![];
+            if config.username.is_some() {
+                secrets.push((None, config.password_env.as_deref(), "password"));
+            }
+            secrets
+        }
+        NotifierKind::Pagerduty {
+            routing_key,
+            routing_key_env,
+            ..
+        } => vec![(
+            routing_key.as_deref(),
+            routing_key_env.as_deref(),
+            "routing_key",
+        )],
+        NotifierKind::Opsgenie {
+            api_key,
+            api_key_env,
+            ..
+        } => vec![(api_key.as_deref(), api_key_env.as_deref(), "api_key")],
+        NotifierKind::Pushover(config) => vec![
+            (
+                config.token.as_deref(),
+                config.token_env.as_deref(),
+                "token",
+            ),
+            (config.user.as_deref(), config.user_env.as_deref(), "user"),
+        ],
+    };
+    let mut problems: Vec<String> = secrets
+        .into_iter()
+        .filter_map(|(value, env, field)| notify::secret(value, env, field).err())
+        .map(|e| e.to_string())
+        .collect();
+    if let Some(templates) = &notifier.templates {
+        if let Err(e) = templates.check() {
+            problems.push(format!("invalid template: {}", e));
+        }
+        let ignored = match &notifier.kind {
+            NotifierKind::Slack { settings, .. } | NotifierKind::SlackApi { settings, .. } => {
+                settings.format == SlackFormat::Blocks
+            }
+            NotifierKind::Email(_) | NotifierKind::Pushover(_) => false,
+            _ => true,
+        };
+        if ignored {
+            problems.push("templates are only used by plain text reports".to_string());
+        }
+    }
+    problems
+        .into_iter()
+        .map(|e| format!("notifier `{}`: {}", notifier.name, e))
+        .collect()
+}
+
+fn server_problems(
+    server: &Server,
+    target: &SshTarget,
+    notifiers: &[&str],
+    groups: &[&str],
+) -> Vec<String> {
+    let mut problems = vec![];
+
+    // Local servers do not connect over SSH
+    if !server.local {
+        problems.extend(connection_problems(target));
+    }
+    for name in server.notify.iter().flatten() {
+        if !notifiers.contains(&name.as_str()) {
+            problems.push(format!("unknown notifier `{}`", name));
+        }
+    }
+    for name in server.groups.iter().flatten() {
+        if !groups.contains(&name.as_str()) {
+            problems.push(format!("unknown group `{}`", name));
+        }
+    }
+    problems.extend(maintenance_problems(server.maintenance.as_deref()));
+    problems.extend(checks_problems(server.checks.as_ref()));
+    problems
+}
+
+/// Returns the problems of an inventory: its credentials or file, and the settings of its servers
+/// as those of a server at `localhost`.
+fn inventory_problems(
+    inventory: &Inventory,
+    config: &Config,
+    ssh_config: &SshConfig,
+    notifiers: &[&str],
+    groups: &[&str],
+) -> Vec<String> {
+    let mut problems = vec![];
+    if let Err(e) = inventory.check_access() {
+        problems.push(e.to_string());
+    }
+    let host = Host {
+        name: inventory.kind().to_string(),
+        public: Some("localhost".to_string()),
+        private: Some("localhost".to_string()),
+        settings: Mapping::new(),
+    };
+    match inventory.server(&host) {
+        Ok(Some(server)) => {
+            let target = server.ssh_target(config.general.as_ref(), ssh_config);
+            problems.extend(server_problems(&server, &target, notifiers, groups));
+            problems.extend(policy_problems(server.checks.as_ref(), config));
+            problems.extend(state_problems(server.checks.as_ref(), config));
+            problems.extend(state_problems(server.checks.as_ref(), config));
+        }
+        Ok(None) => {}
+        Err(e) => problems.push(format!("server: {}", e)),
+    }
+    problems
+}
+
+fn connection_problems(target: &SshTarget) -> Vec<String> {
+    let mut problems = vec![];
+    match target.auth {
+        AuthMethod::Key => {
+            if let Err(e) = File::open(&target.private_key) {
+                problems.push(format!("private key {}: {}", target.private_key, e));
+            }
+        }
+        AuthMethod::Password => {
+            let password = ssh::password(
+                target.password_env.as_deref(),
+                target.password_file.as_deref(),
+            );
+            if let Err(e) = password {
+                problems.push(format!("password: {}", e));
+            }
+        }
+    }
+    if let Some(fingerprint) = &target.host_key_fingerprint {
+        if !fingerprint.trim().starts_with("SHA256:") {
+            problems.push(format!(
+                "host key fingerprint `{}` is not a SHA256: fingerprint",
+                fingerprint
+            ));
+        }
+    } else if target.host_key_checking == HostKeyChecking::Strict {
+        if let Err(e) = File::open(&target.known_hosts) {
+            problems.push(format!("known hosts {}: {}", target.known_hosts, e));
+        }
+    }
+    if target.connect_timeout.is_zero() {
+        problems.push("`connect_timeout` must be positive".to_string());
+    }
+    if target.keepalive.is_some_and(|k| k.interval == 0) {
+        problems.push("keepalive: `interval` must be positive".to_string());
+    }
+    problems.extend(resolution_problem(target));
+    problems
+}
+
+fn resolution_problem(target: &SshTarget) -> Option<String> {
+    // Hosts behind jump hosts are usually only resolvable from the jump hosts
+    match (target.host.as_str(), target.port).to_socket_addrs() {
+        _ if target.proxy_jump.is_some() => None,
+        Ok(addresses) if addresses.len() > 0 => None,
+        Ok(_) => Some(format!("{} does not resolve to any address", target.host)),
+        Err(e) => Some(format!("could not resolve {}: {}", target.host, e)),
+    }
+}
+
+fn checks_problems(checks: Option<&HashMap<String, CheckConfig>>) -> Vec<String> {
+    let mut checks: Vec<_> = checks.into_iter().flatten().collect();
+    checks.sort_by(|a, b| a.0.cmp(b.0));
+    checks
+        .into_iter()
+        .flat_map(|(name, check)| {
+            check_problems(check)
+                .into_iter()
+                .map(move |p| format!("check `{}`: {}", name, p))
+        })
+        .collect()
+}
+
+/// Returns the commands of the checks breaking the `command_policy`, refused with `--read-only`.
+fn policy_problems(checks: Option<&HashMap<String, CheckConfig>>, config: &Config) -> Vec<String> {
+    let Some(policy) = &config.command_policy else {
+        return vec![];
+    };
+    let mut checks: Vec<_> = checks.into_iter().flatten().collect();
+    checks.sort_by(|a, b| a.0.cmp(b.0));
+    checks
+        .into_iter()
+        .filter_map(|(name, check)| {
+            let e = policy.check(check.check.command()?).err()?;
+            Some(format!("check `{}`: command policy: {}", name, e))
+        })
+        .collect()
+}
+
+/// Returns the checks comparing their findings with those of the previous run, e.g. the boot time
+/// of `uptime`, when no `state_file` keeps them between the runs. Only the daemon keeps them
+/// without it, in memory.
+fn state_problems(checks: Option<&HashMap<String, CheckConfig>>, config: &Config) -> Vec<String> {
+    if config
+        .general
+        .as_ref()
+        .is_some_and(|g| g.state_file.is_some())
+    {
+        return vec![];
+    }
+    let mut checks: Vec<_> = checks.into_iter().flatten().collect();
+    checks.sort_by(|a, b| a.0.cmp(b.0));
+    checks
+        .into_iter()
+        .filter(|(_, check)| {
+            matches!(
+                check.check,
+                Check::Uptime { .. }
+                    | Check::OomKills { .. }
+                    | Check::Logins { .. }
+                    | Check::AccountChanges { .. }
+            )
+        })
+        .map(|(name, check)| {
+            format!(
+                "check `{}`: `{}` compares with the previous run, which requires a `state_file` \
+                 unless running with `--daemon`",
+                name,
+                check.check.kind()
+            )
+        })
+        .collect()
+}
+
+fn maintenance_problems(windows: Option<&[MaintenanceWindow]>) -> Vec<String> {
+    let mut problems = vec![];
+    for window in windows.into_iter().flatten() {
+        match (&window.cron, window.start, window.end) {
+            (Some(cron), _, _) => {
+                if let Err(e) = maintenance::parse_cron(cron) {
+                    problems.push(format!("maintenance: invalid cron `{}`: {}", cron, e));
+                }
+                if window.duration.is_none_or(|d| d <= 0) {
+                    problems.push(format!(
+                        "maintenance: `{}` needs a positive `duration`",
+                        cron
+                    ));
+                }
+            }
+            (None, Some(start), Some(end)) if end <= start => {
+                problems.push(format!(
+                    "maintenance: window ends ({}) before it starts",
+                    end
+                ));
+            }
+            (None, None, None) => {
+                problems.push("maintenance: window without `cron`, `start` or `end`".to_string())
+            }
+            _ => {}
+        }
+    }
+    problems
+}
+
+fn check_problems(config: &CheckConfig) -> Vec<String> {
+    let check = &config.check;
+    let mut problems = vec![];
+    if config.retry_delay.is_some() && config.retries == 0 {
+        problems.push("`retry_delay` has no effect without `retries`".to_string());
+    }
+    if config.timeout == Some(0) {
+        problems.push("`timeout` must be at least 1 second".to_string());
+    }
+    if let Some(every) = &config.every {
+        match digest::parse_period(every) {
+            Ok(every) if every <= chrono::Duration::zero() => {
+                problems.push("`every` must be positive".to_string())
+            }
+            Ok(_) => {}
+            Err(e) => problems.push(format!("every: {}", e)),
+        }
+    }
+    if let Some(cron) = &config.cron {
+        if let Err(e) = maintenance::parse_cron(cron) {
+            problems.push(format!("invalid cron `{}`: {}", cron, e));
+        }
+    }
+    let mut below = |warning: Option<f64>, critical: f64| {
+        if warning.is_some_and(|w| w >= critical) {
+            problems.push(format!(
+                "warning threshold must be below the critical one ({})",
+                critical
+            ));
+        }
+    };
+
+    match check {
+        Check::Load {
+            warning, critical, ..
+        } => below(*warning, critical.unwrap_or(50.0)),
+        Check::ProcessStates {
+            max_zombies,
+            zombies_warning,
+            max_blocked,
+            blocked_warning,
+        } => {
+            below(zombies_warning.map(f64::from), f64::from(*max_zombies));
+            if let Some(max_blocked) = max_blocked {
+                below(blocked_warning.map(f64::from), f64::from(*max_blocked));
+            }
+        }
+        Check::Network {
+            max_mbps: Some(max_mbps),
+            mbps_warning,
+            ..
+        } => below(*mbps_warning, *max_mbps),
+        Check::Connections {
+            max_established,
+            established_warning,
+            max_time_wait,
+            time_wait_warning,
+            ..
+        } => {
+            below(
+                established_warning.map(f64::from),
+                f64::from(*max_established),
+            );
+            if let Some(max_time_wait) = max_time_wait {
+                below(time_wait_warning.map(f64::from), f64::from(*max_time_wait));
+            }
+        }
+        Check::Cpu {
+            max_usage,
+            warning,
+            max_iowait,
+            iowait_warning,
+        } => {
+            below(*warning, *max_usage);
+            if let Some(max_iowait) = max_iowait {
+                below(*iowait_warning, *max_iowait);
+            }
+        }
+        Check::Temperature {
+            sensor: Sensors::Several(sensors),
+            warning,
+            critical,
+        } => {
+            for sensor in sensors {
+                below(
+                    sensor.warning.or(*warning).map(f64::from),
+                    f64::from(sensor.max.or(*critical).unwrap_or(30)),
+                );
+            }
+        }
+        Check::Temperature {
+            warning, critical, ..
+        } => below(warning.map(f64::from), f64::from(critical.unwrap_or(30))),
+        Check::NumberOfSubfolders {
+            warning,
+            max_folders,
+            ..
+        } => below(warning.map(f64::from), f64::from(*max_folders)),
+        Check::NumberOfFiles {
+            warning, max_files, ..
+        } => below(warning.map(f64::from), f64::from(*max_files)),
+        Check::DirSize {
+            warning, max_size, ..
+        }
+        | Check::TopDirectories {
+            warning, max_size, ..
+        } => below(warning.map(|w| w.0 as f64), max_size.0 as f64),
+        Check::DockerDisk {
+            max_docker_usage,
+            docker_usage_warning,
+            ..
+        } => below(
+            docker_usage_warning.map(|w| w.0 as f64),
+            max_docker_usage.0 as f64,
+        ),
+        Check::Quota {
+            warning,
+            max_quota_usage,
+            ..
+        } => below(*warning, *max_quota_usage),
+        Check::Logins {
+            max_failed: Some(max_failed),
+            failed_warning,
+            ..
+        } => below(failed_warning.map(f64::from), f64::from(*max_failed)),
+        Check::SecurityModule {
+            max_denials: Some(max_denials),
+            denials_warning,
+            ..
+        } => below(denials_warning.map(f64::from), f64::from(*max_denials)),
+        Check::Fail2ban {
+            max_banned: Some(max_banned),
+            banned_warning,
+            ..
+        } => below(banned_warning.map(f64::from), f64::from(*max_banned)),
+        Check::WebServer {
+            max_connections,
+            connections_warning,
+            max_busy_workers,
+            busy_workers_warning,
+            ..
+        } => {
+            if let Some(max_connections) = max_connections {
+                below(
+                    connections_warning.map(f64::from),
+                    f64::from(*max_connections),
+                );
+            }
+            if let Some(max_busy_workers) = max_busy_workers {
+                below(*busy_workers_warning, *max_busy_workers);
+            }
+        }
+        Check::LmSensors { chips } => {
+            for chip in chips {
+                if let Some(max) = chip.max {
+                    below(chip.warning, max);
+                }
+            }
+        }
+        _ => {}
+    }
+
+    match check {
+        Check::Load { interval, .. } if ![1, 5, 15].contains(interval) => {
+            problems.push(format!("interval must be 1, 5 or 15, not {}", interval));
+        }
+        Check::Ping { url } if url.is_empty() => problems.push("no URL to ping".to_string()),
+        Check::NumberOfSubfolders { path, .. }
+        | Check::NumberOfFiles { path, .. }
+        | Check::DirSize { path, .. }
+            if path.is_empty() =>
+        {
+            problems.push("no path to inspect".to_string())
+        }
+        Check::Network { interfaces, .. } if interfaces.is_empty() => {
+            problems.push("no interface to inspect".to_string())
+        }
+        Check::Mounts { mounts, .. } if mounts.is_empty() => {
+            problems.push("no mount point to inspect".to_string())
+        }
+        Check::Temperature {
+            sensor: Sensors::Several(sensors),
+            ..
+        } => {
+            if sensors.is_empty() {
+                problems.push("`sensors` is empty".to_string());
+            }
+            let mut labels = HashSet::new();
+            for sensor in sensors {
+                if !labels.insert(&sensor.label) {
+                    problems.push(format!("duplicate sensor label `{}`", sensor.label));
+                }
+            }
+        }
+        Check::Entropy {
+            min_entropy,
+            entropy_warning: Some(entropy_warning),
+        } if entropy_warning <= min_entropy => problems.push(format!(
+            "`entropy_warning` must be above `min_entropy` ({})",
+            min_entropy
+        )),
+        Check::Ups {
+            min_charge: Some(min_charge),
+            charge_warning: Some(charge_warning),
+            ..
+        } if charge_warning <= min_charge => problems.push(format!(
+            "`charge_warning` must be above `min_charge` ({})",
+            min_charge
+        )),
+        Check::CertificateFiles {
+            certificates,
+            min_days,
+            warning_days,
+        } => {
+            if certificates.is_empty() {
+                problems.push("`certificates` is empty".to_string());
+            }
+            let min_days = min_days.unwrap_or(7);
+            if warning_days.unwrap_or(30) <= min_days {
+                problems.push(format!(
+                    "`warning_days` must be above `min_days` ({})",
+                    min_days
+                ));
+            }
+        }
+        Check::Permissions { audit_paths, .. } if audit_paths.is_empty() => {
+            problems.push("`audit_paths` is empty".to_string())
+        }
+        Check::Permissions {
+            max_listed: Some(0),
+            ..
+        } => problems.push("`max_listed` must be positive".to_string()),
+        Check::LmSensors { chips } if chips.is_empty() => {
+            problems.push("`chips` is empty".to_string())
+        }
+        Check::Compose { compose_files, .. } if compose_files.is_empty() => {
+            problems.push("`compose_files` is empty".to_string())
+        }
+        Check::ContainerRestarts {
+            restart_window: Some(0),
+            ..
+        } => problems.push("`restart_window` must be positive".to_string()),
+        Check::TopDirectories { top: Some(0), .. } => {
+            problems.push("`top` must be positive".to_string())
+        }
+        Check::Quota {
+            xfs: true,
+            filesystem: None,
+            ..
+        } => problems.push("`xfs` requires a `filesystem`".to_string()),
+        Check::Mounts {
+            stat_timeout: Some(0),
+            ..
+        } => problems.push("`stat_timeout` must be positive".to_string()),
+        Check::ListAge { .. } => problems.push("`list_age` is not implemented".to_string()),
+        Check::CustomCommand { command, .. } if command.trim().is_empty() => {
+            problems.push("empty command".to_string())
+        }
+        Check::Database { engine, .. } if !["postgres", "mysql"].contains(&engine.as_str()) => {
+            problems.push(format!(
+                "unknown engine `{}`, expected `postgres` or `mysql`",
+                engine
+            ));
+        }
+        Check::Redis { redis_url, .. }
+            if !redis_url.starts_with("redis://") && !redis_url.starts_with("rediss://") =>
+        {
+            problems.push(format!("`{}` is not a redis:// URL", redis_url));
+        }
+        _ => {}
+    }
+    problems
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ssh_config::SshConfig;
+
+    fn server(yaml: &str) -> Server {
+        serde_yaml::from_str(yaml).unwrap()
+    }
+
+    fn target(server: &Server) -> SshTarget {
+        server.ssh_target(None, &SshConfig::default())
+    }
+
+    #[test]
+    fn test_check_problems() {
+        let check: CheckConfig = serde_yaml::from_str("{interval: 10, warning: 60}").unwrap();
+        let problems = check_problems(&check);
+        assert_eq!(problems.len(), 2);
+        assert!(problems[0].contains("below the critical"));
+        assert!(problems[1].contains("1, 5 or 15"));
+
+        let check: CheckConfig = serde_yaml::from_str("{interval: 5, warning: 20}").unwrap();
+        assert!(check_problems(&check).is_empty());
+
+        let check: CheckConfig =
+            serde_yaml::from_str("{max_usage: 90, warning: 95, iowait_warning: 30}").unwrap();
+        assert_eq!(check.check.kind(), "cpu");
+        assert_eq!(check_problems(&check).len(), 1);
+
+        let check: CheckConfig =
+            serde_yaml::from_str("{path: [/var/log], max_size: 1G, warning: 2048 MiB}").unwrap();
+        assert_eq!(check.check.kind(), "dir_size");
+        assert_eq!(check_problems(&check).len(), 1);
+        assert!(serde_yaml::from_str::<CheckConfig>("{path: [/], max_size: 1 parsec}").is_err());
+
+        let check: CheckConfig = serde_yaml::from_str(
+            "{sensors: [{label: CPU, sensor: /a, max: 40}, {label: CPU, sensor: /b}], warning: 50, critical: 60}",
+        )
+        .unwrap();
+        let problems = check_problems(&check);
+        assert_eq!(problems.len(), 2);
+        assert!(problems[1].contains("duplicate sensor label `CPU`"));
+
+        let check: CheckConfig = serde_yaml::from_str("{engine: oracle}").unwrap();
+        assert_eq!(check_problems(&check).len(), 1);
+
+        let check: CheckConfig = serde_yaml::from_str("{url: [/], retry_delay: 10}").unwrap();
+        assert_eq!(check_problems(&check).len(), 1);
+
+        let check: CheckConfig = serde_yaml::from_str("{command: ls, timeout: 0}").unwrap();
+        assert_eq!(check_problems(&check).len(), 1);
+
+        let check: CheckConfig =
+            serde_yaml::from_str("{command: ls, every: 10 minutes, cron: daily}").unwrap();
+        assert_eq!(check_problems(&check).len(), 2);
+    }
+
+    #[test]
+    fn test_server_problems() {
+        let valid = server(
+            "{name: web01, host: localhost, port: 22, user: monitor, private_key: Cargo.toml, \
+             notify: [slack]}",
+        );
+        assert_eq!(
+            server_problems(&valid, &target(&valid), &["slack"], &[]),
+            Vec::<String>::new()
+        );
+
+        let invalid = server(
+            "{name: web01, host: localhost, port: 22, user: monitor, \
+             private_key: /does/not/exist, notify: [pager], groups: [web], \
+             maintenance: [{cron: '0 3 * * Sun'}], host_key_fingerprint: 'MD5:ab:cd'}",
+        );
+        let problems = server_problems(&invalid, &target(&invalid), &["slack"], &["storage"]);
+        assert_eq!(problems.len(), 5);
+        assert!(problems[0].starts_with("private key /does/not/exist"));
+        assert!(problems[1].contains("not a SHA256: fingerprint"));
+        assert_eq!(problems[2], "unknown notifier `pager`");
+        assert_eq!(problems[3], "unknown group `web`");
+        assert!(problems[4].contains("positive `duration`"));
+
+        let password =
+            server("{name: web01, host: localhost, private_key: /does/not/exist, auth: password}");
+        assert_eq!(
+            server_problems(&password, &target(&password), &[], &[]),
+            vec!["password: `auth: password` requires `password_env` or `password_file`"]
+        );
+
+        let local = server("{name: self, host: does.not.resolve.invalid, local: true}");
+        assert!(server_problems(&local, &target(&local), &[], &[]).is_empty());
+    }
+
+    #[test]
+    fn test_notifier_problems() {
+        let notifier: Notifier = serde_yaml::from_str(
+            "{name: ops, type: slack, url: 'https://example.com', format: text, \
+             templates: {result: '{{ emoji }} {{ message }}'}}",
+        )
+        .unwrap();
+        assert!(notifier_problems(&notifier).is_empty());
+
+        let notifier: Notifier = serde_yaml::from_str(
+            "{name: ops, type: slack, url: 'https://example.com', \
+             templates: {server: '{{ name'}}",
+        )
+        .unwrap();
+        let problems = notifier_problems(&notifier);
+        assert_eq!(problems.len(), 2);
+        assert!(problems[0].starts_with("notifier `ops`: invalid template"));
+        assert_eq!(
+            problems[1],
+            "notifier `ops`: templates are only used by plain text reports"
+        );
+    }
+
+    #[test]
+    fn test_example_config() {
+        let config = crate::config::load_config("conf/conf.example.yaml").unwrap();
+        let sections = validate(&config);
+        assert_eq!(sections.len(), 1 + config.servers.len());
+    }
+
+    #[test]
+    fn test_validate_duplicates() {
+        let config: Config = serde_yaml::from_str(
+            "notifiers: [{name: ops, type: slack, url: 'https://example.com'}]\n\
+             servers:\n\
+             - {name: a, host: localhost, port: 22, user: u, private_key: Cargo.toml}\n\
+             - {name: a, host: localhost, port: 22, user: u, private_key: Cargo.toml}",
+        )
+        .unwrap();
+        let sections = validate(&config);
+        assert_eq!(sections.len(), 3);
+        assert!(sections[0].problems.is_empty());
+        assert!(sections[1].problems.is_empty());
+        assert_eq!(sections[2].problems, vec!["duplicate server name `a`"]);
+    }
+
+    #[test]
+    fn test_state_problems() {
+        let yaml = "notifiers: [{name: ops, type: slack, url: 'https://example.com'}]\n\
+                    servers:\n\
+                    - {name: a, host: localhost, port: 22, user: u, private_key: Cargo.toml, \
+                    checks: {boots: {on_reboot: warning}, oom: {on_kill: critical}, \
+                    accounts: {on_change: warning}, load: {interval: 5, critical: 4}}}";
+        let config: Config = serde_yaml::from_str(yaml).unwrap();
+        assert_eq!(
+            validate(&config)[1].problems,
+            [
+                "check `accounts`: `account_changes` compares with the previous run, which \
+                 requires a `state_file` unless running with `--daemon`",
+                "check `boots`: `uptime` compares with the previous run, which requires a \
+                 `state_file` unless running with `--daemon`",
+                "check `oom`: `oom_kills` compares with the previous run, which requires a \
+                 `state_file` unless running with `--daemon`"
+            ]
+        );
+        let config: Config =
+            serde_yaml::from_str(&format!("general: {{state_file: state.json}}\n{}", yaml))
+                .unwrap();
+        assert!(validate(&config)[1].problems.is_empty());
+    }
+}
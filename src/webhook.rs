@@ -0,0 +1,219 @@
+use log::warn;
+use serde_json::Value;
+use std::time::Duration;
+
+/// Number of attempts to deliver a payload before giving up.
+const ATTEMPTS: u32 = 4;
+/// Delay before the first retry, doubled after every failed attempt.
+const RETRY_DELAY: Duration = Duration::from_secs(1);
+
+/// Posts a JSON payload to a webhook.
+///
+/// Transient failures (connection errors, timeouts, rate limiting and server errors) are retried
+/// with an exponential backoff, honoring the `Retry-After` header sent by the service when rate
+/// limited.
+///
+/// # Errors
+///
+/// Returns an error if the URL is invalid, if the service rejects the payload with a client error
+/// (e.g. `400 Bad Request`), or if the payload could not be delivered after all the attempts.
+pub fn post_json(url: &str, payload: &Value) -> Result<(), Box<dyn std::error::Error>> {
+    post_with_retry(
+        url,
+        &[],
+        ("application/json", &payload.to_string()),
+        ATTEMPTS,
+        RETRY_DELAY,
+    )
+    .map(|_| ())
+}
+
+/// Posts a JSON payload to an API with additional headers, e.g. `Authorization`.
+///
+/// Failures are handled as in `post_json`.
+pub fn post_json_with_headers(
+    url: &str,
+    headers: &[(&str, &str)],
+    payload: &Value,
+) -> Result<(), Box<dyn std::error::Error>> {
+    post_with_retry(
+        url,
+        headers,
+        ("application/json", &payload.to_string()),
+        ATTEMPTS,
+        RETRY_DELAY,
+    )
+    .map(|_| ())
+}
+
+/// Posts a JSON payload to an API and returns its JSON response, e.g. for the Slack Web API
+/// which answers with the identifier of the message posted.
+///
+/// Failures are handled as in `post_json`.
+///
+/// # Errors
+///
+/// Returns an error as `post_json` does, or if the response is not JSON.
+pub fn call_json(
+    url: &str,
+    headers: &[(&str, &str)],
+    payload: &Value,
+) -> Result<Value, Box<dyn std::error::Error>> {
+    let response = post_with_retry(
+        url,
+        headers,
+        ("application/json; charset=utf-8", &payload.to_string()),
+        ATTEMPTS,
+        RETRY_DELAY,
+    )?;
+    Ok(serde_json::from_str(&response)?)
+}
+
+/// Posts form fields to an API with additional headers, and returns its JSON response, e.g. for
+/// the methods of the Slack Web API that do not accept JSON.
+///
+/// Failures are handled as in `post_json`.
+///
+/// # Errors
+///
+/// Returns an error as `post_json` does, or if the response is not JSON.
+pub fn call_form(
+    url: &str,
+    headers: &[(&str, &str)],
+    form: &[(&str, &str)],
+) -> Result<Value, Box<dyn std::error::Error>> {
+    let body = form_urlencoded::Serializer::new(String::new())
+        .extend_pairs(form)
+        .finish();
+    let response = post_with_retry(
+        url,
+        headers,
+        ("application/x-www-form-urlencoded", &body),
+        ATTEMPTS,
+        RETRY_DELAY,
+    )?;
+    Ok(serde_json::from_str(&response)?)
+}
+
+/// Posts a plain text body to an API with additional headers, e.g. metrics in a text protocol.
+///
+/// Failures are handled as in `post_json`.
+pub fn post_text(
+    url: &str,
+    headers: &[(&str, &str)],
+    body: &str,
+) -> Result<(), Box<dyn std::error::Error>> {
+    post_with_retry(
+        url,
+        headers,
+        ("text/plain; charset=utf-8", body),
+        ATTEMPTS,
+        RETRY_DELAY,
+    )
+    .map(|_| ())
+}
+
+/// Posts a body, given with its content type, retrying transient failures. Returns the body of
+/// the response.
+fn post_with_retry(
+    url: &str,
+    headers: &[(&str, &str)],
+    (content_type, body): (&str, &str),
+    attempts: u32,
+    delay: Duration,
+) -> Result<String, Box<dyn std::error::Error>> {
+    let client = reqwest::blocking::Client::builder()
+        .timeout(Duration::from_secs(30))
+        .build()?;
+    let mut delay = delay;
+    let mut attempt = 1;
+    loop {
+        let mut request = client
+            .post(url)
+            .header(reqwest::header::CONTENT_TYPE, content_type);
+        for (name, value) in headers {
+            request = request.header(*name, *value);
+        }
+        let res = request.body(body.to_string()).send();
+
+        let (error, retry_after): (Box<dyn std::error::Error>, _) = match res {
+            Ok(response) if response.status().is_success() => return Ok(response.text()?),
+            Ok(response) => {
+                let status = response.status();
+                let retry_after = response
+                    .headers()
+                    .get(reqwest::header::RETRY_AFTER)
+                    .and_then(|v| v.to_str().ok())
+                    .and_then(|v| v.parse().ok())
+                    .map(Duration::from_secs);
+                let body = response.text().unwrap_or_default();
+                let error = format!("webhook answered `{}`: {}", status, body).into();
+                if !(status.is_server_error() || status.as_u16() == 429) {
+                    return Err(error);
+                }
+                (error, retry_after)
+            }
+            Err(e) if e.is_builder() || e.is_redirect() => return Err(e.into()),
+            Err(e) => (e.into(), None),
+        };
+
+        if attempt >= attempts {
+            return Err(error);
+        }
+        let wait = retry_after.unwrap_or(delay);
+        warn!(
+            "Could not post to webhook (attempt {}/{}): {}, retrying in {:?}",
+            attempt, attempts, error, wait
+        );
+        std::thread::sleep(wait);
+        delay *= 2;
+        attempt += 1;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_post_with_retry() {
+        let mut server = mockito::Server::new();
+        let url = format!("{}/hook", server.url());
+        let payload = ("application/json", r#"{"text":"hello"}"#);
+
+        // Server errors are retried until the attempts are exhausted
+        let mock = server
+            .mock("POST", "/hook")
+            .with_status(503)
+            .expect(3)
+            .create();
+        assert!(post_with_retry(&url, &[], payload, 3, Duration::ZERO).is_err());
+        mock.assert();
+
+        // Client errors are not
+        let mock = server
+            .mock("POST", "/hook")
+            .with_status(400)
+            .with_body("invalid_blocks")
+            .expect(1)
+            .create();
+        let error = post_with_retry(&url, &[], payload, 3, Duration::ZERO).unwrap_err();
+        assert!(error.to_string().contains("invalid_blocks"));
+        mock.assert();
+
+        let mock = server
+            .mock("POST", "/hook")
+            .match_header("authorization", "GenieKey abc")
+            .with_status(202)
+            .create();
+        post_with_retry(
+            &url,
+            &[("Authorization", "GenieKey abc")],
+            payload,
+            1,
+            Duration::ZERO,
+        )
+        .unwrap();
+        mock.assert();
+    }
+}
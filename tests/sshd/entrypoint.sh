@@ -0,0 +1,9 @@
+#!/bin/sh
+# Installs the public key mounted at /authorized_keys, sshd refuses keys not owned by the user
+set -e
+mkdir -p /home/monitor/.ssh
+cp /authorized_keys /home/monitor/.ssh/authorized_keys
+chown -R monitor:monitor /home/monitor/.ssh
+chmod 700 /home/monitor/.ssh
+chmod 600 /home/monitor/.ssh/authorized_keys
+exec /usr/sbin/sshd -D -e
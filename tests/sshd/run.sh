@@ -0,0 +1,30 @@
+#!/bin/sh
+# Runs the integration tests against a disposable SSH server:
+#
+#   tests/sshd/run.sh [cargo test arguments]
+#
+# The server listens on port 2222 (REMOTESYSMONITOR_TEST_SSH_PORT), as user `monitor` with the
+# password `monitor` or a key generated in target/sshd.
+set -e
+cd "$(dirname "$0")/../.."
+
+port=${REMOTESYSMONITOR_TEST_SSH_PORT:-2222}
+key=target/sshd/id_ed25519
+mkdir -p target/sshd
+[ -f "$key" ] || ssh-keygen -q -t ed25519 -N "" -f "$key"
+
+docker build -q -t remotesysmonitor-sshd tests/sshd
+container=$(docker run -d --rm -p "127.0.0.1:$port:22" \
+    -v "$PWD/$key.pub:/authorized_keys:ro" remotesysmonitor-sshd)
+trap 'docker stop "$container" >/dev/null' EXIT
+
+# Wait for sshd to accept connections
+for _ in $(seq 50); do
+    if ssh-keyscan -p "$port" 127.0.0.1 >/dev/null 2>&1; then
+        break
+    fi
+    sleep 0.2
+done
+
+REMOTESYSMONITOR_TEST_SSH_PORT=$port REMOTESYSMONITOR_TEST_SSH_KEY=$PWD/$key \
+    cargo test "$@" -- --ignored